@@ -0,0 +1,33 @@
+//! Embed the snapshot of the network datagram config if it exists,
+//! see `EMBEDDED_NETWORK_DATAGRAM_CONFIG` in `src/steam_server.rs`.
+//! The snapshot is not committed, the release workflow fetches it and
+//! sets `REQUIRE_NETWORK_DATAGRAM_CONFIG` so that a release cannot be
+//! built without it.
+//!
+//! Generate the gRPC code with the `grpc` feature, see `src/grpc.rs`.
+
+use std::path::Path;
+
+const SNAPSHOT_PATH: &str = "data/NetworkDatagramConfig.json";
+
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(embedded_network_datagram_config)");
+    println!("cargo::rerun-if-changed={}", SNAPSHOT_PATH);
+    println!("cargo::rerun-if-env-changed=REQUIRE_NETWORK_DATAGRAM_CONFIG");
+    if Path::new(SNAPSHOT_PATH).exists() {
+        println!("cargo::rustc-cfg=embedded_network_datagram_config");
+    } else if std::env::var_os("REQUIRE_NETWORK_DATAGRAM_CONFIG").is_some() {
+        panic!(
+            "`{}` not found and `REQUIRE_NETWORK_DATAGRAM_CONFIG` is set",
+            SNAPSHOT_PATH
+        );
+    } else {
+        println!(
+            "cargo::warning=`{}` not found, the binary has no fallback server list",
+            SNAPSHOT_PATH
+        );
+    }
+
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/steam_server_disable.proto").unwrap();
+}
@@ -0,0 +1,150 @@
+#ifndef STEAM_SERVER_DISABLE_H
+#define STEAM_SERVER_DISABLE_H
+
+/* Generated with cbindgen, do not edit manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Kind of event reported through [`SsdEventCallback`].
+ */
+typedef enum SsdEventKind {
+  /**
+   * All IPs of the region were blocked.
+   */
+  SSD_EVENT_KIND_REGION_DISABLED,
+  /**
+   * All IPs of the region were unblocked.
+   */
+  SSD_EVENT_KIND_REGION_ENABLED,
+} SsdEventKind;
+
+/**
+ * Status of a region.
+ */
+typedef enum SsdRegionStatus {
+  /**
+   * Region does not exist or the status could not be determined.
+   */
+  SSD_REGION_STATUS_UNKNOWN,
+  /**
+   * None of the region's IPs are blocked.
+   */
+  SSD_REGION_STATUS_NONE_DISABLED,
+  /**
+   * Some of the region's IPs are blocked.
+   */
+  SSD_REGION_STATUS_SOME_DISABLED,
+  /**
+   * All of the region's IPs are blocked.
+   */
+  SSD_REGION_STATUS_ALL_DISABLED,
+} SsdRegionStatus;
+
+/**
+ * Opaque context, created with [`ssd_context_new()`] and freed with
+ * [`ssd_context_free()`].
+ */
+typedef struct SsdContext SsdContext;
+
+/**
+ * Region information passed to [`SsdRegionCallback`].
+ */
+typedef struct SsdRegion {
+  /**
+   * Abreviation of the region, eg: `sgp`.
+   */
+  const char *abr;
+  /**
+   * Description of the region, `NULL` if not available.
+   */
+  const char *desc;
+  /**
+   * Number of IPv4 relays of the region.
+   */
+  size_t num_ipv4s;
+  /**
+   * Number of IPv6 relays of the region.
+   */
+  size_t num_ipv6s;
+  /**
+   * If `latitude` and `longitude` are valid.
+   */
+  bool has_geo;
+  float latitude;
+  float longitude;
+} SsdRegion;
+
+/**
+ * Callback for [`ssd_list_regions()`].
+ */
+typedef void (*SsdRegionCallback)(const struct SsdRegion *region, void *user_data);
+
+/**
+ * Callback for the events, see [`ssd_set_event_callback()`].
+ */
+typedef void (*SsdEventCallback)(enum SsdEventKind kind, const char *abr, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Get the last error message of the current thread, `NULL` if there
+ * was no error. The string is valid until the next failing call on
+ * the same thread.
+ */
+const char *ssd_last_error_message(void);
+
+/**
+ * Create a new context. `network_datagram_config_path` may be `NULL`
+ * to use the (downloaded if necessary) default config file.
+ *
+ * Returns `NULL` on failure.
+ */
+struct SsdContext *ssd_context_new(const char *network_datagram_config_path);
+
+/**
+ * Free the context.
+ */
+void ssd_context_free(struct SsdContext *context);
+
+/**
+ * Call `callback` for every region. Returns the number of regions.
+ */
+size_t ssd_list_regions(const struct SsdContext *context,
+                        SsdRegionCallback callback,
+                        void *user_data);
+
+/**
+ * Block all the IPs of the region.
+ */
+int ssd_disable_region(struct SsdContext *context, const char *abr);
+
+/**
+ * Unblock all the IPs of the region.
+ */
+int ssd_enable_region(struct SsdContext *context, const char *abr);
+
+/**
+ * Get the status of the region.
+ */
+enum SsdRegionStatus ssd_get_status(const struct SsdContext *context, const char *abr);
+
+/**
+ * Set the callback that is called after a region is enabled or
+ * disabled through the context. Pass `NULL` to remove the callback.
+ */
+void ssd_set_event_callback(struct SsdContext *context,
+                            SsdEventCallback callback,
+                            void *user_data);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* STEAM_SERVER_DISABLE_H */
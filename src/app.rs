@@ -1,70 +1,92 @@
 use std::{
     borrow::Cow,
-    collections::{HashMap, VecDeque},
-    net::Ipv4Addr,
+    collections::{HashMap, HashSet, VecDeque},
+    net::IpAddr,
     path::PathBuf,
-    sync::{mpsc, Arc},
-    thread,
-    time::Duration,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 use clap::Parser;
 
 use crate::{
-    firewall::Firewall,
-    ping::{self, PingInfo, Pinger},
-    steam_server::{ServerInfo, ServerState, Servers},
+    asn::{AsnInfo, AsnResolver},
+    block_list::{BlockList, BlockListFormat},
+    block_suggestions::{self, BlockSuggestion, RegionStats, SuggestionThresholds},
+    cli::CommandLineArguments,
+    command_palette::{CommandPalette, PaletteCommand, PaletteEntry},
+    config::{Config, Theme},
+    config_refresh::ConfigRefresher,
+    connections::{self, ActiveRelayGuard},
+    controller::RegionStatus,
+    cs2,
+    discord::DiscordWebhook,
+    event::{Event, Notifier, Notifiers},
+    file_ops,
+    firewall::{self, Audit, Firewall, FirewallOptions},
+    game::{self, MatchGuard},
+    geoip::{self, Location},
+    health::{Health, ThreadStatus},
+    history::{self, History},
+    hooks::Hooks,
+    ip_list::{self, IpList},
+    logger, map_tiles,
+    ping::{self, FailureCause, PingInfo, PingerFactory, PingerRequirements, ProbeKind},
+    port_check::{PortChecker, RegionPortReport, RegionPortStatus},
+    presets::{self, Preset},
+    profiles::{Profile, Profiles},
+    recommend::Recommendation,
+    regions::Continent,
+    report::{RegionReport, Report, ReportFormat},
+    rules::{self, Rule, RuleCondition, RuleDecision, Rules},
+    score::{ConnectionStats, ScoreWeights},
+    session,
+    steam::{self, InstalledApp, RunningAppWatcher},
+    steam_cm,
+    steam_pings::SteamPings,
+    steam_server::{self, ServerCategory, ServerInfo, ServerState, Servers, ServersDiff},
+    steam_status::{self, DatacenterHealth, DatacenterStatus, SteamStatusFetcher},
+    timings,
+    trial_block::{LatencySnapshot, TrialBlock, TrialReport},
+    view_state::{GridSortColumn, GridViewState, MapColoring, ViewState},
+    waker::Waker,
+    worker::{Command, Response, StatusPriority, Worker},
+    world_map::WorldOutline,
+    Error,
 };
 
-#[derive(Debug)]
-pub enum PingerMessage {
-    PushToList(Ipv4Addr),
-    RemoveFromList(Ipv4Addr),
-    AppendToList(Vec<Ipv4Addr>),
-    ClearList,
-    KillThread,
-}
-
-pub enum ServerStatusMessage {
-    AppendToList(Vec<(String, Vec<Ipv4Addr>)>),
-    RemoveServer(String),
-    ClearList,
-    KillThread,
+/// State of the suggestion of the regions to block based on the
+/// location of the user.
+enum LocationSuggestion {
+    None,
+    /// Ask the user if they want to detect their location.
+    Ask,
+    Suggested {
+        location: Location,
+        block_list: BlockList,
+    },
 }
 
-/// Command line arguments for the `steam_server_disable`.
-#[derive(Debug, Parser)]
-#[command(author, version, about, long_about = None)]
-pub struct CommandLineArguments {
-    /// No GUI.
-    #[arg(long, default_value_t)]
-    pub no_gui: bool,
-
-    /// Enable all the IPs of the server regions matching the given
-    /// regex.
-    #[arg(long)]
-    pub enable: Option<regex::Regex>,
-
-    /// Exclusion regex for `--enable`.
-    #[arg(long, requires = "enable")]
-    pub enable_exclude: Option<regex::Regex>,
-
-    /// Disable all the IPs of the server regions matching the given
-    /// regex.
-    #[arg(long)]
-    pub disable: Option<regex::Regex>,
-
-    /// Exclusion regex for `--disable`.
-    #[arg(long, requires = "disable")]
-    pub disable_exclude: Option<regex::Regex>,
-
-    /// Use the given network datagram config file instead.
-    #[arg(long)]
-    pub network_datagram_config: Option<PathBuf>,
+/// Action of the [`App`] that reads the firewall, it is run once the
+/// firewall changes sent to the worker before it are applied, see
+/// [`App::after_worker_ack()`].
+enum AfterAck {
+    /// See [`App::save_profile()`].
+    SaveProfile { name: String, bind_app: bool },
+    /// See [`App::clear_active_profile()`].
+    ClearActiveProfile(String),
+    /// See [`App::begin_trial_block()`].
+    StartTrialBlock {
+        regions: BlockList,
+        duration: Duration,
+    },
+    /// See [`App::keep_trial_block()`].
+    KeepTrialBlock(Vec<String>),
 }
 
 /// [`App`] mode.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum AppMode {
     Grid,
     Map,
@@ -97,22 +119,234 @@ impl AppMode {
     }
 }
 
+/// Row of the grid for the front-ends other than the GUI, see
+/// [`App::region_rows()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionRow {
+    /// Abreviation of the region, eg: `sgp`.
+    pub region: String,
+    pub description: Option<String>,
+    pub state: ServerState,
+    /// Label of the state, eg: `Some Disabled (2/5)`.
+    pub state_label: String,
+    pub ping_label: String,
+    pub loss_label: String,
+    pub jitter_label: String,
+    pub score_label: String,
+}
+
 pub struct App {
     servers: Servers,
     firewall: Arc<Firewall>,
+    /// IPs whose traffic is delayed instead of blocked, see
+    /// [`Command::SetDeprioritized`].
+    deprioritized_ips: HashSet<IpAddr>,
 
-    ip_selection_status: HashMap<Ipv4Addr, bool>,
-
-    ping_info: HashMap<Ipv4Addr, VecDeque<Result<PingInfo, ping::Error>>>,
+    ip_selection_status: HashMap<IpAddr, bool>,
 
-    pinger_message_sender: mpsc::Sender<PingerMessage>,
-    ping_receiver: mpsc::Receiver<(Ipv4Addr, Result<PingInfo, ping::Error>)>,
-    pinger_thread_handle: Option<thread::JoinHandle<()>>,
+    ping_info: HashMap<IpAddr, VecDeque<Result<PingInfo, ping::Error>>>,
+    /// Recent and session loss of every IP pinged in the session.
+    loss_stats: HashMap<IpAddr, LossStats>,
+    /// Echo requests per IP per round, all of them are lost when a
+    /// ping fails.
+    probes_per_round: u16,
 
     server_status_info: HashMap<String, ServerState>,
-    server_status_message_sender: mpsc::Sender<ServerStatusMessage>,
-    server_status_receiver: mpsc::Receiver<(String, ServerState)>,
-    server_status_thread_handle: Option<thread::JoinHandle<()>>,
+
+    /// Pings the servers, checks their status and applies the
+    /// firewall changes.
+    worker: Worker,
+    /// Woken up when the background threads have new data, see
+    /// [`Self::set_waker()`].
+    waker: Waker,
+    ping_send_failures: PingSendFailures,
+    /// Colors of the ping badges in the grid.
+    ping_thresholds: PingThresholds,
+    score_weights: ScoreWeights,
+    /// Sort and filter preferences of the grid and the map.
+    view_state: ViewState,
+    /// The view state as last saved, it is saved again when it
+    /// changes.
+    saved_view_state: ViewState,
+    /// Settings, saved when they differ from `saved_config`.
+    config: Config,
+    saved_config: Config,
+    show_settings: bool,
+    /// Theme set on the [`egui::Context`].
+    applied_theme: Option<Theme>,
+    /// Changes of the server list downloaded with "Download Server
+    /// List", shown until the window is closed.
+    server_list_diff: Option<ServersDiff>,
+    /// Number of the best scored regions left out by
+    /// [`App::select_all_but_best()`].
+    keep_best_regions: usize,
+    /// Blocks and unblocks of the regions across the sessions.
+    history: History,
+    show_history: bool,
+    /// Show the health of the background threads.
+    show_diagnostics: bool,
+    /// Last audit of the firewall rules, from the diagnostics window.
+    firewall_audit: Option<Audit>,
+    active_relay_guard: ActiveRelayGuard,
+    /// See [`Command::SetWatchdog`].
+    rules_watchdog: bool,
+    /// Regions the game traffic is flowing through along with their
+    /// IPs in use, guarded by the worker, see
+    /// [`Self::update_active_relay_guard()`].
+    regions_in_use: Vec<(String, Vec<IpAddr>)>,
+    next_connections_check: Instant,
+    /// Blocks refused by the guard waiting for the confirmation of the
+    /// user, see [`ActiveRelayGuard::Warn`] and [`MatchGuard::Warn`].
+    refused_blocks: Vec<(String, Vec<IpAddr>)>,
+    match_guard: MatchGuard,
+    /// Watches the running game, [`None`] if the [`MatchGuard`] is
+    /// off.
+    game_watcher: Option<RunningAppWatcher>,
+    /// Game that is running, see [`Self::update_match_guard()`].
+    running_game: Option<u32>,
+    /// Region guarded while the game is running, see
+    /// [`Self::find_home_region()`].
+    home_region: Option<String>,
+    /// Actions waiting for the acknowledgement of the worker with the
+    /// given sequence number, see [`App::after_worker_ack()`].
+    pending_after_ack: Vec<(u64, AfterAck)>,
+    command_palette: CommandPalette,
+    /// Region to scroll to in the grid in the next frame, see
+    /// [`PaletteCommand::JumpToRegion`].
+    scroll_to_region: Option<String>,
+    /// Regions shown in the history, substring of the abreviation.
+    history_filter: String,
+    /// Only show the history of the current session.
+    history_this_session: bool,
+    /// Regions suggested to be blocked, see
+    /// [`Self::update_block_suggestions()`].
+    block_suggestions: Vec<BlockSuggestion>,
+    /// Rules blocking the regions automatically, see
+    /// [`Self::update_rules()`].
+    rules: Rules,
+    /// Recent decisions of the rules, newest first.
+    rule_decisions: VecDeque<RuleDecision>,
+    /// Reasons of the blocks requested but not applied yet, by region,
+    /// recorded in the history once the region is blocked.
+    pending_block_reasons: HashMap<String, String>,
+    /// Path of the last generated report.
+    last_report: Option<PathBuf>,
+    /// Percentage of the IPs to block of every region, see
+    /// [`ServerInfo::partial_block_ips()`].
+    partial_block_percent: HashMap<String, u8>,
+    /// Pasted list of IPs and CIDRs to block.
+    paste_block_text: String,
+    /// [`Self::paste_block_text`] parsed, updated when it changes.
+    paste_block_ips: IpList,
+
+    grid_cache: GridCache,
+    /// Servers visible in the UI in the last frame.
+    visible_servers: Vec<String>,
+
+    steam_status_fetcher: Option<SteamStatusFetcher>,
+    steam_status_info: HashMap<String, DatacenterStatus>,
+
+    /// Refreshes the network datagram config on a schedule, if
+    /// enabled.
+    config_refresher: Option<ConfigRefresher>,
+    /// The network datagram config is a local file
+    /// ([`CommandLineArguments::network_datagram_config`]), it is
+    /// never refreshed.
+    local_network_datagram_config: bool,
+
+    notifiers: Notifiers,
+    /// See [`CommandLineArguments::ping_alert_ms`].
+    ping_alert: Option<Duration>,
+    /// Regions whose mean ping is above the alert threshold, see
+    /// [`Self::update_ping_alerts()`].
+    ping_alerts: HashSet<String>,
+    /// See [`CommandLineArguments::loss_alert_percent`].
+    loss_alert: Option<f64>,
+    /// Regions whose recent loss is above the alert threshold.
+    loss_alerts: HashSet<String>,
+
+    location_suggestion: LocationSuggestion,
+    /// Location of the user, if detected.
+    location: Option<Location>,
+    keep_nearest_regions: usize,
+    /// Nearest regions highlighted in the grid and the map, see
+    /// [`Self::ui_recommendation()`].
+    recommendation: Option<Recommendation>,
+
+    cs2_user_convars: Option<cs2::UserConvars>,
+    /// Maximum acceptable matchmaking ping of CS2 shown in the UI,
+    /// only written to the convars file on request.
+    cs2_max_ping: u32,
+
+    /// Are the Steam connection manager (CM) servers managed too?
+    cm_servers: bool,
+    /// Are the Steam content (CDN) servers managed too?
+    #[cfg(feature = "cdn")]
+    cdn_servers: bool,
+
+    profiles: Profiles,
+    /// Name of the last applied profile.
+    active_profile: Option<String>,
+    /// If the blocks of the active profile were cleared, see
+    /// [`App::toggle_active_profile()`].
+    active_profile_cleared: bool,
+    #[cfg(feature = "hotkey")]
+    hotkey: Option<crate::hotkey::Hotkey>,
+    /// Serves the commands forwarded by the non elevated CLI.
+    #[cfg(windows)]
+    _pipe_server: Option<crate::ipc::PipeServer>,
+    /// Name of the profile to save the blocked regions as.
+    new_profile_name: String,
+    /// Bind the profile being saved to the Steam app of the loaded
+    /// servers.
+    new_profile_bind_app: bool,
+    /// Watches the running game when the profiles follow it.
+    running_app_watcher: Option<RunningAppWatcher>,
+    /// Games installed in the Steam libraries, offered in the app
+    /// picker.
+    installed_apps: Vec<InstalledApp>,
+    /// Steam apps with a tab, in the order of the tabs. The firewall
+    /// state is per IP, so the IPs shared by the apps show the same
+    /// state in every tab.
+    app_tabs: Vec<u32>,
+    /// Relays of the apps with a tab other than the loaded one, see
+    /// [`Self::switch_app()`].
+    app_tab_servers: HashMap<u32, Servers>,
+    /// Pings measured by Steam, if enabled.
+    steam_pings: Option<SteamPings>,
+
+    port_checker: PortChecker,
+    /// Latest port check of every region.
+    port_reports: HashMap<String, RegionPortReport>,
+    /// Regions whose ports are being checked.
+    port_checks_pending: HashSet<String>,
+
+    /// Looks up the AS of the relay IPs, if enabled.
+    asn_resolver: Option<AsnResolver>,
+    /// AS announcing every resolved IP.
+    asn_infos: HashMap<IpAddr, AsnInfo>,
+    /// IPs queued to the [`Self::asn_resolver`].
+    asn_requested: HashSet<IpAddr>,
+
+    /// Trial block in progress.
+    trial_block: Option<TrialBlock>,
+    /// Report of the last trial block, shown until dismissed.
+    trial_report: Option<TrialReport>,
+    /// Duration of the trial blocks in minutes.
+    trial_block_minutes: u32,
+
+    /// Expression matching the regions to select, same as
+    /// `--enable`/`--disable`.
+    selection_expression: String,
+    /// Treat [`Self::selection_expression`] as a glob instead of a
+    /// regex.
+    selection_glob: bool,
+    /// Only the regions whose abreviation or description contains it
+    /// are shown in the grid, case insensitive.
+    grid_filter: String,
+    /// Groups of the grid whose regions are hidden, see
+    /// [`GridViewState::group_regions`].
+    collapsed_groups: HashSet<String>,
 
     /// Is the [`App`] running in no GUI mode?
     pub no_gui: bool,
@@ -120,7 +354,7 @@ pub struct App {
     /// Currently active [`AppMode`].
     pub app_mode: AppMode,
 
-    /// [`walkers::HttpTiles`] for the map.
+    /// [`walkers::HttpTiles`] for the map, see [`crate::map_tiles`].
     ///
     /// Is [`Some`] if it has been initialized, is expected to be
     /// initialized only once. Cannot use [`std::cell::OnceCell`]
@@ -129,782 +363,4372 @@ pub struct App {
 
     /// [`walkers::MapMemory`].
     pub map_memory: walkers::MapMemory,
-}
-
-impl Drop for App {
-    fn drop(&mut self) {
-        // request threads to stop
-        self.server_status_message_sender
-            .send(ServerStatusMessage::KillThread)
-            .unwrap();
-        self.pinger_message_sender
-            .send(PingerMessage::KillThread)
-            .unwrap();
-
-        // wait for threads to join
-        self.server_status_thread_handle
-            .take()
-            .unwrap()
-            .join()
-            .unwrap();
-        self.pinger_thread_handle.take().unwrap().join().unwrap();
-    }
-}
 
-impl App {
-    pub fn new() -> Self {
-        let command_line_arguments = CommandLineArguments::parse();
-
-        log::info!("command_line_arguments: {:#?}", command_line_arguments);
-
-        let (pinger_message_sender, pinger_message_receiver) = mpsc::channel::<PingerMessage>();
-        let (ping_sender, ping_receiver) =
-            mpsc::channel::<(Ipv4Addr, Result<PingInfo, ping::Error>)>();
-
-        let pinger_thread_handle = thread::spawn(move || {
-            let pinger_message_receiver = pinger_message_receiver;
-            let ping_sender = ping_sender;
-            let mut list = Vec::new();
-            let mut pinger = Pinger::new();
-            pinger.set_timeout(Duration::from_millis(500));
-            let mut index = 0;
-            loop {
-                let messages: Vec<_> = pinger_message_receiver.try_iter().collect();
-                if messages
-                    .iter()
-                    .any(|message| matches!(message, PingerMessage::KillThread))
-                {
-                    break;
-                }
+    /// Draw the offline map, see [`crate::world_map`].
+    pub offline_map: bool,
 
-                messages.into_iter().for_each(|message| match message {
-                    PingerMessage::PushToList(add_ip) => {
-                        // add ip if it doesn't already exist in the list
-                        if !list.iter().any(|ip| *ip == add_ip) {
-                            list.push(add_ip);
-                        }
-                    }
-                    PingerMessage::RemoveFromList(remove_ip) => {
-                        if let Some(index) = list.iter().enumerate().find_map(|(index, ip)| {
-                            if *ip == remove_ip {
-                                Some(index)
-                            } else {
-                                None
-                            }
-                        }) {
-                            list.swap_remove(index);
-                        }
-                    }
-                    PingerMessage::AppendToList(ip_list) => {
-                        ip_list.into_iter().for_each(|add_ip| {
-                            // add ip if it doesn't already exist in the list
-                            if !list.iter().any(|ip| *ip == add_ip) {
-                                list.push(add_ip);
-                            }
-                        });
-                    }
-                    PingerMessage::ClearList => list.clear(),
-                    PingerMessage::KillThread => unreachable!(),
-                });
+    /// Regions selected on the map with a Shift + drag.
+    pub map_selection: Vec<String>,
 
-                if !list.is_empty() {
-                    if index >= list.len() {
-                        index = 0;
-                    }
-                    let ping_data = pinger.ping(list[index], 0);
-                    if let Err(ping::Error::SendError) = &ping_data {
-                        log::error!("Check your internet connection, unable to send packets");
-                        thread::sleep(Duration::from_secs(1));
-                    }
-                    ping_sender.send((list[index], ping_data)).unwrap();
-                    index += 1;
-                } else {
-                    thread::sleep(Duration::from_millis(50));
-                }
-            }
-        });
+    /// Start of the selection rectangle being dragged on the map.
+    pub map_selection_start: Option<egui::Pos2>,
+}
 
-        let firewall = Arc::new(Firewall::new());
+/// Thresholds of the colors of the ping, loss and jitter badges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PingThresholds {
+    /// Pings below this are good (green).
+    pub good_millis: u32,
+    /// Pings below this (and not good) are ok (yellow), the higher
+    /// pings are bad (red).
+    pub ok_millis: u32,
+    /// Recent losses of this many percent or more are bad (red),
+    /// below half of it they are good (green).
+    pub bad_loss_percent: u32,
+    /// Jitters of this many milliseconds or more are bad (red), below
+    /// half of it they are good (green).
+    pub bad_jitter_millis: u32,
+}
 
-        let (server_status_message_sender, server_status_message_receiver) =
-            mpsc::channel::<ServerStatusMessage>();
-        let (server_status_sender, server_status_receiver) =
-            mpsc::channel::<(String, ServerState)>();
+impl PingThresholds {
+    /// Get the color of the badge of the ping.
+    fn color(&self, ping: Duration) -> egui::Color32 {
+        Self::badge_color(
+            ping.as_secs_f64() * 1000.0,
+            f64::from(self.good_millis),
+            f64::from(self.ok_millis),
+        )
+    }
 
-        let thread_firewall = firewall.clone();
-        let server_status_thread_handle = thread::spawn(|| {
-            let server_status_message_receiver = server_status_message_receiver;
-            let server_status_sender = server_status_sender;
-            let firewall = thread_firewall;
+    /// Get the color of the badge of the recent loss in percent.
+    fn loss_color(&self, loss_percent: f64) -> egui::Color32 {
+        let bad = f64::from(self.bad_loss_percent);
+        Self::badge_color(loss_percent, bad / 2.0, bad)
+    }
 
-            let mut list = VecDeque::new();
-            loop {
-                let messages: Vec<_> = server_status_message_receiver.try_iter().collect();
-                if messages
-                    .iter()
-                    .any(|message| matches!(message, ServerStatusMessage::KillThread))
-                {
-                    break;
-                }
+    /// Get the color of the badge of the jitter.
+    fn jitter_color(&self, jitter: Duration) -> egui::Color32 {
+        let bad = f64::from(self.bad_jitter_millis);
+        Self::badge_color(jitter.as_secs_f64() * 1000.0, bad / 2.0, bad)
+    }
 
-                messages.into_iter().for_each(|message| match message {
-                    ServerStatusMessage::AppendToList(add_list) => {
-                        debug_assert!(
-                            !list.iter().any(|(server, _)| add_list
-                                .iter()
-                                .any(|(add_server, _add_ip_list)| server == add_server)),
-                            "attempting to add duplicate server to the server status list"
-                        );
-                        list.extend(add_list.into_iter());
-                    }
-                    ServerStatusMessage::RemoveServer(remove_server) => {
-                        // Remove server from list if it exists, no
-                        // error if it does not exist
-                        if let Some(server_index) =
-                            list.iter().enumerate().find_map(|(index, (server, _))| {
-                                (server == &remove_server).then(|| index)
-                            })
-                        {
-                            list.remove(server_index);
-                        }
-                    }
-                    ServerStatusMessage::ClearList => list.clear(),
-                    ServerStatusMessage::KillThread => unreachable!(),
-                });
+    /// Get the color of the ping on a gradient, green up to the good
+    /// pings, yellow at the ok pings and red from twice the ok pings,
+    /// see [`MapColoring::Latency`].
+    pub fn gradient_color(&self, ping: Duration) -> egui::Color32 {
+        let millis = ping.as_secs_f32() * 1000.0;
+        let good = self.good_millis as f32;
+        let ok = self.ok_millis as f32;
+        let green = egui::Color32::from_rgb(0, 160, 0);
+        let yellow = egui::Color32::from_rgb(200, 160, 0);
+        let red = egui::Color32::from_rgb(200, 0, 0);
+        if millis <= good {
+            green
+        } else if millis <= ok {
+            green.lerp_to_gamma(yellow, (millis - good) / (ok - good).max(1.0))
+        } else {
+            yellow.lerp_to_gamma(red, ((millis - ok) / ok.max(1.0)).min(1.0))
+        }
+    }
 
-                if let Some((server, ip_list)) = list.pop_front() {
-                    let ip_list_len = ip_list.len();
-                    let blocked_ip_list = ip_list
-                        .into_iter()
-                        .filter_map(|ip| {
-                            if let Ok(blocked) = firewall.is_blocked(ip) {
-                                blocked.then(|| ip)
-                            } else {
-                                // Drop the firewall error
-                                None
-                            }
-                        })
-                        .collect::<Vec<_>>();
-                    let server_state = if blocked_ip_list.len() == ip_list_len {
-                        ServerState::AllDisabled
-                    } else if blocked_ip_list.is_empty() {
-                        ServerState::NoneDisabled
-                    } else {
-                        ServerState::SomeDisabled(blocked_ip_list)
-                    };
+    /// Green below `good`, yellow below `ok`, red otherwise.
+    fn badge_color(value: f64, good: f64, ok: f64) -> egui::Color32 {
+        if value < good {
+            egui::Color32::from_rgb(0, 160, 0)
+        } else if value < ok {
+            egui::Color32::from_rgb(200, 160, 0)
+        } else {
+            egui::Color32::from_rgb(200, 0, 0)
+        }
+    }
 
-                    server_status_sender.send((server, server_state)).unwrap();
-                } else {
-                    // not a high priority
-                    thread::sleep(Duration::from_millis(500));
-                }
-            }
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Good below:");
+            ui.add(
+                egui::DragValue::new(&mut self.good_millis)
+                    .range(1..=self.ok_millis)
+                    .suffix(" ms"),
+            );
         });
+        ui.horizontal(|ui| {
+            ui.label("Ok below:");
+            ui.add(
+                egui::DragValue::new(&mut self.ok_millis)
+                    .range(self.good_millis..=1000)
+                    .suffix(" ms"),
+            );
+        });
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Bad loss from:");
+            ui.add(
+                egui::DragValue::new(&mut self.bad_loss_percent)
+                    .range(1..=100)
+                    .suffix("%"),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Bad jitter from:");
+            ui.add(
+                egui::DragValue::new(&mut self.bad_jitter_millis)
+                    .range(1..=1000)
+                    .suffix(" ms"),
+            );
+        });
+    }
+}
 
-        let servers = Servers::new(command_line_arguments.network_datagram_config.as_ref());
-        let ip_selection_status = servers
-            .get_servers()
-            .iter()
-            .flat_map(|server| server.get_ipv4s().iter().map(|ip| (*ip, false)))
-            .collect();
-
-        let mut res = Self {
-            servers,
-            firewall,
-
-            ip_selection_status,
+/// Lost packets of an IP over the last
+/// [`LossStats::RECENT_WINDOW`] and over the whole session, so that
+/// an old spike of losses does not dominate the recent loss.
+#[derive(Debug, Default)]
+struct LossStats {
+    /// Time every recent sample was received at with its number of
+    /// packets and lost packets, the oldest first.
+    recent: VecDeque<(Instant, usize, usize)>,
+    session_packets: usize,
+    session_lost: usize,
+}
 
-            ping_info: HashMap::new(),
-            pinger_message_sender,
-            ping_receiver,
-            pinger_thread_handle: Some(pinger_thread_handle),
+impl LossStats {
+    /// Window of the recent loss.
+    const RECENT_WINDOW: Duration = Duration::from_secs(60);
+
+    /// Record a sample of `packets` packets (a burst) received now,
+    /// `lost` of them were lost.
+    fn push(&mut self, packets: usize, lost: usize) {
+        let now = Instant::now();
+        self.recent.push_back((now, packets, lost));
+        while self
+            .recent
+            .front()
+            .is_some_and(|(time, _, _)| now.duration_since(*time) > Self::RECENT_WINDOW)
+        {
+            self.recent.pop_front();
+        }
+        self.session_packets += packets;
+        self.session_lost += lost;
+    }
 
-            server_status_info: HashMap::new(),
-            server_status_message_sender,
-            server_status_receiver,
-            server_status_thread_handle: Some(server_status_thread_handle),
+    /// Get the counts of the packets.
+    fn counts(&self) -> LossCounts {
+        let recent = self
+            .recent
+            .iter()
+            .filter(|(time, _, _)| time.elapsed() <= Self::RECENT_WINDOW);
+        LossCounts {
+            recent_packets: recent.clone().map(|(_, packets, _)| packets).sum(),
+            recent_lost: recent.map(|(_, _, lost)| lost).sum(),
+            session_packets: self.session_packets,
+            session_lost: self.session_lost,
+        }
+    }
+}
 
-            no_gui: command_line_arguments.no_gui,
+/// Packet counts of [`LossStats`], summed over the IPs of a region.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct LossCounts {
+    recent_packets: usize,
+    recent_lost: usize,
+    session_packets: usize,
+    session_lost: usize,
+}
 
-            app_mode: AppMode::Grid,
+impl LossCounts {
+    /// Get the recent loss in percent, [`None`] if no packet was sent
+    /// recently.
+    fn recent_percent(&self) -> Option<f64> {
+        (self.recent_packets != 0)
+            .then(|| self.recent_lost as f64 / self.recent_packets as f64 * 100.0)
+    }
 
-            map_tiles: None,
-            map_memory: {
-                let mut map_memory = walkers::MapMemory::default();
-                map_memory.set_zoom(2.0).expect("valid zoom level");
-                map_memory
-            },
+    /// Create the label `<recent loss> / <session loss>`.
+    fn label(&self) -> String {
+        let percentage = |lost: usize, packets: usize| {
+            if packets == 0 {
+                "NA".to_string()
+            } else {
+                format!("{:.2}%", lost as f64 / packets as f64 * 100.0)
+            }
         };
+        format!(
+            "{} / {}",
+            percentage(self.recent_lost, self.recent_packets),
+            percentage(self.session_lost, self.session_packets)
+        )
+    }
+}
 
-        // send all the servers to the server status gatherer thread
-        res.server_status_message_sender
-            .send(ServerStatusMessage::AppendToList(
-                res.servers
-                    .get_servers()
-                    .iter()
-                    .map(|info| {
-                        let server = info.get_abr().to_string();
-                        let ips = info.get_ipv4s().to_vec();
-                        (server, ips)
-                    })
-                    .collect(),
-            ))
-            .unwrap();
-
-        res.send_currently_active_ip_list_to_pinger();
+impl std::ops::Add for LossCounts {
+    type Output = Self;
 
-        if let Some(enable) = &command_line_arguments.enable {
-            res.enable_matching(&enable, command_line_arguments.enable_exclude.as_ref());
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            recent_packets: self.recent_packets + rhs.recent_packets,
+            recent_lost: self.recent_lost + rhs.recent_lost,
+            session_packets: self.session_packets + rhs.session_packets,
+            session_lost: self.session_lost + rhs.session_lost,
         }
+    }
+}
 
-        if let Some(disable) = &command_line_arguments.disable {
-            res.disable_matching(&disable, command_line_arguments.disable_exclude.as_ref());
-        }
+/// Ping summary of an IP or a region, the labels are cached to avoid
+/// formatting them every frame.
+struct PingSummary {
+    /// Average ping of the received packets.
+    ping: Option<Duration>,
+    ping_label: Cow<'static, str>,
+    loss_label: Cow<'static, str>,
+    /// Most common cause of the lost packets.
+    failure_cause: Option<FailureCause>,
+    /// Probe of the newest ping, labeled if not ICMP.
+    probe: Option<ProbeKind>,
+    stats: ConnectionStats,
+    /// Lowest and highest ping and jitter, shown on hover.
+    stats_label: Cow<'static, str>,
+    jitter_label: Cow<'static, str>,
+    /// Recent pings, oldest first, [`None`] for the lost packets, see
+    /// [`ping_history()`].
+    history: Cow<'static, [Option<Duration>]>,
+    loss_counts: LossCounts,
+    /// Composite score, see [`crate::score`].
+    score: Option<f64>,
+    score_label: Cow<'static, str>,
+}
 
-        res
+impl PingSummary {
+    /// Summary when no packets were received.
+    const NONE: Self = Self {
+        ping: None,
+        ping_label: Cow::Borrowed("NA"),
+        loss_label: Cow::Borrowed("100.00%"),
+        failure_cause: None,
+        probe: None,
+        stats: ConnectionStats::NONE,
+        stats_label: Cow::Borrowed("No pings received"),
+        jitter_label: Cow::Borrowed("NA"),
+        history: Cow::Borrowed(&[]),
+        loss_counts: LossCounts {
+            recent_packets: 0,
+            recent_lost: 0,
+            session_packets: 0,
+            session_lost: 0,
+        },
+        score: None,
+        score_label: Cow::Borrowed("NA"),
+    };
+
+    /// Create the summary from the connection statistics, the recent
+    /// pings, the recent and session losses, the most common cause of
+    /// the lost packets and the probe of the newest ping.
+    fn new(
+        stats: &ConnectionStats,
+        history: Vec<Option<Duration>>,
+        loss_counts: LossCounts,
+        failure_cause: Option<FailureCause>,
+        probe: Option<ProbeKind>,
+        score_weights: &ScoreWeights,
+    ) -> Self {
+        let score = stats.score(score_weights);
+        Self {
+            ping: stats.mean_ping,
+            ping_label: stats
+                .mean_ping
+                .map_or(Self::NONE.ping_label, |ping| match probe {
+                    Some(probe) if probe != ProbeKind::Icmp => {
+                        format!("{} ({})", PingInfo::new(ping), probe).into()
+                    }
+                    _ => format!("{}", PingInfo::new(ping)).into(),
+                }),
+            loss_label: loss_counts.label().into(),
+            failure_cause,
+            probe,
+            stats: *stats,
+            stats_label: match (stats.min_ping, stats.max_ping) {
+                (Some(min_ping), Some(max_ping)) => format!(
+                    "Min: {} ms, max: {} ms, jitter: {} ms",
+                    min_ping.as_millis(),
+                    max_ping.as_millis(),
+                    stats.jitter.as_millis()
+                )
+                .into(),
+                _ => Self::NONE.stats_label,
+            },
+            jitter_label: match stats.mean_ping {
+                Some(_) => format!("{:.1} ms", stats.jitter.as_secs_f64() * 1000.0).into(),
+                None => Self::NONE.jitter_label,
+            },
+            history: history.into(),
+            loss_counts,
+            score,
+            score_label: score.map_or(Self::NONE.score_label, |score| {
+                format!("{:.0}", score).into()
+            }),
+        }
     }
 
-    /// note: it is generally a good idea to clear the list before
-    /// sending the complete server ip list to the pinger thread, it
-    /// can lead to duplications otherwise
-    fn send_currently_active_ip_list_to_pinger(&self) {
-        self.servers.get_servers().iter().for_each(|info| {
-            if !matches!(
-                self.server_status_info
-                    .get(info.get_abr())
-                    .unwrap_or(&ServerState::Unknown),
-                ServerState::AllDisabled
-            ) {
-                self.pinger_message_sender
-                    .send(PingerMessage::AppendToList(info.get_ipv4s().to_vec()))
-                    .unwrap();
+    fn ui(
+        &self,
+        ping_ui: &mut egui::Ui,
+        loss_ui: &mut egui::Ui,
+        jitter_ui: &mut egui::Ui,
+        score_ui: &mut egui::Ui,
+        thresholds: &PingThresholds,
+    ) {
+        match self.ping {
+            Some(_) => jitter_ui.label(
+                egui::RichText::new(self.jitter_label.as_ref())
+                    .color(egui::Color32::WHITE)
+                    .background_color(thresholds.jitter_color(self.stats.jitter)),
+            ),
+            None => jitter_ui.label(self.jitter_label.as_ref()),
+        };
+        score_ui.label(self.score_label.as_ref());
+        ping_ui.horizontal(|ui| {
+            let ping_response = match self.ping {
+                Some(ping) => ui.label(
+                    egui::RichText::new(self.ping_label.as_ref())
+                        .color(egui::Color32::WHITE)
+                        .background_color(thresholds.color(ping)),
+                ),
+                None => ui.label(self.ping_label.as_ref()),
+            };
+            if let Some(probe) = self.probe.filter(|probe| *probe != ProbeKind::Icmp) {
+                ping_response.on_hover_text(format!(
+                    "The ICMP pings failed, measured with {} probes of the relay port",
+                    probe
+                ));
+            }
+            ui_sparkline(ui, &self.history).on_hover_text(self.stats_label.as_ref());
+        });
+        loss_ui.horizontal(|ui| {
+            match self.loss_counts.recent_percent() {
+                Some(loss_percent) => ui.label(
+                    egui::RichText::new(self.loss_label.as_ref())
+                        .color(egui::Color32::WHITE)
+                        .background_color(thresholds.loss_color(loss_percent)),
+                ),
+                None => ui.label(self.loss_label.as_ref()),
+            };
+            if let Some(failure_cause) = self.failure_cause {
+                let color = match failure_cause {
+                    FailureCause::Prohibited => ui.visuals().warn_fg_color,
+                    FailureCause::Socket => ui.visuals().error_fg_color,
+                    FailureCause::Timeout | FailureCause::Unreachable => {
+                        ui.visuals().weak_text_color()
+                    }
+                };
+                ui.colored_label(color, failure_cause.name())
+                    .on_hover_text(failure_cause.description());
             }
         });
     }
+}
 
-    /// Update server status info by flushing the server status messages channel.
-    fn update_server_status_info(&mut self) {
-        let server_status_info = &mut self.server_status_info;
-        let servers = &self.servers;
-        let pinger_message_sender = &self.pinger_message_sender;
-        let mut ping_info_remove_ips = Vec::new();
-        self.server_status_receiver
-            .try_iter()
-            .for_each(|(server_abr, status)| {
-                let server = servers
-                    .get_servers()
-                    .iter()
-                    .find(|info| info.get_abr() == server_abr)
-                    .unwrap();
-
-                match &status {
-                    ServerState::AllDisabled => {
-                        server.get_ipv4s().iter().for_each(|ip| {
-                            pinger_message_sender
-                                .send(PingerMessage::RemoveFromList(*ip))
-                                .unwrap();
-                        });
+/// Get the mean of the received pings of the IPs at every index of
+/// their recent pings, oldest first, [`None`] if none of the pings at
+/// the index were received.
+fn ping_history(pings: &[&VecDeque<Result<PingInfo, ping::Error>>]) -> Vec<Option<Duration>> {
+    // the pings are ordered from the newest
+    let history_len = pings.iter().map(|pings| pings.len()).max().unwrap_or(0);
+    (0..history_len)
+        .rev()
+        .map(|i| {
+            let rtts: Vec<_> = pings
+                .iter()
+                .filter_map(|pings| pings.get(i)?.as_ref().ok())
+                .map(PingInfo::get_rtt)
+                .collect();
+            (!rtts.is_empty())
+                .then(|| rtts.iter().sum::<Duration>() / u32::try_from(rtts.len()).unwrap())
+        })
+        .collect()
+}
 
-                        ping_info_remove_ips.extend(server.get_ipv4s().iter().copied());
-                    }
-                    ServerState::SomeDisabled(disabled_ips) => {
-                        // remove disabled ips from the list
-                        disabled_ips.iter().for_each(|ip| {
-                            pinger_message_sender
-                                .send(PingerMessage::RemoveFromList(*ip))
-                                .unwrap();
-                        });
+/// Create a sparkline of the recent pings (oldest first) scaled to the
+/// highest of them, the lost packets are marked in red.
+fn ui_sparkline(ui: &mut egui::Ui, pings: &[Option<Duration>]) -> egui::Response {
+    const SIZE: egui::Vec2 = egui::vec2(60.0, 14.0);
 
-                        // tell to ping non disabled ips
-                        pinger_message_sender
-                            .send(PingerMessage::AppendToList(
-                                server
-                                    .get_ipv4s()
-                                    .iter()
-                                    .copied()
-                                    .filter(|ip| {
-                                        !disabled_ips.iter().any(|disabled_ip| disabled_ip == ip)
-                                    })
-                                    .collect(),
-                            ))
-                            .unwrap();
+    let (rect, response) = ui.allocate_exact_size(SIZE, egui::Sense::hover());
+    let max_ping = pings.iter().flatten().max().copied().unwrap_or_default();
+    if pings.len() < 2 || max_ping.is_zero() || !ui.is_rect_visible(rect) {
+        return response;
+    }
+    let step = rect.width() / (pings.len() - 1) as f32;
+    let x = |i: usize| rect.left() + i as f32 * step;
+    let y = |ping: Duration| {
+        rect.bottom() - ping.as_secs_f32() / max_ping.as_secs_f32() * rect.height()
+    };
+
+    let painter = ui.painter();
+    pings
+        .iter()
+        .enumerate()
+        .filter(|(_, ping)| ping.is_none())
+        .for_each(|(i, _)| {
+            painter.vline(
+                x(i),
+                rect.y_range(),
+                egui::Stroke::new(1.0, egui::Color32::from_rgb(220, 50, 50)),
+            );
+        });
+    // consecutive received pings are joined, the lost packets break
+    // the line
+    pings
+        .windows(2)
+        .enumerate()
+        .filter_map(|(i, pair)| Some((i, pair[0]?, pair[1]?)))
+        .for_each(|(i, a, b)| {
+            painter.line_segment(
+                [egui::pos2(x(i), y(a)), egui::pos2(x(i + 1), y(b))],
+                egui::Stroke::new(1.5, egui::Color32::from_rgb(34, 170, 119)),
+            );
+        });
+    response
+}
 
-                        ping_info_remove_ips.extend(disabled_ips.iter());
-                    }
-                    ServerState::NoneDisabled => {
-                        pinger_message_sender
-                            .send(PingerMessage::AppendToList(server.get_ipv4s().to_vec()))
-                            .unwrap();
-                    }
-                    ServerState::Unknown => unreachable!(),
+/// Tracks the failures to send the pings so that they are logged when
+/// they start and stop instead of for every ping.
+#[derive(Debug, Default)]
+struct PingSendFailures {
+    /// Start of the current run of failures.
+    failing_since: Option<Instant>,
+    /// Number of failures in the current run.
+    num_failures: usize,
+    last_logged: Option<Instant>,
+}
+
+impl PingSendFailures {
+    /// Interval between the reminders while the failures continue.
+    const REMINDER_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Record the result of sending a ping.
+    fn record(&mut self, sent: bool) {
+        match (sent, self.failing_since) {
+            (false, None) => {
+                log::error!("Check your internet connection, unable to send packets");
+                let now = Instant::now();
+                self.failing_since = Some(now);
+                self.num_failures = 1;
+                self.last_logged = Some(now);
+            }
+            (false, Some(failing_since)) => {
+                self.num_failures += 1;
+                if self
+                    .last_logged
+                    .is_none_or(|last_logged| last_logged.elapsed() >= Self::REMINDER_INTERVAL)
+                {
+                    // not an error so that the logging window is not
+                    // forced open again
+                    log::warn!(
+                        "still unable to send packets ({} failures in {}s)",
+                        self.num_failures,
+                        failing_since.elapsed().as_secs()
+                    );
+                    self.last_logged = Some(Instant::now());
                 }
+            }
+            (true, Some(failing_since)) => {
+                log::info!(
+                    "able to send packets again after {} failures in {}s",
+                    self.num_failures,
+                    failing_since.elapsed().as_secs()
+                );
+                *self = Self::default();
+            }
+            (true, None) => {}
+        }
+    }
+}
 
-                let server_status = server_status_info
-                    .entry(server_abr)
-                    .or_insert(ServerState::Unknown);
-                *server_status = status;
-            });
+/// Labels of an IP in the grid.
+struct IpLabels {
+    ip: String,
+    enable: String,
+    disable: String,
+}
 
-        if !ping_info_remove_ips.is_empty() {
-            // hack: wait for the channel to get all the
-            // messages before flushing them
-            std::thread::sleep(Duration::from_secs(1));
-            // flush the ping messages channel
-            self.update_ping_info();
+/// Cached labels and ping summaries for [`App::ui_grid_mode()`],
+/// updated when the underlying data changes instead of every frame.
+struct GridCache {
+    ip_labels: HashMap<IpAddr, IpLabels>,
+    /// Region (abreviation) of every IP.
+    ip_regions: HashMap<IpAddr, String>,
+    /// Group of every region, see [`grid_group()`].
+    region_groups: HashMap<String, String>,
+    ip_ping_summaries: HashMap<IpAddr, PingSummary>,
+    region_ping_summaries: HashMap<String, PingSummary>,
+    /// Summaries of the pings of all the IPs of every group.
+    group_ping_summaries: HashMap<String, PingSummary>,
+    /// Ping measured by Steam of every region.
+    steam_ping_labels: HashMap<String, String>,
+}
 
-            ping_info_remove_ips.iter().for_each(|ip| {
-                self.ping_info.remove(ip);
-            });
+impl GridCache {
+    fn new(servers: &Servers) -> Self {
+        let ips = || {
+            servers
+                .get_servers()
+                .iter()
+                .flat_map(|server| server.get_ips().iter().map(move |ip| (*ip, server)))
+        };
+        Self {
+            ip_labels: ips()
+                .map(|(ip, _)| {
+                    let labels = IpLabels {
+                        ip: ip.to_string(),
+                        enable: format!("Enable {}", ip),
+                        disable: format!("Disable {}", ip),
+                    };
+                    (ip, labels)
+                })
+                .collect(),
+            ip_regions: ips()
+                .map(|(ip, server)| (ip, server.get_abr().to_string()))
+                .collect(),
+            region_groups: servers
+                .get_servers()
+                .iter()
+                .map(|server| (server.get_abr().to_string(), grid_group(server).to_string()))
+                .collect(),
+            ip_ping_summaries: HashMap::new(),
+            region_ping_summaries: HashMap::new(),
+            group_ping_summaries: HashMap::new(),
+            steam_ping_labels: HashMap::new(),
         }
     }
 
-    /// Update ping info by flushing the ping messages channel.
-    fn update_ping_info(&mut self) {
-        let max_pings_per_ip = 20;
-
-        let ping_info = &mut self.ping_info;
-        self.ping_receiver.try_iter().for_each(|(ip, info)| {
-            let ip_info = ping_info.entry(ip).or_insert_with(VecDeque::new);
-            ip_info.push_front(info);
-
-            if ip_info.len() > max_pings_per_ip {
-                ip_info.truncate(max_pings_per_ip);
-            }
-        });
+    /// Get the score of the region for sorting, regions without a
+    /// score are given [`f64::INFINITY`] (the worst).
+    fn region_score(&self, abr: &str) -> f64 {
+        self.region_ping_summaries
+            .get(abr)
+            .and_then(|summary| summary.score)
+            .unwrap_or(f64::INFINITY)
     }
 
-    /// Update all information that must happen very so often. eg:
-    /// ping information receiving
-    pub fn update(&mut self) {
-        self.update_ping_info();
-        self.update_server_status_info();
+    /// Get the value of the ping, loss, jitter or score column of the
+    /// region for sorting, [`None`] if the region has none.
+    fn region_sort_value(&self, abr: &str, column: GridSortColumn) -> Option<f64> {
+        let summary = self.region_ping_summaries.get(abr)?;
+        match column {
+            GridSortColumn::Region | GridSortColumn::State => None,
+            GridSortColumn::Ping => summary.ping.map(|ping| ping.as_secs_f64()),
+            GridSortColumn::Loss => summary.loss_counts.recent_percent(),
+            GridSortColumn::Jitter => summary.ping.map(|_| summary.stats.jitter.as_secs_f64()),
+            GridSortColumn::Score => summary.score,
+        }
     }
 
-    /// Calculate the total ping for the given ip. Returns the rtt, total
-    /// number of packets number of packets dropped.
-    ///
-    /// note: this returns the total ping not the average ping of the
-    /// packets
-    fn calculate_total_ping_for_ip(
-        ping_info: &HashMap<Ipv4Addr, VecDeque<Result<PingInfo, ping::Error>>>,
-        ip: Ipv4Addr,
-    ) -> (Duration, usize, usize) {
-        ping_info
-            .get(&ip)
-            .map(|list| {
-                let (total_ping, num_lost_packets) =
-                    list.iter()
-                        .fold((Duration::ZERO, 0), |acc, info| match info {
-                            Ok(info) => (acc.0 + info.get_rtt(), acc.1),
-                            Err(_) => (acc.0, acc.1 + 1),
-                        });
+    /// Get the labels of the IP.
+    fn ip_label(&self, ip: &IpAddr) -> &IpLabels {
+        &self.ip_labels[ip]
+    }
 
-                (total_ping, list.len(), num_lost_packets)
-            })
-            .unwrap_or((Duration::ZERO, 0, 0))
+    /// Recompute the labels of the pings measured by Steam.
+    fn update_steam_pings(&mut self, steam_pings: &SteamPings) {
+        self.steam_ping_labels = steam_pings
+            .pings()
+            .iter()
+            .map(|(region, ping)| (region.clone(), format!("{} ms", ping.as_millis())))
+            .collect();
     }
 
-    /// Enable all servers.
-    fn enable_all_servers(&self) {
-        for server in self.servers.get_servers().iter() {
-            let unban_res = server.unban(&self.firewall);
-            if let Err(err) = unban_res {
-                log::error!("{}: {}", server.get_abr(), err);
+    /// Recompute the ping summaries of the given IPs, of their regions
+    /// and of their groups.
+    fn update_ping_summaries(
+        &mut self,
+        servers: &Servers,
+        ping_info: &HashMap<IpAddr, VecDeque<Result<PingInfo, ping::Error>>>,
+        loss_stats: &HashMap<IpAddr, LossStats>,
+        ips: &HashSet<IpAddr>,
+        score_weights: &ScoreWeights,
+    ) {
+        let loss_counts = |ip: &IpAddr| {
+            loss_stats
+                .get(ip)
+                .map(LossStats::counts)
+                .unwrap_or_default()
+        };
+        let mut regions = HashSet::new();
+        ips.iter().for_each(|ip| {
+            if let Some(pings) = ping_info.get(ip) {
+                self.ip_ping_summaries.insert(
+                    *ip,
+                    PingSummary::new(
+                        // the pings are ordered from the newest, the
+                        // order does not matter for the jitter
+                        &ConnectionStats::new(pings),
+                        ping_history(&[pings]),
+                        loss_counts(ip),
+                        ping::dominant_failure_cause(pings),
+                        ping::latest_probe(pings),
+                        score_weights,
+                    ),
+                );
+            } else {
+                self.ip_ping_summaries.remove(ip);
+            }
+            if let Some(region) = self.ip_regions.get(ip) {
+                regions.insert(region.as_str());
             }
+        });
 
-            // send message to server status checker
-            // to update server status
-            self.server_status_message_sender
-                .send(ServerStatusMessage::AppendToList(vec![(
-                    server.get_abr().to_string(),
-                    server.get_ipv4s().to_vec(),
-                )]))
-                .unwrap();
-        }
-        self.pinger_message_sender
-            .send(PingerMessage::ClearList)
-            .unwrap();
-        self.send_currently_active_ip_list_to_pinger();
+        // the summary of several IPs combines their pings and losses
+        let combined_summary = |ips: &[IpAddr]| {
+            let pings: Vec<_> = ips.iter().filter_map(|ip| ping_info.get(ip)).collect();
+            let stats: Vec<_> = pings.iter().copied().map(ConnectionStats::new).collect();
+            let failure_cause = ping::dominant_failure_cause(pings.iter().copied().flatten());
+            let loss_counts = ips
+                .iter()
+                .filter(|ip| ping_info.contains_key(ip))
+                .map(loss_counts)
+                .fold(LossCounts::default(), |acc, counts| acc + counts);
+            // a fallback probe of any IP is labeled
+            let probe = pings
+                .iter()
+                .filter_map(|pings| ping::latest_probe(pings.iter()))
+                .max_by_key(|probe| *probe != ProbeKind::Icmp);
+            PingSummary::new(
+                &ConnectionStats::combine(&stats),
+                ping_history(&pings),
+                loss_counts,
+                failure_cause,
+                probe,
+                score_weights,
+            )
+        };
+
+        let mut groups = HashSet::new();
+        regions.into_iter().for_each(|region| {
+            let Some(server) = servers.get_server(region) else {
+                return;
+            };
+            self.region_ping_summaries
+                .insert(region.to_string(), combined_summary(server.get_ips()));
+            groups.insert(grid_group(server));
+        });
+
+        groups.into_iter().for_each(|group| {
+            let ips: Vec<_> = servers
+                .get_servers()
+                .iter()
+                .filter(|server| grid_group(server) == group)
+                .flat_map(|server| server.get_ips().iter().copied())
+                .collect();
+            self.group_ping_summaries
+                .insert(group.to_string(), combined_summary(&ips));
+        });
     }
+}
 
-    /// Disable all servers.
-    fn disable_all_servers(&mut self) {
-        for server in self.servers.get_servers().iter() {
-            let ban_res = server.ban(&self.firewall);
-            if let Err(err) = ban_res {
-                log::error!("{}: {}", server.get_abr(), err);
-            }
+/// Group the region is listed under when the grid is grouped, see
+/// [`ServerInfo::group()`].
+fn grid_group(server: &ServerInfo) -> &str {
+    server.group().unwrap_or("other")
+}
 
-            // send message to server status checker
-            // to update server status
-            self.server_status_message_sender
-                .send(ServerStatusMessage::AppendToList(vec![(
-                    server.get_abr().to_string(),
-                    server.get_ipv4s().to_vec(),
-                )]))
-                .unwrap();
-        }
+/// Row of the grid.
+enum GridRow<'a> {
+    /// Header of a group along with its regions.
+    Group(&'a str, Vec<&'a ServerInfo>),
+    Region(&'a ServerInfo),
+}
 
-        self.pinger_message_sender
-            .send(PingerMessage::ClearList)
-            .unwrap();
+/// Create a badge for the [`DatacenterStatus`].
+fn ui_datacenter_status(ui: &mut egui::Ui, status: &DatacenterStatus) -> egui::Response {
+    let color = match status.health() {
+        DatacenterHealth::Good => egui::Color32::from_rgb(0, 160, 0),
+        DatacenterHealth::Busy => egui::Color32::from_rgb(200, 160, 0),
+        DatacenterHealth::Overloaded => egui::Color32::from_rgb(200, 0, 0),
+        DatacenterHealth::Unknown => egui::Color32::GRAY,
+    };
+    ui.label(
+        egui::RichText::new(&status.load)
+            .color(egui::Color32::WHITE)
+            .background_color(color),
+    )
+    .on_hover_text(format!("Datacenter {}", status))
+}
 
-        // hack: wait for the channel to get all the
-        // messages before flushing them
-        std::thread::sleep(Duration::from_secs(1));
-        // flush the ping messages channel
-        self.update_ping_info();
+/// Convert the glob to an anchored regex, `*` matches any number of
+/// characters and `?` matches a single character.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    glob.chars().for_each(|c| match c {
+        '*' => regex.push_str(".*"),
+        '?' => regex.push('.'),
+        c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+    });
+    regex.push('$');
+    regex
+}
 
-        self.ping_info.clear();
-    }
+/// Create a badge for the port check of a region, see
+/// [`crate::port_check`].
+fn ui_port_report(ui: &mut egui::Ui, report: &RegionPortReport) -> egui::Response {
+    let status = report.status();
+    let color = match status {
+        RegionPortStatus::Reachable => egui::Color32::from_rgb(0, 160, 0),
+        RegionPortStatus::PartlyRejected => egui::Color32::from_rgb(200, 160, 0),
+        RegionPortStatus::Unusable => egui::Color32::from_rgb(200, 0, 0),
+        RegionPortStatus::Unknown => egui::Color32::GRAY,
+    };
+    let rejected: Vec<_> = report.rejected().map(|addr| addr.to_string()).collect();
+    let mut hover_text = format!(
+        "{} of {} probed ports rejected",
+        rejected.len(),
+        report.ports.len()
+    );
+    rejected.iter().for_each(|addr| {
+        hover_text.push('\n');
+        hover_text.push_str(addr);
+    });
+    ui.label(
+        egui::RichText::new(format!("ports: {}", status))
+            .color(egui::Color32::WHITE)
+            .background_color(color),
+    )
+    .on_hover_text(hover_text)
+}
 
-    /// Enable the given server.
-    fn enable_server(
-        server: &ServerInfo,
-        firewall: &Firewall,
-        server_status_message_sender: &mpsc::Sender<ServerStatusMessage>,
-        pinger_message_sender: &mpsc::Sender<PingerMessage>,
-    ) {
-        let unban_res = server.unban(firewall);
-        if let Err(err) = unban_res {
-            log::error!("{}: {}", server.get_abr(), err);
-        }
-
-        // send message to server status checker
-        // to update server status
-        server_status_message_sender
-            .send(ServerStatusMessage::AppendToList(vec![(
-                server.get_abr().to_string(),
-                server.get_ipv4s().to_vec(),
-            )]))
-            .unwrap();
-
-        // update pinger ip list
-        let ips = server.get_ipv4s().to_vec();
-        ips.iter().for_each(|ip| {
-            pinger_message_sender
-                .send(PingerMessage::RemoveFromList(*ip))
-                .unwrap();
-        });
-        pinger_message_sender
-            .send(PingerMessage::AppendToList(ips))
-            .unwrap();
+/// Builder for [`App`], allows injecting the components instead of
+/// creating them from the command line arguments.
+pub struct AppBuilder {
+    command_line_arguments: Option<CommandLineArguments>,
+    servers: Option<Servers>,
+    firewall: Option<Arc<Firewall>>,
+    pinger: Option<PingerFactory>,
+    config: Option<Config>,
+    ping_timeout: Option<Duration>,
+    ping_interval: Duration,
+}
+
+impl Default for AppBuilder {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    /// Disable the given server.
-    fn disable_server(
-        server: &ServerInfo,
-        firewall: &Firewall,
-        server_status_message_sender: &mpsc::Sender<ServerStatusMessage>,
-        pinger_message_sender: &mpsc::Sender<PingerMessage>,
-        ping_info_remove_ips: &mut Option<Vec<Ipv4Addr>>,
-    ) {
-        let ban_res = server.ban(firewall);
-        if let Err(err) = ban_res {
-            log::error!("{}: {}", server.get_abr(), err);
+impl AppBuilder {
+    /// Create a new [`AppBuilder`], components that are not set are
+    /// created when building the [`App`].
+    pub fn new() -> Self {
+        Self {
+            command_line_arguments: None,
+            servers: None,
+            firewall: None,
+            pinger: None,
+            config: None,
+            ping_timeout: None,
+            ping_interval: Duration::ZERO,
         }
+    }
 
-        // send message to server status checker
-        // to update server status
-        server_status_message_sender
-            .send(ServerStatusMessage::AppendToList(vec![(
-                server.get_abr().to_string(),
-                server.get_ipv4s().to_vec(),
-            )]))
-            .unwrap();
+    /// Use the given [`CommandLineArguments`], defaults to no
+    /// arguments (not the arguments of the process).
+    pub fn command_line_arguments(mut self, command_line_arguments: CommandLineArguments) -> Self {
+        self.command_line_arguments = Some(command_line_arguments);
+        self
+    }
 
-        let ips = server.get_ipv4s().to_vec();
+    /// Use the given [`Servers`] instead of loading them from the
+    /// network datagram config file.
+    pub fn servers(mut self, servers: Servers) -> Self {
+        self.servers = Some(servers);
+        self
+    }
 
-        // update pinger ip list
-        ips.iter().for_each(|ip| {
-            pinger_message_sender
-                .send(PingerMessage::RemoveFromList(*ip))
-                .unwrap();
-        });
+    /// Use the given [`Firewall`] instead of the system firewall, see
+    /// [`Firewall::with_backend()`].
+    pub fn firewall(mut self, firewall: Firewall) -> Self {
+        self.firewall = Some(Arc::new(firewall));
+        self
+    }
 
-        if let Some(prev_removed_ips) = ping_info_remove_ips {
-            prev_removed_ips.extend(ips.into_iter());
-        } else {
-            *ping_info_remove_ips = Some(ips);
-        }
+    /// Create the pingers of the worker thread with `new_pinger` (eg:
+    /// a [`MockPinger`](crate::ping::MockPinger)) instead of
+    /// [`Pinger::new()`], it is called again when the worker thread is
+    /// restarted.
+    pub fn pinger<P: PingerRequirements + 'static>(
+        mut self,
+        new_pinger: impl Fn() -> Result<P, ping::Error> + Send + Sync + 'static,
+    ) -> Self {
+        self.pinger = Some(Arc::new(move || {
+            Ok(Box::new(new_pinger()?) as Box<dyn PingerRequirements>)
+        }));
+        self
     }
 
-    /// Enable the given IP.
-    fn enable_ip(
-        ip: Ipv4Addr,
-        server: &ServerInfo,
-        firewall: &Firewall,
-        server_status_message_sender: &mpsc::Sender<ServerStatusMessage>,
-        pinger_message_sender: &mpsc::Sender<PingerMessage>,
-    ) {
-        let unban_res = firewall.unban_ip(ip);
-        if let Err(err) = unban_res {
-            log::error!("{}: {}", server.get_abr(), err);
+    /// Use the given [`Config`] instead of loading it from the config
+    /// file.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Timeout of a single ping, defaults to the one of the
+    /// [`Config`].
+    pub fn ping_timeout(mut self, ping_timeout: Duration) -> Self {
+        self.ping_timeout = Some(ping_timeout);
+        self
+    }
+
+    /// Delay between consecutive batches of pings of the worker
+    /// thread, see `--concurrent-pings`.
+    pub fn ping_interval(mut self, ping_interval: Duration) -> Self {
+        self.ping_interval = ping_interval;
+        self
+    }
+
+    /// Build the [`App`].
+    pub fn build(self) -> Result<App, Error> {
+        App::from_builder(self)
+    }
+}
+
+impl App {
+    /// Interval between the detections of the relays in use, see
+    /// [`Self::update_active_relay_guard()`].
+    const CONNECTIONS_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Create the [`App`] using the command line arguments of the
+    /// process, see [`AppBuilder`] for more control.
+    pub fn new() -> Result<Self, Error> {
+        AppBuilder::new()
+            .command_line_arguments(CommandLineArguments::parse())
+            .build()
+    }
+
+    fn from_builder(builder: AppBuilder) -> Result<Self, Error> {
+        let command_line_arguments = builder
+            .command_line_arguments
+            .unwrap_or_else(|| CommandLineArguments::parse_from([env!("CARGO_PKG_NAME")]));
+
+        log::info!(
+            "command_line_arguments: {:#?}",
+            command_line_arguments.without_secrets()
+        );
+
+        if let Some(level) = command_line_arguments.gui_log_level {
+            logger::LOGGER.set_first_max_level(level);
+        }
+        if let Some(level) = command_line_arguments.terminal_log_level {
+            logger::LOGGER.set_second_max_level(level);
+        }
+        logger::LOGGER
+            .first()
+            .set_max_records(command_line_arguments.log_max_records);
+        logger::LOGGER
+            .first()
+            .set_max_bytes(command_line_arguments.log_max_bytes);
+        if let Some(backend) = command_line_arguments.firewall_backend {
+            FirewallOptions::override_backend(backend);
+        }
+
+        let config = builder.config.unwrap_or_else(Config::load_or_default);
+
+        let mut servers = match builder.servers {
+            Some(servers) => servers,
+            None => Servers::new_for_app(
+                command_line_arguments.network_datagram_config.as_ref(),
+                command_line_arguments.app_id.unwrap_or(config.app_id),
+            )?,
+        };
+        if command_line_arguments.cm_servers {
+            steam_cm::load_into(&mut servers);
+        }
+        #[cfg(feature = "cdn")]
+        if command_line_arguments.cdn_servers {
+            crate::steam_cdn::load_into(&mut servers);
+        }
+        let mut app_tabs = vec![servers.get_app_id()];
+        let mut app_tab_servers = HashMap::new();
+        command_line_arguments.app_ids.iter().for_each(|app_id| {
+            if app_tabs.contains(app_id) {
+                return;
+            }
+            match Servers::new_for_app(None::<PathBuf>, *app_id) {
+                Ok(servers) => {
+                    app_tabs.push(*app_id);
+                    app_tab_servers.insert(*app_id, servers);
+                }
+                Err(err) => log::error!("unable to load the servers of app {}: {}", app_id, err),
+            }
+        });
+        let firewall = match builder.firewall {
+            Some(firewall) => firewall,
+            None => Arc::new(Firewall::from_config()?),
+        };
+        if command_line_arguments.session {
+            session::start(&firewall)?;
+        }
+        let new_pinger = builder.pinger.unwrap_or_else(ping::pinger_factory);
+        let mut pinger = new_pinger()?;
+        pinger.set_timeout(
+            builder
+                .ping_timeout
+                .unwrap_or_else(|| config.ping_timeout()),
+        );
+        pinger.set_mode(command_line_arguments.probe_mode);
+
+        let waker = Waker::default();
+        let worker = Worker::new(
+            firewall.clone(),
+            pinger,
+            new_pinger,
+            builder.ping_interval,
+            command_line_arguments.probes_per_round,
+            usize::from(command_line_arguments.concurrent_pings),
+            waker.clone(),
+        );
+        if command_line_arguments.no_rules_watchdog {
+            worker.send(Command::SetWatchdog(false));
+        }
+        let deprioritized_ips = firewall.list_deprioritized().unwrap_or_else(|err| {
+            log::error!("unable to list the deprioritized IPs: {}", err);
+            HashSet::new()
+        });
+
+        let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+        if let Some(url) = &command_line_arguments.discord_webhook_url {
+            notifiers.push(Box::new(DiscordWebhook::new(url)));
+        }
+        #[cfg(feature = "notify")]
+        if command_line_arguments.desktop_notifications {
+            notifiers.push(Box::new(crate::desktop_notification::DesktopNotifier::new()));
+        }
+        match Hooks::load() {
+            Ok(hooks) if !hooks.hooks.is_empty() => notifiers.push(Box::new(hooks)),
+            Ok(_) => {}
+            Err(err) => log::error!("unable to load the hooks: {}", err),
+        }
+        let notifiers = Notifiers::new(notifiers);
+
+        let profiles = Profiles::load().unwrap_or_else(|err| {
+            log::error!("unable to load the profiles: {}", err);
+            Profiles::default()
+        });
+
+        let view_state = ViewState::load().unwrap_or_else(|err| {
+            log::error!("unable to load the view state: {}", err);
+            ViewState::default()
+        });
+
+        let steam_install_dir = command_line_arguments
+            .steam_dir
+            .clone()
+            .or_else(steam::find_install_dir);
+        let installed_apps = steam_install_dir
+            .as_ref()
+            .map(steam::get_installed_apps)
+            .unwrap_or_default();
+        log::info!("found {} installed games", installed_apps.len());
+
+        let steam_pings = match (&steam_install_dir, command_line_arguments.steam_pings) {
+            (Some(steam_install_dir), true) => Some(SteamPings::new(steam_install_dir)),
+            (None, true) => {
+                log::error!("cannot read Steam's pings, Steam installation not found");
+                None
+            }
+            (_, false) => None,
+        };
+
+        let cs2_user_convars = steam_install_dir.and_then(cs2::UserConvars::find);
+        match &cs2_user_convars {
+            Some(cs2_user_convars) => {
+                log::info!("cs2 convars file: {}", cs2_user_convars.path().display())
+            }
+            None => log::info!("cs2 convars file not found"),
+        }
+        if let Some(max_ping) = command_line_arguments.cs2_max_ping {
+            match &cs2_user_convars {
+                Some(cs2_user_convars) => cs2_user_convars.set_max_ping(max_ping)?,
+                None => log::error!("cannot set the cs2 max ping, convars file not found"),
+            }
+        }
+        let cs2_max_ping = cs2_user_convars
+            .as_ref()
+            .and_then(|cs2_user_convars| {
+                cs2_user_convars.max_ping().unwrap_or_else(|err| {
+                    log::error!("{}", err);
+                    None
+                })
+            })
+            .unwrap_or(*cs2::MAX_PING_RANGE.end());
+
+        let mut grid_cache = GridCache::new(&servers);
+        if let Some(steam_pings) = &steam_pings {
+            grid_cache.update_steam_pings(steam_pings);
+        }
+
+        let ip_selection_status = servers
+            .get_servers()
+            .iter()
+            .flat_map(|server| server.get_ips().iter().map(|ip| (*ip, false)))
+            .collect();
+
+        let config_refresh_hours = command_line_arguments
+            .config_refresh_hours
+            .unwrap_or(config.config_refresh_hours);
+        let config_refresher = (config_refresh_hours != 0
+            && command_line_arguments.network_datagram_config.is_none())
+        .then(|| {
+            ConfigRefresher::new(
+                servers.get_app_id(),
+                servers.get_revision(),
+                Duration::from_secs(config_refresh_hours * 60 * 60),
+                waker.clone(),
+            )
+        });
+
+        let mut res = Self {
+            servers,
+            firewall,
+            deprioritized_ips,
+
+            ip_selection_status,
+
+            ping_info: HashMap::new(),
+            loss_stats: HashMap::new(),
+            probes_per_round: command_line_arguments.probes_per_round,
+
+            server_status_info: HashMap::new(),
+
+            worker,
+            waker: waker.clone(),
+            ping_send_failures: PingSendFailures::default(),
+            ping_thresholds: PingThresholds {
+                good_millis: command_line_arguments.good_ping_ms,
+                ok_millis: command_line_arguments
+                    .ok_ping_ms
+                    .max(command_line_arguments.good_ping_ms),
+                bad_loss_percent: command_line_arguments.bad_loss_percent.clamp(1, 100),
+                bad_jitter_millis: command_line_arguments.bad_jitter_ms.max(1),
+            },
+            score_weights: ScoreWeights {
+                ping: command_line_arguments.score_ping_weight,
+                jitter: command_line_arguments.score_jitter_weight,
+                loss: command_line_arguments.score_loss_weight,
+            },
+            view_state: view_state.clone(),
+            saved_view_state: view_state,
+            config: config.clone(),
+            saved_config: config.clone(),
+            show_settings: false,
+            applied_theme: None,
+            server_list_diff: None,
+            keep_best_regions: 3,
+            block_suggestions: Vec::new(),
+            rules: Rules::load().unwrap_or_else(|err| {
+                log::error!("unable to load the rules: {}", err);
+                Rules::default()
+            }),
+            rule_decisions: VecDeque::new(),
+            pending_block_reasons: HashMap::new(),
+            last_report: None,
+            partial_block_percent: HashMap::new(),
+            paste_block_text: String::new(),
+            paste_block_ips: IpList::default(),
+            history: History::load().unwrap_or_else(|err| {
+                log::error!("unable to load the history: {}", err);
+                History::default()
+            }),
+            show_history: false,
+            show_diagnostics: false,
+            firewall_audit: None,
+            active_relay_guard: command_line_arguments.active_relay_guard,
+            rules_watchdog: !command_line_arguments.no_rules_watchdog,
+            regions_in_use: Vec::new(),
+            next_connections_check: Instant::now(),
+            refused_blocks: Vec::new(),
+            match_guard: command_line_arguments.match_guard,
+            game_watcher: (command_line_arguments.match_guard != MatchGuard::Off).then(|| {
+                RunningAppWatcher::with_detector(
+                    Duration::from_secs(5),
+                    waker.clone(),
+                    game::running_game,
+                )
+            }),
+            running_game: None,
+            home_region: None,
+            pending_after_ack: Vec::new(),
+            command_palette: CommandPalette::default(),
+            scroll_to_region: None,
+            history_filter: String::new(),
+            history_this_session: false,
+
+            grid_cache,
+            visible_servers: Vec::new(),
+
+            steam_status_fetcher: command_line_arguments
+                .steam_web_api_key
+                .clone()
+                .map(|api_key| {
+                    SteamStatusFetcher::new(
+                        api_key,
+                        Duration::from_secs(command_line_arguments.steam_status_interval),
+                        waker.clone(),
+                    )
+                }),
+            steam_status_info: HashMap::new(),
+
+            config_refresher,
+            local_network_datagram_config: command_line_arguments.network_datagram_config.is_some(),
+
+            notifiers,
+            ping_alert: command_line_arguments
+                .ping_alert_ms
+                .map(|ms| Duration::from_millis(ms.into())),
+            ping_alerts: HashSet::new(),
+            loss_alert: command_line_arguments.loss_alert_percent.map(f64::from),
+            loss_alerts: HashSet::new(),
+
+            location_suggestion: LocationSuggestion::None,
+            location: None,
+            keep_nearest_regions: command_line_arguments.keep_nearest_regions,
+            recommendation: None,
+
+            cs2_user_convars,
+            cs2_max_ping,
+
+            cm_servers: command_line_arguments.cm_servers,
+            #[cfg(feature = "cdn")]
+            cdn_servers: command_line_arguments.cdn_servers,
+
+            profiles,
+            active_profile: None,
+            active_profile_cleared: false,
+            #[cfg(feature = "hotkey")]
+            hotkey: command_line_arguments
+                .hotkey
+                .as_deref()
+                .map(|hotkey| crate::hotkey::Hotkey::new(hotkey, waker.clone()))
+                .transpose()?,
+            #[cfg(windows)]
+            _pipe_server: crate::ipc::PipeServer::new(
+                command_line_arguments.network_datagram_config.clone(),
+            )
+            .map_err(|err| log::error!("unable to serve the CLI commands: {}", err))
+            .ok(),
+            new_profile_name: String::new(),
+            new_profile_bind_app: true,
+            running_app_watcher: command_line_arguments
+                .follow_running_game
+                .then(|| RunningAppWatcher::new(Duration::from_secs(5), waker.clone())),
+            installed_apps,
+            app_tabs,
+            app_tab_servers,
+            steam_pings,
+
+            port_checker: PortChecker::new(waker.clone()),
+            port_reports: HashMap::new(),
+            port_checks_pending: HashSet::new(),
+
+            asn_resolver: command_line_arguments
+                .asn_lookup
+                .then(|| AsnResolver::new(waker.clone())),
+            asn_infos: HashMap::new(),
+            asn_requested: HashSet::new(),
+
+            trial_block: None,
+            trial_report: None,
+            trial_block_minutes: 5,
+
+            selection_expression: String::new(),
+            selection_glob: false,
+            grid_filter: String::new(),
+            collapsed_groups: HashSet::new(),
+
+            no_gui: command_line_arguments.no_gui,
+
+            app_mode: config.app_mode,
+
+            map_tiles: None,
+            map_memory: {
+                let mut map_memory = walkers::MapMemory::default();
+                map_memory.set_zoom(2.0).expect("valid zoom level");
+                map_memory
+            },
+            offline_map: command_line_arguments.offline_map || config.offline_map,
+            map_selection: Vec::new(),
+            map_selection_start: None,
+        };
+
+        res.watch_all_servers();
+
+        if let Some(enable) = &command_line_arguments.enable {
+            res.enable_matching(&enable, command_line_arguments.enable_exclude.as_ref());
+        }
+
+        if let Some(disable) = &command_line_arguments.disable {
+            res.disable_matching(&disable, command_line_arguments.disable_exclude.as_ref());
+        }
+
+        if let Some(name) = &command_line_arguments.preset {
+            match presets::find(&res.config.presets, name).cloned() {
+                Some(preset) => res.apply_preset(&preset),
+                None => log::error!(
+                    "unknown preset `{}`, the presets are: {}",
+                    name,
+                    res.config
+                        .presets
+                        .iter()
+                        .map(|preset| preset.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            }
+        }
+
+        if let Some(path) = &command_line_arguments.import_block_list {
+            let format = command_line_arguments
+                .block_list_format
+                .unwrap_or_else(|| BlockListFormat::from_path(path));
+            let (block_list, unknown) = BlockList::read(path, format, &res.servers)?;
+            if !unknown.is_empty() {
+                log::warn!("unknown entries in `{}`: {:?}", path.display(), unknown);
+            }
+            res.apply_block_list(&block_list);
+        }
+
+        if let Some(name) = &command_line_arguments.profile {
+            if res.profiles.get(name).is_none() {
+                return Err(Error::UnknownProfile(name.clone()));
+            }
+            res.apply_profile(name);
+        } else if command_line_arguments.restore_profile || res.config.restore_profile {
+            match res.profiles.last_applied() {
+                Some(profile) => {
+                    let name = profile.name.clone();
+                    res.apply_profile(&name);
+                }
+                None => log::info!("no profile to restore"),
+            }
+        }
+
+        let first_run_file_path = file_ops::get_data_dir()?.join("first_run_done");
+        if command_line_arguments.suggest_regions_from_location {
+            res.suggest_regions_from_location();
+        } else if !res.no_gui && !first_run_file_path.exists() {
+            res.location_suggestion = LocationSuggestion::Ask;
+        }
+        if !res.no_gui {
+            if let Err(err) = file_ops::write_atomic(&first_run_file_path, "") {
+                log::error!("{}: {}", first_run_file_path.display(), err);
+            }
+        }
+
+        if let Some(path) = &command_line_arguments.export_block_list {
+            let format = command_line_arguments
+                .block_list_format
+                .unwrap_or_else(|| BlockListFormat::from_path(path));
+            // the firewall changes requested above must be applied
+            // before reading the firewall
+            res.worker.flush();
+            BlockList::from_firewall(&res.servers, &res.firewall)?.write(
+                path,
+                format,
+                &res.servers,
+            )?;
+            log::info!("exported block list to `{}`", path.display());
+        }
+
+        Ok(res)
+    }
+
+    /// Send all the servers to the worker, their status is checked and
+    /// their unblocked IPs are pinged.
+    fn watch_all_servers(&self) {
+        self.worker
+            .send(Command::SetPortRanges(self.servers.port_ranges()));
+        self.worker.send(Command::WatchServers(
+            self.servers
+                .get_servers()
+                .iter()
+                .map(|info| {
+                    let server = info.get_abr().to_string();
+                    let ips = info.get_ips().to_vec();
+                    (server, ips)
+                })
+                .collect(),
+            StatusPriority::Background,
+        ));
+    }
+
+    /// Update the ping info and the server status info by flushing the
+    /// worker's responses channel.
+    fn update_worker_responses(&mut self) {
+        let max_pings_per_ip = self.config.max_pings_per_ip;
+
+        let mut updated_ips = HashSet::new();
+        // `self.worker` is borrowed by the iterator, collect to be able
+        // to update `self`
+        let responses: Vec<_> = self.worker.try_iter().collect();
+        let has_responses = !responses.is_empty();
+        responses.into_iter().for_each(|response| match response {
+            Response::Pings(pings) => {
+                pings.into_iter().for_each(|(ip, info)| {
+                    self.ping_send_failures
+                        .record(!matches!(info, Err(ping::Error::SendError)));
+                    let (sent, received) = match &info {
+                        Ok(info) => (info.sent(), info.received()),
+                        Err(_) => (self.probes_per_round, 0),
+                    };
+                    self.loss_stats
+                        .entry(ip)
+                        .or_default()
+                        .push(usize::from(sent), usize::from(sent - received));
+                    let ip_info = self.ping_info.entry(ip).or_insert_with(VecDeque::new);
+                    ip_info.push_front(info);
+
+                    if ip_info.len() > max_pings_per_ip {
+                        ip_info.truncate(max_pings_per_ip);
+                    }
+
+                    updated_ips.insert(ip);
+                });
+            }
+            Response::PingStopped(ips) => {
+                // no more pings of these IPs follow, the ping info can
+                // be removed right away
+                ips.into_iter().for_each(|ip| {
+                    self.ping_info.remove(&ip);
+                    updated_ips.insert(ip);
+                });
+            }
+            Response::ServerState(server_abr, status) => {
+                self.update_server_status(server_abr, status)
+            }
+            Response::FirewallError { server, error } => {
+                log::error!("{}: {}", server, error);
+                self.pending_block_reasons.remove(&server);
+                self.notifiers.notify(Event::FirewallError {
+                    region: server,
+                    error: error.to_string(),
+                });
+            }
+            Response::Deprioritized { ips, deprioritized } => {
+                if deprioritized {
+                    self.deprioritized_ips.extend(ips);
+                } else {
+                    ips.iter().for_each(|ip| {
+                        self.deprioritized_ips.remove(ip);
+                    });
+                }
+            }
+            Response::RulesRestored(ips) => {
+                self.notifiers
+                    .notify(Event::RulesRestored { num_ips: ips.len() });
+            }
+            Response::BlockRefused { server, ips } => {
+                self.pending_block_reasons.remove(&server);
+                let warn = if self.regions_in_use.iter().any(|(abr, _)| *abr == server) {
+                    self.active_relay_guard == ActiveRelayGuard::Warn
+                } else {
+                    self.match_guard == MatchGuard::Warn
+                };
+                if warn {
+                    self.refused_blocks.push((server, ips));
+                }
+            }
+            Response::Acked(sequence) => self.run_acked(sequence),
+        });
+
+        if !updated_ips.is_empty() {
+            self.grid_cache.update_ping_summaries(
+                &self.servers,
+                &self.ping_info,
+                &self.loss_stats,
+                &updated_ips,
+                &self.score_weights,
+            );
+            self.update_ping_alerts();
+        }
+        if has_responses {
+            self.update_block_suggestions();
+            self.update_rules();
+        }
+    }
+
+    /// Notify when the mean ping or the recent loss of a region goes
+    /// above its alert threshold, and when it is back below 90% of it
+    /// so that a value hovering around the threshold does not keep
+    /// notifying.
+    fn update_ping_alerts(&mut self) {
+        /// Update the alerts with the value of the region, get if the
+        /// region is now above the threshold when it crossed it.
+        fn crossed(
+            alerts: &mut HashSet<String>,
+            region: &str,
+            value: f64,
+            threshold: f64,
+        ) -> Option<bool> {
+            let alerted = alerts.contains(region);
+            let above = if alerted {
+                value >= threshold * 0.9
+            } else {
+                value > threshold
+            };
+            if above == alerted {
+                return None;
+            }
+            if above {
+                alerts.insert(region.to_string());
+            } else {
+                alerts.remove(region);
+            }
+            Some(above)
+        }
+
+        let mut events = Vec::new();
+        self.grid_cache
+            .region_ping_summaries
+            .iter()
+            .for_each(|(region, summary)| {
+                if let Some((threshold, ping)) = self.ping_alert.zip(summary.ping) {
+                    if let Some(above) = crossed(
+                        &mut self.ping_alerts,
+                        region,
+                        ping.as_secs_f64(),
+                        threshold.as_secs_f64(),
+                    ) {
+                        events.push(Event::PingThresholdCrossed {
+                            region: region.clone(),
+                            ping_ms: ping.as_millis() as u64,
+                            threshold_ms: threshold.as_millis() as u64,
+                            above,
+                        });
+                    }
+                }
+                if let Some((threshold, loss)) =
+                    self.loss_alert.zip(summary.loss_counts.recent_percent())
+                {
+                    if let Some(above) = crossed(&mut self.loss_alerts, region, loss, threshold) {
+                        events.push(Event::LossThresholdCrossed {
+                            region: region.clone(),
+                            loss_percent: loss.round() as u64,
+                            threshold_percent: threshold as u64,
+                            above,
+                        });
+                    }
+                }
+            });
+        events
+            .into_iter()
+            .for_each(|event| self.notifiers.notify(event));
+    }
+
+    /// Block the regions whose recent pings meet a rule, the blocked
+    /// regions and the ones being blocked are left out.
+    fn update_rules(&mut self) {
+        /// Maximum number of decisions shown.
+        const MAX_RULE_DECISIONS: usize = 20;
+
+        if !self.rules.rules.iter().any(|rule| rule.enabled) {
+            return;
+        }
+        let decisions: Vec<_> = self
+            .servers
+            .get_servers_in(ServerCategory::Relay)
+            .filter(|server| {
+                !self.pending_block_reasons.contains_key(server.get_abr())
+                    && !matches!(
+                        self.server_status_info.get(server.get_abr()),
+                        Some(ServerState::AllDisabled)
+                    )
+            })
+            .filter_map(|server| {
+                let ip_pings: Vec<Vec<_>> = server
+                    .get_ips()
+                    .iter()
+                    .filter_map(|ip| self.ping_info.get(ip))
+                    .map(|pings| {
+                        pings
+                            .iter()
+                            .filter_map(|ping| ping.as_ref().ok())
+                            .map(PingInfo::get_rtt)
+                            .collect()
+                    })
+                    .collect();
+                let (rule, value) = self.rules.evaluate(&ip_pings)?;
+                Some(RuleDecision {
+                    region: server.get_abr().to_string(),
+                    rule,
+                    value,
+                    time: history::unix_time_now(),
+                })
+            })
+            .collect();
+
+        decisions.into_iter().for_each(|decision| {
+            let Some(server) = self.servers.get_server(&decision.region) else {
+                return;
+            };
+            log::info!("rule {}: {}", decision.rule.condition, decision);
+            Self::disable_server(server, &self.worker);
+            self.pending_block_reasons.insert(
+                decision.region.clone(),
+                format!("rule: {}", decision.rule.condition),
+            );
+            self.rule_decisions.push_front(decision);
+            self.rule_decisions.truncate(MAX_RULE_DECISIONS);
+        });
+    }
+
+    /// Create the UI of the rules and their recent decisions.
+    fn ui_rules(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(format!(
+            "Rules ({} enabled)",
+            self.rules.rules.iter().filter(|rule| rule.enabled).count()
+        ))
+        .id_source("rules")
+        .show(ui, |ui| {
+            let mut changed = false;
+            let mut to_remove = None;
+            self.rules
+                .rules
+                .iter_mut()
+                .enumerate()
+                .for_each(|(index, rule)| {
+                    ui.horizontal(|ui| {
+                        changed |= ui.checkbox(&mut rule.enabled, "").changed();
+                        match &mut rule.condition {
+                            RuleCondition::MeanPingAbove {
+                                max_ping_ms,
+                                samples,
+                            } => {
+                                ui.label("Block regions with a mean ping above");
+                                changed |= ui
+                                    .add(egui::DragValue::new(max_ping_ms).range(1..=1000))
+                                    .changed();
+                                ui.label("ms over the last");
+                                changed |= ui
+                                    .add(
+                                        egui::DragValue::new(samples).range(1..=rules::MAX_SAMPLES),
+                                    )
+                                    .changed();
+                                ui.label("samples");
+                            }
+                        }
+                        if ui.button("Remove").clicked() {
+                            to_remove = Some(index);
+                        }
+                    });
+                });
+            if let Some(index) = to_remove {
+                self.rules.rules.remove(index);
+                changed = true;
+            }
+            if ui.button("Add Rule").clicked() {
+                self.rules.rules.push(Rule::default());
+                changed = true;
+            }
+            if changed {
+                if let Err(err) = self.rules.save() {
+                    log::error!("unable to save the rules: {}", err);
+                }
+            }
+
+            if !self.rule_decisions.is_empty() {
+                ui.separator();
+                self.rule_decisions.iter().for_each(|decision| {
+                    ui.label(format!(
+                        "{} {}",
+                        history::format_unix_time(decision.time),
+                        decision
+                    ));
+                });
+            }
+        });
+    }
+
+    /// Recompute the regions suggested to be blocked from the ping
+    /// summaries and the location of the user, the blocked regions
+    /// are left out.
+    fn update_block_suggestions(&mut self) {
+        let location = self
+            .location
+            .as_ref()
+            .map(|location| [location.longitude, location.latitude]);
+        let stats = self
+            .servers
+            .get_servers_in(ServerCategory::Relay)
+            .filter(|server| {
+                !matches!(
+                    self.server_status_info.get(server.get_abr()),
+                    Some(ServerState::AllDisabled)
+                )
+            })
+            .map(|server| {
+                let summary = self
+                    .grid_cache
+                    .region_ping_summaries
+                    .get(server.get_abr())
+                    .unwrap_or(&PingSummary::NONE);
+                RegionStats {
+                    region: server.get_abr().to_string(),
+                    min_ping: summary.stats.min_ping,
+                    session_packets: summary.loss_counts.session_packets,
+                    session_lost: summary.loss_counts.session_lost,
+                    distance_km: location
+                        .zip(server.geo())
+                        .map(|(location, geo)| geoip::distance_km(location, *geo)),
+                }
+            });
+        self.block_suggestions = block_suggestions::suggest(stats, &SuggestionThresholds::DEFAULT);
+    }
+
+    /// Create the UI of the regions suggested to be blocked.
+    fn ui_block_suggestions(&mut self, ui: &mut egui::Ui) {
+        if self.block_suggestions.is_empty() {
+            return;
+        }
+        egui::CollapsingHeader::new(format!(
+            "Suggested to Block ({})",
+            self.block_suggestions.len()
+        ))
+        .id_source("block_suggestions")
+        .show(ui, |ui| {
+            let mut to_block = Vec::new();
+            self.block_suggestions.iter().for_each(|suggestion| {
+                ui.horizontal(|ui| {
+                    if ui.button("Block").clicked() {
+                        to_block.push(suggestion.region.as_str());
+                    }
+                    ui.label(suggestion.to_string());
+                });
+            });
+            if ui
+                .button("Apply Suggestions")
+                .on_hover_text("Block all the suggested regions")
+                .clicked()
+            {
+                to_block = self
+                    .block_suggestions
+                    .iter()
+                    .map(|suggestion| suggestion.region.as_str())
+                    .collect();
+            }
+            to_block.into_iter().for_each(|region| {
+                if let Some(server) = self.servers.get_server(region) {
+                    log::info!("blocking suggested region {}", region);
+                    Self::disable_server(server, &self.worker);
+                }
+            });
+        });
+    }
+
+    /// Create the UI to block a pasted list of IPs and CIDRs, the
+    /// regions of the IPs are shown before blocking.
+    fn ui_paste_block(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Paste & Block")
+            .id_source("paste_block")
+            .show(ui, |ui| {
+                let response = ui.add(
+                    egui::TextEdit::multiline(&mut self.paste_block_text)
+                        .hint_text("IPs or CIDRs (/24 or longer), one per line or comma separated")
+                        .desired_rows(3),
+                );
+                if response.changed() {
+                    self.paste_block_ips = ip_list::parse(&self.paste_block_text);
+                }
+
+                self.paste_block_ips.invalid.iter().for_each(|invalid| {
+                    ui.colored_label(egui::Color32::from_rgb(200, 0, 0), invalid.to_string());
+                });
+                let groups = ip_list::group_by_region(&self.servers, &self.paste_block_ips.ips);
+                groups.regions.iter().for_each(|(server, ips)| {
+                    ui.label(format!(
+                        "{}: {} of {} IPs",
+                        server.get_abr(),
+                        ips.len(),
+                        server.get_ips().len()
+                    ));
+                });
+                if !groups.unknown.is_empty() {
+                    ui.label(format!("No known region: {} IPs", groups.unknown.len()));
+                }
+
+                let num_ips = self.paste_block_ips.ips.len();
+                if ui
+                    .add_enabled(
+                        num_ips != 0 && self.paste_block_ips.invalid.is_empty(),
+                        egui::Button::new(format!("Block {} IPs", num_ips)),
+                    )
+                    .on_disabled_hover_text("Fix or remove the invalid entries first")
+                    .clicked()
+                {
+                    groups.regions.iter().for_each(|(server, ips)| {
+                        self.worker.send(Command::SetBlocked {
+                            server: server.get_abr().to_string(),
+                            ips: ips.clone(),
+                            blocked: true,
+                        });
+                    });
+                    if !groups.unknown.is_empty() {
+                        // not a region, the worker only logs the name
+                        self.worker.send(Command::SetBlocked {
+                            server: "pasted IPs".to_string(),
+                            ips: groups.unknown.clone(),
+                            blocked: true,
+                        });
+                    }
+                    log::info!("blocking {} pasted IPs", num_ips);
+                    self.paste_block_text.clear();
+                    self.paste_block_ips = IpList::default();
+                }
+            });
+    }
+
+    /// Create the [`Report`] of the blocked regions and of the
+    /// connection statistics of the relay regions.
+    fn report(&self) -> Report {
+        let server_state = |server: &ServerInfo| {
+            self.server_status_info
+                .get(server.get_abr())
+                .unwrap_or(&ServerState::Unknown)
+        };
+        let relays = || self.servers.get_servers_in(ServerCategory::Relay);
+        Report {
+            generated_at: history::unix_time_now(),
+            app_id: self.servers.get_app_id(),
+            revision: self.servers.get_revision(),
+            blocked_regions: relays()
+                .filter(|server| {
+                    matches!(
+                        server_state(server),
+                        ServerState::AllDisabled | ServerState::SomeDisabled(_)
+                    )
+                })
+                .map(|server| server.get_abr().to_string())
+                .collect(),
+            regions: relays()
+                .map(|server| {
+                    let summary = self
+                        .grid_cache
+                        .region_ping_summaries
+                        .get(server.get_abr())
+                        .unwrap_or(&PingSummary::NONE);
+                    let percent = |lost: usize, packets: usize| {
+                        (packets != 0).then(|| lost as f64 / packets as f64 * 100.0)
+                    };
+                    let pings: Vec<_> = server
+                        .get_ips()
+                        .iter()
+                        .filter_map(|ip| self.ping_info.get(ip))
+                        .collect();
+                    let ping_history = ping_history(&pings);
+                    RegionReport {
+                        abr: server.get_abr().to_string(),
+                        desc: server.desc().map(str::to_string),
+                        state: server_state(server).to_string(),
+                        mean_ping: summary.stats.mean_ping,
+                        min_ping: summary.stats.min_ping,
+                        jitter: summary.stats.jitter,
+                        recent_loss_percent: percent(
+                            summary.loss_counts.recent_lost,
+                            summary.loss_counts.recent_packets,
+                        ),
+                        session_loss_percent: percent(
+                            summary.loss_counts.session_lost,
+                            summary.loss_counts.session_packets,
+                        ),
+                        score: summary.score,
+                        ping_history,
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Create the UI to generate a [`Report`].
+    fn ui_report(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button("Report", |ui| {
+            [ReportFormat::Html, ReportFormat::Markdown]
+                .into_iter()
+                .for_each(|format| {
+                    if ui.button(format!("Generate {} Report", format)).clicked() {
+                        match self.report().write(format) {
+                            Ok(file_path) => self.last_report = Some(file_path),
+                            Err(err) => log::error!("unable to write the report: {}", err),
+                        }
+                        ui.close_menu();
+                    }
+                });
+        })
+        .response
+        .on_hover_text("Write the blocked regions and the ping statistics to a file to share");
+        if let Some(last_report) = &self.last_report {
+            ui.label(last_report.display().to_string())
+                .on_hover_text("Last generated report");
+        }
+    }
+
+    /// Run the action once the firewall changes sent to the worker so
+    /// far are applied, without blocking the UI thread on
+    /// [`Worker::flush()`].
+    fn after_worker_ack(&mut self, action: AfterAck) {
+        let sequence = self.worker.ack();
+        self.pending_after_ack.push((sequence, action));
+    }
+
+    /// Run the actions waiting for the acknowledgements up to
+    /// `sequence`, see [`Self::after_worker_ack()`].
+    fn run_acked(&mut self, sequence: u64) {
+        let (acked, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending_after_ack)
+            .into_iter()
+            .partition(|(action_sequence, _)| *action_sequence <= sequence);
+        self.pending_after_ack = pending;
+        acked.into_iter().for_each(|(_, action)| match action {
+            AfterAck::SaveProfile { name, bind_app } => self.save_profile(&name, bind_app),
+            AfterAck::ClearActiveProfile(name) => self.clear_active_profile(&name),
+            AfterAck::StartTrialBlock { regions, duration } => {
+                self.begin_trial_block(regions, duration)
+            }
+            AfterAck::KeepTrialBlock(regions) => self.keep_trial_block(regions),
+        });
+    }
+
+    /// Update the status of the server, the user is notified if the
+    /// server got enabled or disabled.
+    fn update_server_status(&mut self, server_abr: String, status: ServerState) {
+        let Some(server) = self.servers.get_server(&server_abr) else {
+            // server list was updated since the status was requested
+            return;
+        };
+
+        let server_status = self
+            .server_status_info
+            .entry(server_abr)
+            .or_insert(ServerState::Unknown);
+        match (&*server_status, &status) {
+            (
+                ServerState::NoneDisabled | ServerState::SomeDisabled(_),
+                ServerState::AllDisabled,
+            ) => {
+                let reason = self.pending_block_reasons.remove(server.get_abr());
+                self.history.record(server.get_abr(), true, reason.clone());
+                self.notifiers.notify(Event::RegionDisabled {
+                    region: server.get_abr().to_string(),
+                    ips: server.get_ips().to_vec(),
+                    reason,
+                });
+            }
+            (
+                ServerState::AllDisabled | ServerState::SomeDisabled(_),
+                ServerState::NoneDisabled,
+            ) => {
+                self.history.record(server.get_abr(), false, None);
+                self.notifiers.notify(Event::RegionEnabled {
+                    region: server.get_abr().to_string(),
+                    ips: server.get_ips().to_vec(),
+                    reason: None,
+                });
+            }
+            _ => {}
+        }
+        *server_status = status;
+    }
+
+    /// Update the datacenter status with the latest fetched status
+    /// (if any).
+    fn update_steam_status_info(&mut self) {
+        let Some(steam_status_fetcher) = &self.steam_status_fetcher else {
+            return;
+        };
+        match steam_status_fetcher.try_recv_latest() {
+            Some(Ok(statuses)) => {
+                self.steam_status_info = steam_status::statuses_by_region(statuses, &self.servers);
+            }
+            Some(Err(err)) => log::error!("unable to fetch the datacenter status: {}", err),
+            None => {}
+        }
+    }
+
+    /// Apply the profile bound to the running game when the running
+    /// game changes.
+    fn update_running_app(&mut self) {
+        let Some(running_app_watcher) = &self.running_app_watcher else {
+            return;
+        };
+        let Some(Some(app_id)) = running_app_watcher.try_recv_latest() else {
+            return;
+        };
+        log::info!("running game changed to app {}", app_id);
+        let Some(profile) = self.profiles.for_app(app_id) else {
+            return;
+        };
+        if self.active_profile.as_deref() != Some(profile.name.as_str()) {
+            let name = profile.name.clone();
+            self.apply_profile(&name);
+        }
+    }
+
+    /// Load the network datagram configs refreshed by the
+    /// [`ConfigRefresher`], the categories other than the relays are
+    /// kept.
+    fn update_config_refresh(&mut self) {
+        let Some(config_refresher) = &self.config_refresher else {
+            return;
+        };
+        let Some(refresh) = config_refresher.try_iter().last() else {
+            return;
+        };
+        let mut servers = refresh.servers;
+        if servers.get_app_id() != self.servers.get_app_id() {
+            // the app was switched while the config was downloaded
+            return;
+        }
+        [ServerCategory::ConnectionManager, ServerCategory::Content]
+            .into_iter()
+            .for_each(|category| {
+                servers.replace_category(category, self.servers.take_category(category));
+            });
+        let diff = self.servers.diff(&servers);
+        log::info!(
+            "scheduled refresh: revision {} to {}: {}",
+            refresh.old_revision,
+            servers.get_revision(),
+            diff
+        );
+        self.notifiers.notify(Event::ServerListRevisionChanged {
+            old_revision: refresh.old_revision,
+            new_revision: servers.get_revision(),
+        });
+        self.set_servers(servers);
+        self.reconcile_blocked_ips(&diff);
+    }
+
+    /// Detect the relays the game traffic is flowing through every
+    /// [`Self::CONNECTIONS_CHECK_INTERVAL`] and have the worker guard their
+    /// IPs against blocking.
+    fn update_active_relay_guard(&mut self) {
+        if self.active_relay_guard == ActiveRelayGuard::Off
+            || Instant::now() < self.next_connections_check
+        {
+            return;
+        }
+        self.next_connections_check = Instant::now() + Self::CONNECTIONS_CHECK_INTERVAL;
+
+        let remote_ips = match connections::remote_ips() {
+            Ok(remote_ips) => remote_ips,
+            Err(err) => {
+                log::error!(
+                    "unable to detect the current connections, the relay in use is not guarded: {}",
+                    err
+                );
+                self.active_relay_guard = ActiveRelayGuard::Off;
+                return;
+            }
+        };
+        let regions_in_use: Vec<_> = connections::regions_in_use(&self.servers, &remote_ips)
+            .into_iter()
+            .map(|(abr, ips)| (abr.to_string(), ips))
+            .collect();
+        if regions_in_use == self.regions_in_use {
+            return;
+        }
+        if !regions_in_use.is_empty() {
+            log::info!(
+                "game traffic flowing through {}",
+                regions_in_use
+                    .iter()
+                    .map(|(abr, _)| abr.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        self.regions_in_use = regions_in_use;
+        self.worker.send(Command::SetGuarded(self.guarded_ips()));
+    }
+
+    /// Follow the running game and have the worker guard the home
+    /// region and the last unblocked region while it runs, see
+    /// [`MatchGuard`].
+    fn update_match_guard(&mut self) {
+        let Some(game_watcher) = &self.game_watcher else {
+            return;
+        };
+        let Some(running_game) = game_watcher.try_recv_latest() else {
+            return;
+        };
+        self.running_game = running_game;
+        // the home region is picked once per game, so that it does not
+        // change with the pings in the middle of a match
+        self.home_region = running_game.and_then(|_| self.find_home_region());
+        match running_game {
+            Some(app_id) => log::info!(
+                "{} is running, guarding the last unblocked region and the home region {} \
+                 from being blocked",
+                game::name(app_id),
+                self.home_region.as_deref().unwrap_or("(unknown)")
+            ),
+            None => log::info!("no game is running, the regions are no longer guarded"),
+        }
+        self.worker.send(Command::SetGuarded(self.guarded_ips()));
+        self.worker
+            .send(Command::SetLastServerGuarded(running_game.is_some()));
+    }
+
+    /// Get the home region: the one nearest to the location of the
+    /// user if it is known, the one with the lowest ping otherwise.
+    fn find_home_region(&self) -> Option<String> {
+        self.recommend_nearest(1).regions.into_iter().next()
+    }
+
+    /// Recommend the `n` regions nearest to the location of the user
+    /// if it is known, the `n` with the lowest ping otherwise.
+    fn recommend_nearest(&self, n: usize) -> Recommendation {
+        match &self.location {
+            Some(location) => Recommendation::by_location(
+                &self.servers,
+                [location.longitude, location.latitude],
+                n,
+            ),
+            None => Recommendation::by_latency(
+                &self.servers,
+                |server| {
+                    self.grid_cache
+                        .region_ping_summaries
+                        .get(server.get_abr())?
+                        .ping
+                },
+                n,
+            ),
+        }
+    }
+
+    /// Get the IPs of the regions in use and of the home region, see
+    /// [`Self::update_active_relay_guard()`] and
+    /// [`Self::update_match_guard()`].
+    fn guarded_ips(&self) -> HashSet<IpAddr> {
+        let home_ips = self
+            .home_region
+            .as_deref()
+            .and_then(|abr| self.servers.get_server(abr))
+            .map(ServerInfo::get_ips)
+            .unwrap_or_default();
+        self.regions_in_use
+            .iter()
+            .flat_map(|(_, ips)| ips.iter())
+            .chain(home_ips)
+            .copied()
+            .collect()
+    }
+
+    /// Create the UI to confirm the blocks refused by the guard of the
+    /// relay in use or by the [`MatchGuard`].
+    fn ui_refused_blocks(&mut self, ctx: &egui::Context) {
+        let Some((server, ips)) = self.refused_blocks.first().cloned() else {
+            return;
+        };
+        let game_name = self
+            .running_game
+            .map_or_else(|| "the game".to_string(), game::name);
+        let message = if self.regions_in_use.iter().any(|(abr, _)| *abr == server) {
+            format!(
+                "The game traffic is flowing through {} ({} IPs), blocking it disconnects \
+                 the current match.",
+                server,
+                ips.len()
+            )
+        } else if self.home_region.as_deref() == Some(server.as_str()) {
+            format!(
+                "{} is your home region, blocking it while {} is running may disconnect the \
+                 current match.",
+                server, game_name
+            )
+        } else {
+            format!(
+                "Blocking {} leaves no region unblocked, while {} is running this \
+                 disconnects the current match.",
+                server, game_name
+            )
+        };
+        egui::Window::new("Confirm Block")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(message);
+                ui.horizontal(|ui| {
+                    if ui.button("Block Anyway").clicked() {
+                        // lift the guards for this block only
+                        self.worker.send(Command::SetGuarded(HashSet::new()));
+                        self.worker.send(Command::SetLastServerGuarded(false));
+                        self.worker.send(Command::SetBlocked {
+                            server: server.clone(),
+                            ips: ips.clone(),
+                            blocked: true,
+                        });
+                        self.worker.send(Command::SetGuarded(self.guarded_ips()));
+                        self.worker
+                            .send(Command::SetLastServerGuarded(self.running_game.is_some()));
+                        self.refused_blocks.remove(0);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.refused_blocks.remove(0);
+                    }
+                });
+            });
+    }
+
+    /// Queue the lookup of the AS of the relay IPs not looked up yet
+    /// and store the resolved ones, if enabled.
+    fn update_asn_infos(&mut self) {
+        let Some(asn_resolver) = &self.asn_resolver else {
+            return;
+        };
+        self.servers
+            .get_servers_in(ServerCategory::Relay)
+            .flat_map(|server| server.get_ips())
+            .for_each(|ip| {
+                if self.asn_requested.insert(*ip) {
+                    asn_resolver.resolve(*ip);
+                }
+            });
+        self.asn_infos.extend(asn_resolver.try_iter());
+    }
+
+    /// Save the view state if it changed since it was last saved.
+    fn update_view_state(&mut self) {
+        if self.view_state == self.saved_view_state {
+            return;
+        }
+        if let Err(err) = self.view_state.save() {
+            log::error!("unable to save the view state: {}", err);
+        }
+        // not retried on failure, until the next change
+        self.saved_view_state = self.view_state.clone();
+    }
+
+    /// Apply and save the settings changed since the last call.
+    fn update_settings(&mut self) {
+        if self.config == self.saved_config {
+            return;
+        }
+        if self.config.ping_timeout_ms != self.saved_config.ping_timeout_ms {
+            self.worker.set_ping_timeout(self.config.ping_timeout());
+        }
+        if self.config.config_refresh_hours != self.saved_config.config_refresh_hours {
+            let interval = Duration::from_secs(self.config.config_refresh_hours * 60 * 60);
+            if self.config.config_refresh_hours == 0 {
+                self.config_refresher = None;
+            } else if let Some(config_refresher) = &self.config_refresher {
+                config_refresher.set_interval(interval);
+            } else if !self.local_network_datagram_config {
+                self.config_refresher = Some(ConfigRefresher::new(
+                    self.servers.get_app_id(),
+                    self.servers.get_revision(),
+                    interval,
+                    self.waker.clone(),
+                ));
+            }
+        }
+        if self.config.map_tile_url != self.saved_config.map_tile_url {
+            // created again from the new URL when the map is shown
+            self.map_tiles = None;
+        }
+        if let Err(err) = self.config.save() {
+            log::error!("unable to save the config: {}", err);
+        }
+        // not retried on failure, until the next change
+        self.saved_config = self.config.clone();
+    }
+
+    /// Set the callback called when the background threads have new
+    /// data for [`Self::update()`], it is called from the background
+    /// threads.
+    pub fn set_waker(&self, wake_fn: impl Fn() + Send + Sync + 'static) {
+        self.waker.set(wake_fn);
+    }
+
+    /// Update all information that must happen very so often. eg:
+    /// ping information receiving
+    pub fn update(&mut self) {
+        self.update_worker_responses();
+        self.update_steam_status_info();
+        self.update_running_app();
+        self.update_config_refresh();
+        self.update_view_state();
+        self.update_settings();
+        self.update_active_relay_guard();
+        self.update_match_guard();
+        #[cfg(feature = "hotkey")]
+        if self
+            .hotkey
+            .as_ref()
+            .is_some_and(|hotkey| hotkey.presses() % 2 == 1)
+        {
+            self.toggle_active_profile();
+        }
+        if self
+            .trial_block
+            .as_ref()
+            .is_some_and(|trial_block| trial_block.is_done())
+        {
+            self.finish_trial_block();
+        }
+        self.port_checker.try_iter().for_each(|report| {
+            self.port_checks_pending.remove(&report.region);
+            self.port_reports.insert(report.region.clone(), report);
+        });
+        self.update_asn_infos();
+        if let Some(steam_pings) = &mut self.steam_pings {
+            if steam_pings.update() {
+                self.grid_cache.update_steam_pings(steam_pings);
+            }
+        }
+    }
+
+    /// Calculate the total ping for the given ip. Returns the rtt, total
+    /// number of packets number of packets dropped.
+    ///
+    /// note: this returns the total ping not the average ping of the
+    /// packets
+    fn calculate_total_ping_for_ip(
+        ping_info: &HashMap<IpAddr, VecDeque<Result<PingInfo, ping::Error>>>,
+        ip: IpAddr,
+    ) -> (Duration, usize, usize) {
+        ping_info
+            .get(&ip)
+            .map(|list| {
+                let (total_ping, num_lost_packets) =
+                    list.iter()
+                        .fold((Duration::ZERO, 0), |acc, info| match info {
+                            Ok(info) => (acc.0 + info.get_rtt(), acc.1),
+                            Err(_) => (acc.0, acc.1 + 1),
+                        });
+
+                (total_ping, list.len(), num_lost_packets)
+            })
+            .unwrap_or((Duration::ZERO, 0, 0))
+    }
+
+    /// Enable all servers of the category shown in the grid.
+    fn enable_all_servers(&self) {
+        self.servers
+            .get_servers_in(self.view_state.grid.category)
+            .for_each(|server| {
+                Self::enable_server(server, &self.worker);
+            });
+    }
+
+    /// Disable all servers of the category shown in the grid.
+    fn disable_all_servers(&self) {
+        self.servers
+            .get_servers_in(self.view_state.grid.category)
+            .for_each(|server| {
+                Self::disable_server(server, &self.worker);
+            });
+    }
+
+    /// Enable the given server.
+    fn enable_server(server: &ServerInfo, worker: &Worker) {
+        worker.send(Command::SetBlocked {
+            server: server.get_abr().to_string(),
+            ips: server.get_ips().to_vec(),
+            blocked: false,
+        });
+    }
+
+    /// Disable the given server.
+    fn disable_server(server: &ServerInfo, worker: &Worker) {
+        worker.send(Command::SetBlocked {
+            server: server.get_abr().to_string(),
+            ips: server.get_ips().to_vec(),
+            blocked: true,
+        });
+    }
+
+    /// Deprioritize (or restore) the given server, see
+    /// [`Command::SetDeprioritized`].
+    fn deprioritize_server(server: &ServerInfo, deprioritized: bool, worker: &Worker) {
+        worker.send(Command::SetDeprioritized {
+            server: server.get_abr().to_string(),
+            ips: server.get_ips().to_vec(),
+            deprioritized,
+        });
+    }
+
+    /// Block `percent` percent of the IPs of the given server, the
+    /// other IPs are unblocked.
+    fn partial_block_server(server: &ServerInfo, percent: u8, worker: &Worker) {
+        let (blocked, unblocked) = server.partial_block_ips(percent);
+        log::info!(
+            "blocking {}% ({}/{}) of the IPs of {}",
+            percent,
+            blocked.len(),
+            server.get_ips().len(),
+            server.get_abr()
+        );
+        [(blocked, true), (unblocked, false)]
+            .into_iter()
+            .filter(|(ips, _)| !ips.is_empty())
+            .for_each(|(ips, blocked)| {
+                worker.send(Command::SetBlocked {
+                    server: server.get_abr().to_string(),
+                    ips,
+                    blocked,
+                });
+            });
+    }
+
+    /// Enable the given IP.
+    fn enable_ip(ip: IpAddr, server: &ServerInfo, worker: &Worker) {
+        worker.send(Command::SetBlocked {
+            server: server.get_abr().to_string(),
+            ips: vec![ip],
+            blocked: false,
+        });
+    }
+
+    /// Disable the given IP.
+    fn disable_ip(ip: IpAddr, server: &ServerInfo, worker: &Worker) {
+        worker.send(Command::SetBlocked {
+            server: server.get_abr().to_string(),
+            ips: vec![ip],
+            blocked: true,
+        });
+    }
+
+    /// Get the [`ServerSelectionStatus`] for the servers of the given
+    /// category. The returned vector will have the elements correspond
+    /// exactly with [`Servers::get_servers_in()`] (so zipping the
+    /// result is possible).
+    fn servers_selection_status(
+        servers: &Servers,
+        category: ServerCategory,
+        ip_selection_status: &HashMap<IpAddr, bool>,
+    ) -> Vec<ServerSelectionStatus> {
+        servers
+            .get_servers_in(category)
+            .map(|server| {
+                let num_ips_selected = server
+                    .get_ips()
+                    .iter()
+                    .filter(|ip| *ip_selection_status.get(*ip).unwrap_or(&false))
+                    .count();
+
+                if num_ips_selected == 0 {
+                    ServerSelectionStatus::None
+                } else if num_ips_selected == server.get_ips().len() {
+                    ServerSelectionStatus::All
+                } else {
+                    ServerSelectionStatus::Some
+                }
+            })
+            .collect::<Vec<_>>()
+    }
+
+    /// Enable the IPs that are currently selected.
+    fn enable_selected_ips(&self) {
+        let servers_selected = Self::servers_selection_status(
+            &self.servers,
+            self.view_state.grid.category,
+            &self.ip_selection_status,
+        );
+        if servers_selected
+            .iter()
+            .all(|selected| matches!(selected, ServerSelectionStatus::All))
+        {
+            // this is for optimization, if all the
+            // servers are selected, then it is faster
+            // to enable all the servers
+            self.enable_all_servers();
+        } else {
+            self.servers
+                .get_servers_in(self.view_state.grid.category)
+                .zip(servers_selected.into_iter())
+                .for_each(|(server, status)| match status {
+                    ServerSelectionStatus::All => {
+                        Self::enable_server(server, &self.worker);
+                    }
+                    ServerSelectionStatus::Some => {
+                        server
+                            .get_ips()
+                            .iter()
+                            .filter(|ip| *self.ip_selection_status.get(ip).unwrap_or(&false))
+                            .for_each(|ip| Self::enable_ip(*ip, server, &self.worker));
+                    }
+                    ServerSelectionStatus::None => {
+                        // do nothing
+                    }
+                });
+        }
+    }
+
+    /// Disable the IPs that are currently selected.
+    fn disable_selected_ips(&mut self) {
+        let servers_selected = Self::servers_selection_status(
+            &self.servers,
+            self.view_state.grid.category,
+            &self.ip_selection_status,
+        );
+        if servers_selected
+            .iter()
+            .all(|selected| matches!(selected, ServerSelectionStatus::All))
+        {
+            // this is for optimization, if all the
+            // servers are selected, then it is faster
+            // to enable all the servers
+            self.disable_all_servers();
+        } else {
+            self.servers
+                .get_servers_in(self.view_state.grid.category)
+                .zip(servers_selected.into_iter())
+                .for_each(|(server, status)| match status {
+                    ServerSelectionStatus::All => {
+                        Self::disable_server(server, &self.worker);
+                    }
+                    ServerSelectionStatus::Some => {
+                        server
+                            .get_ips()
+                            .iter()
+                            .filter(|ip| *self.ip_selection_status.get(ip).unwrap_or(&false))
+                            .for_each(|ip| Self::disable_ip(*ip, server, &self.worker));
+                    }
+                    ServerSelectionStatus::None => {
+                        // do nothing
+                    }
+                });
+        }
+    }
+
+    /// Get the rows of the grid, filtered and sorted like the grid,
+    /// for the front-ends other than the GUI (eg: [`crate::tui`]).
+    /// Cheap enough to be called every frame, unlike
+    /// [`Self::region_statuses()`].
+    pub fn region_rows(&self) -> Vec<RegionRow> {
+        let mut servers = Self::grid_servers(&self.servers, &self.view_state, &self.grid_filter);
+        Self::sort_grid_servers(
+            &mut servers,
+            &self.view_state.grid,
+            &self.grid_cache,
+            &self.server_status_info,
+        );
+        servers
+            .into_iter()
+            .map(|server| {
+                let state = self
+                    .server_status_info
+                    .get(server.get_abr())
+                    .cloned()
+                    .unwrap_or(ServerState::Unknown);
+                let summary = self
+                    .grid_cache
+                    .region_ping_summaries
+                    .get(server.get_abr())
+                    .filter(|_| state != ServerState::AllDisabled)
+                    .unwrap_or(&PingSummary::NONE);
+                RegionRow {
+                    region: server.get_abr().to_string(),
+                    description: server.label().map(Cow::into_owned),
+                    state_label: state.label(server.get_ips().len()).into_owned(),
+                    state,
+                    ping_label: summary.ping_label.to_string(),
+                    loss_label: summary.loss_label.to_string(),
+                    jitter_label: summary.jitter_label.to_string(),
+                    score_label: summary.score_label.to_string(),
+                }
+            })
+            .collect()
+    }
+
+    /// Block (or unblock) all the IPs of the region.
+    pub fn set_region_blocked(&self, abr: &str, blocked: bool) -> Result<(), Error> {
+        let server = self
+            .servers
+            .get_servers()
+            .iter()
+            .find(|server| server.get_abr() == abr)
+            .ok_or_else(|| Error::UnknownRegion(abr.to_string()))?;
+        if blocked {
+            Self::disable_server(server, &self.worker);
+        } else {
+            Self::enable_server(server, &self.worker);
+        }
+        Ok(())
+    }
+
+    /// Get the status of every region once the pending firewall
+    /// changes are applied. The ping is the mean of the last ping of
+    /// the IPs of the region, Steam's ping if none were pinged yet.
+    pub fn region_statuses(&self) -> Result<Vec<RegionStatus>, Error> {
+        self.worker.flush();
+        let blocked = self.firewall.list_blocked()?;
+        Ok(self
+            .servers
+            .get_servers()
+            .iter()
+            .map(|server| {
+                let rtts: Vec<_> = server
+                    .get_ips()
+                    .iter()
+                    .filter_map(|ip| self.ping_info.get(ip)?.front()?.as_ref().ok())
+                    .map(PingInfo::get_rtt)
+                    .collect();
+                let ping = if rtts.is_empty() {
+                    self.steam_pings
+                        .as_ref()
+                        .and_then(|steam_pings| steam_pings.get(server.get_abr()))
+                } else {
+                    Some(rtts.iter().sum::<Duration>() / rtts.len() as u32)
+                };
+                RegionStatus::new(server, &blocked, ping)
+            })
+            .collect())
+    }
+
+    /// Keep only the regions of the preset: the other relay regions
+    /// are disabled and the kept ones are enabled.
+    pub fn apply_preset(&mut self, preset: &Preset) {
+        let kept = preset.kept_regex(&self.servers);
+        let all = regex::Regex::new("").expect("empty regex is valid");
+        self.disable_matching(&all, Some(&kept));
+        self.enable_matching(&kept, None);
+        log::info!(
+            "applied preset `{}`: kept {}",
+            preset.name,
+            preset
+                .kept_regions(&self.servers)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    /// Create the buttons applying the presets, see
+    /// [`Self::apply_preset()`].
+    fn ui_presets(&mut self, ui: &mut egui::Ui) {
+        if self.config.presets.is_empty() {
+            return;
+        }
+        let mut apply = None;
+        ui.horizontal(|ui| {
+            ui.label("Presets:");
+            self.config.presets.iter().for_each(|preset| {
+                let kept: Vec<_> = preset.kept_regions(&self.servers).collect();
+                if ui
+                    .button(&preset.name)
+                    .on_hover_text(format!(
+                        "Keep {} and block all the other regions",
+                        if kept.is_empty() {
+                            "no region".to_string()
+                        } else {
+                            kept.join(", ")
+                        }
+                    ))
+                    .clicked()
+                {
+                    apply = Some(preset.clone());
+                }
+            });
+        });
+        if let Some(preset) = apply {
+            self.apply_preset(&preset);
+        }
+    }
+
+    /// Enable the matching IPs of the server regions matching the
+    /// given regex.
+    pub fn enable_matching(&mut self, regex: &regex::Regex, exclude_regex: Option<&regex::Regex>) {
+        self.servers
+            .get_servers_in(ServerCategory::Relay)
+            .filter(|server| {
+                regex.is_match(server.get_abr())
+                    && !exclude_regex.is_some_and(|exclude| exclude.is_match(server.get_abr()))
+            })
+            .for_each(|server| {
+                Self::enable_server(server, &self.worker);
+            });
+    }
+
+    /// Detect the location of the user and suggest the regions to
+    /// block.
+    fn suggest_regions_from_location(&mut self) {
+        self.location_suggestion = match geoip::detect_location() {
+            Ok(location) => {
+                let block_list =
+                    geoip::suggest_block_list(&self.servers, &location, self.keep_nearest_regions);
+                log::info!(
+                    "suggested regions to block from location `{}`: {:?}",
+                    location,
+                    block_list.regions().collect::<Vec<_>>()
+                );
+                self.location = Some(location.clone());
+                self.recommendation = Some(self.recommend_nearest(self.keep_nearest_regions));
+                LocationSuggestion::Suggested {
+                    location,
+                    block_list,
+                }
+            }
+            Err(err) => {
+                log::error!("unable to detect the location: {}", err);
+                LocationSuggestion::None
+            }
+        };
+    }
+
+    /// Create the window of the block/unblock history, newest first.
+    fn ui_history(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_history;
+        egui::Window::new("History")
+            .open(&mut open)
+            .default_height(400.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Region:");
+                    ui.text_edit_singleline(&mut self.history_filter);
+                    ui.checkbox(&mut self.history_this_session, "This session only");
+                });
+                ui.separator();
+
+                let session = self.history.session();
+                let filter = self.history_filter.trim().to_lowercase();
+                let entries: Vec<_> = self
+                    .history
+                    .entries()
+                    .iter()
+                    .rev()
+                    .filter(|entry| !self.history_this_session || entry.session == session)
+                    .filter(|entry| filter.is_empty() || entry.region.contains(&filter))
+                    .collect();
+                if entries.is_empty() {
+                    ui.label("No blocks or unblocks yet");
+                    return;
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("history_grid")
+                        .num_columns(4)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.label("Time (UTC)");
+                            ui.label("Region");
+                            ui.label("Change");
+                            ui.label("Reason");
+                            ui.end_row();
+
+                            let mut prev_session = None;
+                            entries.into_iter().for_each(|entry| {
+                                if prev_session != Some(entry.session) {
+                                    prev_session = Some(entry.session);
+                                    ui.label(
+                                        egui::RichText::new(format!(
+                                            "Session of {}{}",
+                                            history::format_unix_time(entry.session),
+                                            if entry.session == session {
+                                                " (current)"
+                                            } else {
+                                                ""
+                                            }
+                                        ))
+                                        .strong(),
+                                    );
+                                    ui.end_row();
+                                }
+                                ui.label(history::format_unix_time(entry.time));
+                                ui.label(&entry.region);
+                                ui.label(if entry.blocked {
+                                    "Blocked"
+                                } else {
+                                    "Unblocked"
+                                });
+                                ui.label(entry.reason.as_deref().unwrap_or("By the user"));
+                                ui.end_row();
+                            });
+                        });
+                });
+            });
+        self.show_history = open;
+    }
+
+    /// Get the health of the background threads along with their
+    /// names.
+    fn thread_healths(&self) -> impl Iterator<Item = (&'static str, &Health)> {
+        std::iter::once(("Worker (pings, status checks)", self.worker.health())).chain(
+            self.steam_status_fetcher
+                .as_ref()
+                .map(|fetcher| ("Steam status", fetcher.health())),
+        )
+    }
+
+    /// Restart the worker thread, the servers are watched again.
+    fn restart_worker(&mut self) {
+        if let Err(err) = self.worker.restart() {
+            log::error!("unable to restart the worker: {}", err);
+            return;
+        }
+        self.watch_all_servers();
+        self.worker
+            .send(Command::SetVisible(self.visible_servers.clone()));
+        self.worker.send(Command::SetGuarded(self.guarded_ips()));
+        self.worker
+            .send(Command::SetLastServerGuarded(self.running_game.is_some()));
+        self.worker.send(Command::SetWatchdog(self.rules_watchdog));
+        // the acknowledgements of the previous worker are never
+        // received
+        std::mem::take(&mut self.pending_after_ack)
+            .into_iter()
+            .for_each(|(_, action)| self.after_worker_ack(action));
+    }
+
+    /// Create the settings window, see [`Config`].
+    fn ui_settings(&mut self, ctx: &egui::Context) {
+        if self.applied_theme != Some(self.config.theme) {
+            ctx.set_visuals(self.config.theme.visuals());
+            self.applied_theme = Some(self.config.theme);
+        }
+
+        let mut open = self.show_settings;
+        let apps = self.pickable_apps();
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("settings_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Ping timeout:");
+                        ui.add(
+                            egui::DragValue::new(&mut self.config.ping_timeout_ms)
+                                .range(50..=5000)
+                                .suffix(" ms"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Pings kept per IP:")
+                            .on_hover_text("Used for the statistics and the graphs");
+                        ui.add(
+                            egui::DragValue::new(&mut self.config.max_pings_per_ip)
+                                .range(Config::MAX_PINGS_PER_IP_RANGE),
+                        );
+                        ui.end_row();
+
+                        ui.label("Start in:");
+                        self.config
+                            .app_mode
+                            .ui(ui, egui::Id::new("settings_app_mode"));
+                        ui.end_row();
+
+                        ui.label("Game:").on_hover_text("Loaded at the start");
+                        if let Some(app_id) = Self::ui_app_picker(
+                            ui,
+                            "settings_app_picker",
+                            &apps,
+                            self.config.app_id,
+                        ) {
+                            self.config.app_id = app_id;
+                        }
+                        ui.end_row();
+
+                        ui.label("Theme:");
+                        egui::ComboBox::from_id_source("settings_theme")
+                            .selected_text(self.config.theme.name())
+                            .show_ui(ui, |ui| {
+                                Theme::all().into_iter().for_each(|theme| {
+                                    ui.selectable_value(
+                                        &mut self.config.theme,
+                                        theme,
+                                        theme.name(),
+                                    );
+                                });
+                            });
+                        ui.end_row();
+
+                        ui.label("Server list refresh:").on_hover_text(
+                            "Interval between the scheduled downloads of the server list, \
+                             0 disables them",
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut self.config.config_refresh_hours)
+                                .range(0..=24 * 7)
+                                .suffix(" h"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Restore last profile:")
+                            .on_hover_text("Apply the last applied profile at the start");
+                        ui.checkbox(&mut self.config.restore_profile, "");
+                        ui.end_row();
+
+                        ui.label("Offline map:")
+                            .on_hover_text("Start with the map drawn from the bundled coastlines");
+                        ui.checkbox(&mut self.config.offline_map, "");
+                        ui.end_row();
+
+                        ui.label("Map tile server:").on_hover_text(
+                            "URL of the map tiles, `{z}`, `{x}` and `{y}` are replaced by \
+                             the coordinates of the tile. OpenStreetMap if empty",
+                        );
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.config.map_tile_url)
+                                .hint_text("https://tiles.example.com/{z}/{x}/{y}.png"),
+                        );
+                        ui.end_row();
+                    });
+
+                if let Ok(file_path) = file_ops::get_config_file_path() {
+                    ui.separator();
+                    ui.weak(format!("Saved to `{}`", file_path.display()));
+                }
+            });
+        self.show_settings = open;
+    }
+
+    /// Create the diagnostics window, the health of the background
+    /// threads.
+    fn ui_diagnostics(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_diagnostics;
+        let mut restart = None;
+        let mut audit = false;
+        let mut remove_orphaned = false;
+        egui::Window::new("Diagnostics")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("diagnostics_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Thread");
+                        ui.label("Status");
+                        ui.label("Last activity");
+                        ui.end_row();
+
+                        self.thread_healths()
+                            .enumerate()
+                            .for_each(|(index, (name, health))| {
+                                let status = health.status();
+                                ui.label(name);
+                                ui.label(egui::RichText::new(status.name()).color(match status {
+                                    ThreadStatus::Running => egui::Color32::from_rgb(0, 160, 0),
+                                    ThreadStatus::Stalled => egui::Color32::from_rgb(200, 160, 0),
+                                    ThreadStatus::Panicked => egui::Color32::from_rgb(200, 0, 0),
+                                }));
+                                ui.label(format!(
+                                    "{} s ago",
+                                    health.last_activity().elapsed().as_secs()
+                                ));
+                                if ui
+                                    .add_enabled(
+                                        status != ThreadStatus::Running,
+                                        egui::Button::new("Restart"),
+                                    )
+                                    .clicked()
+                                {
+                                    restart = Some(index);
+                                }
+                                ui.end_row();
+                            });
+                    });
+
+                ui.separator();
+                egui::CollapsingHeader::new("Performance")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        if ui.button("Reset").clicked() {
+                            timings::reset();
+                        }
+                        let timings = timings::snapshot();
+                        if timings.is_empty() {
+                            ui.label("No operations timed yet");
+                            return;
+                        }
+                        egui::Grid::new("timings_grid")
+                            .num_columns(5)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.label("Operation");
+                                ui.label("Count");
+                                ui.label("Total");
+                                ui.label("Mean");
+                                ui.label("Max");
+                                ui.end_row();
+
+                                timings.iter().for_each(|(operation, timing)| {
+                                    ui.label(*operation);
+                                    ui.label(timing.count.to_string());
+                                    ui.label(timings::format_duration(timing.total));
+                                    ui.label(timings::format_duration(timing.mean()));
+                                    ui.label(timings::format_duration(timing.max))
+                                        .on_hover_text(format!(
+                                            "Last: {}",
+                                            timings::format_duration(timing.last)
+                                        ));
+                                    ui.end_row();
+                                });
+                            });
+                    });
+
+                egui::CollapsingHeader::new("Firewall Rules").show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        audit = ui
+                            .button("Audit")
+                            .on_hover_text(
+                                "Find the rules of the app whose IPs are not in the server \
+                                 list anymore",
+                            )
+                            .clicked();
+                        if let Some(firewall_audit) = &self.firewall_audit {
+                            remove_orphaned = ui
+                                .add_enabled(
+                                    !firewall_audit.orphaned.is_empty(),
+                                    egui::Button::new("Remove Orphaned"),
+                                )
+                                .clicked();
+                        }
+                    });
+                    let Some(firewall_audit) = &self.firewall_audit else {
+                        return;
+                    };
+                    ui.label(format!(
+                        "{} rules, {} orphaned",
+                        firewall_audit.known.len() + firewall_audit.orphaned.len(),
+                        firewall_audit.orphaned.len()
+                    ));
+                    firewall_audit.orphaned.iter().for_each(|ip| {
+                        ui.label(ip.to_string());
+                    });
+                });
+            });
+        if open {
+            // keep the last activity and the timings up to date
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
+        self.show_diagnostics = open;
+
+        if audit {
+            self.audit_firewall();
+        }
+        if remove_orphaned {
+            self.remove_orphaned_rules();
+        }
+
+        match restart {
+            Some(0) => self.restart_worker(),
+            Some(_) => {
+                if let Some(fetcher) = &mut self.steam_status_fetcher {
+                    fetcher.restart();
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Audit the firewall rules against the IPs of the loaded servers
+    /// (of every app tab), see [`firewall::audit()`].
+    fn audit_firewall(&mut self) {
+        let known_ips: HashSet<IpAddr> = std::iter::once(&self.servers)
+            .chain(self.app_tab_servers.values())
+            .flat_map(|servers| servers.get_servers())
+            .flat_map(|server| server.get_ips().iter().copied())
+            .collect();
+        match firewall::audit(&self.firewall, &known_ips) {
+            Ok(audit) => {
+                log::info!(
+                    "firewall audit: {} rules, {} orphaned",
+                    audit.known.len() + audit.orphaned.len(),
+                    audit.orphaned.len()
+                );
+                self.firewall_audit = Some(audit);
+            }
+            Err(err) => log::error!("unable to audit the firewall rules: {}", err),
+        }
+    }
+
+    /// Remove the rules of the orphaned IPs of the last audit, through
+    /// the worker so that the watchdog does not apply them again.
+    fn remove_orphaned_rules(&mut self) {
+        let Some(audit) = self.firewall_audit.take() else {
+            return;
+        };
+        log::info!("removing {} orphaned rules", audit.orphaned.len());
+        self.worker.send(Command::SetBlocked {
+            server: "orphaned rules".to_string(),
+            ips: audit.orphaned,
+            blocked: false,
+        });
+    }
+
+    /// Create the UI for the [`LocationSuggestion`].
+    fn ui_location_suggestion(&mut self, ctx: &egui::Context) {
+        let mut apply = None;
+        match &self.location_suggestion {
+            LocationSuggestion::None => return,
+            LocationSuggestion::Ask => {
+                let mut detect = None;
+                let mut app_id = None;
+                let apps = self.pickable_apps();
+                egui::Window::new("Suggest Regions").show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Game:");
+                        app_id = Self::ui_app_picker(
+                            ui,
+                            "first_run_app_picker",
+                            &apps,
+                            self.servers.get_app_id(),
+                        );
+                    });
+                    ui.label(
+                        "Detect your approximate location from your public IP to suggest \
+                         which regions to block? This sends a request to ipapi.co.",
+                    );
+                    ui.horizontal(|ui| {
+                        if ui.button("Detect").clicked() {
+                            detect = Some(true);
+                        }
+                        if ui.button("No Thanks").clicked() {
+                            detect = Some(false);
+                        }
+                    });
+                });
+                if let Some(app_id) = app_id {
+                    if let Err(err) = self.switch_app(app_id) {
+                        log::error!("unable to load the servers of app {}: {}", app_id, err);
+                    }
+                }
+                match detect {
+                    Some(true) => self.suggest_regions_from_location(),
+                    Some(false) => self.location_suggestion = LocationSuggestion::None,
+                    None => {}
+                }
+                return;
+            }
+            LocationSuggestion::Suggested {
+                location,
+                block_list,
+            } => {
+                egui::Window::new("Suggested Regions").show(ctx, |ui| {
+                    ui.label(format!("Detected location: {}", location));
+                    ui.label(format!(
+                        "Keep the nearest {} regions and block:",
+                        self.keep_nearest_regions
+                    ));
+                    ui.label(block_list.regions().collect::<Vec<_>>().join(", "));
+                    ui.horizontal(|ui| {
+                        if ui.button("Block").clicked() {
+                            apply = Some(true);
+                        }
+                        if ui.button("Dismiss").clicked() {
+                            apply = Some(false);
+                        }
+                    });
+                });
+            }
+        }
+
+        if let Some(apply) = apply {
+            let location_suggestion =
+                std::mem::replace(&mut self.location_suggestion, LocationSuggestion::None);
+            if let (true, LocationSuggestion::Suggested { block_list, .. }) =
+                (apply, location_suggestion)
+            {
+                self.apply_block_list(&block_list);
+            }
+        }
+    }
+
+    /// Block the regions in the block list and unblock all the other
+    /// regions. The regions on which the block list and the last known
+    /// status of the regions disagree are logged.
+    pub fn apply_block_list(&self, block_list: &BlockList) {
+        let current_block_list = BlockList::new(
+            self.server_status_info
+                .iter()
+                .filter(|(_, status)| matches!(status, ServerState::AllDisabled))
+                .map(|(region, _)| region.clone()),
+        );
+        let diff = block_list.diff(&current_block_list);
+        if !diff.only_in_first.is_empty() {
+            log::info!("block list: blocking {:?}", diff.only_in_first);
+        }
+        if !diff.only_in_second.is_empty() {
+            log::info!("block list: unblocking {:?}", diff.only_in_second);
+        }
+
+        self.servers
+            .get_servers_in(ServerCategory::Relay)
+            .for_each(|server| {
+                if block_list.contains(server.get_abr()) {
+                    Self::disable_server(server, &self.worker);
+                } else {
+                    Self::enable_server(server, &self.worker);
+                }
+            });
+    }
+
+    /// Replace the servers with `servers`, returns the replaced
+    /// servers. The worker watches the new servers, the IPs in both
+    /// are pinged without interruption. The cached grid data is reset
+    /// for them.
+    fn set_servers(&mut self, servers: Servers) -> Servers {
+        self.grid_cache = GridCache::new(&servers);
+        if let Some(steam_pings) = &self.steam_pings {
+            self.grid_cache.update_steam_pings(steam_pings);
+        }
+        servers
+            .get_servers()
+            .iter()
+            .flat_map(|server| server.get_ips())
+            .for_each(|ip| {
+                self.ip_selection_status.entry(*ip).or_insert(false);
+            });
+        let previous_servers = std::mem::replace(&mut self.servers, servers);
+        if let Some(config_refresher) = &self.config_refresher {
+            config_refresher.set_app(self.servers.get_app_id(), self.servers.get_revision());
+        }
+        self.worker
+            .send(Command::SetPortRanges(self.servers.port_ranges()));
+        self.worker.send(Command::ReplaceServers(
+            self.servers
+                .get_servers()
+                .iter()
+                .map(|info| (info.get_abr().to_string(), info.get_ips().to_vec()))
+                .collect(),
+        ));
+        self.grid_cache.update_ping_summaries(
+            &self.servers,
+            &self.ping_info,
+            &self.loss_stats,
+            &self.ping_info.keys().copied().collect(),
+            &self.score_weights,
+        );
+        previous_servers
+    }
+
+    /// Load the relays of the network datagram config of the given
+    /// Steam app, the other categories are kept. The app gets a tab,
+    /// the relays of the apps with a tab are kept loaded.
+    fn switch_app(&mut self, app_id: u32) -> Result<(), Error> {
+        let mut servers = match self.app_tab_servers.remove(&app_id) {
+            Some(servers) => servers,
+            None => Servers::new_for_app(None::<PathBuf>, app_id)?,
+        };
+        [ServerCategory::ConnectionManager, ServerCategory::Content]
+            .into_iter()
+            .for_each(|category| {
+                servers.replace_category(category, self.servers.take_category(category));
+            });
+        log::info!("switched to the servers of app {}", app_id);
+        self.config.app_id = app_id;
+        let previous_servers = self.set_servers(servers);
+        if self.app_tabs.contains(&previous_servers.get_app_id()) {
+            self.app_tab_servers
+                .insert(previous_servers.get_app_id(), previous_servers);
+        }
+        if !self.app_tabs.contains(&app_id) {
+            self.app_tabs.push(app_id);
+        }
+        Ok(())
+    }
+
+    /// Get the name of the Steam app, from the installed games or the
+    /// known SDR games if possible.
+    fn app_name(&self, app_id: u32) -> String {
+        self.pickable_apps()
+            .iter()
+            .find(|app| app.app_id == app_id)
+            .map_or_else(|| format!("App {}", app_id), |app| app.to_string())
+    }
+
+    /// Create the tabs of the Steam apps, shown when more than one app
+    /// is loaded.
+    fn ui_app_tabs(&mut self, ui: &mut egui::Ui) {
+        if self.app_tabs.len() < 2 {
+            return;
+        }
+        let loaded_app_id = self.servers.get_app_id();
+        let mut switch_to = None;
+        let mut close = None;
+        ui.horizontal(|ui| {
+            self.app_tabs.iter().for_each(|app_id| {
+                let relays: HashSet<_> = match self.app_tab_servers.get(app_id) {
+                    Some(servers) => servers,
+                    None => &self.servers,
+                }
+                .get_servers_in(ServerCategory::Relay)
+                .flat_map(|server| server.get_ips())
+                .collect();
+                let num_shared = self
+                    .app_tabs
+                    .iter()
+                    .filter(|other_app_id| *other_app_id != app_id)
+                    .filter_map(
+                        |other_app_id| match self.app_tab_servers.get(other_app_id) {
+                            Some(servers) => Some(servers),
+                            None => (*other_app_id == loaded_app_id).then_some(&self.servers),
+                        },
+                    )
+                    .flat_map(|servers| servers.get_servers_in(ServerCategory::Relay))
+                    .flat_map(|server| server.get_ips())
+                    .filter(|ip| relays.contains(ip))
+                    .collect::<HashSet<_>>()
+                    .len();
+                if ui
+                    .selectable_label(*app_id == loaded_app_id, self.app_name(*app_id))
+                    .on_hover_text(format!(
+                        "{} relays, {} shared with the other tabs (blocked in every tab)",
+                        relays.len(),
+                        num_shared
+                    ))
+                    .clicked()
+                    && *app_id != loaded_app_id
+                {
+                    switch_to = Some(*app_id);
+                }
+                if *app_id != loaded_app_id
+                    && ui
+                        .small_button("x")
+                        .on_hover_text("Close the tab")
+                        .clicked()
+                {
+                    close = Some(*app_id);
+                }
+            });
+        });
+
+        if let Some(app_id) = close {
+            self.app_tabs.retain(|tab| *tab != app_id);
+            self.app_tab_servers.remove(&app_id);
+        }
+        if let Some(app_id) = switch_to {
+            if let Err(err) = self.switch_app(app_id) {
+                log::error!("unable to load the servers of app {}: {}", app_id, err);
+            }
+        }
+    }
+
+    /// Apply the profile with the given name, the servers of the app
+    /// the profile is bound to are loaded first (if not already
+    /// loaded).
+    pub fn apply_profile(&mut self, name: &str) {
+        let Some(profile) = self.profiles.get(name).cloned() else {
+            log::error!("no profile named `{}`", name);
+            return;
+        };
+        if let Some(app_id) = profile.app_id {
+            if app_id != self.servers.get_app_id() {
+                if let Err(err) = self.switch_app(app_id) {
+                    log::error!("unable to load the servers of app {}: {}", app_id, err);
+                    return;
+                }
+            }
+        }
+        log::info!("applying profile `{}`", profile.name);
+        self.apply_block_list(&profile.block_list());
+        // the partial blocks, after the regions are unblocked
+        self.servers
+            .get_servers_in(ServerCategory::Relay)
+            .for_each(|server| {
+                server
+                    .get_ips()
+                    .iter()
+                    .filter(|ip| profile.blocked_ips.contains(ip))
+                    .for_each(|ip| Self::disable_ip(*ip, server, &self.worker));
+            });
+        self.profiles.set_last_applied(&profile.name);
+        if let Err(err) = self.profiles.save() {
+            log::error!("unable to save the profiles: {}", err);
+        }
+        self.active_profile = Some(profile.name);
+        self.active_profile_cleared = false;
+    }
+
+    /// Unblock the regions of the active profile, or block them again
+    /// if they were unblocked by the previous call.
+    pub fn toggle_active_profile(&mut self) {
+        let Some(name) = self.active_profile.clone() else {
+            log::warn!("no active profile to toggle");
+            return;
+        };
+        if self.active_profile_cleared {
+            self.apply_profile(&name);
+        } else {
+            if self.profiles.get(&name).is_none() {
+                log::error!("no profile named `{}`", name);
+                return;
+            }
+            self.active_profile_cleared = true;
+            self.after_worker_ack(AfterAck::ClearActiveProfile(name.clone()));
+        }
+        self.notifiers.notify(Event::ProfileToggled {
+            profile: name,
+            blocked: !self.active_profile_cleared,
+        });
+    }
+
+    /// Unblock the blocked regions of the profile, unless its blocks
+    /// were restored since they were cleared. The pending firewall
+    /// changes must be applied, see [`Self::after_worker_ack()`].
+    fn clear_active_profile(&mut self, name: &str) {
+        if !self.active_profile_cleared || self.active_profile.as_deref() != Some(name) {
+            return;
+        }
+        let Some(profile) = self.profiles.get(name) else {
+            log::error!("no profile named `{}`", name);
+            return;
+        };
+        let profile_block_list = profile.block_list();
+        let blocked = match BlockList::from_firewall(&self.servers, &self.firewall) {
+            Ok(blocked) => blocked,
+            Err(err) => {
+                log::error!("{}", err);
+                return;
+            }
+        };
+        self.apply_block_list(&BlockList::new(
+            blocked.diff(&profile_block_list).only_in_first,
+        ));
+    }
+
+    /// Save the currently blocked regions as a profile with the given
+    /// name, bound to the Steam app of the loaded servers if
+    /// `bind_app`. The pending firewall changes must be applied, see
+    /// [`Self::after_worker_ack()`].
+    fn save_profile(&mut self, name: &str, bind_app: bool) {
+        let blocked = match self.firewall.list_blocked() {
+            Ok(blocked) => blocked,
+            Err(err) => {
+                log::error!("{}", err);
+                return;
+            }
+        };
+        let block_list = BlockList::from_blocked(&self.servers, &blocked);
+        let partially_blocked_ips = self
+            .servers
+            .get_servers_in(ServerCategory::Relay)
+            .filter(|server| !block_list.contains(server.get_abr()))
+            .flat_map(|server| server.get_ips())
+            .filter(|ip| blocked.contains(ip))
+            .copied();
+        let app_id = bind_app.then(|| self.servers.get_app_id());
+        self.profiles.insert(Profile::new(
+            name,
+            app_id,
+            &block_list,
+            partially_blocked_ips,
+        ));
+        self.profiles.set_last_applied(name);
+        if let Err(err) = self.profiles.save() {
+            log::error!("unable to save the profiles: {}", err);
+        }
+        self.active_profile = Some(name.to_string());
+        self.active_profile_cleared = false;
+    }
+
+    /// Get the games that can be picked, the installed games followed
+    /// by the known SDR games that are not installed, see
+    /// [`steam_server::KNOWN_APPS`].
+    fn pickable_apps(&self) -> Vec<InstalledApp> {
+        let mut apps = self.installed_apps.clone();
+        steam_server::KNOWN_APPS.iter().for_each(|(app_id, name)| {
+            if !apps.iter().any(|app| app.app_id == *app_id) {
+                apps.push(InstalledApp {
+                    app_id: *app_id,
+                    name: name.to_string(),
+                });
+            }
+        });
+        apps
+    }
+
+    /// Create a picker of the given games, returns the app id of the
+    /// game picked (if a different game is picked).
+    fn ui_app_picker(
+        ui: &mut egui::Ui,
+        id_source: impl std::hash::Hash,
+        apps: &[InstalledApp],
+        app_id: u32,
+    ) -> Option<u32> {
+        let selected_text = apps
+            .iter()
+            .find(|app| app.app_id == app_id)
+            .map(|app| app.to_string())
+            .unwrap_or_else(|| format!("App {}", app_id));
+        let mut picked = None;
+        egui::ComboBox::from_id_source(id_source)
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                apps.iter().for_each(|app| {
+                    if ui
+                        .selectable_label(app.app_id == app_id, app.to_string())
+                        .clicked()
+                        && app.app_id != app_id
+                    {
+                        picked = Some(app.app_id);
+                    }
+                });
+            });
+        picked
+    }
+
+    /// Get the average ping of every relay region, the regions in
+    /// `blocked` are left out.
+    fn latency_snapshot(&self, blocked: &BlockList) -> LatencySnapshot {
+        LatencySnapshot::new(
+            self.servers
+                .get_servers_in(ServerCategory::Relay)
+                .filter_map(|server| {
+                    let (total_ping, num_packets, lost_packets) = server
+                        .get_ips()
+                        .iter()
+                        .map(|ip| Self::calculate_total_ping_for_ip(&self.ping_info, *ip))
+                        .fold(
+                            (Duration::ZERO, 0, 0),
+                            |acc, (ping, num_packets, lost_packets)| {
+                                (acc.0 + ping, acc.1 + num_packets, acc.2 + lost_packets)
+                            },
+                        );
+                    let num_valid_packets = u32::try_from(num_packets - lost_packets).ok()?;
+                    (num_valid_packets != 0)
+                        .then(|| (server.get_abr().to_string(), total_ping / num_valid_packets))
+                }),
+            blocked,
+        )
+    }
+
+    /// Get the pings measured by Steam, if enabled, the regions in
+    /// `blocked` are left out.
+    fn steam_latency_snapshot(&self, blocked: &BlockList) -> Option<LatencySnapshot> {
+        self.steam_pings.as_ref().map(|steam_pings| {
+            LatencySnapshot::new(
+                steam_pings
+                    .pings()
+                    .iter()
+                    .map(|(region, ping)| (region.clone(), *ping)),
+                blocked,
+            )
+        })
+    }
+
+    /// Get the regex of [`Self::selection_expression`], [`None`] if
+    /// the expression is empty.
+    fn selection_regex(&self) -> Option<Result<regex::Regex, regex::Error>> {
+        let expression = self.selection_expression.trim();
+        if expression.is_empty() {
+            None
+        } else if self.selection_glob {
+            Some(regex::Regex::new(&glob_to_regex(expression)))
+        } else {
+            Some(regex::Regex::new(expression))
         }
+    }
+
+    /// Create the UI to select the relay regions matching an
+    /// expression.
+    fn ui_selection_expression(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Match:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.selection_expression).hint_text(
+                    if self.selection_glob {
+                        "s*"
+                    } else {
+                        "^(sto|fra)$"
+                    },
+                ),
+            )
+            .on_hover_text("Regions whose abreviation matches the expression");
+            ui.checkbox(&mut self.selection_glob, "Glob")
+                .on_hover_text("Use `*` and `?` wildcards instead of a regex");
+
+            let regex = match self.selection_regex() {
+                Some(Ok(regex)) => regex,
+                Some(Err(err)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, "Invalid expression")
+                        .on_hover_text(err.to_string());
+                    return;
+                }
+                None => return,
+            };
+            let matching: Vec<_> = self
+                .servers
+                .get_servers_in(ServerCategory::Relay)
+                .filter(|server| regex.is_match(server.get_abr()))
+                .collect();
+
+            if ui
+                .add_enabled(
+                    !matching.is_empty(),
+                    egui::Button::new(format!("Select {} Matching", matching.len())),
+                )
+                .on_hover_text("Select only the matching regions in the grid")
+                .clicked()
+            {
+                self.ip_selection_status
+                    .values_mut()
+                    .for_each(|selected| *selected = false);
+                matching
+                    .iter()
+                    .flat_map(|server| server.get_ips())
+                    .for_each(|ip| {
+                        self.ip_selection_status.insert(*ip, true);
+                    });
+            }
+            let enable_clicked = ui
+                .add_enabled(!matching.is_empty(), egui::Button::new("Enable Matching"))
+                .clicked();
+            let disable_clicked = ui
+                .add_enabled(!matching.is_empty(), egui::Button::new("Disable Matching"))
+                .clicked();
+
+            ui.label(if matching.is_empty() {
+                "No matching region".to_string()
+            } else {
+                matching
+                    .iter()
+                    .map(|server| server.get_abr())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            });
 
-        // send message to server status checker
-        // to update server status
-        server_status_message_sender
-            .send(ServerStatusMessage::RemoveServer(
-                server.get_abr().to_string(),
-            ))
-            .unwrap();
-        server_status_message_sender
-            .send(ServerStatusMessage::AppendToList(vec![(
-                server.get_abr().to_string(),
-                server.get_ipv4s().to_vec(),
-            )]))
-            .unwrap();
+            if enable_clicked {
+                self.enable_matching(&regex, None);
+            }
+            if disable_clicked {
+                self.disable_matching(&regex, None);
+            }
+        });
+    }
 
-        // update pinger ip list
-        pinger_message_sender
-            .send(PingerMessage::PushToList(ip))
-            .unwrap();
+    /// Get the servers shown in the grid: of the category shown, the
+    /// regions without any IPs only if they are shown, matching the
+    /// filter (if any).
+    fn grid_servers<'a>(
+        servers: &'a Servers,
+        view_state: &ViewState,
+        filter: &str,
+    ) -> Vec<&'a ServerInfo> {
+        let filter = filter.trim().to_lowercase();
+        servers
+            .get_servers_in(view_state.grid.category)
+            .filter(|server| view_state.grid.show_empty_regions || !server.get_ips().is_empty())
+            .filter(|server| {
+                filter.is_empty()
+                    || server.get_abr().to_lowercase().contains(&filter)
+                    || server
+                        .label()
+                        .is_some_and(|label| label.to_lowercase().contains(&filter))
+            })
+            .collect()
     }
 
-    /// Disable the given IP.
-    fn disable_ip(
-        ip: Ipv4Addr,
-        server: &ServerInfo,
-        firewall: &Firewall,
-        server_status_message_sender: &mpsc::Sender<ServerStatusMessage>,
-        pinger_message_sender: &mpsc::Sender<PingerMessage>,
-        ping_info_remove_ips: &mut Option<Vec<Ipv4Addr>>,
+    /// Sort the regions of the grid by the column of `grid`. The
+    /// regions without a value (eg: not pinged yet) are always last.
+    fn sort_grid_servers(
+        servers: &mut [&ServerInfo],
+        grid: &GridViewState,
+        grid_cache: &GridCache,
+        server_status_info: &HashMap<String, ServerState>,
     ) {
-        let ban_res = firewall.ban_ip(ip);
-        if let Err(err) = ban_res {
-            log::error!("{}: {}", server.get_abr(), err);
+        let Some(column) = grid.sort_by else {
+            return;
+        };
+        let order = |ordering: std::cmp::Ordering| {
+            if grid.sort_descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        };
+        match column {
+            GridSortColumn::Region => {
+                servers.sort_by(|a, b| order(a.get_abr().cmp(b.get_abr())));
+            }
+            GridSortColumn::State => {
+                let key = |server: &ServerInfo| {
+                    server_status_info
+                        .get(server.get_abr())
+                        .and_then(|state| state.blocked_fraction(server.get_ips().len()))
+                };
+                servers.sort_by(|a, b| match (key(a), key(b)) {
+                    (Some(a), Some(b)) => order(a.total_cmp(&b)),
+                    (a, b) => b.is_some().cmp(&a.is_some()),
+                });
+            }
+            _ => {
+                let key = |server: &ServerInfo| {
+                    grid_cache
+                        .region_sort_value(server.get_abr(), column)
+                        .filter(|value| value.is_finite())
+                };
+                servers.sort_by(|a, b| match (key(a), key(b)) {
+                    (Some(a), Some(b)) => order(a.total_cmp(&b)),
+                    (a, b) => b.is_some().cmp(&a.is_some()),
+                });
+            }
         }
+    }
 
-        // send message to server status checker
-        // to update server status
-        server_status_message_sender
-            .send(ServerStatusMessage::RemoveServer(
-                server.get_abr().to_string(),
-            ))
-            .unwrap();
-        server_status_message_sender
-            .send(ServerStatusMessage::AppendToList(vec![(
-                server.get_abr().to_string(),
-                server.get_ipv4s().to_vec(),
-            )]))
-            .unwrap();
-
-        // update pinger ip list
-        pinger_message_sender
-            .send(PingerMessage::RemoveFromList(ip))
-            .unwrap();
-
-        if let Some(prev_removed_ips) = ping_info_remove_ips {
-            prev_removed_ips.push(ip);
-        } else {
-            *ping_info_remove_ips = Some(vec![ip]);
+    /// Create the header of a column the grid can be sorted by, see
+    /// [`GridViewState::toggle_sort()`].
+    fn ui_sort_header(
+        ui: &mut egui::Ui,
+        column: GridSortColumn,
+        grid: &mut GridViewState,
+    ) -> egui::Response {
+        let arrow = match grid.sort_by {
+            Some(sort_by) if sort_by == column && grid.sort_descending => " ⏷",
+            Some(sort_by) if sort_by == column => " ⏶",
+            _ => "",
+        };
+        let response = ui.add(
+            egui::Label::new(format!("{}{}", column.name(), arrow)).sense(egui::Sense::click()),
+        );
+        if response.clicked() {
+            grid.toggle_sort(column);
         }
+        response
     }
 
-    /// Get the [`ServerSelectionStatus`] for the given
-    /// [`Servers`]. The returned vector will have the elements
-    /// correspond exactly with the given servers (so zipping the
-    /// result is possible).
-    fn servers_selection_status(
+    /// Create the UI to filter the regions of the grid, with the
+    /// actions on exactly the regions shown while filtering.
+    fn ui_grid_filter(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Filter:");
+            ui.add(egui::TextEdit::singleline(&mut self.grid_filter).hint_text("sgp, Tokyo"))
+                .on_hover_text(
+                    "Only show the regions whose abreviation or description contains it",
+                );
+            if self.grid_filter.trim().is_empty() {
+                return;
+            }
+            if ui.button("Clear").clicked() {
+                self.grid_filter.clear();
+                return;
+            }
+
+            let shown = Self::grid_servers(&self.servers, &self.view_state, &self.grid_filter);
+            ui.separator();
+            ui.label(format!("{} shown", shown.len()));
+            if ui
+                .add_enabled(!shown.is_empty(), egui::Button::new("Enable All Shown"))
+                .clicked()
+            {
+                shown
+                    .iter()
+                    .for_each(|server| Self::enable_server(server, &self.worker));
+            }
+            if ui
+                .add_enabled(!shown.is_empty(), egui::Button::new("Disable All Shown"))
+                .clicked()
+            {
+                shown
+                    .iter()
+                    .for_each(|server| Self::disable_server(server, &self.worker));
+            }
+        });
+    }
+
+    /// Create the toggle to reveal the regions without any IPs (eg:
+    /// pops without relays), of the `category` or of all the
+    /// categories. Nothing is shown if there are no such regions.
+    fn ui_show_empty_regions(
+        ui: &mut egui::Ui,
         servers: &Servers,
-        ip_selection_status: &HashMap<Ipv4Addr, bool>,
-    ) -> Vec<ServerSelectionStatus> {
-        servers
+        category: Option<ServerCategory>,
+        show_empty_regions: &mut bool,
+    ) {
+        let num_empty = servers
             .get_servers()
             .iter()
-            .map(|server| {
-                let num_ips_selected = server
-                    .get_ipv4s()
-                    .iter()
-                    .filter(|ip| *ip_selection_status.get(*ip).unwrap_or(&false))
-                    .count();
+            .filter(|server| category.is_none() || category == Some(server.category()))
+            .filter(|server| server.get_ips().is_empty())
+            .count();
+        if num_empty == 0 {
+            return;
+        }
+        ui.checkbox(
+            show_empty_regions,
+            format!("Show Empty Regions ({})", num_empty),
+        )
+        .on_hover_text("Show the regions without any IPs to block, eg: pops without relays");
+    }
 
-                if num_ips_selected == 0 {
-                    ServerSelectionStatus::None
-                } else if num_ips_selected == server.get_ipv4s().len() {
-                    ServerSelectionStatus::All
+    /// Create the UI to sort the grid (also done by clicking the
+    /// headers) and to select the regions other than the best scored
+    /// ones.
+    fn ui_score(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let grid = &mut self.view_state.grid;
+            ui.label("Sort by:");
+            egui::ComboBox::from_id_source("grid_sort_by")
+                .selected_text(grid.sort_by.map_or("Server List", |column| column.name()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut grid.sort_by, None, "Server List");
+                    GridSortColumn::all().into_iter().for_each(|column| {
+                        ui.selectable_value(&mut grid.sort_by, Some(column), column.name());
+                    });
+                });
+            ui.add_enabled(
+                grid.sort_by.is_some(),
+                egui::Checkbox::new(&mut grid.sort_descending, "Descending"),
+            );
+
+            ui.separator();
+
+            if ui
+                .button(format!("Select All But Best {}", self.keep_best_regions))
+                .on_hover_text(
+                    "Select every region except the ones with the best score, \
+                     the regions without a score are selected too",
+                )
+                .clicked()
+            {
+                self.select_all_but_best(self.keep_best_regions);
+            }
+            ui.add(egui::DragValue::new(&mut self.keep_best_regions).range(1..=usize::MAX));
+        });
+    }
+
+    /// Create the UI to recommend the nearest regions (highlighted in
+    /// the grid and the map) and to block all the other regions.
+    fn ui_recommendation(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui
+                .button(format!("Recommend Nearest {}", self.keep_nearest_regions))
+                .on_hover_text(match &self.location {
+                    Some(location) => format!("Nearest to {}", location),
+                    None => "Lowest ping, the location is not detected".to_string(),
+                })
+                .clicked()
+            {
+                let recommendation = self.recommend_nearest(self.keep_nearest_regions);
+                log::info!(
+                    "nearest regions by {}: {}",
+                    recommendation.basis.name().to_lowercase(),
+                    recommendation.regions.join(", ")
+                );
+                self.recommendation = Some(recommendation);
+            }
+            ui.add(egui::DragValue::new(&mut self.keep_nearest_regions).range(1..=usize::MAX));
+
+            let Some(recommendation) = &self.recommendation else {
+                return;
+            };
+            ui.separator();
+            ui.label(format!(
+                "★ {}",
+                if recommendation.regions.is_empty() {
+                    "None".to_string()
                 } else {
-                    ServerSelectionStatus::Some
+                    recommendation.regions.join(", ")
                 }
-            })
-            .collect::<Vec<_>>()
+            ))
+            .on_hover_text(format!(
+                "Nearest regions by {}",
+                recommendation.basis.name().to_lowercase()
+            ));
+            if ui
+                .add_enabled(
+                    !recommendation.regions.is_empty(),
+                    egui::Button::new(format!(
+                        "Block All Except Nearest {}",
+                        recommendation.regions.len()
+                    )),
+                )
+                .on_hover_text("Unblock the nearest regions and block all the others")
+                .clicked()
+            {
+                self.apply_block_list(&recommendation.block_list());
+            }
+            if ui.button("Clear").clicked() {
+                self.recommendation = None;
+            }
+        });
     }
 
-    /// Enable the IPs that are currently selected.
-    fn enable_selected_ips(&self) {
-        let servers_selected =
-            Self::servers_selection_status(&self.servers, &self.ip_selection_status);
-        if servers_selected
+    /// Select the IPs of the regions of the category shown in the grid
+    /// except the `keep` regions with the best score, the other IPs
+    /// are deselected.
+    fn select_all_but_best(&mut self, keep: usize) {
+        let mut servers: Vec<_> = self
+            .servers
+            .get_servers_in(self.view_state.grid.category)
+            .collect();
+        servers.sort_by(|a, b| {
+            self.grid_cache
+                .region_score(a.get_abr())
+                .total_cmp(&self.grid_cache.region_score(b.get_abr()))
+        });
+        let best: HashSet<_> = servers
             .iter()
-            .all(|selected| matches!(selected, ServerSelectionStatus::All))
-        {
-            // this is for optimization, if all the
-            // servers are selected, then it is faster
-            // to enable all the servers
-            self.enable_all_servers();
-        } else {
+            .take(keep)
+            .filter(|server| self.grid_cache.region_score(server.get_abr()).is_finite())
+            .map(|server| server.get_abr())
+            .collect();
+        servers.iter().for_each(|server| {
+            let selected = !best.contains(server.get_abr());
+            server.get_ips().iter().for_each(|ip| {
+                self.ip_selection_status.insert(*ip, selected);
+            });
+        });
+    }
+
+    /// Get the relay regions whose IPs are all selected in the grid.
+    fn selected_regions(&self) -> BlockList {
+        BlockList::new(
             self.servers
-                .get_servers()
-                .iter()
-                .zip(servers_selected.into_iter())
-                .for_each(|(server, status)| match status {
-                    ServerSelectionStatus::All => {
-                        Self::enable_server(
-                            server,
-                            &self.firewall,
-                            &self.server_status_message_sender,
-                            &self.pinger_message_sender,
-                        );
-                    }
-                    ServerSelectionStatus::Some => {
-                        server
-                            .get_ipv4s()
+                .get_servers_in(ServerCategory::Relay)
+                .filter(|server| {
+                    !server.get_ips().is_empty()
+                        && server
+                            .get_ips()
                             .iter()
-                            .filter(|ip| *self.ip_selection_status.get(ip).unwrap_or(&false))
-                            .for_each(|ip| {
-                                Self::enable_ip(
-                                    *ip,
-                                    server,
-                                    &self.firewall,
-                                    &self.server_status_message_sender,
-                                    &self.pinger_message_sender,
-                                )
-                            });
-                    }
-                    ServerSelectionStatus::None => {
-                        // do nothing
-                    }
-                });
+                            .all(|ip| *self.ip_selection_status.get(ip).unwrap_or(&false))
+                })
+                .map(|server| server.get_abr().to_string()),
+        )
+    }
+
+    /// Block the regions for `duration` in addition to the blocked
+    /// regions once the pending firewall changes are applied, see
+    /// [`TrialBlock`].
+    pub fn start_trial_block(&mut self, regions: BlockList, duration: Duration) {
+        if self.trial_block.is_some() {
+            log::error!("a trial block is already in progress");
+            return;
         }
+        self.after_worker_ack(AfterAck::StartTrialBlock { regions, duration });
     }
 
-    /// Disable the IPs that are currently selected.
-    fn disable_selected_ips(&mut self) {
-        let servers_selected =
-            Self::servers_selection_status(&self.servers, &self.ip_selection_status);
-        if servers_selected
-            .iter()
-            .all(|selected| matches!(selected, ServerSelectionStatus::All))
-        {
-            // this is for optimization, if all the
-            // servers are selected, then it is faster
-            // to enable all the servers
-            self.disable_all_servers();
-        } else {
-            let mut ping_info_remove_ips: Option<Vec<Ipv4Addr>> = None;
-            self.servers
-                .get_servers()
-                .iter()
-                .zip(servers_selected.into_iter())
-                .for_each(|(server, status)| match status {
-                    ServerSelectionStatus::All => {
-                        Self::disable_server(
-                            server,
-                            &self.firewall,
-                            &self.server_status_message_sender,
-                            &self.pinger_message_sender,
-                            &mut ping_info_remove_ips,
-                        );
-                    }
-                    ServerSelectionStatus::Some => {
-                        server
-                            .get_ipv4s()
-                            .iter()
-                            .filter(|ip| *self.ip_selection_status.get(ip).unwrap_or(&false))
-                            .for_each(|ip| {
-                                Self::disable_ip(
-                                    *ip,
-                                    server,
-                                    &self.firewall,
-                                    &self.server_status_message_sender,
-                                    &self.pinger_message_sender,
-                                    &mut ping_info_remove_ips,
-                                )
-                            });
-                    }
-                    ServerSelectionStatus::None => {
-                        // do nothing
-                    }
-                });
-            if let Some(ip_list) = ping_info_remove_ips {
-                // HACK: wait for the channel to get all the
-                // messages before flushing them
-                std::thread::sleep(Duration::from_secs(1));
-                // flush the ping messages channel
-                self.update_ping_info();
-
-                for ip in ip_list.iter() {
-                    self.ping_info.remove(ip);
+    /// Start the trial block, the pending firewall changes must be
+    /// applied, see [`Self::after_worker_ack()`].
+    fn begin_trial_block(&mut self, regions: BlockList, duration: Duration) {
+        // another one may have started while waiting for the worker
+        if self.trial_block.is_some() {
+            log::error!("a trial block is already in progress");
+            return;
+        }
+        let previous = match BlockList::from_firewall(&self.servers, &self.firewall) {
+            Ok(previous) => previous,
+            Err(err) => {
+                log::error!("{}", err);
+                return;
+            }
+        };
+        let trial_block = TrialBlock::new(
+            regions,
+            previous.clone(),
+            duration,
+            self.latency_snapshot(&previous),
+            self.steam_latency_snapshot(&previous),
+        );
+        let trial_block_list = trial_block.trial_block_list();
+        log::info!(
+            "trial block of {:?} for {}s",
+            trial_block_list.diff(&previous).only_in_first,
+            duration.as_secs()
+        );
+        self.apply_block_list(&trial_block_list);
+        self.trial_block = Some(trial_block);
+    }
+
+    /// End the trial block in progress (if any), the blocked regions
+    /// are restored and the report is shown.
+    fn finish_trial_block(&mut self) {
+        let Some(trial_block) = self.trial_block.take() else {
+            return;
+        };
+        let trial_block_list = trial_block.trial_block_list();
+        let after = self.latency_snapshot(&trial_block_list);
+        let steam_after = self.steam_latency_snapshot(&trial_block_list);
+        self.apply_block_list(trial_block.previous());
+        let report = trial_block.finish(after, steam_after);
+        log::info!("trial block report:\n{}", report);
+        self.trial_report = Some(report);
+    }
+
+    /// Create the UI for the [`TrialBlock`] and its [`TrialReport`].
+    fn ui_trial_block(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| match &self.trial_block {
+            Some(trial_block) => {
+                let remaining = trial_block.remaining();
+                ui.label(format!(
+                    "Trial block: {}:{:02} left",
+                    remaining.as_secs() / 60,
+                    remaining.as_secs() % 60
+                ));
+                if ui.button("Stop Trial").clicked() {
+                    self.finish_trial_block();
+                }
+                // countdown, also ends the trial on time
+                ui.ctx()
+                    .request_repaint_after(remaining.min(Duration::from_secs(1)));
+            }
+            None => {
+                let selected_regions = self.selected_regions();
+                let num_selected = selected_regions.regions().count();
+                if ui
+                    .add_enabled(
+                        num_selected != 0,
+                        egui::Button::new(format!("Trial Block {} Selected", num_selected)),
+                    )
+                    .on_hover_text(
+                        "Block the selected regions for a while, then restore the \
+                         blocked regions and compare the pings",
+                    )
+                    .clicked()
+                {
+                    self.start_trial_block(
+                        selected_regions,
+                        Duration::from_secs(u64::from(self.trial_block_minutes) * 60),
+                    );
+                }
+                ui.label("for");
+                ui.add(
+                    egui::DragValue::new(&mut self.trial_block_minutes)
+                        .range(1..=60)
+                        .suffix(" min"),
+                );
+            }
+        });
+
+        let Some(report) = &self.trial_report else {
+            return;
+        };
+        let mut keep = None;
+        egui::Window::new("Trial Block Report").show(ui.ctx(), |ui| {
+            ui.label(report.to_string());
+            ui.horizontal(|ui| {
+                if ui.button("Keep Blocked").clicked() {
+                    keep = Some(true);
+                }
+                if ui.button("Dismiss").clicked() {
+                    keep = Some(false);
                 }
+            });
+        });
+        if let Some(keep) = keep {
+            let report = self.trial_report.take().expect("is shown");
+            if keep {
+                self.after_worker_ack(AfterAck::KeepTrialBlock(report.regions));
             }
         }
     }
 
-    /// Enable the matching IPs of the server regions matching the
+    /// Block the regions of a finished trial block in addition to the
+    /// blocked regions. The pending firewall changes must be applied,
+    /// see [`Self::after_worker_ack()`].
+    fn keep_trial_block(&mut self, regions: Vec<String>) {
+        match BlockList::from_firewall(&self.servers, &self.firewall) {
+            Ok(blocked) => self.apply_block_list(&BlockList::new(
+                blocked.regions().map(str::to_string).chain(regions),
+            )),
+            Err(err) => log::error!("{}", err),
+        }
+    }
+
+    /// Create the UI for the [`Profiles`].
+    fn ui_profiles(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Profile:");
+
+            let mut apply = None;
+            egui::ComboBox::from_id_source("profiles")
+                .selected_text(self.active_profile.as_deref().unwrap_or("None"))
+                .show_ui(ui, |ui| {
+                    self.profiles.profiles().iter().for_each(|profile| {
+                        let label = match profile.app_id {
+                            Some(app_id) => format!("{} (app {})", profile.name, app_id),
+                            None => profile.name.clone(),
+                        };
+                        let selected = self.active_profile.as_ref() == Some(&profile.name);
+                        if ui.selectable_label(selected, label).clicked() {
+                            apply = Some(profile.name.clone());
+                        }
+                    });
+                });
+            if let Some(name) = apply {
+                self.apply_profile(&name);
+            }
+
+            if let Some(name) = self.active_profile.clone() {
+                let toggle_label = if self.active_profile_cleared {
+                    "Block Again"
+                } else {
+                    "Clear Blocks"
+                };
+                if ui
+                    .button(toggle_label)
+                    .on_hover_text("Toggle the blocks of the profile")
+                    .clicked()
+                {
+                    self.toggle_active_profile();
+                }
+                if ui.button("Delete").clicked() {
+                    self.profiles.remove(&name);
+                    if let Err(err) = self.profiles.save() {
+                        log::error!("unable to save the profiles: {}", err);
+                    }
+                    self.active_profile = None;
+                }
+            }
+
+            ui.separator();
+
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_profile_name)
+                    .hint_text("Profile name")
+                    .desired_width(120.0),
+            );
+            ui.checkbox(
+                &mut self.new_profile_bind_app,
+                format!("Bind to app {}", self.servers.get_app_id()),
+            );
+            let name = self.new_profile_name.trim().to_string();
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("Save Profile"))
+                .on_hover_text("Save the blocked regions as a profile")
+                .clicked()
+            {
+                self.after_worker_ack(AfterAck::SaveProfile {
+                    name,
+                    bind_app: self.new_profile_bind_app,
+                });
+                self.new_profile_name.clear();
+            }
+
+            ui.separator();
+
+            let mut follow_running_game = self.running_app_watcher.is_some();
+            if ui
+                .checkbox(&mut follow_running_game, "Follow running game")
+                .on_hover_text("Apply the profile bound to the game that is running")
+                .changed()
+            {
+                self.running_app_watcher = follow_running_game
+                    .then(|| RunningAppWatcher::new(Duration::from_secs(5), self.waker.clone()));
+            }
+        });
+    }
+
+    /// Disable the matching IPs of the server regions matching the
     /// given regex.
-    pub fn enable_matching(&mut self, regex: &regex::Regex, exclude_regex: Option<&regex::Regex>) {
+    pub fn disable_matching(&mut self, regex: &regex::Regex, exclude_regex: Option<&regex::Regex>) {
         self.servers
-            .get_servers()
-            .iter()
+            .get_servers_in(ServerCategory::Relay)
             .filter(|server| {
                 regex.is_match(server.get_abr())
                     && !exclude_regex.is_some_and(|exclude| exclude.is_match(server.get_abr()))
             })
             .for_each(|server| {
-                Self::enable_server(
-                    server,
-                    &self.firewall,
-                    &self.server_status_message_sender,
-                    &self.pinger_message_sender,
-                );
+                Self::disable_server(server, &self.worker);
             });
     }
 
-    /// Disable the matching IPs of the server regions matching the
-    /// given regex.
-    pub fn disable_matching(&mut self, regex: &regex::Regex, exclude_regex: Option<&regex::Regex>) {
-        let mut ping_info_remove_ips = None;
+    /// Download the server lists of the managed categories and load
+    /// them.
+    fn download_server_list(&mut self) {
+        let app_id = self.servers.get_app_id();
+        let download_file_res = Servers::download_file_for_app(app_id);
+        if let Err(err) = download_file_res {
+            log::error!("{}", err);
+        }
+        if self.cm_servers {
+            if let Err(err) = steam_cm::download_file() {
+                log::error!("{}", err);
+            }
+        }
+        #[cfg(feature = "cdn")]
+        if self.cdn_servers {
+            if let Err(err) = crate::steam_cdn::download_file() {
+                log::error!("{}", err);
+            }
+        }
+        match Servers::new_for_app(None::<PathBuf>, app_id) {
+            Ok(mut servers) => {
+                if self.cm_servers {
+                    steam_cm::load_into(&mut servers);
+                }
+                #[cfg(feature = "cdn")]
+                if self.cdn_servers {
+                    crate::steam_cdn::load_into(&mut servers);
+                }
+                if servers.get_revision() != self.servers.get_revision() {
+                    self.notifiers.notify(Event::ServerListRevisionChanged {
+                        old_revision: self.servers.get_revision(),
+                        new_revision: servers.get_revision(),
+                    });
+                }
+                let diff = self.servers.diff(&servers);
+                log::info!("downloaded the server list: {}", diff);
+                self.set_servers(servers);
+                self.reconcile_blocked_ips(&diff);
+                self.server_list_diff = Some(diff);
+            }
+            Err(err) => log::error!("{}", err),
+        }
+    }
 
-        self.servers
-            .get_servers()
-            .iter()
-            .filter(|server| {
-                regex.is_match(server.get_abr())
-                    && !exclude_regex.is_some_and(|exclude| exclude.is_match(server.get_abr()))
+    /// Reconcile the firewall rules with the changes of the server
+    /// list, once the new list is loaded so that the added IPs are
+    /// blocked with its port ranges. The blocked IPs removed
+    /// from the list are unblocked, their rules would otherwise
+    /// linger. The IPs added to a fully blocked region are blocked.
+    fn reconcile_blocked_ips(&self, diff: &ServersDiff) {
+        diff.removed_ips_by_region()
+            .into_iter()
+            .for_each(|(region, ips)| {
+                let ips: Vec<_> = match self.server_status_info.get(region) {
+                    Some(ServerState::AllDisabled) => ips,
+                    Some(ServerState::SomeDisabled(blocked_ips)) => ips
+                        .into_iter()
+                        .filter(|ip| blocked_ips.contains(ip))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                if ips.is_empty() {
+                    return;
+                }
+                log::info!(
+                    "unblocking {} IPs of {} removed from the server list",
+                    ips.len(),
+                    region
+                );
+                self.worker.send(Command::SetBlocked {
+                    server: region.to_string(),
+                    ips,
+                    blocked: false,
+                });
+            });
+        diff.added_ips_by_region()
+            .into_iter()
+            .filter(|(region, _)| {
+                matches!(
+                    self.server_status_info.get(*region),
+                    Some(ServerState::AllDisabled)
+                )
             })
-            .for_each(|server| {
-                Self::disable_server(
-                    server,
-                    &self.firewall,
-                    &self.server_status_message_sender,
-                    &self.pinger_message_sender,
-                    &mut ping_info_remove_ips,
+            .for_each(|(region, ips)| {
+                log::info!(
+                    "blocking {} IPs added to the blocked region {}",
+                    ips.len(),
+                    region
                 );
+                self.worker.send(Command::SetBlocked {
+                    server: region.to_string(),
+                    ips,
+                    blocked: true,
+                });
+            });
+    }
+
+    /// Create the window of the changes of the downloaded server list,
+    /// see [`App::download_server_list()`].
+    fn ui_server_list_diff(&mut self, ctx: &egui::Context) {
+        let Some(diff) = &self.server_list_diff else {
+            return;
+        };
+        const ADDED_COLOR: egui::Color32 = egui::Color32::from_rgb(0, 160, 0);
+        const REMOVED_COLOR: egui::Color32 = egui::Color32::from_rgb(200, 0, 0);
+        let ip_list = |ips: &[IpAddr]| {
+            ips.iter()
+                .map(IpAddr::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let mut open = true;
+        egui::Window::new("Server List Changes")
+            .open(&mut open)
+            .default_height(300.0)
+            .show(ctx, |ui| {
+                if diff.is_empty() {
+                    ui.label("The server list is unchanged");
+                    return;
+                }
+                ui.label(diff.to_string());
+                ui.separator();
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("server_list_diff_grid")
+                        .num_columns(2)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            diff.added_regions.iter().for_each(|region| {
+                                ui.colored_label(ADDED_COLOR, format!("+ {}", region));
+                                ui.label("region added");
+                                ui.end_row();
+                            });
+                            diff.removed_regions.iter().for_each(|region| {
+                                ui.colored_label(REMOVED_COLOR, format!("- {}", region));
+                                ui.label("region removed");
+                                ui.end_row();
+                            });
+                            diff.added_ips_by_region()
+                                .into_iter()
+                                .for_each(|(region, ips)| {
+                                    ui.colored_label(ADDED_COLOR, format!("+ {}", region));
+                                    ui.label(ip_list(&ips));
+                                    ui.end_row();
+                                });
+                            diff.removed_ips_by_region()
+                                .into_iter()
+                                .for_each(|(region, ips)| {
+                                    ui.colored_label(REMOVED_COLOR, format!("- {}", region));
+                                    ui.label(ip_list(&ips));
+                                    ui.end_row();
+                                });
+                        });
+                });
             });
+        if !open {
+            self.server_list_diff = None;
+        }
+    }
 
-        if let Some(ip_list) = ping_info_remove_ips {
-            // HACK: wait for the channel to get all the
-            // messages before flushing them
-            std::thread::sleep(Duration::from_secs(1));
-            // flush the ping messages channel
-            self.update_ping_info();
+    /// Get the entries of the command palette: the regions, the
+    /// continents, the profiles and the actions.
+    fn palette_entries(&self) -> Vec<PaletteEntry> {
+        let mut entries = Vec::new();
+        self.servers.get_servers().iter().for_each(|server| {
+            let name = match server.label() {
+                Some(desc) => format!("{} ({})", server.get_abr(), desc),
+                None => server.get_abr().to_string(),
+            };
+            entries.push(PaletteEntry::new(
+                format!("Jump to {}", name),
+                PaletteCommand::JumpToRegion(server.get_abr().to_string()),
+            ));
+            entries.push(PaletteEntry::new(
+                format!("Disable {}", name),
+                PaletteCommand::DisableRegion(server.get_abr().to_string()),
+            ));
+            entries.push(PaletteEntry::new(
+                format!("Enable {}", name),
+                PaletteCommand::EnableRegion(server.get_abr().to_string()),
+            ));
+        });
+        Continent::all().into_iter().for_each(|continent| {
+            entries.push(PaletteEntry::new(
+                format!("Disable all {}", continent),
+                PaletteCommand::DisableContinent(continent),
+            ));
+            entries.push(PaletteEntry::new(
+                format!("Enable all {}", continent),
+                PaletteCommand::EnableContinent(continent),
+            ));
+        });
+        self.profiles.profiles().iter().for_each(|profile| {
+            entries.push(PaletteEntry::new(
+                format!("Apply profile {}", profile.name),
+                PaletteCommand::ApplyProfile(profile.name.clone()),
+            ));
+        });
+        AppMode::all().into_iter().for_each(|app_mode| {
+            entries.push(PaletteEntry::new(
+                format!("Switch to {}", app_mode),
+                PaletteCommand::SetAppMode(app_mode),
+            ));
+        });
+        entries.extend([
+            PaletteEntry::new(
+                format!("Disable all {}", self.view_state.grid.category),
+                PaletteCommand::DisableAll,
+            ),
+            PaletteEntry::new(
+                format!("Enable all {}", self.view_state.grid.category),
+                PaletteCommand::EnableAll,
+            ),
+            PaletteEntry::new("Toggle history", PaletteCommand::ToggleHistory),
+            PaletteEntry::new("Toggle diagnostics", PaletteCommand::ToggleDiagnostics),
+            PaletteEntry::new("Open settings", PaletteCommand::OpenSettings),
+            PaletteEntry::new("Download server list", PaletteCommand::DownloadServerList),
+        ]);
+        entries
+    }
 
-            for ip in ip_list.iter() {
-                self.ping_info.remove(ip);
+    /// Run the command chosen in the command palette.
+    fn run_palette_command(&mut self, command: PaletteCommand) {
+        let set_blocked = |abr: &str, blocked: bool| match self.servers.get_server(abr) {
+            Some(server) if blocked => Self::disable_server(server, &self.worker),
+            Some(server) => Self::enable_server(server, &self.worker),
+            None => log::error!("no region named `{}`", abr),
+        };
+        let set_continent_blocked = |continent: Continent, blocked: bool| {
+            self.servers
+                .get_servers_in(ServerCategory::Relay)
+                .filter(|server| Continent::of_server(server) == Some(continent))
+                .for_each(|server| set_blocked(server.get_abr(), blocked));
+        };
+        match command {
+            PaletteCommand::JumpToRegion(abr) => {
+                let Some(server) = self.servers.get_server(&abr) else {
+                    log::error!("no region named `{}`", abr);
+                    return;
+                };
+                self.app_mode = AppMode::Grid;
+                self.view_state.grid.category = server.category();
+                if server.get_ips().is_empty() {
+                    self.view_state.grid.show_empty_regions = true;
+                }
+                self.scroll_to_region = Some(abr);
             }
+            PaletteCommand::EnableRegion(abr) => set_blocked(&abr, false),
+            PaletteCommand::DisableRegion(abr) => set_blocked(&abr, true),
+            PaletteCommand::EnableContinent(continent) => set_continent_blocked(continent, false),
+            PaletteCommand::DisableContinent(continent) => set_continent_blocked(continent, true),
+            PaletteCommand::EnableAll => self.enable_all_servers(),
+            PaletteCommand::DisableAll => self.disable_all_servers(),
+            PaletteCommand::ApplyProfile(name) => self.apply_profile(&name),
+            PaletteCommand::SetAppMode(app_mode) => self.app_mode = app_mode,
+            PaletteCommand::ToggleHistory => self.show_history = !self.show_history,
+            PaletteCommand::ToggleDiagnostics => self.show_diagnostics = !self.show_diagnostics,
+            PaletteCommand::OpenSettings => self.show_settings = true,
+            PaletteCommand::DownloadServerList => self.download_server_list(),
+        }
+    }
+
+    /// Create the UI of the command palette, if it is open.
+    fn ui_command_palette(&mut self, ctx: &egui::Context) {
+        if !self.command_palette.is_open() {
+            return;
+        }
+        let entries = self.palette_entries();
+        if let Some(command) = self.command_palette.ui(ctx, &entries) {
+            self.run_palette_command(command);
         }
     }
 
     /// Create the UI for the [`App`].
     pub fn ui(&mut self, ui: &mut egui::Ui, id: egui::Id) {
+        if self.servers.is_embedded() {
+            ui.label(
+                egui::RichText::new(format!(
+                    "Stale data: the server list could not be downloaded, using the snapshot \
+                     built into the app (revision {}). Download the server list once online.",
+                    self.servers.get_revision()
+                ))
+                .color(egui::Color32::WHITE)
+                .background_color(egui::Color32::from_rgb(200, 0, 0)),
+            );
+        }
+
+        if ui
+            .ctx()
+            .input_mut(|input| input.consume_key(egui::Modifiers::COMMAND, egui::Key::P))
+        {
+            self.command_palette.toggle();
+        }
+
         ui.horizontal(|ui| {
             if ui.button("Download Server List").clicked() {
-                let download_file_res = Servers::download_file();
-                if let Err(err) = download_file_res {
-                    log::error!("{}", err);
+                self.download_server_list();
+            }
+
+            ui.separator();
+
+            ui.label("Game:");
+            if let Some(app_id) = Self::ui_app_picker(
+                ui,
+                "app_picker",
+                &self.pickable_apps(),
+                self.servers.get_app_id(),
+            ) {
+                if let Err(err) = self.switch_app(app_id) {
+                    log::error!("unable to load the servers of app {}: {}", app_id, err);
                 }
-                self.servers = Servers::new(None::<PathBuf>);
             }
 
             ui.separator();
@@ -912,8 +4736,118 @@ impl App {
             ui.label("App mode:");
 
             self.app_mode.ui(ui, id.with("app_mode"));
+
+            ui.separator();
+
+            ui.toggle_value(&mut self.show_history, "History")
+                .on_hover_text("When the regions were blocked and unblocked");
+
+            self.ui_report(ui);
+
+            let unhealthy = self
+                .thread_healths()
+                .any(|(_, health)| health.status() != ThreadStatus::Running);
+            ui.toggle_value(
+                &mut self.show_diagnostics,
+                if unhealthy {
+                    egui::RichText::new("Diagnostics (!)").color(egui::Color32::from_rgb(200, 0, 0))
+                } else {
+                    egui::RichText::new("Diagnostics")
+                },
+            )
+            .on_hover_text("Health of the background threads");
+
+            ui.toggle_value(&mut self.show_settings, "Settings");
+
+            if self.active_relay_guard != ActiveRelayGuard::Off && !self.regions_in_use.is_empty() {
+                ui.separator();
+                ui.label(format!(
+                    "In use: {}",
+                    self.regions_in_use
+                        .iter()
+                        .map(|(abr, _)| abr.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+                .on_hover_text(format!(
+                    "The game traffic is flowing through these regions, blocking them is {}",
+                    match self.active_relay_guard {
+                        ActiveRelayGuard::Warn => "confirmed first",
+                        _ => "refused",
+                    }
+                ));
+            }
+
+            if let Some(app_id) = self.running_game {
+                ui.separator();
+                ui.label(format!("In game: {}", game::name(app_id)))
+                    .on_hover_text(format!(
+                        "Blocking every region{} is {} while the game is running",
+                        self.home_region
+                            .as_deref()
+                            .map_or_else(String::new, |abr| format!(" or the home region {}", abr)),
+                        match self.match_guard {
+                            MatchGuard::Warn => "confirmed first",
+                            _ => "refused",
+                        }
+                    ));
+            }
+
+            if let Some(cs2_user_convars) = &self.cs2_user_convars {
+                ui.separator();
+
+                ui.label("CS2 max ping:");
+                ui.add(
+                    egui::DragValue::new(&mut self.cs2_max_ping)
+                        .range(cs2::MAX_PING_RANGE)
+                        .suffix(" ms"),
+                )
+                .on_hover_text(cs2::MAX_PING_CONVAR);
+
+                if ui
+                    .button("Read")
+                    .on_hover_text(cs2_user_convars.path().display().to_string())
+                    .clicked()
+                {
+                    match cs2_user_convars.max_ping() {
+                        Ok(max_ping) => {
+                            self.cs2_max_ping = max_ping.unwrap_or(*cs2::MAX_PING_RANGE.end())
+                        }
+                        Err(err) => log::error!("{}", err),
+                    }
+                }
+                if ui
+                    .button("Write")
+                    .on_hover_text("CS2 must not be running")
+                    .clicked()
+                {
+                    if let Err(err) = cs2_user_convars.set_max_ping(self.cs2_max_ping) {
+                        log::error!("{}", err);
+                    }
+                }
+            }
         });
 
+        self.ui_app_tabs(ui);
+
+        self.ui_profiles(ui);
+
+        self.ui_trial_block(ui);
+
+        self.ui_location_suggestion(ui.ctx());
+
+        self.ui_history(ui.ctx());
+
+        self.ui_diagnostics(ui.ctx());
+
+        self.ui_settings(ui.ctx());
+
+        self.ui_server_list_diff(ui.ctx());
+
+        self.ui_command_palette(ui.ctx());
+
+        self.ui_refused_blocks(ui.ctx());
+
         // debug ping info
         if false {
             egui::Window::new("debug_ping_info_window")
@@ -952,14 +4886,74 @@ impl App {
 
     /// Create the UI for the [`App`] in [`AppMode::Grid`].
     pub fn ui_grid_mode(&mut self, ui: &mut egui::Ui, _id: egui::Id) {
-        let num_columns = 6;
+        let categories: Vec<_> = ServerCategory::all()
+            .into_iter()
+            .filter(|category| self.servers.has_category(*category))
+            .collect();
+        if !categories.contains(&self.view_state.grid.category) {
+            // the saved category is not managed in this run
+            self.view_state.grid.category = ServerCategory::Relay;
+        }
+        if categories.len() > 1 {
+            ui.horizontal(|ui| {
+                categories.into_iter().for_each(|category| {
+                    ui.selectable_value(
+                        &mut self.view_state.grid.category,
+                        category,
+                        category.name(),
+                    );
+                });
+            });
+        }
+        if self.view_state.grid.category == ServerCategory::Content {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "Blocking content servers affects the Steam downloads, \
+                 not the game servers.",
+            );
+        }
+
+        self.ui_grid_filter(ui);
+        if self.view_state.grid.category == ServerCategory::Relay {
+            self.ui_selection_expression(ui);
+            self.ui_block_suggestions(ui);
+            self.ui_rules(ui);
+            self.ui_paste_block(ui);
+        }
+        self.ui_score(ui);
+        self.ui_recommendation(ui);
+        self.ui_presets(ui);
+        Self::ui_show_empty_regions(
+            ui,
+            &self.servers,
+            Some(self.view_state.grid.category),
+            &mut self.view_state.grid.show_empty_regions,
+        );
+        if self
+            .servers
+            .get_servers_in(self.view_state.grid.category)
+            .any(|server| server.group().is_some())
+        {
+            ui.checkbox(&mut self.view_state.grid.group_regions, "Group Regions")
+                .on_hover_text("List the regions under their group (eg: continent or country)");
+        } else {
+            self.view_state.grid.group_regions = false;
+        }
+
+        let show_steam_pings = self.steam_pings.is_some();
+        let num_columns = if show_steam_pings { 9 } else { 8 };
         egui::Grid::new("ui_grid")
             .max_col_width(ui.available_width())
             .num_columns(num_columns)
             .striped(true)
             .show(ui, |ui| {
                 ui.columns(num_columns, |columns| {
-                    columns[0].label("Region");
+                    Self::ui_sort_header(
+                        &mut columns[0],
+                        GridSortColumn::Region,
+                        &mut self.view_state.grid,
+                    )
+                    .on_hover_text("Click to sort");
 
                     columns[1].horizontal(|ui| {
                         let mut all_ips_selected =
@@ -973,7 +4967,8 @@ impl App {
                                 .for_each(|selected| *selected = all_ips_selected);
                         }
 
-                        ui.label("State");
+                        Self::ui_sort_header(ui, GridSortColumn::State, &mut self.view_state.grid)
+                            .on_hover_text("Click to sort by the fraction of blocked IPs");
                     });
                     if columns[2].button("Enable Selected").clicked() {
                         self.enable_selected_ips();
@@ -981,30 +4976,264 @@ impl App {
                     if columns[3].button("Disable Selected").clicked() {
                         self.disable_selected_ips();
                     }
-                    columns[4].label("Ping");
-                    columns[5].label("Loss");
+                    Self::ui_sort_header(
+                        &mut columns[4],
+                        GridSortColumn::Ping,
+                        &mut self.view_state.grid,
+                    )
+                    .on_hover_text("Click to sort, right click to change the colors")
+                    .context_menu(|ui| self.ping_thresholds.ui(ui));
+                    Self::ui_sort_header(
+                        &mut columns[5],
+                        GridSortColumn::Loss,
+                        &mut self.view_state.grid,
+                    )
+                    .on_hover_text(
+                        "Over the last minute / over the session, click to sort by the loss \
+                         over the last minute, right click to change the colors",
+                    )
+                    .context_menu(|ui| self.ping_thresholds.ui(ui));
+                    Self::ui_sort_header(
+                        &mut columns[6],
+                        GridSortColumn::Jitter,
+                        &mut self.view_state.grid,
+                    )
+                    .on_hover_text(
+                        "Standard deviation of the recent pings, click to sort, right click \
+                         to change the colors",
+                    )
+                    .context_menu(|ui| self.ping_thresholds.ui(ui));
+                    Self::ui_sort_header(
+                        &mut columns[7],
+                        GridSortColumn::Score,
+                        &mut self.view_state.grid,
+                    )
+                    .on_hover_text(format!(
+                        "{} × ping + {} × jitter + {} × loss %, lower is better, click to sort",
+                        self.score_weights.ping, self.score_weights.jitter, self.score_weights.loss
+                    ));
+                    if show_steam_pings {
+                        columns[8]
+                            .label("Steam's Ping")
+                            .on_hover_text("Ping measured by Steam through the relays");
+                    }
                 });
                 ui.end_row();
 
-                let server_status_message_sender = &self.server_status_message_sender;
                 let server_status_info = &self.server_status_info;
-                let pinger_message_sender = &self.pinger_message_sender;
-                let ping_info = &mut self.ping_info;
-                let firewall = self.firewall.clone();
-                let mut ping_info_remove_ips: Option<Vec<Ipv4Addr>> = None;
-                for server in self.servers.get_servers() {
+                let steam_status_info = &self.steam_status_info;
+                let port_checker = &self.port_checker;
+                let port_reports = &self.port_reports;
+                let asn_infos = &self.asn_infos;
+                let port_checks_pending = &mut self.port_checks_pending;
+                let partial_block_percent = &mut self.partial_block_percent;
+                let worker = &self.worker;
+                let grid_cache = &self.grid_cache;
+                let ping_thresholds = &self.ping_thresholds;
+                let can_deprioritize = self.firewall.can_deprioritize();
+                let deprioritized_ips = &self.deprioritized_ips;
+                let mut visible_servers = Vec::new();
+                let mut servers =
+                    Self::grid_servers(&self.servers, &self.view_state, &self.grid_filter);
+                Self::sort_grid_servers(
+                    &mut servers,
+                    &self.view_state.grid,
+                    grid_cache,
+                    server_status_info,
+                );
+                let scroll_to_region = self.scroll_to_region.take();
+                let rows: Vec<_> = if self.view_state.grid.group_regions {
+                    // the groups are in the order of their first region
+                    let mut groups: Vec<(&str, Vec<&ServerInfo>)> = Vec::new();
+                    servers.into_iter().for_each(|server| {
+                        let group = grid_group(server);
+                        match groups.iter_mut().find(|(name, _)| *name == group) {
+                            Some((_, members)) => members.push(server),
+                            None => groups.push((group, vec![server])),
+                        }
+                    });
+                    if let Some(group) = scroll_to_region
+                        .as_deref()
+                        .and_then(|region| grid_cache.region_groups.get(region))
+                    {
+                        self.collapsed_groups.remove(group);
+                    }
+                    groups
+                        .into_iter()
+                        .flat_map(|(group, members)| {
+                            let shown = if self.collapsed_groups.contains(group) {
+                                Vec::new()
+                            } else {
+                                members.clone()
+                            };
+                            std::iter::once(GridRow::Group(group, members))
+                                .chain(shown.into_iter().map(GridRow::Region))
+                        })
+                        .collect()
+                } else {
+                    servers.into_iter().map(GridRow::Region).collect()
+                };
+                for row in rows {
+                    let server = match row {
+                        GridRow::Group(group, members) => {
+                            let collapsed = self.collapsed_groups.contains(group);
+                            ui.columns(num_columns, |columns| {
+                                let arrow = if collapsed { "⏵" } else { "⏷" };
+                                if columns[0]
+                                    .add(
+                                        egui::Label::new(
+                                            egui::RichText::new(format!(
+                                                "{} {} ({})",
+                                                arrow,
+                                                group,
+                                                members.len()
+                                            ))
+                                            .strong(),
+                                        )
+                                        .sense(egui::Sense::click()),
+                                    )
+                                    .on_hover_text("Click to show or hide the regions of the group")
+                                    .clicked()
+                                {
+                                    if collapsed {
+                                        self.collapsed_groups.remove(group);
+                                    } else {
+                                        self.collapsed_groups.insert(group.to_string());
+                                    }
+                                }
+
+                                let num_disabled = members
+                                    .iter()
+                                    .filter(|server| {
+                                        matches!(
+                                            server_status_info.get(server.get_abr()),
+                                            Some(ServerState::AllDisabled)
+                                        )
+                                    })
+                                    .count();
+                                columns[1].label(format!(
+                                    "{} of {} disabled",
+                                    num_disabled,
+                                    members.len()
+                                ));
+
+                                if columns[2].button("Enable Group").clicked() {
+                                    members
+                                        .iter()
+                                        .for_each(|server| Self::enable_server(server, worker));
+                                }
+                                if columns[3].button("Disable Group").clicked() {
+                                    members
+                                        .iter()
+                                        .for_each(|server| Self::disable_server(server, worker));
+                                }
+
+                                let [ping_ui, loss_ui, jitter_ui, score_ui] = &mut columns[4..8]
+                                else {
+                                    unreachable!("the grid has at least 8 columns");
+                                };
+                                grid_cache
+                                    .group_ping_summaries
+                                    .get(group)
+                                    .unwrap_or(&PingSummary::NONE)
+                                    .ui(ping_ui, loss_ui, jitter_ui, score_ui, ping_thresholds);
+                            });
+                            ui.end_row();
+                            continue;
+                        }
+                        GridRow::Region(server) => server,
+                    };
+                    let recommended = self
+                        .recommendation
+                        .as_ref()
+                        .is_some_and(|recommendation| recommendation.contains(server.get_abr()));
                     ui.columns(num_columns, |columns| {
-                        let region_with_ips_response =
-                            columns[0].collapsing(server.get_abr(), |ui| {
-                                server.get_ipv4s().iter().for_each(|ip| {
-                                    ui.label(ip.to_string());
+                        let region_header = if recommended {
+                            egui::RichText::new(format!("★ {}", server.get_abr()))
+                                .color(egui::Color32::GOLD)
+                        } else {
+                            egui::RichText::new(server.get_abr())
+                        };
+                        let region_with_ips_response = egui::CollapsingHeader::new(region_header)
+                            .id_source(server.get_abr())
+                            .show(&mut columns[0], |ui| {
+                                server.get_ips().iter().for_each(|ip| {
+                                    let Some(asn_info) = asn_infos.get(ip) else {
+                                        ui.label(grid_cache.ip_label(ip).ip.as_str());
+                                        return;
+                                    };
+                                    ui.horizontal(|ui| {
+                                        ui.label(grid_cache.ip_label(ip).ip.as_str());
+                                        if asn_info.is_valve() {
+                                            ui.weak(asn_info.to_string());
+                                        } else {
+                                            ui.colored_label(
+                                                egui::Color32::from_rgb(200, 0, 0),
+                                                format!("not Valve: {}", asn_info),
+                                            )
+                                            .on_hover_text(
+                                                "The IP is not announced by Valve's AS, the \
+                                                 entry of the config may be stale",
+                                            );
+                                        }
+                                    });
                                 });
                             });
 
-                        if let Some(server_description) = server.desc() {
+                        if scroll_to_region.as_deref() == Some(server.get_abr()) {
                             region_with_ips_response
                                 .header_response
-                                .on_hover_text(server_description);
+                                .scroll_to_me(Some(egui::Align::Center));
+                        }
+
+                        if columns[0].is_rect_visible(region_with_ips_response.header_response.rect)
+                        {
+                            visible_servers.push(server.get_abr());
+                        }
+
+                        region_with_ips_response.header_response.context_menu(|ui| {
+                            if ui
+                                .add_enabled(
+                                    !port_checks_pending.contains(server.get_abr()),
+                                    egui::Button::new("Check Ports"),
+                                )
+                                .on_hover_text("Check if the UDP ports of the relays are reachable")
+                                .clicked()
+                            {
+                                port_checker.check(server);
+                                port_checks_pending.insert(server.get_abr().to_string());
+                                ui.close_menu();
+                            }
+
+                            ui.separator();
+                            let num_ips = server.get_ips().len();
+                            let percent = partial_block_percent
+                                .entry(server.get_abr().to_string())
+                                .or_insert_with(|| {
+                                    server_status_info
+                                        .get(server.get_abr())
+                                        .and_then(|state| state.blocked_fraction(num_ips))
+                                        .map_or(0, |fraction| (fraction * 100.0).round() as u8)
+                                });
+                            ui.add(egui::Slider::new(percent, 0..=100).suffix("%"));
+                            let (blocked, _) = server.partial_block_ips(*percent);
+                            if ui
+                                .button(format!("Block {} of {} IPs", blocked.len(), num_ips))
+                                .on_hover_text(
+                                    "Block a fixed subset of the relays so that the region \
+                                     is picked less often without removing it",
+                                )
+                                .clicked()
+                            {
+                                Self::partial_block_server(server, *percent, worker);
+                                ui.close_menu();
+                            }
+                        });
+
+                        if let Some(server_label) = server.label() {
+                            region_with_ips_response
+                                .header_response
+                                .on_hover_text(server_label);
                         }
 
                         let ip_list_shown = region_with_ips_response.body_returned.is_some();
@@ -1015,179 +5244,135 @@ impl App {
 
                         columns[1].horizontal(|ui| {
                             let mut all_ips_selected = server
-                                .get_ipv4s()
+                                .get_ips()
                                 .iter()
                                 .all(|ip| *self.ip_selection_status.entry(*ip).or_insert(false));
                             let prev_all_ips_selected = all_ips_selected;
                             ui.checkbox(&mut all_ips_selected, "");
                             if prev_all_ips_selected != all_ips_selected {
                                 // the user selected or deselected all ips
-                                server.get_ipv4s().iter().for_each(|ip| {
+                                server.get_ips().iter().for_each(|ip| {
                                     *self.ip_selection_status.get_mut(ip).unwrap() =
                                         all_ips_selected
                                 });
                             }
-                            ui.label(server_status.to_string());
+                            ui.label(server_status.label(server.get_ips().len()));
+                            if let Some(steam_status) = steam_status_info.get(server.get_abr()) {
+                                ui_datacenter_status(ui, steam_status);
+                            }
+                            if port_checks_pending.contains(server.get_abr()) {
+                                ui.spinner().on_hover_text("Checking the ports");
+                            } else if let Some(report) = port_reports.get(server.get_abr()) {
+                                ui_port_report(ui, report);
+                            }
                         });
 
                         if columns[2].button("Enable").clicked() {
-                            Self::enable_server(
-                                server,
-                                &firewall,
-                                server_status_message_sender,
-                                pinger_message_sender,
-                            );
+                            Self::enable_server(server, worker);
                         }
 
                         if ip_list_shown {
-                            server.get_ipv4s().iter().for_each(|ip| {
+                            server.get_ips().iter().for_each(|ip| {
                                 columns[1]
                                     .checkbox(self.ip_selection_status.get_mut(ip).unwrap(), "");
-                                if columns[2].button(format!("Enable {}", ip)).clicked() {
-                                    Self::enable_ip(
-                                        *ip,
-                                        server,
-                                        &firewall,
-                                        server_status_message_sender,
-                                        pinger_message_sender,
-                                    );
+                                if columns[2]
+                                    .button(grid_cache.ip_label(ip).enable.as_str())
+                                    .clicked()
+                                {
+                                    Self::enable_ip(*ip, server, worker);
                                 }
                             });
                         }
 
-                        if columns[3].button("Disable").clicked() {
-                            Self::disable_server(
-                                server,
-                                &firewall,
-                                server_status_message_sender,
-                                pinger_message_sender,
-                                &mut ping_info_remove_ips,
-                            );
-                        }
+                        columns[3].horizontal(|ui| {
+                            if ui.button("Disable").clicked() {
+                                Self::disable_server(server, worker);
+                            }
+                            if can_deprioritize {
+                                let deprioritized = server
+                                    .get_ips()
+                                    .iter()
+                                    .any(|ip| deprioritized_ips.contains(ip));
+                                if ui
+                                    .selectable_label(deprioritized, "Deprioritize")
+                                    .on_hover_text(
+                                        "Delay the traffic of the region instead of blocking \
+                                         it, matchmaking avoids it but can still use it as \
+                                         the last resort, click again to restore it",
+                                    )
+                                    .clicked()
+                                {
+                                    Self::deprioritize_server(server, !deprioritized, worker);
+                                }
+                            }
+                        });
 
                         if ip_list_shown {
-                            server.get_ipv4s().iter().for_each(|ip| {
-                                if columns[3].button(format!("Disable {}", ip)).clicked() {
-                                    Self::disable_ip(
-                                        *ip,
-                                        server,
-                                        &firewall,
-                                        server_status_message_sender,
-                                        pinger_message_sender,
-                                        &mut ping_info_remove_ips,
-                                    );
+                            server.get_ips().iter().for_each(|ip| {
+                                if columns[3]
+                                    .button(grid_cache.ip_label(ip).disable.as_str())
+                                    .clicked()
+                                {
+                                    Self::disable_ip(*ip, server, worker);
                                 }
                             });
                         }
 
                         if let ServerState::AllDisabled = server_status {
-                            columns[4].label("Disabled");
-                            columns[5].label("Disabled");
+                            columns[4..8].iter_mut().for_each(|column| {
+                                column.label("Disabled");
+                            });
                         } else {
-                            let server_ping_info: Vec<_> = server
-                                .get_ipv4s()
-                                .iter()
-                                .map(|ip| {
-                                    if ping_info.contains_key(ip) {
-                                        Some(Self::calculate_total_ping_for_ip(ping_info, *ip))
-                                    } else {
-                                        None
-                                    }
-                                })
-                                .collect();
-
-                            let (server_total_ping, server_num_packets, server_lost_packets) =
-                                server_ping_info
-                                    .iter()
-                                    .filter_map(|ping_info| ping_info.as_ref())
-                                    .fold(
-                                        (Duration::ZERO, 0, 0),
-                                        |acc, (ping, total_num_packets, lost_packets)| {
-                                            (
-                                                acc.0 + *ping,
-                                                acc.1 + total_num_packets,
-                                                acc.2 + lost_packets,
-                                            )
-                                        },
-                                    );
-
-                            let ui_ping_info =
-                                |ping_ui: &mut egui::Ui,
-                                 loss_ui: &mut egui::Ui,
-                                 total_ping: Duration,
-                                 num_packets: usize,
-                                 lost_packets: usize| {
-                                    if num_packets == lost_packets {
-                                        ping_ui.label("NA");
-                                        loss_ui.label("100.00%");
-                                    } else {
-                                        let num_valid_packets = num_packets - lost_packets;
-                                        let ping =
-                                            total_ping / u32::try_from(num_valid_packets).unwrap();
-
-                                        ping_ui.label(format!("{}", PingInfo::new(ping)));
-                                        loss_ui.label(format!(
-                                            "{:.2}%",
-                                            lost_packets as f64 / num_packets as f64 * 100.0
-                                        ));
-                                    }
-                                };
-
-                            let (ping_ui, column_ui) = {
-                                let splits = columns.split_at_mut(5);
-                                (splits.0.last_mut().unwrap(), splits.1.first_mut().unwrap())
+                            let [ping_ui, loss_ui, jitter_ui, score_ui] = &mut columns[4..8] else {
+                                unreachable!("the grid has at least 8 columns");
                             };
 
-                            ui_ping_info(
-                                ping_ui,
-                                column_ui,
-                                server_total_ping,
-                                server_num_packets,
-                                server_lost_packets,
-                            );
+                            grid_cache
+                                .region_ping_summaries
+                                .get(server.get_abr())
+                                .unwrap_or(&PingSummary::NONE)
+                                .ui(ping_ui, loss_ui, jitter_ui, score_ui, ping_thresholds);
 
                             if ip_list_shown {
-                                server_ping_info.into_iter().for_each(|ping_info| {
-                                    if let Some((total_ping, num_packets, lost_packets)) = ping_info
-                                    {
-                                        ui_ping_info(
-                                            ping_ui,
-                                            column_ui,
-                                            total_ping,
-                                            num_packets,
-                                            lost_packets,
-                                        );
-                                    } else {
-                                        ping_ui.label("NA");
-                                        column_ui.label("100.00%");
-                                    }
+                                server.get_ips().iter().for_each(|ip| {
+                                    grid_cache
+                                        .ip_ping_summaries
+                                        .get(ip)
+                                        .unwrap_or(&PingSummary::NONE)
+                                        .ui(ping_ui, loss_ui, jitter_ui, score_ui, ping_thresholds);
                                 });
                             }
                         }
+
+                        if show_steam_pings {
+                            columns[8].label(
+                                grid_cache
+                                    .steam_ping_labels
+                                    .get(server.get_abr())
+                                    .map_or("NA", String::as_str),
+                            );
+                        }
                     });
 
                     ui.end_row();
                 }
 
-                if let Some(ip_list) = ping_info_remove_ips {
-                    // HACK: wait for the channel to get all the
-                    // messages before flushing them
-                    std::thread::sleep(Duration::from_secs(1));
-                    // flush the ping messages channel
-                    self.update_ping_info();
-
-                    for ip in ip_list.iter() {
-                        self.ping_info.remove(ip);
-                    }
+                if !visible_servers.iter().eq(self.visible_servers.iter()) {
+                    self.visible_servers = visible_servers
+                        .into_iter()
+                        .map(|server| server.to_string())
+                        .collect();
+                    self.worker
+                        .send(Command::SetVisible(self.visible_servers.clone()));
                 }
             });
     }
 
     /// Create the UI for the [`App`] in [`AppMode::Map`].
     pub fn ui_map_mode(&mut self, ui: &mut egui::Ui, _id: egui::Id) {
-        if self.map_tiles.is_none() {
-            self.map_tiles = Some(walkers::HttpTiles::new(
-                walkers::sources::OpenStreetMap,
+        if self.map_tiles.is_none() && !self.offline_map {
+            self.map_tiles = Some(map_tiles::http_tiles(
+                &self.config.map_tile_url,
                 ui.ctx().clone(),
             ));
         }
@@ -1201,19 +5386,217 @@ impl App {
                 let _ = self.map_memory.zoom_out();
             }
             ui.label(self.map_memory.zoom().to_string());
+
+            ui.separator();
+
+            ui.checkbox(&mut self.offline_map, "Offline Map")
+                .on_hover_text("Draw the map without downloading the map tiles");
+
+            Self::ui_show_empty_regions(
+                ui,
+                &self.servers,
+                None,
+                &mut self.view_state.map.show_empty_regions,
+            );
+
+            ui.separator();
+
+            ui.label("Color by:");
+            MapColoring::all().into_iter().for_each(|coloring| {
+                ui.selectable_value(&mut self.view_state.map.coloring, coloring, coloring.name());
+            });
         });
 
-        ui.add(
-            walkers::Map::new(
-                Some(self.map_tiles.as_mut().expect("is initialized by now")),
-                &mut self.map_memory,
-                walkers::Position::from_lon_lat(0.0, 0.0),
-            )
-            .with_plugin(ServersOnMap {
-                servers: self.servers.get_servers(),
-                server_status_info: &self.server_status_info,
-            }),
-        );
+        self.ui_map_legend(ui);
+
+        self.ui_recommendation(ui);
+
+        ui.horizontal(|ui| {
+            if self.map_selection.is_empty() {
+                ui.weak("Click a region to block or unblock it, Shift + drag to select regions");
+                return;
+            }
+            ui.label(format!("{} regions selected", self.map_selection.len()))
+                .on_hover_text(self.map_selection.join(", "));
+            let enable = ui.button("Enable Selected").clicked();
+            let disable = ui.button("Disable Selected").clicked();
+            if enable || disable {
+                self.map_selection
+                    .iter()
+                    .filter_map(|region| self.servers.get_server(region))
+                    .for_each(|server| {
+                        if enable {
+                            Self::enable_server(server, &self.worker);
+                        } else {
+                            Self::disable_server(server, &self.worker);
+                        }
+                    });
+            }
+            if ui.button("Clear Selection").clicked() {
+                self.map_selection.clear();
+            }
+        });
+
+        // the map is not dragged while selecting
+        let selecting = ui.input(|input| input.modifiers.shift);
+        let mut interaction = MapInteraction::default();
+        let servers_on_map = ServersOnMap {
+            servers: self.servers.get_servers(),
+            server_status_info: &self.server_status_info,
+            coloring: self.view_state.map.coloring,
+            pings: self
+                .grid_cache
+                .region_ping_summaries
+                .iter()
+                .filter_map(|(region, summary)| Some((region.as_str(), summary.ping?)))
+                .collect(),
+            ping_thresholds: &self.ping_thresholds,
+            show_empty: self.view_state.map.show_empty_regions,
+            selected: &self.map_selection,
+            recommended: self
+                .recommendation
+                .as_ref()
+                .map_or(&[], |recommendation| recommendation.regions.as_slice()),
+            selecting,
+            selection_start: &mut self.map_selection_start,
+            interaction: &mut interaction,
+        };
+        let response = match self.map_tiles.as_mut().filter(|_| !self.offline_map) {
+            Some(map_tiles) => ui.add(
+                walkers::Map::new(
+                    Some(map_tiles),
+                    &mut self.map_memory,
+                    walkers::Position::from_lon_lat(0.0, 0.0),
+                )
+                .drag_gesture(!selecting)
+                .with_plugin(servers_on_map),
+            ),
+            None => ui.add(
+                walkers::Map::new(
+                    None,
+                    &mut self.map_memory,
+                    walkers::Position::from_lon_lat(0.0, 0.0),
+                )
+                .drag_gesture(!selecting)
+                .with_plugin(WorldOutline)
+                .with_plugin(servers_on_map),
+            ),
+        };
+
+        if let Some(server) = interaction
+            .hovered
+            .and_then(|region| self.servers.get_server(&region))
+        {
+            response.on_hover_text_at_pointer(self.map_marker_text(server));
+        }
+        if let Some(server) = interaction
+            .clicked
+            .and_then(|region| self.servers.get_server(&region))
+        {
+            match self.server_status_info.get(server.get_abr()) {
+                Some(ServerState::AllDisabled) => Self::enable_server(server, &self.worker),
+                _ => Self::disable_server(server, &self.worker),
+            }
+        }
+        if let Some(selected) = interaction.selected {
+            self.map_selection = selected;
+        }
+    }
+
+    /// Create the legend of the colors of the markers of the map, see
+    /// [`MapColoring`].
+    fn ui_map_legend(&self, ui: &mut egui::Ui) {
+        let swatch = |ui: &mut egui::Ui, color: egui::Color32, label: &str| {
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+            ui.painter().circle_filled(rect.center(), 5.0, color);
+            ui.label(label);
+        };
+        ui.horizontal(|ui| match self.view_state.map.coloring {
+            MapColoring::Status => {
+                [
+                    ServerState::NoneDisabled,
+                    ServerState::SomeDisabled(Vec::new()),
+                    ServerState::AllDisabled,
+                    ServerState::Unknown,
+                ]
+                .iter()
+                .for_each(|state| {
+                    let label = match state {
+                        ServerState::NoneDisabled => "Not blocked",
+                        ServerState::SomeDisabled(_) => "Partially blocked",
+                        ServerState::AllDisabled => "Blocked",
+                        ServerState::Unknown => "Unknown",
+                    };
+                    swatch(ui, ServersOnMap::status_color(state), label);
+                });
+            }
+            MapColoring::Latency => {
+                let max_millis = self.ping_thresholds.ok_millis * 2;
+                ui.label("0 ms");
+                let (rect, _) =
+                    ui.allocate_exact_size(egui::vec2(160.0, 12.0), egui::Sense::hover());
+                const STEPS: u32 = 32;
+                (0..STEPS).for_each(|step| {
+                    let width = rect.width() / STEPS as f32;
+                    let millis = u64::from(max_millis * step / STEPS);
+                    ui.painter().rect_filled(
+                        egui::Rect::from_min_size(
+                            rect.min + egui::vec2(width * step as f32, 0.0),
+                            egui::vec2(width, rect.height()),
+                        ),
+                        0.0,
+                        self.ping_thresholds
+                            .gradient_color(Duration::from_millis(millis)),
+                    );
+                });
+                ui.label(format!("{} ms+", max_millis));
+                ui.separator();
+                swatch(ui, ServersOnMap::NO_PING_COLOR, "No ping");
+            }
+        });
+    }
+
+    /// Get the hover text of the marker of the server on the map: its
+    /// label, state, ping, loss and IPs.
+    fn map_marker_text(&self, server: &ServerInfo) -> String {
+        let summary = self
+            .grid_cache
+            .region_ping_summaries
+            .get(server.get_abr())
+            .unwrap_or(&PingSummary::NONE);
+        let mut text = match server.label() {
+            Some(label) => format!("{}: {}", server.get_abr(), label),
+            None => server.get_abr().to_string(),
+        };
+        let state = self
+            .server_status_info
+            .get(server.get_abr())
+            .unwrap_or(&ServerState::Unknown);
+        text.push_str(&format!(
+            "\n{}\nPing: {}, loss: {}",
+            state.label(server.get_ips().len()),
+            summary.ping_label,
+            summary.loss_label
+        ));
+        server.get_ips().iter().for_each(|ip| {
+            text.push_str(&format!("\n{}", ip));
+        });
+        text
+    }
+}
+
+impl Drop for App {
+    fn drop(&mut self) {
+        // restore the blocked regions of an unfinished trial block
+        self.finish_trial_block();
+
+        if session::is_active() {
+            // the queued firewall changes must be applied first
+            self.worker.flush();
+            if let Err(err) = session::rollback(&self.firewall) {
+                log::error!("unable to undo the blocks of the session: {}", err);
+            }
+        }
     }
 }
 
@@ -1227,10 +5610,17 @@ enum ServerSelectionStatus {
     None,
 }
 
-impl Default for App {
-    fn default() -> Self {
-        Self::new()
-    }
+/// Interaction with the markers of the [`ServersOnMap`] during a
+/// frame.
+#[derive(Debug, Default)]
+pub struct MapInteraction {
+    /// Region whose marker is hovered.
+    pub hovered: Option<String>,
+    /// Region whose marker was clicked.
+    pub clicked: Option<String>,
+    /// Regions within the selection rectangle, [`Some`] once the drag
+    /// ends.
+    pub selected: Option<Vec<String>>,
 }
 
 /// Servers on the map.
@@ -1240,13 +5630,85 @@ pub struct ServersOnMap<'a> {
 
     /// Server status info.
     pub server_status_info: &'a HashMap<String, ServerState>,
+
+    /// What the color of the markers shows.
+    pub coloring: MapColoring,
+
+    /// Average ping of the regions, the regions without any ping
+    /// received are missing.
+    pub pings: HashMap<&'a str, Duration>,
+
+    /// Thresholds of the colors of the pings with
+    /// [`MapColoring::Latency`].
+    pub ping_thresholds: &'a PingThresholds,
+
+    /// Draw the servers without any IPs too.
+    pub show_empty: bool,
+
+    /// Regions that are highlighted as selected.
+    pub selected: &'a [String],
+
+    /// Regions that are highlighted as recommended, see
+    /// [`crate::recommend`].
+    pub recommended: &'a [String],
+
+    /// Dragging draws a selection rectangle (instead of moving the
+    /// map).
+    pub selecting: bool,
+
+    /// Start of the selection rectangle, kept across the frames of
+    /// the drag.
+    pub selection_start: &'a mut Option<egui::Pos2>,
+
+    /// Interaction with the markers during this frame.
+    pub interaction: &'a mut MapInteraction,
 }
 
 impl<'a> ServersOnMap<'a> {
-    /// Paint the given [`ServerInfo`] at the given screen position.
+    /// Distance from the marker (in points) within which it is
+    /// hovered.
+    const MARKER_HOVER_DISTANCE: f32 = 8.0;
+
+    /// Color of the markers of the regions without any ping received
+    /// with [`MapColoring::Latency`].
+    pub const NO_PING_COLOR: egui::Color32 = egui::Color32::GRAY;
+
+    /// Get the color of the marker of the region in the state with
+    /// [`MapColoring::Status`].
+    pub fn status_color(server_state: &ServerState) -> egui::Color32 {
+        match server_state {
+            ServerState::AllDisabled => egui::Color32::RED,
+            ServerState::SomeDisabled(_) => egui::Color32::YELLOW,
+            ServerState::NoneDisabled => egui::Color32::GREEN,
+            ServerState::Unknown => egui::Color32::BLUE,
+        }
+    }
+
+    /// Get the color of the marker of the given [`ServerInfo`], see
+    /// [`MapColoring`].
+    fn marker_color(&self, server_info: &ServerInfo) -> egui::Color32 {
+        match self.coloring {
+            MapColoring::Status => Self::status_color(
+                self.server_status_info
+                    .get(server_info.get_abr())
+                    .unwrap_or(&ServerState::Unknown),
+            ),
+            MapColoring::Latency => self
+                .pings
+                .get(server_info.get_abr())
+                .map_or(Self::NO_PING_COLOR, |ping| {
+                    self.ping_thresholds.gradient_color(*ping)
+                }),
+        }
+    }
+
+    /// Paint the given [`ServerInfo`] at the given screen position,
+    /// its marker is of the given color.
     pub fn paint_server(
         server_info: &ServerInfo,
-        server_state: &ServerState,
+        color: egui::Color32,
+        selected: bool,
+        recommended: bool,
         screen_position: egui::Pos2,
         painter: &egui::Painter,
     ) {
@@ -1282,59 +5744,122 @@ impl<'a> ServersOnMap<'a> {
             egui::Color32::RED,
         );
 
-        let (circle_fill, circle_stroke) = match server_state {
-            ServerState::AllDisabled => (
-                egui::Color32::RED.linear_multiply(0.3),
-                egui::Stroke::new(1.0, egui::Color32::RED),
-            ),
-            ServerState::SomeDisabled(_) => (
-                egui::Color32::YELLOW.linear_multiply(0.3),
-                egui::Stroke::new(1.0, egui::Color32::YELLOW),
-            ),
-            ServerState::NoneDisabled => (
-                egui::Color32::GREEN.linear_multiply(0.3),
-                egui::Stroke::new(1.0, egui::Color32::GREEN),
-            ),
-            ServerState::Unknown => (
-                egui::Color32::BLUE.linear_multiply(0.3),
-                egui::Stroke::new(1.0, egui::Color32::BLUE),
-            ),
-        };
-
-        painter.circle(screen_position, 4.0, circle_fill, circle_stroke);
+        painter.circle(
+            screen_position,
+            4.0,
+            color.linear_multiply(0.3),
+            egui::Stroke::new(1.0, color),
+        );
+        if recommended {
+            painter.circle_stroke(
+                screen_position,
+                10.0,
+                egui::Stroke::new(2.0, egui::Color32::GOLD),
+            );
+        }
+        if selected {
+            painter.circle_stroke(screen_position, 7.0, style.visuals.selection.stroke);
+        }
     }
 }
 
 impl<'a> walkers::Plugin for ServersOnMap<'a> {
     fn run(
         &mut self,
-        _response: &egui::Response,
+        response: &egui::Response,
         painter: egui::Painter,
         projector: &walkers::Projector,
     ) {
-        self.servers
+        let hover_pos = response.hover_pos();
+        let markers: Vec<_> = self
+            .servers
             .iter()
+            .filter(|server_info| self.show_empty || !server_info.get_ips().is_empty())
             .filter_map(|server_info| {
                 let geo = server_info.geo()?;
-                let server_status = self
-                    .server_status_info
-                    .get(server_info.get_abr())
-                    .map(Cow::Borrowed)
-                    .unwrap_or_else(|| Cow::Owned(ServerState::Unknown));
-                Some((server_info, geo, server_status))
+                let screen_position = projector
+                    .project(walkers::Position::from_lon_lat(
+                        geo[0].into(),
+                        geo[1].into(),
+                    ))
+                    .to_pos2();
+                Some((server_info, screen_position))
             })
-            .for_each(|(server_info, geo, server_status)| {
-                Self::paint_server(
-                    server_info,
-                    &server_status,
-                    projector
-                        .project(walkers::Position::from_lon_lat(
-                            geo[0].into(),
-                            geo[1].into(),
-                        ))
-                        .to_pos2(),
-                    &painter,
+            .collect();
+
+        // a halo under the markers, drawn first so that it does not
+        // cover the labels, gives a heatmap of the latency
+        if self.coloring == MapColoring::Latency {
+            markers.iter().for_each(|(server_info, screen_position)| {
+                painter.circle_filled(
+                    *screen_position,
+                    16.0,
+                    self.marker_color(server_info).linear_multiply(0.15),
                 );
             });
+        }
+
+        markers.iter().for_each(|(server_info, screen_position)| {
+            let color = self.marker_color(server_info);
+            let selected = self
+                .selected
+                .iter()
+                .any(|region| region == server_info.get_abr());
+            let recommended = self
+                .recommended
+                .iter()
+                .any(|region| region == server_info.get_abr());
+            Self::paint_server(
+                server_info,
+                color,
+                selected,
+                recommended,
+                *screen_position,
+                &painter,
+            );
+        });
+
+        // the nearest marker is hovered when they overlap
+        let hovered = hover_pos.and_then(|pos| {
+            markers
+                .iter()
+                .map(|(server_info, screen_position)| (server_info, pos.distance(*screen_position)))
+                .filter(|(_, distance)| *distance < Self::MARKER_HOVER_DISTANCE)
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(server_info, _)| server_info.get_abr().to_string())
+        });
+        if response.clicked() {
+            self.interaction.clicked.clone_from(&hovered);
+        }
+        self.interaction.hovered = hovered;
+
+        if self.selecting && response.drag_started() {
+            *self.selection_start = response.interact_pointer_pos();
+        }
+        let Some(start) = *self.selection_start else {
+            return;
+        };
+        let Some(end) = response.interact_pointer_pos().or(hover_pos) else {
+            return;
+        };
+        let selection_rect = egui::Rect::from_two_pos(start, end);
+        if response.drag_stopped() || !response.dragged() {
+            *self.selection_start = None;
+            self.interaction.selected = Some(
+                markers
+                    .iter()
+                    .filter(|(_, screen_position)| selection_rect.contains(*screen_position))
+                    .map(|(server_info, _)| server_info.get_abr().to_string())
+                    .collect(),
+            );
+        } else {
+            let selection_visuals = painter.ctx().style().visuals.selection;
+            painter.rect(
+                selection_rect,
+                0.0,
+                selection_visuals.bg_fill.linear_multiply(0.2),
+                selection_visuals.stroke,
+            );
+        }
     }
 }
@@ -0,0 +1,204 @@
+//! Lookup of the autonomous system (AS) announcing the relay IPs, to
+//! spot the stale or suspicious entries of the network datagram
+//! config: the relays are expected to be announced by Valve's AS.
+//!
+//! The lookups are done with the RIPEstat API, so they are opt-in.
+//! One lookup is done per announced prefix, the other IPs of the
+//! prefix are resolved from it.
+
+use std::{net::IpAddr, sync::mpsc, thread};
+
+use serde::Deserialize;
+
+use crate::{downloader, waker::Waker, Error};
+
+/// AS number of Valve.
+pub const VALVE_ASN: u32 = 32590;
+
+/// Announcement of an IP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsnInfo {
+    /// Announced prefix covering the IP, eg: `155.133.224.0/23`.
+    pub prefix: Option<String>,
+    /// AS announcing the prefix along with its holder, eg: `(32590,
+    /// "VALVE-CORPORATION - Valve Corporation")`. Empty if the IP is
+    /// not announced.
+    pub asns: Vec<(u32, String)>,
+}
+
+impl AsnInfo {
+    /// Is the IP announced by [`VALVE_ASN`]?
+    pub fn is_valve(&self) -> bool {
+        self.asns.iter().any(|(asn, _)| *asn == VALVE_ASN)
+    }
+
+    /// Does the announced prefix cover the IP?
+    pub fn covers(&self, ip: IpAddr) -> bool {
+        let Some((network, len)) = self.prefix.as_deref().and_then(parse_prefix) else {
+            return false;
+        };
+        match (network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(len)).unwrap_or(0);
+                u32::from(ip) & mask == u32::from(network) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(len)).unwrap_or(0);
+                u128::from(ip) & mask == u128::from(network) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for AsnInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.asns.is_empty() {
+            return write!(f, "not announced");
+        }
+        let asns: Vec<_> = self
+            .asns
+            .iter()
+            .map(|(asn, holder)| format!("AS{} {}", asn, holder))
+            .collect();
+        write!(f, "{}", asns.join(", "))
+    }
+}
+
+/// Parse the `a.b.c.d/len` (or IPv6) prefix.
+fn parse_prefix(prefix: &str) -> Option<(IpAddr, u8)> {
+    let (network, len) = prefix.split_once('/')?;
+    let network: IpAddr = network.parse().ok()?;
+    let len: u8 = len.parse().ok()?;
+    let max_len = if network.is_ipv4() { 32 } else { 128 };
+    (len <= max_len).then_some((network, len))
+}
+
+#[derive(Debug, Deserialize)]
+struct PrefixOverview {
+    data: PrefixOverviewData,
+}
+
+#[derive(Debug, Deserialize)]
+struct PrefixOverviewData {
+    resource: Option<String>,
+    #[serde(default)]
+    asns: Vec<PrefixOverviewAsn>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PrefixOverviewAsn {
+    asn: u32,
+    holder: Option<String>,
+}
+
+/// Look up the announcement of the IP.
+///
+/// note: this sends the IP to RIPEstat.
+pub fn lookup(ip: IpAddr) -> Result<AsnInfo, Error> {
+    let data = downloader::Download::to_bytes(&format!(
+        "https://stat.ripe.net/data/prefix-overview/data.json?resource={}",
+        ip
+    ))?;
+    let overview: PrefixOverview = serde_json::from_slice(&data)?;
+    Ok(AsnInfo {
+        prefix: overview
+            .data
+            .resource
+            .filter(|resource| resource.contains('/')),
+        asns: overview
+            .data
+            .asns
+            .into_iter()
+            .map(|asn| (asn.asn, asn.holder.unwrap_or_default()))
+            .collect(),
+    })
+}
+
+/// Looks up the announcements of the IPs on a separate thread, one IP
+/// at a time.
+pub struct AsnResolver {
+    kill_sender: mpsc::Sender<()>,
+    /// Is [`Some`] until the [`AsnResolver`] is dropped.
+    request_sender: Option<mpsc::Sender<IpAddr>>,
+    info_receiver: mpsc::Receiver<(IpAddr, AsnInfo)>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+}
+
+impl AsnResolver {
+    /// Start the thread, `waker` is woken up after every IP is
+    /// resolved.
+    pub fn new(waker: Waker) -> Self {
+        let (kill_sender, kill_receiver) = mpsc::channel();
+        let (request_sender, request_receiver) = mpsc::channel::<IpAddr>();
+        let (info_sender, info_receiver) = mpsc::channel();
+
+        let thread_handle = thread::spawn(move || {
+            // announcements looked up so far, the other IPs of their
+            // prefixes need no lookup
+            let mut announcements: Vec<AsnInfo> = Vec::new();
+            while let Ok(ip) = request_receiver.recv() {
+                // skip the queued IPs once killed
+                if !matches!(kill_receiver.try_recv(), Err(mpsc::TryRecvError::Empty)) {
+                    break;
+                }
+                let info = match announcements.iter().find(|info| info.covers(ip)) {
+                    Some(info) => info.clone(),
+                    None => match lookup(ip) {
+                        Ok(info) => {
+                            if !info.is_valve() {
+                                log::warn!("{} is not announced by Valve: {}", ip, info);
+                            }
+                            announcements.push(info.clone());
+                            info
+                        }
+                        Err(err) => {
+                            log::error!("unable to look up the AS of {}: {}", ip, err);
+                            continue;
+                        }
+                    },
+                };
+                if info_sender.send((ip, info)).is_err() {
+                    break;
+                }
+                waker.wake();
+            }
+        });
+
+        Self {
+            kill_sender,
+            request_sender: Some(request_sender),
+            info_receiver,
+            thread_handle: Some(thread_handle),
+        }
+    }
+
+    /// Queue the lookup of the IP.
+    pub fn resolve(&self, ip: IpAddr) {
+        let sent = self
+            .request_sender
+            .as_ref()
+            .is_some_and(|request_sender| request_sender.send(ip).is_ok());
+        if !sent {
+            log::error!("AS resolver thread is not running");
+        }
+    }
+
+    /// Get an iterator over the IPs resolved since the last call.
+    pub fn try_iter(&self) -> mpsc::TryIter<'_, (IpAddr, AsnInfo)> {
+        self.info_receiver.try_iter()
+    }
+}
+
+impl Drop for AsnResolver {
+    fn drop(&mut self) {
+        let _ = self.kill_sender.send(());
+        // wakes up the thread if it is waiting for a request
+        self.request_sender.take();
+        if let Some(thread_handle) = self.thread_handle.take() {
+            if thread_handle.join().is_err() {
+                log::error!("AS resolver thread panicked");
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Async (tokio) variants of the core operations.
+//!
+//! The underlying operations are blocking (curl, iptables/netsh, raw
+//! sockets), so they are run on tokio's blocking thread pool. All the
+//! functions must be called from within a tokio runtime.
+
+use std::{
+    collections::HashSet,
+    net::IpAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::mpsc;
+
+use crate::{
+    firewall::Firewall,
+    ping::{self, PingInfo, Pinger},
+    steam_server::Servers,
+    Error,
+};
+
+/// Ping result sent by [`ping_stream()`].
+pub type PingResult = (IpAddr, Result<PingInfo, ping::Error>);
+
+/// Download the network datagram config file, see
+/// [`Servers::download_file()`].
+pub async fn download_file() -> Result<(), Error> {
+    tokio::task::spawn_blocking(Servers::download_file).await?
+}
+
+/// Download the network datagram config file and load the
+/// [`Servers`] from it, see [`Servers::new()`].
+pub async fn refresh_servers(
+    network_datagram_config_file_path: Option<PathBuf>,
+) -> Result<Servers, Error> {
+    if network_datagram_config_file_path.is_none() {
+        download_file().await?;
+    }
+    tokio::task::spawn_blocking(move || Servers::new(network_datagram_config_file_path)).await?
+}
+
+/// Ping the given IPs in rounds, all the IPs of a round are pinged
+/// at the same time (see [`Pinger::ping_many()`]). A round is started
+/// at most once every `interval`.
+///
+/// Pinging stops once the returned receiver is dropped.
+pub fn ping_stream(
+    ips: Vec<IpAddr>,
+    timeout: Duration,
+    interval: Duration,
+) -> Result<mpsc::Receiver<PingResult>, Error> {
+    let mut pinger = Pinger::new()?;
+    pinger.set_timeout(timeout);
+
+    let (sender, receiver) = mpsc::channel(ips.len().max(1));
+
+    tokio::task::spawn_blocking(move || {
+        while !sender.is_closed() {
+            let round_start = Instant::now();
+
+            for result in pinger.ping_many(&ips, 1) {
+                if sender.blocking_send(result).is_err() {
+                    return;
+                }
+            }
+
+            std::thread::sleep(interval.saturating_sub(round_start.elapsed()));
+        }
+    });
+
+    Ok(receiver)
+}
+
+/// Ban all the given IPs, see [`Firewall::ban_ips()`].
+pub async fn ban_ips(firewall: Arc<Firewall>, ips: Vec<IpAddr>) -> Result<(), Error> {
+    tokio::task::spawn_blocking(move || firewall.ban_ips(&ips).map_err(Error::from)).await?
+}
+
+/// Unban all the given IPs, see [`Firewall::unban_ips()`].
+pub async fn unban_ips(firewall: Arc<Firewall>, ips: Vec<IpAddr>) -> Result<(), Error> {
+    tokio::task::spawn_blocking(move || firewall.unban_ips(&ips).map_err(Error::from)).await?
+}
+
+/// Get a snapshot of all the blocked IPs, see
+/// [`Firewall::list_blocked()`].
+pub async fn list_blocked(firewall: Arc<Firewall>) -> Result<HashSet<IpAddr>, Error> {
+    tokio::task::spawn_blocking(move || firewall.list_blocked().map_err(Error::from)).await?
+}
+
+/// Check which of the given IPs are blocked, see
+/// [`Firewall::is_blocked()`].
+pub async fn blocked_ips(
+    firewall: Arc<Firewall>,
+    ips: Vec<IpAddr>,
+) -> Result<Vec<(IpAddr, bool)>, Error> {
+    tokio::task::spawn_blocking(move || {
+        ips.into_iter()
+            .map(|ip| Ok((ip, firewall.is_blocked(ip)?)))
+            .collect()
+    })
+    .await?
+}
@@ -1,12 +1,96 @@
-use egui_glfw::EguiBackend;
-use glfw::{self, Context};
-use steam_server_disable::{app::App, logger};
+use clap::Parser;
+#[cfg(unix)]
+use steam_server_disable::privileged::{self, Elevation};
+#[cfg(feature = "gui")]
+use steam_server_disable::{
+    app::{App, AppBuilder},
+    cli::{self, OutputFormat},
+    Error,
+};
+use steam_server_disable::{cli::CommandLineArguments, crash, firewall::FirewallOptions, logger};
 
 fn main() {
+    crash::install_panic_hook();
+
+    let command_line_arguments = CommandLineArguments::parse();
+    if let Some(backend) = command_line_arguments.firewall_backend {
+        FirewallOptions::override_backend(backend);
+    }
+    if let Some(command) = &command_line_arguments.command {
+        #[cfg(unix)]
+        if command.needs_elevation() {
+            sudo::escalate_if_needed().unwrap();
+        }
+        logger::init().unwrap();
+        if let Err(err) = command.run(&command_line_arguments) {
+            log::error!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     #[cfg(unix)]
-    {
+    if command_line_arguments.privileged_helper {
+        // the logs are written to stderr, stdout is for the replies
+        logger::init().unwrap();
+        if let Err(err) = privileged::serve() {
+            log::error!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // with polkit only the firewall operations run as root, the API
+    // servers are run as root as a whole
+    #[cfg(unix)]
+    let use_polkit = matches!(sudo::check(), sudo::RunningAs::User)
+        && !command_line_arguments.serves_api()
+        && command_line_arguments.elevation.resolve() == Elevation::Polkit;
+    #[cfg(unix)]
+    if !use_polkit {
         sudo::escalate_if_needed().unwrap();
     }
+
+    if command_line_arguments.rpc {
+        logger::init().unwrap();
+        if let Err(err) = command_line_arguments.serve_rpc() {
+            log::error!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    #[cfg(feature = "grpc")]
+    if let Some(address) = command_line_arguments.grpc {
+        logger::init().unwrap();
+        if let Err(err) = command_line_arguments.serve_grpc(address) {
+            log::error!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    #[cfg(feature = "gui")]
+    run_app(
+        command_line_arguments,
+        #[cfg(unix)]
+        use_polkit,
+    );
+    #[cfg(not(feature = "gui"))]
+    {
+        logger::init().unwrap();
+        log::error!(
+            "built without the `gui` feature, run one of the commands (see `--help`), \
+             `--rpc` or `--grpc` instead"
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Run the app with the GUI (or `--no-gui`, `--tui`), once the
+/// privileges are acquired.
+#[cfg(feature = "gui")]
+fn run_app(command_line_arguments: CommandLineArguments, #[cfg(unix)] use_polkit: bool) {
     // TODO: need to find something to auto escalate to sudo on
     // windows
 
@@ -23,66 +107,118 @@ fn main() {
 
     logger::init().unwrap();
 
+    let mut builder = AppBuilder::new();
+    #[cfg(unix)]
+    if use_polkit {
+        match FirewallOptions::load()
+            .map_err(Error::from)
+            .and_then(|options| privileged::Helper::firewall(&options))
+        {
+            Ok(firewall) => builder = builder.firewall(firewall),
+            Err(err) => {
+                log::error!("unable to start the privileged helper: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+    #[cfg(unix)]
+    let is_running_as_sudo = is_running_as_sudo || use_polkit;
+
     if !is_running_as_sudo {
         log::error!("Not running as sudo/administrator. Rerun application as sudo/admin.");
     }
 
-    let mut app = App::new();
+    let output = command_line_arguments.output;
+    let no_gui = command_line_arguments.no_gui;
+    #[cfg(feature = "tui")]
+    let tui = command_line_arguments.tui;
+    let app = builder
+        .command_line_arguments(command_line_arguments)
+        .build();
+    if let Err(err) = &app {
+        log::error!("unable to start: {}", err);
+    }
 
-    if app.no_gui {
+    #[cfg(feature = "tui")]
+    if tui {
+        let Ok(mut app) = app else {
+            std::process::exit(1);
+        };
+        if let Err(err) = steam_server_disable::tui::run(&mut app) {
+            log::error!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if no_gui {
+        let Ok(app) = app else {
+            std::process::exit(1);
+        };
+        if output == OutputFormat::Json {
+            if let Err(err) = app
+                .region_statuses()
+                .and_then(|statuses| cli::print_json(&statuses))
+            {
+                log::error!("{}", err);
+                std::process::exit(1);
+            }
+        }
         return;
     }
 
     log::info!("starting GUI");
 
-    let mut glfw = glfw::init(glfw::fail_on_errors).unwrap();
-
-    // set to opengl 3.3 or higher
-    glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3));
-    glfw.window_hint(glfw::WindowHint::OpenGlProfile(
-        glfw::OpenGlProfileHint::Core,
-    ));
-    // if msaa is available, use it
-    glfw.window_hint(glfw::WindowHint::Samples(Some(16)));
-    glfw.window_hint(glfw::WindowHint::ScaleToMonitor(true));
-    #[cfg(target_os = "macos")]
-    glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
-    let (mut window, events) = glfw
-        .create_window(
-            1280,
-            720,
-            "Steam Server Disable",
-            glfw::WindowMode::Windowed,
-        )
-        .expect("Failed to create glfw window");
-
-    // setup bunch of polling data
-    window.set_key_polling(true);
-    window.set_cursor_pos_polling(true);
-    window.set_mouse_button_polling(true);
-    window.set_framebuffer_size_polling(true);
-    window.set_scroll_polling(true);
-    window.set_char_polling(true);
-    window.make_current();
-
-    // load opengl symbols
-    gl::load_with(|symbol| window.get_proc_address(symbol));
-
-    // enable vsync
-    glfw.set_swap_interval(glfw::SwapInterval::Sync(1));
-
-    // enable and disable certain opengl features
-    unsafe {
-        gl::Disable(gl::CULL_FACE);
-        gl::Enable(gl::DEPTH_TEST);
-        gl::Enable(gl::MULTISAMPLE);
-        gl::Enable(gl::FRAMEBUFFER_SRGB);
-    }
-
-    let mut egui = EguiBackend::new(&mut window, &mut glfw);
-
-    // larger text
-    let mut style = (*egui.get_egui_ctx().style()).clone();
+    let failed_to_start = is_running_as_sudo && app.is_err();
+
+    let native_options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("Steam Server Disable")
+            .with_inner_size([1280.0, 720.0]),
+        // if msaa is available, use it
+        multisampling: 16,
+        ..Default::default()
+    };
+    let res = eframe::run_native(
+        "Steam Server Disable",
+        native_options,
+        Box::new(move |cc| {
+            set_style(&cc.egui_ctx);
+
+            if !is_running_as_sudo {
+                return Ok(Box::new(NonSudoGui) as Box<dyn eframe::App>);
+            }
+            Ok(match app {
+                Ok(app) => {
+                    // wake up the event loop when a repaint is requested
+                    // from another thread (eg: map tiles)
+                    let egui_ctx = cc.egui_ctx.clone();
+                    app.set_waker(move || egui_ctx.request_repaint());
+                    Box::new(Gui {
+                        app,
+                        open_logging_window: false,
+                    })
+                }
+                Err(err) => Box::new(ErrorGui {
+                    err,
+                    show_log: false,
+                }),
+            })
+        }),
+    );
+    if let Err(err) = res {
+        log::error!("unable to start the GUI: {}", err);
+        std::process::exit(1);
+    }
+    if failed_to_start {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(feature = "gui")]
+/// Use larger text than the egui defaults.
+fn set_style(ctx: &egui::Context) {
+    let mut style = (*ctx.style()).clone();
     style.text_styles = [
         (
             egui::TextStyle::Heading,
@@ -106,93 +242,77 @@ fn main() {
         ),
     ]
     .into();
-    egui.get_egui_ctx().set_style(style);
-
-    unsafe {
-        gl::ClearColor(0.2, 0.2, 0.2, 1.0);
-    }
-
-    if !is_running_as_sudo {
-        non_sudo_gui(glfw, window, events, egui);
-
-        return;
-    }
-
-    let mut open_logging_window = false;
-
-    while !window.should_close() {
-        glfw.poll_events();
+    ctx.set_style(style);
+}
 
-        glfw::flush_messages(&events).for_each(|(_, event)| {
-            egui.handle_event(&event, &window);
-            handle_window_events(&event, &mut open_logging_window);
-        });
+#[cfg(feature = "gui")]
+/// The app, with the logging window toggled by the grave accent key.
+struct Gui {
+    app: App,
+    open_logging_window: bool,
+}
 
-        unsafe {
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+#[cfg(feature = "gui")]
+impl eframe::App for Gui {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if ctx.input_mut(|input| input.consume_key(egui::Modifiers::NONE, egui::Key::Backtick)) {
+            self.open_logging_window = !self.open_logging_window;
         }
 
-        app.update();
+        self.app.update();
 
-        egui.begin_frame(&window, &mut glfw);
-
-        egui::CentralPanel::default().show(egui.get_egui_ctx(), |ui| {
+        egui::CentralPanel::default().show(ctx, |ui| {
             egui::ScrollArea::vertical()
                 .auto_shrink([false, false])
                 .show(ui, |ui| {
-                    app.ui(ui, egui::Id::new("app"));
+                    self.app.ui(ui, egui::Id::new("app"));
                 });
         });
 
-        logger::LOGGER
-            .first()
-            .draw_ui(egui.get_egui_ctx(), &mut open_logging_window);
-
-        let (width, height) = window.get_framebuffer_size();
-        let _output = egui.end_frame((width as _, height as _));
-
-        window.swap_buffers();
+        logger::LOGGER.draw_ui(ctx, &mut self.open_logging_window);
     }
 }
 
-fn handle_window_events(event: &glfw::WindowEvent, open_logging_window: &mut bool) {
-    #[allow(clippy::single_match)]
-    match event {
-        glfw::WindowEvent::Key(glfw::Key::GraveAccent, _, glfw::Action::Press, modifiers) => {
-            if modifiers.is_empty() {
-                *open_logging_window = !*open_logging_window;
-            }
-        }
-        _ => {}
-    }
+#[cfg(feature = "gui")]
+/// Show the error that kept the app from starting along with what can
+/// be done about it, until the window is closed.
+struct ErrorGui {
+    err: Error,
+    show_log: bool,
 }
 
-fn non_sudo_gui(
-    mut glfw: glfw::Glfw,
-    mut window: glfw::PWindow,
-    events: glfw::GlfwReceiver<(f64, glfw::WindowEvent)>,
-    mut egui: egui_glfw::EguiBackend,
-) {
-    while !window.should_close() {
-        glfw.poll_events();
-
-        glfw::flush_messages(&events).for_each(|(_, event)| {
-            egui.handle_event(&event, &window);
-        });
-
-        unsafe {
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-        }
-
-        egui.begin_frame(&window, &mut glfw);
+#[cfg(feature = "gui")]
+impl eframe::App for ErrorGui {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::Window::new("Unable to Start")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(self.err.to_string());
+                if let Some(hint) = self.err.hint() {
+                    ui.separator();
+                    ui.label(hint);
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.toggle_value(&mut self.show_log, "Log");
+                    if ui.button("Quit").clicked() {
+                        ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+                    }
+                });
+            });
 
-        logger::LOGGER
-            .first()
-            .draw_ui(egui.get_egui_ctx(), &mut true);
+        logger::LOGGER.draw_ui(ctx, &mut self.show_log);
+    }
+}
 
-        let (width, height) = window.get_framebuffer_size();
-        let _output = egui.end_frame((width as _, height as _));
+#[cfg(feature = "gui")]
+struct NonSudoGui;
 
-        window.swap_buffers();
+#[cfg(feature = "gui")]
+impl eframe::App for NonSudoGui {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        logger::LOGGER.draw_ui(ctx, &mut true);
     }
 }
@@ -0,0 +1,306 @@
+//! Import and export of the blocked regions in formats used by other
+//! server picker tools.
+//!
+//! Supported formats:
+//!
+//! * [`BlockListFormat::Regions`]: one region per line, regions are
+//!   given either by their abreviation (`sgp`) or their description
+//!   (`Singapore`). Lines starting with `#` are ignored.
+//! * [`BlockListFormat::Json`]: an array of regions (`["sgp",
+//!   "hkg"]`) or an object of regions to if they are blocked
+//!   (`{"sgp": true, "hkg": false}`).
+//! * [`BlockListFormat::Ips`]: one IP per line, as used by the tools
+//!   that generate firewall rules directly. A region is blocked if
+//!   all of its IPs are in the list.
+//!
+//! Only the relays ([`ServerCategory::Relay`]) are part of the block
+//! lists.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    net::IpAddr,
+    path::Path,
+};
+
+use crate::{
+    firewall::Firewall,
+    steam_server::{ServerCategory, ServerInfo, ServerState, Servers},
+    Error,
+};
+
+/// Format of a block list file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BlockListFormat {
+    Regions,
+    Json,
+    Ips,
+}
+
+impl BlockListFormat {
+    /// Guess the format from the extension of the file, defaults to
+    /// [`BlockListFormat::Regions`].
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("json") => Self::Json,
+            Some("ips") => Self::Ips,
+            _ => Self::Regions,
+        }
+    }
+}
+
+/// Set of blocked regions, by abreviation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockList {
+    regions: BTreeSet<String>,
+}
+
+/// Differences between two [`BlockList`]s.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockListDiff {
+    /// Regions blocked only in the first list.
+    pub only_in_first: Vec<String>,
+    /// Regions blocked only in the second list.
+    pub only_in_second: Vec<String>,
+}
+
+impl BlockList {
+    /// Create a [`BlockList`] of the given regions.
+    pub fn new(regions: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            regions: regions.into_iter().collect(),
+        }
+    }
+
+    /// Create a [`BlockList`] of the regions whose IPs are all
+    /// blocked by the firewall.
+    pub fn from_firewall(servers: &Servers, firewall: &Firewall) -> Result<Self, Error> {
+        Ok(Self::from_blocked(servers, &firewall.list_blocked()?))
+    }
+
+    /// Create a [`BlockList`] of the regions whose IPs are all in the
+    /// snapshot of the blocked IPs, see [`Firewall::list_blocked()`].
+    pub fn from_blocked(servers: &Servers, blocked: &HashSet<IpAddr>) -> Self {
+        Self::new(
+            servers
+                .get_servers_in(ServerCategory::Relay)
+                .filter(|server| {
+                    matches!(server.state_from_blocked(blocked), ServerState::AllDisabled)
+                })
+                .map(|server| server.get_abr().to_string()),
+        )
+    }
+
+    /// Parse the block list, returns the list along with the entries
+    /// that do not match any region of `servers`.
+    pub fn parse(
+        contents: &str,
+        format: BlockListFormat,
+        servers: &Servers,
+    ) -> Result<(Self, Vec<String>), Error> {
+        let mut unknown = Vec::new();
+        let mut find_region = |entry: &str| {
+            let server = find_server(servers, entry);
+            if server.is_none() {
+                unknown.push(entry.to_string());
+            }
+            server.map(|server| server.get_abr().to_string())
+        };
+
+        let regions = match format {
+            BlockListFormat::Regions => lines(contents).filter_map(find_region).collect(),
+            BlockListFormat::Json => match serde_json::from_str(contents)? {
+                serde_json::Value::Object(map) => map
+                    .iter()
+                    .filter(|(_, blocked)| blocked.as_bool().unwrap_or(false))
+                    .filter_map(|(entry, _)| find_region(entry))
+                    .collect(),
+                value => serde_json::from_value::<Vec<String>>(value)?
+                    .iter()
+                    .filter_map(|entry| find_region(entry))
+                    .collect(),
+            },
+            BlockListFormat::Ips => {
+                let ips = lines(contents)
+                    .map(|ip| {
+                        ip.parse::<IpAddr>().map_err(|error| Error::InvalidIp {
+                            ip: ip.to_string(),
+                            error,
+                        })
+                    })
+                    .collect::<Result<BTreeSet<_>, _>>()?;
+                let regions: BTreeSet<_> = servers
+                    .get_servers_in(ServerCategory::Relay)
+                    .filter(|server| {
+                        !server.get_ips().is_empty()
+                            && server.get_ips().iter().all(|ip| ips.contains(ip))
+                    })
+                    .map(|server| server.get_abr().to_string())
+                    .collect();
+                unknown.extend(
+                    ips.iter()
+                        .filter(|ip| {
+                            !servers
+                                .get_servers_in(ServerCategory::Relay)
+                                .any(|server| server.get_ips().contains(ip))
+                        })
+                        .map(|ip| ip.to_string()),
+                );
+                regions
+            }
+        };
+
+        Ok((Self { regions }, unknown))
+    }
+
+    /// Read and parse the block list file, see [`Self::parse()`].
+    pub fn read(
+        path: impl AsRef<Path>,
+        format: BlockListFormat,
+        servers: &Servers,
+    ) -> Result<(Self, Vec<String>), Error> {
+        Self::parse(&std::fs::read_to_string(path)?, format, servers)
+    }
+
+    /// Convert the block list to the given format.
+    pub fn to_string(&self, format: BlockListFormat, servers: &Servers) -> Result<String, Error> {
+        Ok(match format {
+            BlockListFormat::Regions => self
+                .regions
+                .iter()
+                .map(|region| format!("{}\n", region))
+                .collect(),
+            BlockListFormat::Json => serde_json::to_string_pretty(
+                &servers
+                    .get_servers_in(ServerCategory::Relay)
+                    .map(|server| (server.get_abr(), self.contains(server.get_abr())))
+                    .collect::<BTreeMap<_, _>>(),
+            )?,
+            BlockListFormat::Ips => self
+                .regions
+                .iter()
+                .filter_map(|region| servers.get_server(region))
+                .flat_map(|server| server.get_ips())
+                .map(|ip| format!("{}\n", ip))
+                .collect(),
+        })
+    }
+
+    /// Write the block list file in the given format.
+    pub fn write(
+        &self,
+        path: impl AsRef<Path>,
+        format: BlockListFormat,
+        servers: &Servers,
+    ) -> Result<(), Error> {
+        crate::file_ops::write_atomic(path, self.to_string(format, servers)?)?;
+        Ok(())
+    }
+
+    /// Check if the region is in the block list.
+    pub fn contains(&self, abr: &str) -> bool {
+        self.regions.contains(abr)
+    }
+
+    /// Get an iterator over the abreviations of the regions in the
+    /// block list.
+    pub fn regions(&self) -> impl Iterator<Item = &str> {
+        self.regions.iter().map(String::as_str)
+    }
+
+    /// Get the regions on which `self` and `other` disagree.
+    pub fn diff(&self, other: &Self) -> BlockListDiff {
+        BlockListDiff {
+            only_in_first: self.regions.difference(&other.regions).cloned().collect(),
+            only_in_second: other.regions.difference(&self.regions).cloned().collect(),
+        }
+    }
+
+    /// Block the regions in the block list and unblock all the other
+    /// regions.
+    pub fn apply(&self, servers: &Servers, firewall: &Firewall) -> Result<(), Error> {
+        servers
+            .get_servers_in(ServerCategory::Relay)
+            .try_for_each(|server| {
+                if self.contains(server.get_abr()) {
+                    server.ban(firewall)
+                } else {
+                    server.unban(firewall)
+                }
+            })
+    }
+}
+
+/// Non empty, non comment lines of the file.
+fn lines(contents: &str) -> impl Iterator<Item = &str> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Find the server by abreviation or by description.
+fn find_server<'a>(servers: &'a Servers, entry: &str) -> Option<&'a ServerInfo> {
+    servers
+        .get_servers_in(ServerCategory::Relay)
+        .find(|server| {
+            server.get_abr().eq_ignore_ascii_case(entry)
+                || server
+                    .desc()
+                    .is_some_and(|desc| desc.eq_ignore_ascii_case(entry))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVERS: &str = include_str!("../tests/data/servers.json");
+
+    fn servers() -> Servers {
+        Servers::from_json(SERVERS.as_bytes()).unwrap()
+    }
+
+    fn regions(list: &BlockList) -> Vec<&str> {
+        list.regions().collect()
+    }
+
+    #[test]
+    fn parses_the_regions() {
+        let contents = "# blocked\nFRA\n\nseattle (washington)\nxyz\n";
+        let (list, unknown) =
+            BlockList::parse(contents, BlockListFormat::Regions, &servers()).unwrap();
+        assert_eq!(regions(&list), ["fra", "sea"]);
+        assert_eq!(unknown, ["xyz"]);
+    }
+
+    #[test]
+    fn parses_the_json_array_and_object() {
+        let (list, unknown) =
+            BlockList::parse(r#"["fra", "xyz"]"#, BlockListFormat::Json, &servers()).unwrap();
+        assert_eq!(regions(&list), ["fra"]);
+        assert_eq!(unknown, ["xyz"]);
+
+        let contents = r#"{ "fra": false, "sea": true }"#;
+        let (list, unknown) =
+            BlockList::parse(contents, BlockListFormat::Json, &servers()).unwrap();
+        assert_eq!(regions(&list), ["sea"]);
+        assert!(unknown.is_empty());
+
+        assert!(BlockList::parse("fra", BlockListFormat::Json, &servers()).is_err());
+    }
+
+    #[test]
+    fn blocks_the_regions_with_all_their_ips_listed() {
+        let contents = "192.0.2.1\n198.51.100.1\n203.0.113.1\n";
+        let (list, unknown) = BlockList::parse(contents, BlockListFormat::Ips, &servers()).unwrap();
+        assert!(!list.contains("fra"));
+        assert!(list.contains("sea"));
+        assert_eq!(unknown, ["203.0.113.1"]);
+
+        assert!(BlockList::parse("not an ip", BlockListFormat::Ips, &servers()).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+//! Suggestions of the regions to block from the statistics
+//! accumulated over the session (and the distance to the regions if
+//! the location is known). The regions are only blocked when the user
+//! applies the suggestions.
+
+use std::time::Duration;
+
+/// Limits above which a region is suggested to be blocked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SuggestionThresholds {
+    /// Regions whose lowest ping is above this are suggested.
+    pub max_ping: Duration,
+    /// Regions whose session loss (in percent) is above this are
+    /// suggested.
+    pub max_loss_percent: f64,
+    /// Regions further away (in kilometers) than this are suggested.
+    pub max_distance_km: f32,
+    /// Minimum number of packets sent to a region before it is
+    /// judged by its ping and loss.
+    pub min_packets: usize,
+}
+
+impl SuggestionThresholds {
+    /// Thresholds used unless configured otherwise.
+    pub const DEFAULT: Self = Self {
+        max_ping: Duration::from_millis(180),
+        max_loss_percent: 5.0,
+        max_distance_km: 5000.0,
+        min_packets: 20,
+    };
+}
+
+/// Statistics of a region that the suggestions are based on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionStats {
+    /// Abreviation of the region.
+    pub region: String,
+    /// Lowest ping of the recent packets.
+    pub min_ping: Option<Duration>,
+    /// Packets sent to the region over the session.
+    pub session_packets: usize,
+    /// Packets lost over the session.
+    pub session_lost: usize,
+    /// Distance to the region, if the location of the user is known.
+    pub distance_km: Option<f32>,
+}
+
+/// Why a region is suggested to be blocked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SuggestionReason {
+    /// Even the lowest recent ping is above the threshold.
+    HighPing {
+        min_ping: Duration,
+        threshold: Duration,
+    },
+    /// Too many packets were lost over the session.
+    HighLoss { loss_percent: f64 },
+    /// The region is far away.
+    FarAway { distance_km: f32 },
+}
+
+impl std::fmt::Display for SuggestionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SuggestionReason::HighPing {
+                min_ping,
+                threshold,
+            } => write!(
+                f,
+                "consistently >{} ms (lowest {} ms)",
+                threshold.as_millis(),
+                min_ping.as_millis()
+            ),
+            SuggestionReason::HighLoss { loss_percent } => {
+                write!(f, "{:.1}% loss", loss_percent)
+            }
+            SuggestionReason::FarAway { distance_km } => {
+                write!(f, "{:.0} km away", distance_km)
+            }
+        }
+    }
+}
+
+/// Suggestion to block a region.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockSuggestion {
+    /// Abreviation of the region.
+    pub region: String,
+    /// Never empty.
+    pub reasons: Vec<SuggestionReason>,
+}
+
+impl std::fmt::Display for BlockSuggestion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reasons: Vec<_> = self.reasons.iter().map(ToString::to_string).collect();
+        write!(
+            f,
+            "{}: {} - consider blocking",
+            self.region,
+            reasons.join(" and ")
+        )
+    }
+}
+
+/// Suggest the regions to block, the regions are suggested in the
+/// given order.
+pub fn suggest(
+    stats: impl IntoIterator<Item = RegionStats>,
+    thresholds: &SuggestionThresholds,
+) -> Vec<BlockSuggestion> {
+    stats
+        .into_iter()
+        .filter_map(|stats| {
+            let mut reasons = Vec::new();
+            if stats.session_packets >= thresholds.min_packets {
+                if let Some(min_ping) = stats.min_ping.filter(|ping| *ping > thresholds.max_ping) {
+                    reasons.push(SuggestionReason::HighPing {
+                        min_ping,
+                        threshold: thresholds.max_ping,
+                    });
+                }
+                let loss_percent = stats.session_lost as f64 / stats.session_packets as f64 * 100.0;
+                if loss_percent > thresholds.max_loss_percent {
+                    reasons.push(SuggestionReason::HighLoss { loss_percent });
+                }
+            }
+            if let Some(distance_km) = stats
+                .distance_km
+                .filter(|distance_km| *distance_km > thresholds.max_distance_km)
+            {
+                reasons.push(SuggestionReason::FarAway { distance_km });
+            }
+            (!reasons.is_empty()).then_some(BlockSuggestion {
+                region: stats.region,
+                reasons,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,882 @@
+//! Command line of the binary: the arguments, the commands run
+//! instead of the GUI and the JSON-RPC and gRPC servers. Unlike the
+//! GUI, it is available without the `gui` feature.
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    path::PathBuf,
+};
+
+use clap::Parser;
+
+#[cfg(feature = "gui")]
+use crate::config::Config;
+use crate::{
+    block_list::BlockListFormat,
+    connections::ActiveRelayGuard,
+    controller::{Controller, RegionSummary},
+    firewall::{self, Firewall, FirewallBackend},
+    game::MatchGuard,
+    ip_list, logger,
+    ping::{Pinger, ProbeMode},
+    regions::{self, RegionsFormat},
+    rpc,
+    score::ScoreWeights,
+    steam, steam_cm,
+    steam_pings::SteamPings,
+    steam_server::{ServerInfo, Servers},
+    timings, Error,
+};
+
+/// Command line arguments for the `steam_server_disable`.
+#[derive(Debug, Clone, Parser)]
+#[command(author, version, about, long_about = None)]
+pub struct CommandLineArguments {
+    /// Run the command instead of the GUI.
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+
+    /// Output format of the commands and of `--no-gui`.
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub output: OutputFormat,
+
+    /// No GUI.
+    #[arg(long, default_value_t)]
+    pub no_gui: bool,
+
+    /// Show the grid in the terminal instead of the GUI, see
+    /// [`crate::tui`].
+    #[cfg(feature = "tui")]
+    #[arg(long, default_value_t)]
+    pub tui: bool,
+
+    /// Session mode, the blocks made by the app are undone when it
+    /// exits (or crashes), see [`crate::session`].
+    #[arg(long, default_value_t)]
+    pub session: bool,
+
+    /// Serve newline delimited JSON-RPC requests on stdin instead of
+    /// the GUI, see [`crate::rpc`].
+    #[arg(long, default_value_t)]
+    pub rpc: bool,
+
+    /// Serve the gRPC API on the given address (eg:
+    /// `127.0.0.1:50051`) instead of the GUI, see [`crate::grpc`].
+    #[cfg(feature = "grpc")]
+    #[arg(long)]
+    pub grpc: Option<std::net::SocketAddr>,
+
+    /// How the app gets the privileges to change the firewall rules,
+    /// see [`crate::privileged`].
+    #[cfg(unix)]
+    #[arg(long, value_enum, default_value_t)]
+    pub elevation: crate::privileged::Elevation,
+
+    /// Run the firewall operations of an unprivileged instance, see
+    /// [`crate::privileged`].
+    #[cfg(unix)]
+    #[arg(long, hide = true)]
+    pub privileged_helper: bool,
+
+    /// Enable all the IPs of the server regions matching the given
+    /// regex.
+    #[arg(long)]
+    pub enable: Option<regex::Regex>,
+
+    /// Exclusion regex for `--enable`.
+    #[arg(long, requires = "enable")]
+    pub enable_exclude: Option<regex::Regex>,
+
+    /// Disable all the IPs of the server regions matching the given
+    /// regex.
+    #[arg(long)]
+    pub disable: Option<regex::Regex>,
+
+    /// Exclusion regex for `--disable`.
+    #[arg(long, requires = "disable")]
+    pub disable_exclude: Option<regex::Regex>,
+
+    /// Keep only the regions of the preset (eg: "Only EU") and block
+    /// all the others, the presets are defined in the config file.
+    #[arg(long)]
+    pub preset: Option<String>,
+
+    /// Use the given network datagram config file instead.
+    #[arg(long)]
+    pub network_datagram_config: Option<PathBuf>,
+
+    /// Steam app whose network datagram config is loaded (eg: `570`
+    /// for Dota 2), its config is downloaded and cached separately.
+    /// Defaults to the `app_id` of the config, see [`Config`].
+    #[arg(long, global = true)]
+    pub app_id: Option<u32>,
+
+    /// Also load the network datagram configs of the given Steam apps
+    /// (eg: `570,1422450`), every app gets a tab.
+    #[arg(long, value_delimiter = ',')]
+    pub app_ids: Vec<u32>,
+
+    /// Also manage the Steam connection manager (CM) servers, they are
+    /// shown in a separate category of the grid.
+    #[arg(long, default_value_t)]
+    pub cm_servers: bool,
+
+    /// Also manage the Steam content (CDN) servers used for the
+    /// downloads, they are shown in a separate category of the grid.
+    #[cfg(feature = "cdn")]
+    #[arg(long, default_value_t)]
+    pub cdn_servers: bool,
+
+    /// Show the pings to the relays measured by Steam, read from the
+    /// logs of the Steam client.
+    #[arg(long, default_value_t)]
+    pub steam_pings: bool,
+
+    /// System wide hotkey that toggles the blocks of the active
+    /// profile, eg: `ctrl+alt+s`.
+    #[cfg(feature = "hotkey")]
+    #[arg(long)]
+    pub hotkey: Option<String>,
+
+    /// Draw the map from the bundled coastlines instead of
+    /// downloading the OpenStreetMap tiles.
+    #[arg(long, default_value_t)]
+    pub offline_map: bool,
+
+    /// Apply the profile bound to the game that is running whenever
+    /// the running game changes.
+    #[arg(long, default_value_t)]
+    pub follow_running_game: bool,
+
+    /// Max log level of the logging window.
+    #[arg(long)]
+    pub gui_log_level: Option<log::LevelFilter>,
+
+    /// Max log level of the terminal logger.
+    #[arg(long)]
+    pub terminal_log_level: Option<log::LevelFilter>,
+
+    /// Maximum number of records kept by the logging window.
+    #[arg(long, default_value_t = logger::EguiLogger::DEFAULT_MAX_RECORDS)]
+    pub log_max_records: usize,
+
+    /// Approximate maximum number of bytes used by the records kept
+    /// by the logging window.
+    #[arg(long, default_value_t = logger::EguiLogger::DEFAULT_MAX_BYTES)]
+    pub log_max_bytes: usize,
+
+    /// Steam Web API key, used to fetch the status (capacity and
+    /// load) of the datacenters. The status is not shown if not set.
+    #[arg(long, env = "STEAM_WEB_API_KEY", hide_env_values = true)]
+    pub steam_web_api_key: Option<String>,
+
+    /// Pings below this many milliseconds are shown in green.
+    #[arg(long, default_value_t = 50)]
+    pub good_ping_ms: u32,
+
+    /// Pings below this many milliseconds (and above
+    /// `--good-ping-ms`) are shown in yellow, the higher pings in red.
+    #[arg(long, default_value_t = 100)]
+    pub ok_ping_ms: u32,
+
+    /// Recent losses of this many percent or more are shown in red,
+    /// below half of it in green.
+    #[arg(long, default_value_t = 5)]
+    pub bad_loss_percent: u32,
+
+    /// Jitters of this many milliseconds or more are shown in red,
+    /// below half of it in green.
+    #[arg(long, default_value_t = 20)]
+    pub bad_jitter_ms: u32,
+
+    /// Echo requests sent to every IP per measurement round, as a
+    /// burst aggregated into one sample. More than one makes the loss
+    /// less noisy.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..=16))]
+    pub probes_per_round: u16,
+
+    /// IPs pinged at the same time, a round over all the IPs takes
+    /// about as many ping timeouts as there are batches of this many
+    /// IPs.
+    #[arg(long, default_value_t = 32, value_parser = clap::value_parser!(u16).range(1..=256))]
+    pub concurrent_pings: u16,
+
+    /// Probes measuring the pings, `auto` falls back to TCP and UDP
+    /// probes of the relay ports for the IPs whose ICMP pings fail
+    /// (eg: on the networks that drop ICMP).
+    #[arg(long, value_enum, default_value_t)]
+    pub probe_mode: ProbeMode,
+
+    /// Guard against blocking the region whose relay the game traffic
+    /// is flowing through (Linux only), blocking it would disconnect
+    /// the current match.
+    #[arg(long, value_enum, default_value_t)]
+    pub active_relay_guard: ActiveRelayGuard,
+
+    /// Guard against blocking every region or the home region (the
+    /// nearest one, or the one with the lowest ping if the location is
+    /// not known) while a game is running, blocking them would
+    /// disconnect the current match.
+    #[arg(long, value_enum, default_value_t)]
+    pub match_guard: MatchGuard,
+
+    /// Look up the autonomous system (AS) announcing every relay IP
+    /// with the RIPEstat API, the IPs not announced by Valve are
+    /// flagged.
+    #[arg(long, default_value_t)]
+    pub asn_lookup: bool,
+
+    /// Do not block the IPs again when the rules of the app were
+    /// removed outside of the app (eg: `iptables -F`, a firewalld
+    /// reload).
+    #[arg(long, default_value_t)]
+    pub no_rules_watchdog: bool,
+
+    /// System firewall of the rules (Linux only), overrides the
+    /// `backend` of the firewall config file.
+    #[arg(long, value_enum)]
+    pub firewall_backend: Option<FirewallBackend>,
+
+    /// Weight of the mean ping (ms) in the score of the regions.
+    #[arg(long, default_value_t = ScoreWeights::DEFAULT.ping)]
+    pub score_ping_weight: f64,
+
+    /// Weight of the jitter (ms) in the score of the regions.
+    #[arg(long, default_value_t = ScoreWeights::DEFAULT.jitter)]
+    pub score_jitter_weight: f64,
+
+    /// Weight of the loss (%) in the score of the regions.
+    #[arg(long, default_value_t = ScoreWeights::DEFAULT.loss)]
+    pub score_loss_weight: f64,
+
+    /// Interval in seconds between fetches of the datacenter status.
+    #[arg(long, default_value_t = 120)]
+    pub steam_status_interval: u64,
+
+    /// Interval in hours between the scheduled refreshes of the
+    /// network datagram config, 0 disables them. There is no
+    /// scheduled refresh when `--network-datagram-config` is given.
+    /// Defaults to the `config_refresh_hours` of the config (24 hours),
+    /// see [`Config`].
+    #[arg(long)]
+    pub config_refresh_hours: Option<u64>,
+
+    /// Steam installation directory, detected automatically if not
+    /// given.
+    #[arg(long, env = "STEAM_DIR")]
+    pub steam_dir: Option<PathBuf>,
+
+    /// Set CS2's maximum acceptable matchmaking ping
+    /// (`mm_dedicated_search_maxping`) in milliseconds. CS2 must not
+    /// be running.
+    #[arg(long)]
+    pub cs2_max_ping: Option<u32>,
+
+    /// Block the regions in the given block list file and unblock
+    /// all the other regions. Applied after `--enable` and
+    /// `--disable`.
+    #[arg(long)]
+    pub import_block_list: Option<PathBuf>,
+
+    /// Apply the profile with the given name on startup, after
+    /// `--import-block-list`.
+    #[arg(long, conflicts_with = "restore_profile")]
+    pub profile: Option<String>,
+
+    /// Apply the profile applied last on startup (if any), to restore
+    /// the blocks after a reboot or a flush of the firewall.
+    #[arg(long, default_value_t)]
+    pub restore_profile: bool,
+
+    /// Write the blocked regions to the given block list file.
+    /// Written after all the other changes are applied.
+    #[arg(long)]
+    pub export_block_list: Option<PathBuf>,
+
+    /// Format of the block list files, guessed from the file
+    /// extension if not given (`.json` for json, `.ips` for ips,
+    /// regions otherwise).
+    #[arg(long, value_enum)]
+    pub block_list_format: Option<BlockListFormat>,
+
+    /// Discord webhook URL to post the notifications (regions
+    /// blocked/unblocked, server list updated) to.
+    #[arg(long, env = "DISCORD_WEBHOOK_URL", hide_env_values = true)]
+    pub discord_webhook_url: Option<String>,
+
+    /// Notify (and run the hooks) when the mean ping of a region goes
+    /// above this many milliseconds, and when it is back below 90% of
+    /// it.
+    #[arg(long)]
+    pub ping_alert_ms: Option<u32>,
+
+    /// Notify (and run the hooks) when the recent loss of a region
+    /// goes above this percentage, and when it is back below 90% of
+    /// it.
+    #[arg(long)]
+    pub loss_alert_percent: Option<u32>,
+
+    /// Show the alerts (ping or loss above the threshold, firewall
+    /// errors) as desktop notifications.
+    #[cfg(feature = "notify")]
+    #[arg(long)]
+    pub desktop_notifications: bool,
+
+    /// Detect the approximate location from the public IP (through
+    /// a third party geo-IP service) and suggest which regions to
+    /// block. Offered automatically on the first run of the GUI.
+    #[arg(long, default_value_t)]
+    pub suggest_regions_from_location: bool,
+
+    /// Number of the nearest regions to keep when suggesting regions
+    /// to block from the location.
+    #[arg(long, default_value_t = 5)]
+    pub keep_nearest_regions: usize,
+}
+
+/// Output format of the commands, see [`CommandLineArguments::output`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable text.
+    #[default]
+    Text,
+    /// Pretty printed JSON, eg: to pipe into `jq`.
+    Json,
+}
+
+impl OutputFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Text => "Text",
+            OutputFormat::Json => "JSON",
+        }
+    }
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Print the value as pretty printed JSON.
+pub fn print_json(value: &impl serde::Serialize) -> Result<(), Error> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Result of pinging an IP `sent` times, see [`CliCommand::Ping`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+struct IpPingResult {
+    ip: IpAddr,
+    sent: u16,
+    received: u16,
+    min_ms: Option<f64>,
+    avg_ms: Option<f64>,
+    max_ms: Option<f64>,
+}
+
+/// Network datagram config downloaded by [`CliCommand::Refresh`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+struct RefreshedConfig {
+    app_id: u32,
+    revision: usize,
+    num_regions: usize,
+}
+
+/// Commands that run instead of the GUI, only the ones that modify
+/// the firewall need elevated privileges, see
+/// [`CliCommand::needs_elevation()`].
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum CliCommand {
+    /// Print the abreviation, number of IPs and description of every
+    /// region.
+    List,
+    /// Print whether every region (or the given ones) is blocked,
+    /// partially blocked or not blocked.
+    Status {
+        /// Abreviations of the regions, all the regions if none are
+        /// given.
+        regions: Vec<String>,
+    },
+    /// Ping every IP of the region and print the round trip times.
+    Ping {
+        /// Abreviation of the region, eg: `sgp`.
+        region: String,
+        /// Echo requests sent to every IP.
+        #[arg(long, short, default_value_t = 4)]
+        count: u16,
+    },
+    /// Download the network datagram config (of the `--app-id` and the
+    /// `--app-ids`) to replace the cached one.
+    Refresh,
+    /// Print the metadata (location, relays, port ranges) of every
+    /// region.
+    Regions {
+        /// Output format.
+        #[arg(long, value_enum, default_value_t)]
+        format: RegionsFormat,
+    },
+    /// Block a list of IPs and CIDRs (eg: pasted from the clipboard),
+    /// the regions they belong to are printed.
+    BlockIps {
+        /// IPs or CIDRs (/24 or longer), `-` reads the list from
+        /// stdin.
+        #[arg(required = true)]
+        ips: Vec<String>,
+        /// Only print the IPs with their regions, nothing is blocked.
+        #[arg(long, default_value_t)]
+        dry_run: bool,
+    },
+    /// Unblock the regions whose abreviation matches the regex. On
+    /// Windows, a non elevated invocation is forwarded to the running
+    /// (elevated) app.
+    Enable {
+        /// Regex matched against the abreviations of the regions, eg:
+        /// `sgp` or `^(sgp|hkg)$`.
+        regex: regex::Regex,
+        /// The regions matching this regex are left as is.
+        #[arg(long)]
+        exclude: Option<regex::Regex>,
+    },
+    /// Block the regions whose abreviation matches the regex. On
+    /// Windows, a non elevated invocation is forwarded to the running
+    /// (elevated) app.
+    Disable {
+        /// Regex matched against the abreviations of the regions, eg:
+        /// `sgp` or `^(sgp|hkg)$`.
+        regex: regex::Regex,
+        /// The regions matching this regex are left as is.
+        #[arg(long)]
+        exclude: Option<regex::Regex>,
+    },
+    /// Print the IPs blocked by the firewall rules of the app that are
+    /// not in the server list (of the `--app-id` and the `--app-ids`),
+    /// eg: relays removed since they were blocked.
+    Audit {
+        /// Remove the rules of these IPs.
+        #[arg(long, default_value_t)]
+        remove: bool,
+    },
+    /// Check that the server list, the firewall and pinging work.
+    Doctor {
+        /// Print how long the operations of the checks took.
+        #[arg(long, default_value_t)]
+        timings: bool,
+    },
+}
+
+impl CliCommand {
+    /// Check if the command needs elevated privileges.
+    pub fn needs_elevation(&self) -> bool {
+        match self {
+            CliCommand::List | CliCommand::Regions { .. } => false,
+            // the firewall is listed, the raw ICMP sockets need root
+            // and the config is cached for the (elevated) app
+            CliCommand::Status { .. } | CliCommand::Ping { .. } | CliCommand::Refresh => true,
+            CliCommand::BlockIps { dry_run, .. } => !dry_run,
+            CliCommand::Enable { .. } | CliCommand::Disable { .. } => true,
+            CliCommand::Audit { .. } | CliCommand::Doctor { .. } => true,
+        }
+    }
+
+    /// Run the command, the output is printed to stdout.
+    pub fn run(&self, command_line_arguments: &CommandLineArguments) -> Result<(), Error> {
+        let json = command_line_arguments.output == OutputFormat::Json;
+        match self {
+            CliCommand::List => {
+                let servers = command_line_arguments.load_servers()?;
+                if json {
+                    let regions: Vec<_> = servers
+                        .get_servers()
+                        .iter()
+                        .map(RegionSummary::from)
+                        .collect();
+                    return print_json(&regions);
+                }
+                servers.get_servers().iter().for_each(|server| {
+                    println!(
+                        "{:<6} {:>3} IPs  {}",
+                        server.get_abr(),
+                        server.get_ips().len(),
+                        server.label().unwrap_or_default()
+                    );
+                });
+            }
+            CliCommand::Status { regions } => {
+                let controller = Controller::new(
+                    command_line_arguments.load_servers()?,
+                    Firewall::from_config()?,
+                );
+                // the last ping location logged by Steam, there are no
+                // ICMP pings without the GUI
+                let pings = command_line_arguments
+                    .steam_dir
+                    .clone()
+                    .or_else(steam::find_install_dir)
+                    .map(|steam_install_dir| SteamPings::new(steam_install_dir).pings().clone())
+                    .unwrap_or_default();
+                let statuses = controller.statuses(regions, &pings)?;
+                if json {
+                    return print_json(&statuses);
+                }
+                statuses.iter().for_each(|status| {
+                    let mut line = format!("{:<6} {}", status.region, status.state);
+                    if !status.blocked_ips.is_empty()
+                        && status.blocked_ips.len() != status.ips.len()
+                    {
+                        line += &format!(
+                            " ({} of {} IPs)",
+                            status.blocked_ips.len(),
+                            status.ips.len()
+                        );
+                    }
+                    if let Some(ping_ms) = status.ping_ms {
+                        line += &format!(", Steam ping {:.0} ms", ping_ms);
+                    }
+                    println!("{}", line);
+                });
+            }
+            CliCommand::Ping { region, count } => {
+                let servers = command_line_arguments.load_servers()?;
+                let server = servers
+                    .get_server(region)
+                    .ok_or_else(|| Error::UnknownRegion(region.to_string()))?;
+                let mut pinger = Pinger::new()?;
+                let results: Vec<_> = server
+                    .get_ips()
+                    .iter()
+                    .map(|ip| {
+                        let rtts: Vec<_> = (0..*count)
+                            .filter_map(|sequence| match pinger.ping(*ip, sequence) {
+                                Ok(ping_info) => Some(ping_info.get_rtt().as_secs_f64() * 1000.0),
+                                Err(err) => {
+                                    log::debug!("ping {} failed: {}", ip, err);
+                                    None
+                                }
+                            })
+                            .collect();
+                        let received = rtts.len();
+                        IpPingResult {
+                            ip: *ip,
+                            sent: *count,
+                            received: received as u16,
+                            min_ms: rtts.iter().copied().reduce(f64::min),
+                            avg_ms: (received != 0)
+                                .then(|| rtts.iter().sum::<f64>() / received as f64),
+                            max_ms: rtts.iter().copied().reduce(f64::max),
+                        }
+                    })
+                    .collect();
+                if json {
+                    return print_json(&results);
+                }
+                results.iter().for_each(|result| {
+                    match (result.min_ms, result.avg_ms, result.max_ms) {
+                        (Some(min_ms), Some(avg_ms), Some(max_ms)) => println!(
+                            "{}: {}/{} replies, min/avg/max {:.2}/{:.2}/{:.2} ms",
+                            result.ip, result.received, result.sent, min_ms, avg_ms, max_ms
+                        ),
+                        _ => println!("{}: 0/{} replies", result.ip, result.sent),
+                    }
+                });
+            }
+            CliCommand::Refresh => {
+                let mut app_ids = vec![command_line_arguments.app_id()];
+                command_line_arguments.app_ids.iter().for_each(|app_id| {
+                    if !app_ids.contains(app_id) {
+                        app_ids.push(*app_id);
+                    }
+                });
+                let refreshed = app_ids
+                    .iter()
+                    .map(|app_id| {
+                        let servers = Servers::download_validated_for_app(*app_id)?;
+                        Ok(RefreshedConfig {
+                            app_id: *app_id,
+                            revision: servers.get_revision(),
+                            num_regions: servers.get_servers().len(),
+                        })
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                if command_line_arguments.cm_servers {
+                    steam_cm::download_file()?;
+                }
+                if json {
+                    return print_json(&refreshed);
+                }
+                refreshed.iter().for_each(|refreshed| {
+                    println!(
+                        "app {}: revision {}, {} regions",
+                        refreshed.app_id, refreshed.revision, refreshed.num_regions
+                    );
+                });
+            }
+            CliCommand::Regions { format } => {
+                let servers = command_line_arguments.load_servers()?;
+                let format = if json { RegionsFormat::Json } else { *format };
+                print!(
+                    "{}",
+                    regions::format_regions(&regions::region_infos(&servers), format)?
+                );
+            }
+            CliCommand::BlockIps { ips, dry_run } => {
+                let mut text = String::new();
+                ips.iter().try_for_each(|ips| {
+                    if ips == "-" {
+                        std::io::Read::read_to_string(&mut std::io::stdin(), &mut text)?;
+                    } else {
+                        text.push_str(ips);
+                    }
+                    text.push('\n');
+                    Ok::<_, Error>(())
+                })?;
+                let ip_list = ip_list::parse(&text);
+                if !ip_list.invalid.is_empty() {
+                    let invalid: Vec<_> = ip_list.invalid.iter().map(ToString::to_string).collect();
+                    return Err(Error::InvalidIpList(invalid.join(", ")));
+                }
+
+                let servers = command_line_arguments.load_servers()?;
+                let groups = ip_list::group_by_region(&servers, &ip_list.ips);
+                if !*dry_run {
+                    let firewall = Firewall::from_config()?;
+                    firewall.set_port_ranges(servers.port_ranges())?;
+                    firewall.ban_ips(&ip_list.ips)?;
+                }
+                if json {
+                    let regions: HashMap<_, _> = groups
+                        .regions
+                        .iter()
+                        .map(|(server, ips)| (server.get_abr(), ips))
+                        .collect();
+                    return print_json(&serde_json::json!({
+                        "regions": regions,
+                        "unknown": groups.unknown,
+                        "blocked": !*dry_run,
+                    }));
+                }
+                groups.regions.iter().for_each(|(server, ips)| {
+                    println!(
+                        "{}: {} of {} IPs",
+                        server.get_abr(),
+                        ips.len(),
+                        server.get_ips().len()
+                    );
+                });
+                if !groups.unknown.is_empty() {
+                    println!("no known region: {} IPs", groups.unknown.len());
+                }
+                if !*dry_run {
+                    println!("blocked {} IPs", ip_list.ips.len());
+                }
+            }
+            CliCommand::Enable { regex, exclude } | CliCommand::Disable { regex, exclude } => {
+                let blocked = matches!(self, CliCommand::Disable { .. });
+                let servers = command_line_arguments.load_servers()?;
+                let regions: Vec<_> = servers
+                    .get_servers()
+                    .iter()
+                    .map(ServerInfo::get_abr)
+                    .filter(|abr| {
+                        regex.is_match(abr)
+                            && !exclude
+                                .as_ref()
+                                .is_some_and(|exclude| exclude.is_match(abr))
+                    })
+                    .map(str::to_string)
+                    .collect();
+                if regions.is_empty() {
+                    return Err(Error::NoMatchingRegion(regex.to_string()));
+                }
+                #[cfg(windows)]
+                if !is_elevated::is_elevated() {
+                    crate::ipc::forward(
+                        if blocked { "disable" } else { "enable" },
+                        serde_json::json!({ "regions": regions }),
+                    )?;
+                    return Ok(());
+                }
+
+                let controller = Controller::new(servers, Firewall::from_config()?);
+                regions.iter().try_for_each(|region| {
+                    controller.set_blocked(region, blocked)?;
+                    if !json {
+                        println!(
+                            "{} {}",
+                            if blocked { "blocked" } else { "unblocked" },
+                            region
+                        );
+                    }
+                    Ok::<_, Error>(())
+                })?;
+                if json {
+                    return print_json(&controller.statuses(&regions, &HashMap::new())?);
+                }
+            }
+            CliCommand::Audit { remove } => {
+                let mut servers = command_line_arguments.load_servers()?;
+                if command_line_arguments.cm_servers {
+                    steam_cm::load_into(&mut servers);
+                }
+                #[cfg(feature = "cdn")]
+                if command_line_arguments.cdn_servers {
+                    crate::steam_cdn::load_into(&mut servers);
+                }
+                let mut known_ips: HashSet<IpAddr> = servers
+                    .get_servers()
+                    .iter()
+                    .flat_map(|server| server.get_ips().iter().copied())
+                    .collect();
+                command_line_arguments
+                    .app_ids
+                    .iter()
+                    .try_for_each(|app_id| {
+                        let servers = Servers::new_for_app(None::<PathBuf>, *app_id)?;
+                        known_ips.extend(
+                            servers
+                                .get_servers()
+                                .iter()
+                                .flat_map(|server| server.get_ips().iter().copied()),
+                        );
+                        Ok::<_, Error>(())
+                    })?;
+
+                let firewall = Firewall::from_config()?;
+                let audit = firewall::audit(&firewall, &known_ips)?;
+                if *remove {
+                    firewall.unban_ips(&audit.orphaned)?;
+                }
+                if json {
+                    return print_json(&serde_json::json!({
+                        "known": audit.known.len(),
+                        "orphaned": audit.orphaned,
+                        "removed": *remove,
+                    }));
+                }
+                audit.orphaned.iter().for_each(|ip| println!("{}", ip));
+                println!(
+                    "{} rules, {} orphaned{}",
+                    audit.known.len() + audit.orphaned.len(),
+                    audit.orphaned.len(),
+                    if *remove { " (removed)" } else { "" }
+                );
+            }
+            CliCommand::Doctor { timings } => {
+                let checks = [
+                    (
+                        "server list",
+                        command_line_arguments.load_servers().map(|servers| {
+                            format!(
+                                "revision {}, {} regions",
+                                servers.get_revision(),
+                                servers.get_servers().len()
+                            )
+                        }),
+                    ),
+                    (
+                        "firewall",
+                        Firewall::from_config()
+                            .and_then(|firewall| firewall.list_blocked())
+                            .map(|blocked| format!("{} IPs blocked", blocked.len()))
+                            .map_err(Error::from),
+                    ),
+                    (
+                        "ping",
+                        Pinger::new()
+                            .map(|_| "ICMP socket created".to_string())
+                            .map_err(Error::from),
+                    ),
+                ];
+                let num_failed = checks.iter().filter(|(_, res)| res.is_err()).count();
+                if json {
+                    let checks: Vec<_> = checks
+                        .iter()
+                        .map(|(name, res)| {
+                            serde_json::json!({
+                                "check": name,
+                                "ok": res.is_ok(),
+                                "info": match res {
+                                    Ok(info) => info.clone(),
+                                    Err(err) => err.to_string(),
+                                },
+                            })
+                        })
+                        .collect();
+                    print_json(&checks)?;
+                } else {
+                    checks.iter().for_each(|(name, res)| match res {
+                        Ok(info) => println!("ok    {}: {}", name, info),
+                        Err(err) => println!("error {}: {}", name, err),
+                    });
+                }
+
+                if *timings && !json {
+                    println!();
+                    print!("{}", timings::format_table(&timings::snapshot()));
+                }
+                if num_failed != 0 {
+                    return Err(Error::ChecksFailed(num_failed));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CommandLineArguments {
+    /// Get the `--app-id`, the one of the config if not given.
+    fn app_id(&self) -> u32 {
+        // the config is the one of the GUI
+        #[cfg(feature = "gui")]
+        let default_app_id = || Config::load_or_default().app_id;
+        #[cfg(not(feature = "gui"))]
+        let default_app_id = || crate::steam_server::DEFAULT_APP_ID;
+        self.app_id.unwrap_or_else(default_app_id)
+    }
+
+    /// Load the servers of `--app-id`, from `--network-datagram-config`
+    /// if given.
+    fn load_servers(&self) -> Result<Servers, Error> {
+        Servers::new_for_app(self.network_datagram_config.as_ref(), self.app_id())
+    }
+
+    /// Is the JSON-RPC or the gRPC API served instead of the GUI?
+    pub fn serves_api(&self) -> bool {
+        #[cfg(feature = "grpc")]
+        if self.grpc.is_some() {
+            return true;
+        }
+        self.rpc
+    }
+
+    /// Serve the JSON-RPC requests of stdin until it is closed, see
+    /// [`crate::rpc`].
+    pub fn serve_rpc(&self) -> Result<(), Error> {
+        let controller = Controller::new(self.load_servers()?, Firewall::from_config()?);
+        rpc::serve(controller, std::io::stdin().lock(), std::io::stdout())
+    }
+
+    /// Serve the gRPC API on the given address until the server
+    /// fails, see [`crate::grpc`].
+    #[cfg(feature = "grpc")]
+    pub fn serve_grpc(&self, address: std::net::SocketAddr) -> Result<(), Error> {
+        let controller = Controller::new(self.load_servers()?, Firewall::from_config()?);
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?
+            .block_on(crate::grpc::serve(controller, address))
+    }
+
+    /// Get a copy of the arguments with the secrets (API keys,
+    /// webhook URLs) hidden, for logging.
+    pub fn without_secrets(&self) -> Self {
+        let hide = |secret: &Option<String>| secret.as_ref().map(|_| "<hidden>".to_string());
+        Self {
+            steam_web_api_key: hide(&self.steam_web_api_key),
+            discord_webhook_url: hide(&self.discord_webhook_url),
+            ..self.clone()
+        }
+    }
+}
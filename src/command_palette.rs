@@ -0,0 +1,165 @@
+//! Command palette (Ctrl+P): fuzzy search of the regions and the
+//! actions of the app, mirroring the CLI verbs for keyboard use.
+
+use crate::{app::AppMode, regions::Continent};
+
+/// Maximum number of matching entries shown.
+const MAX_SHOWN_ENTRIES: usize = 20;
+
+/// Command run from the [`CommandPalette`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteCommand {
+    /// Show the region in the grid.
+    JumpToRegion(String),
+    EnableRegion(String),
+    DisableRegion(String),
+    EnableContinent(Continent),
+    DisableContinent(Continent),
+    EnableAll,
+    DisableAll,
+    ApplyProfile(String),
+    SetAppMode(AppMode),
+    ToggleHistory,
+    ToggleDiagnostics,
+    OpenSettings,
+    DownloadServerList,
+}
+
+/// Entry of the [`CommandPalette`], the label is matched against the
+/// query.
+#[derive(Debug, Clone)]
+pub struct PaletteEntry {
+    pub label: String,
+    pub command: PaletteCommand,
+}
+
+impl PaletteEntry {
+    pub fn new(label: impl Into<String>, command: PaletteCommand) -> Self {
+        Self {
+            label: label.into(),
+            command,
+        }
+    }
+}
+
+/// Score of the fuzzy match of the query against the text, [`None`]
+/// if the characters of the query (ignoring the whitespace) are not
+/// all in the text in order. Higher is better, consecutive characters
+/// and characters at the start of the words score more.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut text_index = 0;
+    let mut previous_match = None;
+    for query_char in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let index = text_index + text[text_index..].iter().position(|c| *c == query_char)?;
+        score += 1;
+        if index > 0 && previous_match == Some(index - 1) {
+            score += 2;
+        }
+        if index == 0 || !text[index - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous_match = Some(index);
+        text_index = index + 1;
+    }
+    Some(score)
+}
+
+/// State of the command palette window.
+#[derive(Debug, Default)]
+pub struct CommandPalette {
+    open: bool,
+    query: String,
+    /// Index of the selected entry among the matching entries.
+    selected: usize,
+}
+
+impl CommandPalette {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Open the palette with an empty query, or close it if it is
+    /// open.
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.query.clear();
+        self.selected = 0;
+    }
+
+    /// Create the UI of the palette, if it is open, returns the
+    /// command to run (the palette is closed then).
+    pub fn ui(&mut self, ctx: &egui::Context, entries: &[PaletteEntry]) -> Option<PaletteCommand> {
+        if !self.open {
+            return None;
+        }
+
+        let mut matches: Vec<_> = entries
+            .iter()
+            .filter_map(|entry| Some((fuzzy_score(&self.query, &entry.label)?, entry)))
+            .collect();
+        matches.sort_by_key(|(score, entry)| (std::cmp::Reverse(*score), entry.label.len()));
+        matches.truncate(MAX_SHOWN_ENTRIES);
+
+        let (escape, enter, up, down) = ctx.input(|input| {
+            (
+                input.key_pressed(egui::Key::Escape),
+                input.key_pressed(egui::Key::Enter),
+                input.key_pressed(egui::Key::ArrowUp),
+                input.key_pressed(egui::Key::ArrowDown),
+            )
+        });
+        if escape {
+            self.toggle();
+            return None;
+        }
+        if down {
+            self.selected += 1;
+        }
+        if up {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        self.selected = self.selected.min(matches.len().saturating_sub(1));
+
+        let mut chosen = enter
+            .then(|| matches.get(self.selected))
+            .flatten()
+            .map(|(_, entry)| entry.command.clone());
+
+        egui::Window::new("Command Palette")
+            .title_bar(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 40.0])
+            .show(ctx, |ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.query)
+                        .hint_text("Region, continent, profile or action")
+                        .desired_width(400.0),
+                );
+                response.request_focus();
+                if response.changed() {
+                    self.selected = 0;
+                }
+
+                ui.separator();
+
+                if matches.is_empty() {
+                    ui.label("No matches");
+                }
+                matches.iter().enumerate().for_each(|(index, (_, entry))| {
+                    if ui
+                        .selectable_label(index == self.selected, &entry.label)
+                        .clicked()
+                    {
+                        chosen = Some(entry.command.clone());
+                    }
+                });
+            });
+
+        if chosen.is_some() {
+            self.toggle();
+        }
+        chosen
+    }
+}
@@ -0,0 +1,134 @@
+//! Settings of the app remembered across the restarts, stored as TOML
+//! at [`file_ops::get_config_file_path()`] so that they can also be
+//! edited by hand. They are edited from the settings window of the
+//! GUI, the command line arguments take precedence over them.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{app::AppMode, config_refresh, file_ops, presets::Preset, steam_server, Error};
+
+/// Theme of the GUI.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    /// Get all the [`Theme`]s.
+    pub const fn all() -> [Self; 2] {
+        [Self::Dark, Self::Light]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+        }
+    }
+
+    /// Get the [`egui::Visuals`] of the theme.
+    pub fn visuals(&self) -> egui::Visuals {
+        match self {
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+        }
+    }
+}
+
+impl std::fmt::Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Settings of the app, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Timeout of a single ping in milliseconds.
+    pub ping_timeout_ms: u64,
+    /// Number of the recent pings of every IP kept for the statistics
+    /// and the graphs.
+    pub max_pings_per_ip: usize,
+    /// Mode the app starts in.
+    pub app_mode: AppMode,
+    /// Steam app whose servers are loaded, unless `--app-id` is given.
+    pub app_id: u32,
+    pub theme: Theme,
+    /// Apply the last applied profile at the start, same as
+    /// `--restore-profile`.
+    pub restore_profile: bool,
+    /// Interval in hours between the scheduled refreshes of the
+    /// server list, 0 disables them, see [`crate::config_refresh`].
+    pub config_refresh_hours: u64,
+    /// Presets offered in the GUI and by `--preset`, see
+    /// [`crate::presets`].
+    pub presets: Vec<Preset>,
+    /// Draw the offline map instead of downloading the map tiles, same
+    /// as `--offline-map`.
+    pub offline_map: bool,
+    /// URL of the map tiles (eg: a self-hosted tile server), see
+    /// [`crate::map_tiles::TileServer`], OpenStreetMap if empty.
+    pub map_tile_url: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            ping_timeout_ms: 500,
+            max_pings_per_ip: 20,
+            app_mode: AppMode::Grid,
+            app_id: steam_server::DEFAULT_APP_ID,
+            theme: Theme::default(),
+            restore_profile: false,
+            config_refresh_hours: config_refresh::DEFAULT_REFRESH_INTERVAL.as_secs() / 3600,
+            presets: Preset::defaults(),
+            offline_map: false,
+            map_tile_url: String::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Range of [`Self::max_pings_per_ip`].
+    pub const MAX_PINGS_PER_IP_RANGE: std::ops::RangeInclusive<usize> = 2..=100;
+
+    /// Load the config, the defaults if the file does not exist yet.
+    pub fn load() -> Result<Self, Error> {
+        let file_path = file_ops::get_config_file_path()?;
+        if !file_path.exists() {
+            return Ok(Self::default());
+        }
+        let mut config: Self = toml::from_str(&std::fs::read_to_string(file_path)?)?;
+        config.max_pings_per_ip = config.max_pings_per_ip.clamp(
+            *Self::MAX_PINGS_PER_IP_RANGE.start(),
+            *Self::MAX_PINGS_PER_IP_RANGE.end(),
+        );
+        Ok(config)
+    }
+
+    /// Load the config, the defaults if it cannot be loaded (the error
+    /// is logged).
+    pub fn load_or_default() -> Self {
+        Self::load().unwrap_or_else(|err| {
+            log::error!("unable to load the config: {}", err);
+            Self::default()
+        })
+    }
+
+    /// Save the config.
+    pub fn save(&self) -> Result<(), Error> {
+        file_ops::write_atomic(file_ops::get_config_file_path()?, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Get the timeout of a single ping.
+    pub fn ping_timeout(&self) -> Duration {
+        Duration::from_millis(self.ping_timeout_ms)
+    }
+}
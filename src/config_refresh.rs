@@ -0,0 +1,165 @@
+//! Scheduled refresh of the network datagram config (server list) in
+//! the background, separate from the manual download.
+//!
+//! The config is only replaced once the downloaded one is validated,
+//! see [`Servers::download_validated_for_app()`], so a bad download
+//! never replaces a good cached config.
+
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{steam_server::Servers, waker::Waker};
+
+/// Default interval between the refreshes.
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+enum RefresherMessage {
+    /// The servers of the given app and revision are loaded.
+    SetApp {
+        app_id: u32,
+        revision: usize,
+    },
+    /// Refresh every given interval, the next refresh is after it.
+    SetInterval(Duration),
+    KillThread,
+}
+
+/// Network datagram config with a new revision.
+pub struct ConfigRefresh {
+    /// Revision of the config that was loaded before the refresh.
+    pub old_revision: usize,
+    /// Servers of the new config.
+    pub servers: Servers,
+}
+
+/// Refreshes the network datagram config of the loaded app on a
+/// separate thread every refresh interval.
+pub struct ConfigRefresher {
+    message_sender: mpsc::Sender<RefresherMessage>,
+    refresh_receiver: mpsc::Receiver<ConfigRefresh>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+}
+
+impl ConfigRefresher {
+    /// Start refreshing the config of `app_id` every `interval`, the
+    /// first refresh is after `interval`. `revision` is the revision
+    /// of the loaded config. `waker` is woken up when a config with
+    /// a new revision is downloaded.
+    pub fn new(app_id: u32, revision: usize, interval: Duration, waker: Waker) -> Self {
+        let (message_sender, message_receiver) = mpsc::channel();
+        let (refresh_sender, refresh_receiver) = mpsc::channel();
+
+        let thread_handle = thread::spawn(move || {
+            let (mut app_id, mut revision, mut interval) = (app_id, revision, interval);
+            let mut next_refresh = Instant::now() + interval;
+            loop {
+                match message_receiver
+                    .recv_timeout(next_refresh.saturating_duration_since(Instant::now()))
+                {
+                    Ok(RefresherMessage::SetApp {
+                        app_id: new_app_id,
+                        revision: new_revision,
+                    }) => {
+                        app_id = new_app_id;
+                        revision = new_revision;
+                        continue;
+                    }
+                    Ok(RefresherMessage::SetInterval(new_interval)) => {
+                        interval = new_interval;
+                        next_refresh = Instant::now() + interval;
+                        continue;
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    Ok(RefresherMessage::KillThread)
+                    | Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+                next_refresh = Instant::now() + interval;
+
+                match Servers::download_validated_for_app(app_id) {
+                    Ok(servers) if servers.get_revision() == revision => {
+                        log::info!(
+                            "scheduled refresh: config of app {} is up to date (revision {})",
+                            app_id,
+                            revision
+                        );
+                    }
+                    Ok(servers) => {
+                        log::info!(
+                            "scheduled refresh: config of app {} updated from revision {} to {}",
+                            app_id,
+                            revision,
+                            servers.get_revision()
+                        );
+                        let old_revision = revision;
+                        revision = servers.get_revision();
+                        let refresh = ConfigRefresh {
+                            old_revision,
+                            servers,
+                        };
+                        if refresh_sender.send(refresh).is_err() {
+                            break;
+                        }
+                        waker.wake();
+                    }
+                    Err(err) => {
+                        log::error!(
+                            "scheduled refresh: unable to refresh the config of app {}: {}",
+                            app_id,
+                            err
+                        );
+                    }
+                }
+            }
+        });
+
+        Self {
+            message_sender,
+            refresh_receiver,
+            thread_handle: Some(thread_handle),
+        }
+    }
+
+    /// Refresh the config of the given app from now on, `revision`
+    /// is the revision of its loaded config.
+    pub fn set_app(&self, app_id: u32, revision: usize) {
+        if self
+            .message_sender
+            .send(RefresherMessage::SetApp { app_id, revision })
+            .is_err()
+        {
+            log::error!("config refresher thread is not running");
+        }
+    }
+
+    /// Refresh every `interval` from now on, the next refresh is after
+    /// `interval`.
+    pub fn set_interval(&self, interval: Duration) {
+        if self
+            .message_sender
+            .send(RefresherMessage::SetInterval(interval))
+            .is_err()
+        {
+            log::error!("config refresher thread is not running");
+        }
+    }
+
+    /// Get an iterator over the refreshed configs since the last
+    /// call.
+    pub fn try_iter(&self) -> mpsc::TryIter<'_, ConfigRefresh> {
+        self.refresh_receiver.try_iter()
+    }
+}
+
+impl Drop for ConfigRefresher {
+    fn drop(&mut self) {
+        let _ = self.message_sender.send(RefresherMessage::KillThread);
+        if let Some(thread_handle) = self.thread_handle.take() {
+            if thread_handle.join().is_err() {
+                log::error!("config refresher thread panicked");
+            }
+        }
+    }
+}
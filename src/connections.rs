@@ -0,0 +1,325 @@
+//! Detection of the IPs the game traffic is currently flowing to, used
+//! to guard against blocking the relay in use (a block mid-match
+//! disconnects the game).
+//!
+//! Only supported on Linux, from the connection tracking table of
+//! netfilter (`/proc/net/nf_conntrack`, the Steam relays are reached
+//! over unconnected UDP sockets) and the connected UDP sockets.
+//!
+//! Only the sustained flows count: the conntrack entries have to be
+//! assured (traffic seen both ways) with at least
+//! [`MIN_FLOW_PACKETS`] packets each way when the counters are
+//! enabled (`net.netfilter.nf_conntrack_acct`). The probes of the app
+//! itself (the UDP pings and the port check) are left out by their
+//! local port, see [`register_probe_socket()`].
+
+use std::{
+    collections::HashSet,
+    net::{IpAddr, UdpSocket},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[cfg(target_os = "linux")]
+use std::{fs, path::Path};
+
+use crate::{
+    steam_server::{ServerCategory, Servers},
+    Error,
+};
+
+/// Minimum number of packets each way of a conntrack entry to count
+/// as game traffic, a probe is a single packet each way.
+pub const MIN_FLOW_PACKETS: u64 = 10;
+
+/// How long the local ports of the probes are left out, longer than
+/// the timeout of the UDP entries of conntrack
+/// (`nf_conntrack_udp_timeout_stream`).
+const PROBE_PORT_TTL: Duration = Duration::from_secs(180);
+
+/// Local ports of the recent probes of the app, along with when they
+/// were registered.
+static PROBE_PORTS: Mutex<Vec<(u16, Instant)>> = Mutex::new(Vec::new());
+
+/// Leave the traffic of the probe socket of the app out of the
+/// current connections.
+pub(crate) fn register_probe_socket(socket: &UdpSocket) {
+    let Ok(local_address) = socket.local_addr() else {
+        return;
+    };
+    let mut probe_ports = PROBE_PORTS.lock().unwrap();
+    probe_ports.retain(|(_, registered)| registered.elapsed() < PROBE_PORT_TTL);
+    probe_ports.push((local_address.port(), Instant::now()));
+}
+
+/// Get the local ports of the recent probes of the app.
+#[cfg(target_os = "linux")]
+fn probe_ports() -> HashSet<u16> {
+    PROBE_PORTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, registered)| registered.elapsed() < PROBE_PORT_TTL)
+        .map(|(port, _)| *port)
+        .collect()
+}
+
+/// What to do when blocking a region whose relay the game traffic is
+/// flowing through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ActiveRelayGuard {
+    /// Block it.
+    #[default]
+    Off,
+    /// Ask for confirmation before blocking it.
+    Warn,
+    /// Never block it.
+    Refuse,
+}
+
+impl ActiveRelayGuard {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ActiveRelayGuard::Off => "Off",
+            ActiveRelayGuard::Warn => "Warn",
+            ActiveRelayGuard::Refuse => "Refuse",
+        }
+    }
+}
+
+impl std::fmt::Display for ActiveRelayGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Get the remote IPs of the current connections.
+#[cfg(target_os = "linux")]
+pub fn remote_ips() -> Result<HashSet<IpAddr>, Error> {
+    const CONNTRACK_PATH: &str = "/proc/net/nf_conntrack";
+
+    let probe_ports = probe_ports();
+    let mut ips = HashSet::new();
+    // the conntrack table only exists once the connection tracking
+    // module is loaded (eg: by iptables)
+    if Path::new(CONNTRACK_PATH).exists() {
+        ips.extend(parse_conntrack(
+            &fs::read_to_string(CONNTRACK_PATH)?,
+            &probe_ports,
+        ));
+    }
+    ips.extend(parse_proc_net_udp(
+        &fs::read_to_string("/proc/net/udp")?,
+        &probe_ports,
+    ));
+    // missing if IPv6 is disabled
+    if let Ok(udp6) = fs::read_to_string("/proc/net/udp6") {
+        ips.extend(parse_proc_net_udp(&udp6, &probe_ports));
+    }
+    Ok(ips)
+}
+
+/// Get the remote IPs of the current connections.
+#[cfg(not(target_os = "linux"))]
+pub fn remote_ips() -> Result<HashSet<IpAddr>, Error> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "detecting the current connections is only supported on Linux",
+    )
+    .into())
+}
+
+/// Get the `src=` and `dst=` IPs of the sustained UDP entries of the
+/// conntrack table (see the module documentation), eg: `ipv4 2 udp
+/// 17 179 src=192.168.1.2 dst=155.133.226.75 sport=27015 dport=27031
+/// packets=120 bytes=9600 src=155.133.226.75 dst=192.168.1.2
+/// sport=27031 dport=27015 packets=118 bytes=9440 [ASSURED] ...`. The
+/// entries from the `probe_ports` are left out.
+#[cfg(target_os = "linux")]
+fn parse_conntrack<'a>(
+    conntrack: &'a str,
+    probe_ports: &'a HashSet<u16>,
+) -> impl Iterator<Item = IpAddr> + 'a {
+    conntrack
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .filter(|fields| fields.get(2) == Some(&"udp") && fields.contains(&"[ASSURED]"))
+        .filter(|fields| {
+            // the first `sport=` is the local port of the original
+            // direction
+            let is_probe = fields
+                .iter()
+                .find_map(|field| field.strip_prefix("sport="))
+                .and_then(|port| port.parse().ok())
+                .is_some_and(|port| probe_ports.contains(&port));
+            // one counter per direction if the accounting is enabled
+            let is_sustained = fields
+                .iter()
+                .filter_map(|field| field.strip_prefix("packets="))
+                .all(|packets| {
+                    packets
+                        .parse()
+                        .is_ok_and(|packets: u64| packets >= MIN_FLOW_PACKETS)
+                });
+            !is_probe && is_sustained
+        })
+        .flat_map(|fields| {
+            fields
+                .into_iter()
+                .filter_map(|field| {
+                    field
+                        .strip_prefix("src=")
+                        .or_else(|| field.strip_prefix("dst="))
+                })
+                .filter_map(|ip| ip.parse().ok())
+        })
+}
+
+/// Get the remote IPs of the connected UDP sockets of `/proc/net/udp`
+/// (or `/proc/net/udp6`) whose local port is not one of the
+/// `probe_ports`, the addresses are `hex ip:hex port` with the IP in
+/// network byte order read as native integers (four of them for
+/// IPv6).
+#[cfg(target_os = "linux")]
+fn parse_proc_net_udp<'a>(
+    udp: &'a str,
+    probe_ports: &'a HashSet<u16>,
+) -> impl Iterator<Item = IpAddr> + 'a {
+    udp.lines()
+        // skip the header
+        .skip(1)
+        .map(str::split_whitespace)
+        .filter_map(|mut fields| Some((fields.nth(1)?, fields.next()?)))
+        .filter(|(local, _)| {
+            let port = local
+                .split(':')
+                .nth(1)
+                .and_then(|port| u16::from_str_radix(port, 16).ok());
+            !port.is_some_and(|port| probe_ports.contains(&port))
+        })
+        .filter_map(|(_, remote)| {
+            let hex = remote.split(':').next()?;
+            let words = (0..hex.len())
+                .step_by(8)
+                .map(|start| u32::from_str_radix(hex.get(start..start + 8)?, 16).ok())
+                .collect::<Option<Vec<_>>>()?;
+            let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_ne_bytes()).collect();
+            match bytes.len() {
+                4 => Some(IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+                16 => Some(IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+                _ => None,
+            }
+        })
+        .filter(|ip| !ip.is_unspecified())
+}
+
+/// Get the abreviations of the relay regions the game traffic is
+/// flowing through, along with their IPs in use.
+pub fn regions_in_use<'a>(
+    servers: &'a Servers,
+    remote_ips: &HashSet<IpAddr>,
+) -> Vec<(&'a str, Vec<IpAddr>)> {
+    servers
+        .get_servers_in(ServerCategory::Relay)
+        .filter_map(|server| {
+            let ips: Vec<_> = server
+                .get_ips()
+                .iter()
+                .copied()
+                .filter(|ip| remote_ips.contains(ip))
+                .collect();
+            (!ips.is_empty()).then_some((server.get_abr(), ips))
+        })
+        .collect()
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn ips(ips: &[&str]) -> HashSet<IpAddr> {
+        ips.iter().map(|ip| ip.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn parses_the_sustained_udp_entries_of_conntrack() {
+        let conntrack = "\
+ipv4     2 udp      17 179 src=192.168.1.2 dst=155.133.226.75 sport=27015 dport=27031 \
+src=155.133.226.75 dst=192.168.1.2 sport=27031 dport=27015 [ASSURED] mark=0 use=1
+ipv4     2 udp      17 29 src=192.168.1.2 dst=155.133.226.76 sport=27016 dport=27031 \
+[UNREPLIED] src=155.133.226.76 dst=192.168.1.2 sport=27031 dport=27016 mark=0 use=1
+ipv4     2 tcp      6 431999 ESTABLISHED src=192.168.1.2 dst=93.184.216.34 sport=40000 \
+dport=443 src=93.184.216.34 dst=192.168.1.2 sport=443 dport=40000 [ASSURED] mark=0 use=1
+";
+        let parsed: HashSet<IpAddr> = parse_conntrack(conntrack, &HashSet::new()).collect();
+        assert_eq!(parsed, ips(&["192.168.1.2", "155.133.226.75"]));
+    }
+
+    #[test]
+    fn uses_the_counters_of_conntrack() {
+        let conntrack = "\
+ipv4     2 udp      17 179 src=192.168.1.2 dst=155.133.226.75 sport=27015 dport=27031 \
+packets=120 bytes=9600 src=155.133.226.75 dst=192.168.1.2 sport=27031 dport=27015 \
+packets=118 bytes=9440 [ASSURED] mark=0 use=1
+ipv4     2 udp      17 115 src=192.168.1.2 dst=155.133.226.76 sport=40000 dport=27015 \
+packets=1 bytes=32 src=155.133.226.76 dst=192.168.1.2 sport=27015 dport=40000 \
+packets=1 bytes=32 [ASSURED] mark=0 use=1
+";
+        let parsed: HashSet<IpAddr> = parse_conntrack(conntrack, &HashSet::new()).collect();
+        assert_eq!(parsed, ips(&["192.168.1.2", "155.133.226.75"]));
+    }
+
+    #[test]
+    fn leaves_out_the_probes() {
+        let conntrack = "\
+ipv4     2 udp      17 179 src=192.168.1.2 dst=155.133.226.75 sport=40000 dport=27015 \
+src=155.133.226.75 dst=192.168.1.2 sport=27015 dport=40000 [ASSURED] mark=0 use=1
+";
+        let probe_ports = HashSet::from([40000]);
+        assert_eq!(parse_conntrack(conntrack, &probe_ports).count(), 0);
+        assert_eq!(parse_conntrack(conntrack, &HashSet::new()).count(), 4);
+
+        // 0x9C40 is 40000
+        let udp = "  sl  local_address rem_address   st\n  0: 0201A8C0:9C40 4BE2859B:6997 01\n";
+        assert_eq!(parse_proc_net_udp(udp, &probe_ports).count(), 0);
+        assert_eq!(parse_proc_net_udp(udp, &HashSet::new()).count(), 1);
+    }
+
+    #[test]
+    fn parses_the_remote_ips_of_proc_net_udp() {
+        let remote = u32::from_ne_bytes([155, 133, 226, 75]);
+        let udp = format!(
+            "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  1: 00000000:0044 00000000:0000 07 00000000:00000000 00:00000000 00000000     0        0 1 2 0000000000000000 0
+  2: 0201A8C0:9C40 {:08X}:6997 01 00000000:00000000 00:00000000 00000000  1000        0 2 2 0000000000000000 0
+",
+            remote
+        );
+        let ips: Vec<IpAddr> = parse_proc_net_udp(&udp, &HashSet::new()).collect();
+        assert_eq!(ips, [IpAddr::from([155, 133, 226, 75])]);
+    }
+
+    #[test]
+    fn parses_the_ipv6_remote_ips_of_proc_net_udp() {
+        let ip: std::net::Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let hex: String = ip
+            .octets()
+            .chunks(4)
+            .map(|word| format!("{:08X}", u32::from_ne_bytes(word.try_into().unwrap())))
+            .collect();
+        let udp = format!(
+            "  sl  local_address rem_address   st\n  0: {}:9C40 {}:6997 01\n",
+            "0".repeat(32),
+            hex
+        );
+        let ips: Vec<IpAddr> = parse_proc_net_udp(&udp, &HashSet::new()).collect();
+        assert_eq!(ips, [IpAddr::from(ip)]);
+    }
+
+    #[test]
+    fn registers_the_probe_sockets() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        register_probe_socket(&socket);
+        assert!(probe_ports().contains(&socket.local_addr().unwrap().port()));
+    }
+}
@@ -0,0 +1,199 @@
+//! Control of the blocks of the regions without the GUI, shared by
+//! the integrations (eg: [`crate::rpc`]).
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    sync::{mpsc, Mutex},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{
+    event::Event,
+    firewall::Firewall,
+    steam_server::{ServerInfo, ServerState, Servers},
+    Error,
+};
+
+/// Summary of a region.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegionSummary {
+    /// Abreviation of the region, eg: `sgp`.
+    pub region: String,
+    pub description: Option<String>,
+    /// `[longitude, latitude]` of the region.
+    pub geo: Option<[f32; 2]>,
+    /// Number of IPs (relays) of the region.
+    pub num_ips: usize,
+}
+
+impl From<&ServerInfo> for RegionSummary {
+    fn from(server: &ServerInfo) -> Self {
+        Self {
+            region: server.get_abr().to_string(),
+            description: server.label().map(Cow::into_owned),
+            geo: server.geo().copied(),
+            num_ips: server.get_ips().len(),
+        }
+    }
+}
+
+/// Block state and ping of a region.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegionStatus {
+    /// Abreviation of the region, eg: `sgp`.
+    pub region: String,
+    pub description: Option<String>,
+    pub ips: Vec<IpAddr>,
+    pub blocked_ips: Vec<IpAddr>,
+    /// Name of the [`ServerState`], eg: `All Disabled`.
+    pub state: &'static str,
+    /// Last known ping to the region in milliseconds.
+    pub ping_ms: Option<f64>,
+}
+
+impl RegionStatus {
+    /// Get the status of the region from the snapshot of the blocked
+    /// IPs.
+    pub fn new(server: &ServerInfo, blocked: &HashSet<IpAddr>, ping: Option<Duration>) -> Self {
+        Self {
+            region: server.get_abr().to_string(),
+            description: server.label().map(Cow::into_owned),
+            ips: server.get_ips().to_vec(),
+            blocked_ips: server
+                .get_ips()
+                .iter()
+                .copied()
+                .filter(|ip| blocked.contains(ip))
+                .collect(),
+            state: server.state_from_blocked(blocked).name(),
+            ping_ms: ping.map(|ping| ping.as_secs_f64() * 1000.0),
+        }
+    }
+}
+
+/// Blocks and unblocks the regions, the subscribers are sent an
+/// [`Event`] for every region blocked or unblocked through the
+/// controller.
+pub struct Controller {
+    servers: Servers,
+    firewall: Firewall,
+    subscribers: Mutex<Vec<mpsc::Sender<Event>>>,
+}
+
+impl Controller {
+    pub fn new(servers: Servers, firewall: Firewall) -> Self {
+        if let Err(err) = firewall.set_port_ranges(servers.port_ranges()) {
+            log::error!("unable to set the port ranges of the firewall: {}", err);
+        }
+        Self {
+            servers,
+            firewall,
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Get a reference to the servers.
+    pub fn servers(&self) -> &Servers {
+        &self.servers
+    }
+
+    /// Get the summaries of all the regions.
+    pub fn regions(&self) -> Vec<RegionSummary> {
+        self.servers
+            .get_servers()
+            .iter()
+            .map(RegionSummary::from)
+            .collect()
+    }
+
+    /// Get the region with the given abreviation.
+    pub fn region(&self, abr: &str) -> Result<&ServerInfo, Error> {
+        self.servers
+            .get_server(abr)
+            .ok_or_else(|| Error::UnknownRegion(abr.to_string()))
+    }
+
+    /// Get the state of the given regions, all the regions if none
+    /// are given.
+    pub fn states(&self, regions: &[String]) -> Result<Vec<(String, ServerState)>, Error> {
+        let servers = self.regions_named(regions)?;
+        let blocked = self.firewall.list_blocked()?;
+        Ok(servers
+            .into_iter()
+            .map(|server| {
+                (
+                    server.get_abr().to_string(),
+                    server.state_from_blocked(&blocked),
+                )
+            })
+            .collect())
+    }
+
+    /// Get the status of the given regions, all the regions if none
+    /// are given. `pings` are the last known pings of the regions by
+    /// abreviation.
+    pub fn statuses(
+        &self,
+        regions: &[String],
+        pings: &HashMap<String, Duration>,
+    ) -> Result<Vec<RegionStatus>, Error> {
+        let servers = self.regions_named(regions)?;
+        let blocked = self.firewall.list_blocked()?;
+        Ok(servers
+            .into_iter()
+            .map(|server| RegionStatus::new(server, &blocked, pings.get(server.get_abr()).copied()))
+            .collect())
+    }
+
+    /// Get the regions with the given abreviations, all the regions
+    /// if none are given.
+    fn regions_named(&self, regions: &[String]) -> Result<Vec<&ServerInfo>, Error> {
+        if regions.is_empty() {
+            return Ok(self.servers.get_servers().iter().collect());
+        }
+        regions.iter().map(|abr| self.region(abr)).collect()
+    }
+
+    /// Block (or unblock) all the IPs of the region.
+    pub fn set_blocked(&self, abr: &str, blocked: bool) -> Result<(), Error> {
+        let server = self.region(abr)?;
+        let region = server.get_abr().to_string();
+        if blocked {
+            server.ban(&self.firewall)?;
+            self.send_event(Event::RegionDisabled {
+                region,
+                ips: server.get_ips().to_vec(),
+                reason: None,
+            });
+        } else {
+            server.unban(&self.firewall)?;
+            self.send_event(Event::RegionEnabled {
+                region,
+                ips: server.get_ips().to_vec(),
+                reason: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// Subscribe to the events, the events are sent until the
+    /// receiver or the controller is dropped.
+    pub fn subscribe(&self) -> mpsc::Receiver<Event> {
+        let (event_sender, event_receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(event_sender);
+        event_receiver
+    }
+
+    /// Send the event to the subscribers, the dropped ones are
+    /// removed.
+    fn send_event(&self, event: Event) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
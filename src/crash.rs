@@ -0,0 +1,131 @@
+//! Crash reports: a panic hook writes the panic with its backtrace,
+//! the newest log records and the pending operations to
+//! [`file_ops::get_crash_reports_dir()`]. If the main thread panics
+//! while a [`crate::session`] is active, the blocks of the session are
+//! undone before exiting.
+
+use std::{
+    backtrace::Backtrace,
+    fmt::Write as _,
+    panic::PanicHookInfo,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use lazy_static::lazy_static;
+
+use crate::{file_ops, firewall::Firewall, history, logger, session, Error};
+
+/// Number of the newest log records in the crash report.
+pub const NUM_LOG_RECORDS: usize = 200;
+
+lazy_static! {
+    /// Pending operations by their id, see [`begin_operation()`].
+    static ref PENDING_OPERATIONS: Mutex<Vec<(u64, String)>> = Mutex::new(Vec::new());
+}
+
+static NEXT_OPERATION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Operation listed in the crash reports until it is dropped, see
+/// [`begin_operation()`].
+pub struct PendingOperation {
+    id: u64,
+}
+
+impl Drop for PendingOperation {
+    fn drop(&mut self) {
+        lock_pending_operations().retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// Begin the operation (eg: `blocking 12 IPs of sgp`), it is listed in
+/// the crash reports until the returned guard is dropped.
+pub fn begin_operation(description: impl Into<String>) -> PendingOperation {
+    let id = NEXT_OPERATION_ID.fetch_add(1, Ordering::Relaxed);
+    lock_pending_operations().push((id, description.into()));
+    PendingOperation { id }
+}
+
+/// Lock the pending operations, the lock may be poisoned if a thread
+/// panicked while it was held.
+fn lock_pending_operations() -> std::sync::MutexGuard<'static, Vec<(u64, String)>> {
+    PENDING_OPERATIONS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Install the panic hook, the previous hook (printing the panic) is
+/// still called.
+pub fn install_panic_hook() {
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        match write_report(info) {
+            Ok(path) => log::error!("crash report written to `{}`", path.display()),
+            Err(err) => log::error!("unable to write the crash report: {}", err),
+        }
+
+        // the app only exits when the main thread panics, the other
+        // threads can be restarted
+        if thread::current().name() == Some("main") && session::is_active() {
+            match Firewall::from_config()
+                .map_err(Error::from)
+                .and_then(|firewall| session::rollback(&firewall))
+            {
+                Ok(num_ips) => log::info!("crashed, unblocked the {} IPs of the session", num_ips),
+                Err(err) => {
+                    log::error!("crashed, unable to undo the blocks of the session: {}", err)
+                }
+            }
+        }
+
+        previous_hook(info);
+    }));
+}
+
+/// Write the crash report of the panic, returns its path.
+fn write_report(info: &PanicHookInfo) -> Result<PathBuf, Error> {
+    let time = history::unix_time_now();
+    let mut report = String::new();
+    // writing to a `String` never fails
+    let _ = writeln!(
+        report,
+        "{} {} crash report",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(report, "time: {} UTC", history::format_unix_time(time));
+    let _ = writeln!(
+        report,
+        "thread: {}",
+        thread::current().name().unwrap_or("<unnamed>")
+    );
+    let _ = writeln!(report, "{}", info);
+
+    let _ = writeln!(report, "\npending operations:");
+    lock_pending_operations()
+        .iter()
+        .for_each(|(_, description)| {
+            let _ = writeln!(report, "  {}", description);
+        });
+
+    let _ = writeln!(report, "\nbacktrace:\n{}", Backtrace::force_capture());
+
+    let _ = writeln!(report, "newest {} log records:", NUM_LOG_RECORDS);
+    logger::LOGGER
+        .first()
+        .format_newest_records(NUM_LOG_RECORDS)
+        .iter()
+        .for_each(|record| {
+            let _ = writeln!(report, "  {}", record);
+        });
+
+    let dir = file_ops::get_crash_reports_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("crash_{}.txt", time));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
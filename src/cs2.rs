@@ -0,0 +1,200 @@
+//! Counter-Strike 2 user settings (convars).
+//!
+//! The convars are stored by the game in
+//! `userdata/<account id>/730/local/cfg/cs2_user_convars_0_slot0.vcfg`
+//! of the Steam installation. The game overwrites the file when it
+//! exits, so changes must be made while the game is not running.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{file_ops, steam, Error};
+
+/// Convar of the maximum acceptable ping (in milliseconds) for the
+/// matchmaking servers.
+pub const MAX_PING_CONVAR: &str = "mm_dedicated_search_maxping";
+
+/// Range of the values accepted by the game for
+/// [`MAX_PING_CONVAR`].
+pub const MAX_PING_RANGE: std::ops::RangeInclusive<u32> = 25..=350;
+
+/// Path of the convars file relative to the user data directory.
+const USER_CONVARS_FILE_PATH: &str = "730/local/cfg/cs2_user_convars_0_slot0.vcfg";
+
+/// CS2 user convars file.
+#[derive(Debug, Clone)]
+pub struct UserConvars {
+    path: PathBuf,
+}
+
+impl UserConvars {
+    /// Use the convars file at the given path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Find the convars file in the given Steam installation, the
+    /// most recently modified one is used if there are multiple Steam
+    /// users.
+    pub fn find(steam_install_dir: impl AsRef<Path>) -> Option<Self> {
+        steam::get_userdata_dirs(steam_install_dir)
+            .into_iter()
+            .map(|userdata_dir| userdata_dir.join(USER_CONVARS_FILE_PATH))
+            .filter_map(|path| {
+                let modified = path.metadata().and_then(|metadata| metadata.modified());
+                Some((path, modified.ok()?))
+            })
+            .max_by_key(|(_, modified)| *modified)
+            .map(|(path, _)| Self::new(path))
+    }
+
+    /// Get a reference to the path of the convars file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the value of the convar, [`None`] if it is not set (the
+    /// game uses its default).
+    pub fn get(&self, name: &str) -> Result<Option<String>, Error> {
+        let contents = fs::read_to_string(&self.path)?;
+        Ok(contents
+            .lines()
+            .find_map(|line| match steam::parse_key_value(line) {
+                Some((key, value)) if key == name => Some(value.to_string()),
+                _ => None,
+            }))
+    }
+
+    /// Set the value of the convar.
+    pub fn set(&self, name: &str, value: &str) -> Result<(), Error> {
+        let contents = fs::read_to_string(&self.path)?;
+        let new_line = format!("\t\t\"{}\"\t\t\"{}\"", name, value);
+
+        let mut lines: Vec<String> = contents.lines().map(str::to_string).collect();
+        if let Some(line) = lines
+            .iter_mut()
+            .find(|line| matches!(steam::parse_key_value(line), Some((key, _)) if key == name))
+        {
+            *line = new_line;
+        } else {
+            // insert as the first entry of the `convars` block
+            let convars_index = lines
+                .iter()
+                .position(|line| line.trim() == "\"convars\"")
+                .ok_or_else(|| self.invalid("no `convars` block"))?;
+            let open_brace_index = lines[convars_index..]
+                .iter()
+                .position(|line| line.trim() == "{")
+                .ok_or_else(|| self.invalid("`convars` block is not opened"))?
+                + convars_index;
+            lines.insert(open_brace_index + 1, new_line);
+        }
+
+        let mut new_contents = lines.join("\n");
+        if contents.ends_with('\n') {
+            new_contents.push('\n');
+        }
+
+        #[cfg(unix)]
+        let owner = {
+            use std::os::unix::fs::MetadataExt;
+            fs::metadata(&self.path).map(|metadata| (metadata.uid(), metadata.gid()))?
+        };
+
+        file_ops::write_atomic(&self.path, new_contents)?;
+
+        // the file is written with elevated privileges, give it back to
+        // the user so that the game can still write to it
+        #[cfg(unix)]
+        std::os::unix::fs::chown(&self.path, Some(owner.0), Some(owner.1))?;
+
+        log::info!("set `{}` to `{}` in `{}`", name, value, self.path.display());
+
+        Ok(())
+    }
+
+    /// Get the maximum acceptable matchmaking ping in milliseconds,
+    /// see [`MAX_PING_CONVAR`].
+    pub fn max_ping(&self) -> Result<Option<u32>, Error> {
+        self.get(MAX_PING_CONVAR)?
+            .map(|value| {
+                // the game may store the value as a float
+                value
+                    .parse::<f32>()
+                    .map(|value| value as u32)
+                    .map_err(|_| self.invalid(format!("`{}` is not a number", value)))
+            })
+            .transpose()
+    }
+
+    /// Set the maximum acceptable matchmaking ping in milliseconds,
+    /// see [`MAX_PING_CONVAR`]. The value is clamped to
+    /// [`MAX_PING_RANGE`].
+    pub fn set_max_ping(&self, max_ping: u32) -> Result<(), Error> {
+        let max_ping = max_ping.clamp(*MAX_PING_RANGE.start(), *MAX_PING_RANGE.end());
+        self.set(MAX_PING_CONVAR, &max_ping.to_string())
+    }
+
+    fn invalid(&self, reason: impl Into<String>) -> Error {
+        Error::InvalidConvarsFile {
+            path: self.path.clone(),
+            reason: reason.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONVARS: &str =
+        "\"config\"\n{\n\t\"convars\"\n\t{\n\t\t\"cl_showfps\"\t\t\"0\"\n\t}\n}\n";
+
+    /// Write the contents to a convars file of the test.
+    fn convars(name: &str, contents: &str) -> UserConvars {
+        let path = std::env::temp_dir().join(format!(
+            "steam_server_disable_test_{}_{}.vcfg",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, contents).unwrap();
+        UserConvars::new(path)
+    }
+
+    #[test]
+    fn replaces_the_convar() {
+        let convars = convars("replace", CONVARS);
+        convars.set("cl_showfps", "1").unwrap();
+        assert_eq!(convars.get("cl_showfps").unwrap().as_deref(), Some("1"));
+        assert_eq!(
+            fs::read_to_string(convars.path()).unwrap(),
+            CONVARS.replace("\"0\"", "\"1\"")
+        );
+        fs::remove_file(convars.path()).unwrap();
+    }
+
+    #[test]
+    fn inserts_the_missing_convar() {
+        let convars = convars("insert", CONVARS);
+        assert_eq!(convars.get(MAX_PING_CONVAR).unwrap(), None);
+        convars.set(MAX_PING_CONVAR, "80").unwrap();
+        assert_eq!(convars.max_ping().unwrap(), Some(80));
+        assert_eq!(convars.get("cl_showfps").unwrap().as_deref(), Some("0"));
+        let contents = fs::read_to_string(convars.path()).unwrap();
+        assert!(contents.starts_with(&format!(
+            "\"config\"\n{{\n\t\"convars\"\n\t{{\n\t\t\"{}\"\t\t\"80\"\n",
+            MAX_PING_CONVAR
+        )));
+        assert!(contents.ends_with('\n'));
+        fs::remove_file(convars.path()).unwrap();
+    }
+
+    #[test]
+    fn requires_the_convars_block() {
+        let convars = convars("invalid", "\"config\"\n{\n}\n");
+        assert!(convars.set(MAX_PING_CONVAR, "80").is_err());
+        fs::remove_file(convars.path()).unwrap();
+    }
+}
@@ -0,0 +1,48 @@
+//! Native desktop notification [`Notifier`], so that the degraded
+//! regions and the failed firewall operations are noticed even when
+//! the logging window is closed.
+
+use crate::{
+    event::{Event, Notifier},
+    Error,
+};
+
+/// Shows the alerts (ping or loss above the threshold, firewall
+/// errors, rules removed externally) as desktop notifications, the
+/// other events are left to the log.
+#[derive(Debug, Default)]
+pub struct DesktopNotifier;
+
+impl DesktopNotifier {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Is the event worth a desktop notification?
+    fn is_alert(event: &Event) -> bool {
+        match event {
+            Event::PingThresholdCrossed { .. }
+            | Event::LossThresholdCrossed { .. }
+            | Event::FirewallError { .. }
+            | Event::RulesRestored { .. } => true,
+            Event::RegionDisabled { .. }
+            | Event::RegionEnabled { .. }
+            | Event::ServerListRevisionChanged { .. }
+            | Event::ProfileToggled { .. } => false,
+        }
+    }
+}
+
+impl Notifier for DesktopNotifier {
+    fn notify(&mut self, event: &Event) -> Result<(), Error> {
+        if !Self::is_alert(event) {
+            return Ok(());
+        }
+        notify_rust::Notification::new()
+            .appname(env!("CARGO_PKG_NAME"))
+            .summary(event.title())
+            .body(&event.to_string())
+            .show()?;
+        Ok(())
+    }
+}
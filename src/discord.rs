@@ -0,0 +1,131 @@
+//! Discord webhook [`Notifier`].
+
+use serde_json::json;
+
+use crate::{
+    downloader,
+    event::{Event, Notifier},
+    Error,
+};
+
+/// Posts the [`Event`]s as embeds to a Discord webhook.
+pub struct DiscordWebhook {
+    url: String,
+}
+
+impl DiscordWebhook {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { url: url.into() }
+    }
+
+    /// Color of the embed of the event.
+    fn embed_color(event: &Event) -> u32 {
+        match event {
+            Event::RegionDisabled { .. } => 0xd03030,
+            Event::RegionEnabled { .. } => 0x30a030,
+            Event::PingThresholdCrossed { above: true, .. } => 0xd0a030,
+            Event::PingThresholdCrossed { above: false, .. } => 0x30a030,
+            Event::LossThresholdCrossed { above: true, .. } => 0xd0a030,
+            Event::LossThresholdCrossed { above: false, .. } => 0x30a030,
+            Event::FirewallError { .. } => 0xd03030,
+            Event::ServerListRevisionChanged { .. } => 0x3070d0,
+            Event::ProfileToggled { blocked: true, .. } => 0xd03030,
+            Event::ProfileToggled { blocked: false, .. } => 0x30a030,
+            Event::RulesRestored { .. } => 0xd0a030,
+        }
+    }
+
+    /// Create the webhook message for the event.
+    fn message(event: &Event) -> serde_json::Value {
+        let mut fields = Vec::new();
+        match event {
+            Event::RegionDisabled {
+                region,
+                ips,
+                reason,
+            }
+            | Event::RegionEnabled {
+                region,
+                ips,
+                reason,
+            } => {
+                fields.push(json!({ "name": "Region", "value": region, "inline": true }));
+                fields
+                    .push(json!({ "name": "IPs", "value": ips.len().to_string(), "inline": true }));
+                if let Some(reason) = reason {
+                    fields.push(json!({ "name": "Reason", "value": reason, "inline": true }));
+                }
+            }
+            Event::PingThresholdCrossed {
+                region,
+                ping_ms,
+                threshold_ms,
+                ..
+            } => {
+                fields.push(json!({ "name": "Region", "value": region, "inline": true }));
+                fields.push(
+                    json!({ "name": "Ping", "value": format!("{} ms", ping_ms), "inline": true }),
+                );
+                fields.push(json!({
+                    "name": "Threshold",
+                    "value": format!("{} ms", threshold_ms),
+                    "inline": true
+                }));
+            }
+            Event::LossThresholdCrossed {
+                region,
+                loss_percent,
+                threshold_percent,
+                ..
+            } => {
+                fields.push(json!({ "name": "Region", "value": region, "inline": true }));
+                fields.push(
+                    json!({ "name": "Loss", "value": format!("{}%", loss_percent), "inline": true }),
+                );
+                fields.push(json!({
+                    "name": "Threshold",
+                    "value": format!("{}%", threshold_percent),
+                    "inline": true
+                }));
+            }
+            Event::FirewallError { region, error } => {
+                fields.push(json!({ "name": "Region", "value": region, "inline": true }));
+                fields.push(json!({ "name": "Error", "value": error, "inline": false }));
+            }
+            Event::ServerListRevisionChanged {
+                old_revision,
+                new_revision,
+            } => {
+                fields.push(
+                    json!({ "name": "Old", "value": old_revision.to_string(), "inline": true }),
+                );
+                fields.push(
+                    json!({ "name": "New", "value": new_revision.to_string(), "inline": true }),
+                );
+            }
+            Event::ProfileToggled { profile, .. } => {
+                fields.push(json!({ "name": "Profile", "value": profile, "inline": true }));
+            }
+            Event::RulesRestored { num_ips } => {
+                fields.push(json!({ "name": "IPs", "value": num_ips.to_string(), "inline": true }));
+            }
+        }
+
+        json!({
+            "username": env!("CARGO_PKG_NAME"),
+            "embeds": [{
+                "title": event.title(),
+                "description": event.to_string(),
+                "color": Self::embed_color(event),
+                "fields": fields,
+            }],
+        })
+    }
+}
+
+impl Notifier for DiscordWebhook {
+    fn notify(&mut self, event: &Event) -> Result<(), Error> {
+        downloader::Download::post_json(&self.url, Self::message(event).to_string().as_bytes())?;
+        Ok(())
+    }
+}
@@ -1,58 +1,81 @@
 use curl::easy::Easy;
 use std::path::Path;
 
+use crate::{file_ops, timings};
+
 pub struct Download {}
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum Error {
-    Curl(curl::Error),
-    IO(std::io::Error),
+    #[error("curl: {0}")]
+    Curl(#[from] curl::Error),
+    #[error("io: {0}")]
+    IO(#[from] std::io::Error),
+    /// The server responded with the given (non success) HTTP
+    /// status code.
+    #[error("HTTP status {0}")]
+    Http(u32),
 }
 
-impl From<curl::Error> for Error {
-    fn from(error: curl::Error) -> Self {
-        Error::Curl(error)
-    }
-}
-
-impl From<std::io::Error> for Error {
-    fn from(error: std::io::Error) -> Self {
-        Error::IO(error)
-    }
-}
-
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
-    }
-}
-
-impl std::error::Error for Error {}
-
 impl Download {
     pub fn from_url<P>(url: &str, file_path: P) -> Result<(), Error>
     where
         P: AsRef<Path>,
     {
+        let buf = Self::to_bytes(url)?;
+        let file_path = file_path.as_ref();
+        file_ops::write_atomic(file_path, buf)?;
+
+        log::info!("downloaded `{}` to `{}`", url, file_path.display());
+
+        Ok(())
+    }
+
+    /// Download the contents at the url.
+    pub fn to_bytes(url: &str) -> Result<Vec<u8>, Error> {
+        let _timer = timings::timer("download");
         let mut easy = Easy::new();
         easy.url(url)?;
 
         let mut buf = Vec::new();
         {
             let mut transfer = easy.transfer();
-            transfer
-                .write_function(|data| {
-                    buf.extend_from_slice(data);
-                    Ok(data.len())
-                })
-                .unwrap();
+            transfer.write_function(|data| {
+                buf.extend_from_slice(data);
+                Ok(data.len())
+            })?;
             transfer.perform()?;
         }
-        let file_path = file_path.as_ref();
-        std::fs::write(file_path, buf)?;
 
-        log::info!("downloaded `{}` to `{}`", url, file_path.display());
+        Ok(buf)
+    }
 
-        Ok(())
+    /// Post the JSON body to the url, returns the response.
+    pub fn post_json(url: &str, body: &[u8]) -> Result<Vec<u8>, Error> {
+        let _timer = timings::timer("download (post)");
+        let mut easy = Easy::new();
+        easy.url(url)?;
+        easy.post(true)?;
+        easy.post_fields_copy(body)?;
+        let mut headers = curl::easy::List::new();
+        headers.append("Content-Type: application/json")?;
+        easy.http_headers(headers)?;
+
+        let mut buf = Vec::new();
+        {
+            let mut transfer = easy.transfer();
+            transfer.write_function(|data| {
+                buf.extend_from_slice(data);
+                Ok(data.len())
+            })?;
+            transfer.perform()?;
+        }
+
+        let response_code = easy.response_code()?;
+        if !(200..300).contains(&response_code) {
+            return Err(Error::Http(response_code));
+        }
+
+        Ok(buf)
     }
 }
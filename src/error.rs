@@ -0,0 +1,127 @@
+use crate::{downloader, file_ops, firewall, ping};
+
+/// Crate level error.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("firewall: {0}")]
+    Firewall(#[from] firewall::Error),
+
+    #[error("download: {0}")]
+    Download(#[from] downloader::Error),
+
+    #[error("all downloads failed: {0:?}")]
+    DownloadMultiple(Vec<downloader::Error>),
+
+    #[error("parse: {0}")]
+    Parse(#[from] serde_json::Error),
+
+    #[error("invalid network datagram config: {0}")]
+    InvalidNetworkDatagramConfig(String),
+
+    #[error("invalid ip `{ip}`: {error}")]
+    InvalidIp {
+        ip: String,
+        error: std::net::AddrParseError,
+    },
+
+    #[error("no region named `{0}`")]
+    UnknownRegion(String),
+
+    #[error("no region matches `{0}`")]
+    NoMatchingRegion(String),
+
+    #[error("no profile named `{0}`")]
+    UnknownProfile(String),
+
+    #[cfg(windows)]
+    #[error("ipc: {0}")]
+    Ipc(String),
+
+    #[error("{0} checks failed")]
+    ChecksFailed(usize),
+
+    #[error("invalid ip list entries: {0}")]
+    InvalidIpList(String),
+
+    #[error("ping: {0}")]
+    Ping(#[from] ping::Error),
+
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("versioned file: {0}")]
+    VersionedFile(#[from] file_ops::VersionedFileError),
+
+    #[error("no home directory found, the files of the app cannot be stored")]
+    NoHomeDirectory,
+
+    #[cfg(feature = "gui")]
+    #[error("config: {0}")]
+    ConfigParse(#[from] toml::de::Error),
+
+    #[cfg(feature = "gui")]
+    #[error("config: {0}")]
+    ConfigSerialize(#[from] toml::ser::Error),
+
+    #[error("invalid convars file `{path}`: {reason}")]
+    InvalidConvarsFile {
+        path: std::path::PathBuf,
+        reason: String,
+    },
+
+    #[cfg(feature = "hotkey")]
+    #[error("hotkey: {0}")]
+    Hotkey(#[from] global_hotkey::Error),
+
+    #[cfg(feature = "hotkey")]
+    #[error("invalid hotkey `{hotkey}`: {reason}")]
+    InvalidHotkey { hotkey: String, reason: String },
+
+    #[cfg(feature = "notify")]
+    #[error("desktop notification: {0}")]
+    DesktopNotification(#[from] notify_rust::error::Error),
+
+    #[cfg(feature = "grpc")]
+    #[error("grpc: {0}")]
+    Grpc(#[from] tonic::transport::Error),
+
+    #[cfg(feature = "async")]
+    #[error("task: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+impl Error {
+    /// Get what the user can do about the error, if anything, eg: for
+    /// the dialog shown when the app cannot start.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Error::Firewall(firewall::Error::Custom(_)) => Some(
+                "Check that the firewall (iptables, nftables or ipset) is installed and that \
+                 the app runs as root/administrator. The backend and its options are set in \
+                 the firewall config file",
+            ),
+            Error::Firewall(_) => Some("Check that the app runs as root/administrator"),
+            Error::Download(_) | Error::DownloadMultiple(_) => {
+                Some("Check the internet connection, the last downloaded files are used if any")
+            }
+            Error::Parse(_) | Error::InvalidNetworkDatagramConfig(_) => Some(
+                "The cached network datagram config may be corrupt, delete it to download it \
+                 again",
+            ),
+            Error::Ping(_) => {
+                Some("Pinging needs raw sockets, check that the app runs as root/administrator")
+            }
+            Error::VersionedFile(_) => Some(
+                "The file may have been written by a newer version of the app or edited by \
+                 hand, fix or delete it",
+            ),
+            Error::NoHomeDirectory => Some("Check that the HOME environment variable is set"),
+            #[cfg(feature = "gui")]
+            Error::ConfigParse(_) => Some("Fix or delete `config.toml` in the config directory"),
+            _ => None,
+        }
+    }
+}
+
+/// Crate level result.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
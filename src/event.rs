@@ -0,0 +1,224 @@
+//! Events reported to the user through the [`Notifier`]s.
+
+use std::{net::IpAddr, sync::mpsc, thread};
+
+use serde::Serialize;
+
+use crate::Error;
+
+/// Event that the user may want to be notified about.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Event {
+    /// All the IPs of the region were blocked.
+    RegionDisabled {
+        region: String,
+        ips: Vec<IpAddr>,
+        /// Why the region was blocked, [`None`] if blocked by the
+        /// user.
+        reason: Option<String>,
+    },
+    /// All the IPs of the region were unblocked.
+    RegionEnabled {
+        region: String,
+        ips: Vec<IpAddr>,
+        /// Why the region was unblocked, [`None`] if unblocked by the
+        /// user.
+        reason: Option<String>,
+    },
+    /// The mean ping of the region went above the threshold, or back
+    /// below it.
+    PingThresholdCrossed {
+        region: String,
+        ping_ms: u64,
+        threshold_ms: u64,
+        above: bool,
+    },
+    /// The recent loss of the region went above the threshold, or
+    /// back below it.
+    LossThresholdCrossed {
+        region: String,
+        loss_percent: u64,
+        threshold_percent: u64,
+        above: bool,
+    },
+    /// Blocking or unblocking the IPs of the region failed.
+    FirewallError { region: String, error: String },
+    /// The revision of the network datagram config (server list)
+    /// changed.
+    ServerListRevisionChanged {
+        old_revision: usize,
+        new_revision: usize,
+    },
+    /// The blocks of the active profile were toggled.
+    ProfileToggled {
+        profile: String,
+        /// If the regions of the profile are now blocked.
+        blocked: bool,
+    },
+    /// The rules of the app were removed outside of the app (eg:
+    /// `iptables -F`, a firewalld reload) and were applied again.
+    RulesRestored { num_ips: usize },
+}
+
+impl Event {
+    /// Get the kind of the event, the `kind` of its JSON.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::RegionDisabled { .. } => "region_disabled",
+            Event::RegionEnabled { .. } => "region_enabled",
+            Event::PingThresholdCrossed { .. } => "ping_threshold_crossed",
+            Event::LossThresholdCrossed { .. } => "loss_threshold_crossed",
+            Event::FirewallError { .. } => "firewall_error",
+            Event::ServerListRevisionChanged { .. } => "server_list_revision_changed",
+            Event::ProfileToggled { .. } => "profile_toggled",
+            Event::RulesRestored { .. } => "rules_restored",
+        }
+    }
+
+    /// Get a short title for the event.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Event::RegionDisabled { .. } => "Region disabled",
+            Event::RegionEnabled { .. } => "Region enabled",
+            Event::PingThresholdCrossed { above: true, .. } => "Ping degraded",
+            Event::PingThresholdCrossed { above: false, .. } => "Ping recovered",
+            Event::LossThresholdCrossed { above: true, .. } => "Loss degraded",
+            Event::LossThresholdCrossed { above: false, .. } => "Loss recovered",
+            Event::FirewallError { .. } => "Firewall error",
+            Event::ServerListRevisionChanged { .. } => "Server list updated",
+            Event::ProfileToggled { .. } => "Profile toggled",
+            Event::RulesRestored { .. } => "Rules restored",
+        }
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::RegionDisabled { region, reason, .. } => {
+                write!(f, "blocked {}", region)?;
+                reason
+                    .as_ref()
+                    .map_or(Ok(()), |reason| write!(f, " due to {}", reason))
+            }
+            Event::RegionEnabled { region, reason, .. } => {
+                write!(f, "unblocked {}", region)?;
+                reason
+                    .as_ref()
+                    .map_or(Ok(()), |reason| write!(f, " due to {}", reason))
+            }
+            Event::PingThresholdCrossed {
+                region,
+                ping_ms,
+                threshold_ms,
+                above,
+            } => write!(
+                f,
+                "mean ping of {} is {} ms, {} the threshold of {} ms",
+                region,
+                ping_ms,
+                if *above { "above" } else { "back below" },
+                threshold_ms
+            ),
+            Event::LossThresholdCrossed {
+                region,
+                loss_percent,
+                threshold_percent,
+                above,
+            } => write!(
+                f,
+                "recent loss of {} is {}%, {} the threshold of {}%",
+                region,
+                loss_percent,
+                if *above { "above" } else { "back below" },
+                threshold_percent
+            ),
+            Event::FirewallError { region, error } => {
+                write!(f, "unable to update the rules of {}: {}", region, error)
+            }
+            Event::ServerListRevisionChanged {
+                old_revision,
+                new_revision,
+            } => write!(
+                f,
+                "server list revision changed from {} to {}",
+                old_revision, new_revision
+            ),
+            Event::ProfileToggled { profile, blocked } => {
+                if *blocked {
+                    write!(f, "applied profile `{}`", profile)
+                } else {
+                    write!(f, "cleared the blocks of profile `{}`", profile)
+                }
+            }
+            Event::RulesRestored { num_ips } => write!(
+                f,
+                "the firewall rules were removed externally, blocked the {} IPs again",
+                num_ips
+            ),
+        }
+    }
+}
+
+/// Sends the [`Event`]s to the user.
+pub trait Notifier: Send {
+    /// Notify the user about the event.
+    fn notify(&mut self, event: &Event) -> Result<(), Error>;
+}
+
+enum NotifierMessage {
+    Event(Event),
+    KillThread,
+}
+
+/// Runs the [`Notifier`]s on a separate thread so that sending the
+/// notifications does not block the caller.
+pub struct Notifiers {
+    message_sender: mpsc::Sender<NotifierMessage>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Notifiers {
+    pub fn new(mut notifiers: Vec<Box<dyn Notifier>>) -> Self {
+        let (message_sender, message_receiver) = mpsc::channel();
+
+        let thread_handle = thread::spawn(move || {
+            while let Ok(NotifierMessage::Event(event)) = message_receiver.recv() {
+                notifiers.iter_mut().for_each(|notifier| {
+                    if let Err(err) = notifier.notify(&event) {
+                        log::error!("unable to send notification `{}`: {}", event, err);
+                    }
+                });
+            }
+        });
+
+        Self {
+            message_sender,
+            thread_handle: Some(thread_handle),
+        }
+    }
+
+    /// Send the event to all the notifiers.
+    pub fn notify(&self, event: Event) {
+        log::info!("{}", event);
+        if self
+            .message_sender
+            .send(NotifierMessage::Event(event))
+            .is_err()
+        {
+            log::error!("notifier thread is not running");
+        }
+    }
+}
+
+impl Drop for Notifiers {
+    fn drop(&mut self) {
+        let _ = self.message_sender.send(NotifierMessage::KillThread);
+        if let Some(thread_handle) = self.thread_handle.take() {
+            if thread_handle.join().is_err() {
+                log::error!("notifier thread panicked");
+            }
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! C ABI for embedding region blocking in other languages.
+//!
+//! The header is generated with `cbindgen` (see `cbindgen.toml`) and
+//! shipped at `include/steam_server_disable.h`.
+//!
+//! Strings passed to callbacks are only valid for the duration of the
+//! callback. Functions returning [`c_int`] return `0` on success and
+//! `-1` on failure, the reason for the failure is available through
+//! [`ssd_last_error_message()`]. A panic does not unwind into the
+//! caller, the function fails instead with the panic message as the
+//! reason.
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    ptr,
+};
+
+use crate::{
+    firewall::Firewall,
+    steam_server::{ServerInfo, ServerState, Servers},
+};
+
+thread_local! {
+    static LAST_ERROR_MESSAGE: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Set the last error message of the current thread.
+fn set_last_error_message(message: impl ToString) {
+    let message = CString::new(message.to_string().replace('\0', "")).unwrap_or_default();
+    LAST_ERROR_MESSAGE.with(|last_error_message| {
+        *last_error_message.borrow_mut() = Some(message);
+    });
+}
+
+/// Run `f`, returns `on_panic` if it panics with the panic message set
+/// as the last error message. Unwinding into the caller is undefined
+/// behavior.
+fn catch_panic<T>(on_panic: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        set_last_error_message(format!("panicked: {}", message));
+        on_panic
+    })
+}
+
+/// Status of a region.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SsdRegionStatus {
+    /// Region does not exist or the status could not be determined.
+    Unknown,
+    /// None of the region's IPs are blocked.
+    NoneDisabled,
+    /// Some of the region's IPs are blocked.
+    SomeDisabled,
+    /// All of the region's IPs are blocked.
+    AllDisabled,
+}
+
+impl From<&ServerState> for SsdRegionStatus {
+    fn from(server_state: &ServerState) -> Self {
+        match server_state {
+            ServerState::AllDisabled => Self::AllDisabled,
+            ServerState::SomeDisabled(_) => Self::SomeDisabled,
+            ServerState::NoneDisabled => Self::NoneDisabled,
+            ServerState::Unknown => Self::Unknown,
+        }
+    }
+}
+
+/// Kind of event reported through [`SsdEventCallback`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SsdEventKind {
+    /// All IPs of the region were blocked.
+    RegionDisabled,
+    /// All IPs of the region were unblocked.
+    RegionEnabled,
+}
+
+/// Region information passed to [`SsdRegionCallback`].
+#[repr(C)]
+pub struct SsdRegion {
+    /// Abreviation of the region, eg: `sgp`.
+    pub abr: *const c_char,
+    /// Description of the region, `NULL` if not available.
+    pub desc: *const c_char,
+    /// Number of IPv4 relays of the region.
+    pub num_ipv4s: usize,
+    /// Number of IPv6 relays of the region.
+    pub num_ipv6s: usize,
+    /// If `latitude` and `longitude` are valid.
+    pub has_geo: bool,
+    pub latitude: f32,
+    pub longitude: f32,
+}
+
+/// Callback for [`ssd_list_regions()`].
+pub type SsdRegionCallback = extern "C" fn(region: *const SsdRegion, user_data: *mut c_void);
+
+/// Callback for the events, see [`ssd_set_event_callback()`].
+pub type SsdEventCallback =
+    extern "C" fn(kind: SsdEventKind, abr: *const c_char, user_data: *mut c_void);
+
+/// Opaque context, created with [`ssd_context_new()`] and freed with
+/// [`ssd_context_free()`].
+pub struct SsdContext {
+    servers: Servers,
+    firewall: Firewall,
+    event_callback: Option<(SsdEventCallback, *mut c_void)>,
+}
+
+impl SsdContext {
+    /// Call the event callback (if any) for the given server.
+    fn send_event(&self, kind: SsdEventKind, server: &ServerInfo) {
+        if let Some((callback, user_data)) = self.event_callback {
+            let abr = CString::new(server.get_abr()).unwrap_or_default();
+            callback(kind, abr.as_ptr(), user_data);
+        }
+    }
+}
+
+/// Convert the C string to `&str`, sets the last error message on
+/// failure.
+///
+/// # Safety
+///
+/// `string` must be `NULL` or a valid nul terminated string.
+unsafe fn str_from_c<'a>(string: *const c_char) -> Option<&'a str> {
+    if string.is_null() {
+        set_last_error_message("unexpected NULL string");
+        return None;
+    }
+    CStr::from_ptr(string)
+        .to_str()
+        .map_err(set_last_error_message)
+        .ok()
+}
+
+/// Get the last error message of the current thread, `NULL` if there
+/// was no error. The string is valid until the next failing call on
+/// the same thread.
+#[no_mangle]
+pub extern "C" fn ssd_last_error_message() -> *const c_char {
+    catch_panic(ptr::null(), || {
+        LAST_ERROR_MESSAGE.with(|last_error_message| {
+            last_error_message
+                .borrow()
+                .as_ref()
+                .map_or(ptr::null(), |message| message.as_ptr())
+        })
+    })
+}
+
+/// Create a new context. `network_datagram_config_path` may be `NULL`
+/// to use the (downloaded if necessary) default config file.
+///
+/// Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `network_datagram_config_path` must be `NULL` or a valid nul
+/// terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ssd_context_new(
+    network_datagram_config_path: *const c_char,
+) -> *mut SsdContext {
+    catch_panic(ptr::null_mut(), || {
+        let network_datagram_config_path = if network_datagram_config_path.is_null() {
+            None
+        } else {
+            match str_from_c(network_datagram_config_path) {
+                Some(path) => Some(PathBuf::from(path)),
+                None => return ptr::null_mut(),
+            }
+        };
+
+        let context = Servers::new(network_datagram_config_path).and_then(|servers| {
+            let firewall = Firewall::from_config()?;
+            firewall.set_port_ranges(servers.port_ranges())?;
+            Ok(SsdContext {
+                servers,
+                firewall,
+                event_callback: None,
+            })
+        });
+
+        match context {
+            Ok(context) => Box::into_raw(Box::new(context)),
+            Err(err) => {
+                set_last_error_message(err);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Free the context.
+///
+/// # Safety
+///
+/// `context` must be `NULL` or created by [`ssd_context_new()`] and
+/// not freed already.
+#[no_mangle]
+pub unsafe extern "C" fn ssd_context_free(context: *mut SsdContext) {
+    catch_panic((), || {
+        if !context.is_null() {
+            drop(Box::from_raw(context));
+        }
+    })
+}
+
+/// Call `callback` for every region. Returns the number of regions.
+///
+/// # Safety
+///
+/// `context` must be a valid context.
+#[no_mangle]
+pub unsafe extern "C" fn ssd_list_regions(
+    context: *const SsdContext,
+    callback: SsdRegionCallback,
+    user_data: *mut c_void,
+) -> usize {
+    catch_panic(0, || {
+        let context = &*context;
+        context.servers.get_servers().iter().for_each(|server| {
+            let abr = CString::new(server.get_abr()).unwrap_or_default();
+            let desc = server.desc().and_then(|desc| CString::new(desc).ok());
+            let geo = server.geo();
+            let region = SsdRegion {
+                abr: abr.as_ptr(),
+                desc: desc.as_ref().map_or(ptr::null(), |desc| desc.as_ptr()),
+                num_ipv4s: server.get_ips().len() - server.num_ipv6s(),
+                num_ipv6s: server.num_ipv6s(),
+                has_geo: geo.is_some(),
+                latitude: geo.map_or(0.0, |geo| geo[1]),
+                longitude: geo.map_or(0.0, |geo| geo[0]),
+            };
+            callback(&region, user_data);
+        });
+        context.servers.get_servers().len()
+    })
+}
+
+/// Block all the IPs of the region.
+///
+/// # Safety
+///
+/// `context` must be a valid context and `abr` a valid nul terminated
+/// string.
+#[no_mangle]
+pub unsafe extern "C" fn ssd_disable_region(context: *mut SsdContext, abr: *const c_char) -> c_int {
+    catch_panic(-1, || {
+        let context = &*context;
+        let Some(server) = str_from_c(abr).and_then(|abr| region_from_abr(context, abr)) else {
+            return -1;
+        };
+        match server.ban(&context.firewall) {
+            Ok(()) => {
+                context.send_event(SsdEventKind::RegionDisabled, server);
+                0
+            }
+            Err(err) => {
+                set_last_error_message(err);
+                -1
+            }
+        }
+    })
+}
+
+/// Unblock all the IPs of the region.
+///
+/// # Safety
+///
+/// `context` must be a valid context and `abr` a valid nul terminated
+/// string.
+#[no_mangle]
+pub unsafe extern "C" fn ssd_enable_region(context: *mut SsdContext, abr: *const c_char) -> c_int {
+    catch_panic(-1, || {
+        let context = &*context;
+        let Some(server) = str_from_c(abr).and_then(|abr| region_from_abr(context, abr)) else {
+            return -1;
+        };
+        match server.unban(&context.firewall) {
+            Ok(()) => {
+                context.send_event(SsdEventKind::RegionEnabled, server);
+                0
+            }
+            Err(err) => {
+                set_last_error_message(err);
+                -1
+            }
+        }
+    })
+}
+
+/// Get the status of the region.
+///
+/// # Safety
+///
+/// `context` must be a valid context and `abr` a valid nul terminated
+/// string.
+#[no_mangle]
+pub unsafe extern "C" fn ssd_get_status(
+    context: *const SsdContext,
+    abr: *const c_char,
+) -> SsdRegionStatus {
+    catch_panic(SsdRegionStatus::Unknown, || {
+        let context = &*context;
+        str_from_c(abr)
+            .and_then(|abr| region_from_abr(context, abr))
+            .map_or(SsdRegionStatus::Unknown, |server| {
+                (&server.query_state(&context.firewall)).into()
+            })
+    })
+}
+
+/// Set the callback that is called after a region is enabled or
+/// disabled through the context. Pass `NULL` to remove the callback.
+///
+/// # Safety
+///
+/// `context` must be a valid context, `user_data` must remain valid
+/// as long as the callback is set.
+#[no_mangle]
+pub unsafe extern "C" fn ssd_set_event_callback(
+    context: *mut SsdContext,
+    callback: Option<SsdEventCallback>,
+    user_data: *mut c_void,
+) {
+    catch_panic((), || {
+        let context = &mut *context;
+        context.event_callback = callback.map(|callback| (callback, user_data));
+    })
+}
+
+/// Get the region with the given abreviation, sets the last error
+/// message if it does not exist.
+fn region_from_abr<'a>(context: &'a SsdContext, abr: &str) -> Option<&'a ServerInfo> {
+    let server = context.servers.get_server(abr);
+    if server.is_none() {
+        set_last_error_message(format!("no region named `{}`", abr));
+    }
+    server
+}
@@ -1,35 +1,381 @@
 use std::{
     fs,
+    io::{self, Write},
     path::{Path, PathBuf},
 };
 
 use directories::ProjectDirs;
 use lazy_static::lazy_static;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Error;
 
 lazy_static! {
-    static ref PROJECT_DIRS: ProjectDirs = {
-        let project_dirs = ProjectDirs::from("", "", "steam_server_disable").unwrap();
+    /// [`None`] when no home directory can be found for the user,
+    /// nothing can be stored without it.
+    static ref PROJECT_DIRS: Option<ProjectDirs> = {
+        let project_dirs = ProjectDirs::from("", "", "steam_server_disable")?;
 
-        // Create directories that are required
-        log::info!("project data dir: {}", project_dirs.data_dir().to_str().unwrap());
-        fs::create_dir_all(project_dirs.data_dir()).unwrap();
+        // Create directories that are required, failures surface as
+        // IO errors when the files are accessed
+        [
+            ("config", project_dirs.config_dir()),
+            ("cache", project_dirs.cache_dir()),
+            ("data", project_dirs.data_dir()),
+        ]
+        .into_iter()
+        .for_each(|(name, dir)| {
+            log::info!("project {} dir: {}", name, dir.display());
+            if let Err(err) = fs::create_dir_all(dir) {
+                log::error!("unable to create `{}`: {}", dir.display(), err);
+            }
+        });
 
-        project_dirs
+        Some(project_dirs)
     };
-    static ref NETWORK_DATAGRAM_CONFIG_FILE_PATH: PathBuf = {
-        let mut file_path = get_project_dirs().data_dir().to_path_buf();
+    static ref NETWORK_DATAGRAM_CONFIG_FILE_PATH: Option<PathBuf> = {
+        let mut file_path = get_cache_dir().ok()?.to_path_buf();
         file_path.push("network_datagram_config.json");
 
-        log::info!("network datagram config file: {}", file_path.to_str().unwrap());
+        migrate_from_data_dir(&file_path);
+
+        log::info!("network datagram config file: {}", file_path.display());
 
-        file_path
+        Some(file_path)
     };
 }
 
-pub fn get_project_dirs() -> &'static ProjectDirs {
-    &PROJECT_DIRS
+/// Get the project directories, fails with
+/// [`Error::NoHomeDirectory`] if there is no home directory.
+pub fn get_project_dirs() -> Result<&'static ProjectDirs, Error> {
+    PROJECT_DIRS.as_ref().ok_or(Error::NoHomeDirectory)
+}
+
+/// Get the directory for the settings (config files).
+pub fn get_config_dir() -> Result<&'static Path, Error> {
+    Ok(get_project_dirs()?.config_dir())
+}
+
+/// Get the directory for the files that can be regenerated (eg:
+/// downloaded server lists).
+pub fn get_cache_dir() -> Result<&'static Path, Error> {
+    Ok(get_project_dirs()?.cache_dir())
+}
+
+/// Get the directory for the state and journals.
+pub fn get_data_dir() -> Result<&'static Path, Error> {
+    Ok(get_project_dirs()?.data_dir())
+}
+
+pub fn get_network_datagram_config_file_path() -> Result<&'static Path, Error> {
+    NETWORK_DATAGRAM_CONFIG_FILE_PATH
+        .as_deref()
+        .ok_or(Error::NoHomeDirectory)
+}
+
+/// Get the path of the network datagram config of the given Steam
+/// app, the config of [`crate::steam_server::DEFAULT_APP_ID`] is at
+/// [`get_network_datagram_config_file_path()`].
+pub fn get_network_datagram_config_file_path_for_app(app_id: u32) -> Result<PathBuf, Error> {
+    if app_id == crate::steam_server::DEFAULT_APP_ID {
+        return Ok(get_network_datagram_config_file_path()?.to_path_buf());
+    }
+    Ok(get_cache_dir()?.join(format!("network_datagram_config_{}.json", app_id)))
+}
+
+/// Get the path of the firewall config file, see
+/// [`crate::firewall::FirewallOptions`].
+pub fn get_firewall_config_file_path() -> Result<PathBuf, Error> {
+    Ok(get_config_dir()?.join("firewall.json"))
+}
+
+/// Get the path of the settings of the app, see [`crate::config`].
+pub fn get_config_file_path() -> Result<PathBuf, Error> {
+    Ok(get_config_dir()?.join("config.toml"))
+}
+
+/// Get the path of the profiles file, see [`crate::profiles`].
+pub fn get_profiles_file_path() -> Result<PathBuf, Error> {
+    Ok(get_data_dir()?.join("profiles.json"))
+}
+
+/// Get the path of the sort and filter preferences of the views, see
+/// [`crate::view_state`].
+pub fn get_view_state_file_path() -> Result<PathBuf, Error> {
+    Ok(get_data_dir()?.join("view_state.json"))
+}
+
+/// Get the path of the rules blocking the regions automatically, see
+/// [`crate::rules`].
+pub fn get_rules_file_path() -> Result<PathBuf, Error> {
+    Ok(get_config_dir()?.join("rules.json"))
+}
+
+/// Get the path of the hooks run on the events, see
+/// [`crate::hooks`].
+pub fn get_hooks_file_path() -> Result<PathBuf, Error> {
+    Ok(get_config_dir()?.join("hooks.json"))
+}
+
+/// Get the path of the block/unblock history, see
+/// [`crate::history`].
+pub fn get_history_file_path() -> Result<PathBuf, Error> {
+    Ok(get_data_dir()?.join("history.jsonl"))
+}
+
+/// Get the directory the reports are written to, see
+/// [`crate::report`].
+pub fn get_reports_dir() -> Result<PathBuf, Error> {
+    Ok(get_data_dir()?.join("reports"))
+}
+
+/// Get the directory the crash reports are written to, see
+/// [`crate::crash`].
+pub fn get_crash_reports_dir() -> Result<PathBuf, Error> {
+    Ok(get_data_dir()?.join("crash_reports"))
+}
+
+/// Get the path of the downloaded Steam connection manager (CM)
+/// server list.
+pub fn get_cm_list_file_path() -> Result<PathBuf, Error> {
+    Ok(get_cache_dir()?.join("cm_list.json"))
+}
+
+/// Get the path of the downloaded Steam content (CDN) server list.
+pub fn get_cdn_list_file_path() -> Result<PathBuf, Error> {
+    Ok(get_cache_dir()?.join("cdn_list.json"))
+}
+
+/// Get the directory the map tiles are cached in, see
+/// [`crate::map_tiles`].
+#[cfg(feature = "gui")]
+pub fn get_map_tiles_cache_dir() -> Result<PathBuf, Error> {
+    Ok(get_cache_dir()?.join("map_tiles"))
 }
 
-pub fn get_network_datagram_config_file_path() -> &'static Path {
-    &NETWORK_DATAGRAM_CONFIG_FILE_PATH
+/// Get the path of the backup of the given file, `<file_path>.bak`.
+pub fn get_backup_file_path(file_path: impl AsRef<Path>) -> PathBuf {
+    append_to_file_name(file_path.as_ref(), ".bak")
+}
+
+/// Append the given string to the file name of the given path.
+fn append_to_file_name(file_path: &Path, append: &str) -> PathBuf {
+    let mut file_name = file_path
+        .file_name()
+        .map(|file_name| file_name.to_os_string())
+        .unwrap_or_default();
+    file_name.push(append);
+    file_path.with_file_name(file_name)
+}
+
+/// Atomically write `contents` to `file_path`.
+///
+/// The contents are written to a temporary file which is then renamed
+/// to `file_path`, so a crash mid-write never leaves a partially
+/// written file behind. The previous contents of `file_path` (if any)
+/// are kept at [`get_backup_file_path()`].
+pub fn write_atomic(file_path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let file_path = file_path.as_ref();
+    let temp_file_path = append_to_file_name(file_path, ".tmp");
+
+    {
+        let mut file = fs::File::create(&temp_file_path)?;
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+    }
+
+    if file_path.exists() {
+        fs::copy(file_path, get_backup_file_path(file_path))?;
+    }
+
+    fs::rename(&temp_file_path, file_path)
+}
+
+/// Name of the field that stores the schema version of versioned
+/// files.
+pub const SCHEMA_VERSION_FIELD: &str = "schema_version";
+
+/// Migrate the contents of a versioned file from version `n` to
+/// version `n + 1`.
+pub type Migration = fn(serde_json::Value) -> Result<serde_json::Value, String>;
+
+#[derive(Debug)]
+pub enum VersionedFileError {
+    IO(io::Error),
+    Json(serde_json::Error),
+    /// The top level of the file is not an object, so there is no
+    /// place for the schema version.
+    NotAnObject,
+    /// The file was written by a newer version of the application.
+    UnknownFutureVersion {
+        version: u64,
+        latest_version: u64,
+    },
+    /// Migration from `from_version` to `from_version + 1` failed.
+    Migration {
+        from_version: u64,
+        error: String,
+    },
+}
+
+impl From<io::Error> for VersionedFileError {
+    fn from(error: io::Error) -> Self {
+        Self::IO(error)
+    }
+}
+
+impl From<serde_json::Error> for VersionedFileError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+impl std::fmt::Display for VersionedFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IO(error) => write!(f, "{}", error),
+            Self::Json(error) => write!(f, "{}", error),
+            Self::NotAnObject => write!(f, "top level of the file is not an object"),
+            Self::UnknownFutureVersion {
+                version,
+                latest_version,
+            } => write!(
+                f,
+                "file has schema version {} but only up to {} is supported, \
+                 it was likely written by a newer version of the application",
+                version, latest_version
+            ),
+            Self::Migration {
+                from_version,
+                error,
+            } => write!(
+                f,
+                "migration from schema version {} to {} failed: {}",
+                from_version,
+                from_version + 1,
+                error
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VersionedFileError {}
+
+/// Migrate `value` to the latest schema version, `migrations[n]`
+/// migrates from version `n` to `n + 1` so the latest version is
+/// `migrations.len()`. Values without a schema version are considered
+/// to be version 0.
+///
+/// Returns the migrated value and if any migration was applied.
+pub fn migrate(
+    mut value: serde_json::Value,
+    migrations: &[Migration],
+) -> Result<(serde_json::Value, bool), VersionedFileError> {
+    let latest_version = migrations.len() as u64;
+    let version = value
+        .as_object()
+        .ok_or(VersionedFileError::NotAnObject)?
+        .get(SCHEMA_VERSION_FIELD)
+        .and_then(|version| version.as_u64())
+        .unwrap_or(0);
+
+    if version > latest_version {
+        return Err(VersionedFileError::UnknownFutureVersion {
+            version,
+            latest_version,
+        });
+    }
+
+    for (from_version, migration) in migrations.iter().enumerate().skip(version as usize) {
+        value = migration(value).map_err(|error| VersionedFileError::Migration {
+            from_version: from_version as u64,
+            error,
+        })?;
+        log::info!(
+            "migrated from schema version {} to {}",
+            from_version,
+            from_version + 1
+        );
+    }
+
+    value
+        .as_object_mut()
+        .ok_or(VersionedFileError::NotAnObject)?
+        .insert(SCHEMA_VERSION_FIELD.to_string(), latest_version.into());
+
+    Ok((value, version != latest_version))
+}
+
+/// Load the versioned JSON file at `file_path`, see [`migrate()`].
+///
+/// If the file was migrated, it is written back (the original is kept
+/// as the backup, see [`write_atomic()`]).
+pub fn load_versioned<T: DeserializeOwned>(
+    file_path: impl AsRef<Path>,
+    migrations: &[Migration],
+) -> Result<T, VersionedFileError> {
+    let file_path = file_path.as_ref();
+    let value: serde_json::Value = serde_json::from_slice(&fs::read(file_path)?)?;
+    let (value, migrated) = migrate(value, migrations)?;
+
+    if migrated {
+        log::info!("writing migrated `{}`", file_path.display());
+        write_atomic(file_path, serde_json::to_vec_pretty(&value)?)?;
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Save `data` as a versioned JSON file at `file_path` with the
+/// latest schema version, see [`migrate()`].
+pub fn save_versioned<T: Serialize>(
+    file_path: impl AsRef<Path>,
+    data: &T,
+    migrations: &[Migration],
+) -> Result<(), VersionedFileError> {
+    let mut value = serde_json::to_value(data)?;
+    value
+        .as_object_mut()
+        .ok_or(VersionedFileError::NotAnObject)?
+        .insert(
+            SCHEMA_VERSION_FIELD.to_string(),
+            (migrations.len() as u64).into(),
+        );
+    write_atomic(file_path, serde_json::to_vec_pretty(&value)?)?;
+    Ok(())
+}
+
+/// Previously all the files were stored in the data dir, move the
+/// file with the same file name from the data dir to `file_path` if
+/// `file_path` does not exist yet.
+fn migrate_from_data_dir(file_path: &Path) {
+    let old_file_path = match (file_path.file_name(), get_data_dir()) {
+        (Some(file_name), Ok(data_dir)) => data_dir.join(file_name),
+        _ => return,
+    };
+
+    if old_file_path == file_path || !old_file_path.exists() || file_path.exists() {
+        return;
+    }
+
+    // `rename()` fails across file systems, fallback to copy and
+    // remove
+    let res = fs::rename(&old_file_path, file_path).or_else(|_| {
+        fs::copy(&old_file_path, file_path)?;
+        fs::remove_file(&old_file_path)
+    });
+
+    match res {
+        Ok(()) => log::info!(
+            "migrated `{}` to `{}`",
+            old_file_path.display(),
+            file_path.display()
+        ),
+        Err(err) => log::error!(
+            "unable to migrate `{}` to `{}`: {}",
+            old_file_path.display(),
+            file_path.display(),
+            err
+        ),
+    }
 }
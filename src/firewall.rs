@@ -1,216 +1,1864 @@
-use std::{fmt::Display, net::Ipv4Addr};
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    ops::RangeInclusive,
+    sync::Mutex,
+};
 
-#[derive(Debug)]
-pub enum Error {
-    UnsuccessfulBlockCheck(Ipv4Addr),
-    UnsuccessfulBan(Ipv4Addr),
-    UnsuccessfulUnban(Ipv4Addr),
-    Custom(String),
-}
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 
-impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Error::UnsuccessfulBlockCheck(ip) => {
-                write!(f, "Unsuccessful block check for {}", ip)
-            }
-            Error::UnsuccessfulBan(ip) => {
-                write!(f, "Unsuccessful ban for {}", ip)
-            }
-            Error::UnsuccessfulUnban(ip) => {
-                write!(f, "Unsuccessful unban for {}", ip)
-            }
-            Error::Custom(string) => write!(f, "{}", string),
-        }
-    }
+use crate::{
+    file_ops::{self, Migration},
+    timings,
+};
+
+/// Migrations of the firewall config file, see
+/// [`file_ops::migrate()`].
+const MIGRATIONS: &[Migration] = &[];
+
+lazy_static! {
+    /// Backend overriding the one of the config file, see
+    /// [`FirewallOptions::override_backend()`].
+    static ref BACKEND_OVERRIDE: Mutex<Option<FirewallBackend>> = Mutex::new(None);
 }
 
-impl std::error::Error for Error {}
+/// UDP port ranges of the relay IPs, see
+/// [`crate::steam_server::Servers::port_ranges()`].
+pub type PortRanges = HashMap<IpAddr, RangeInclusive<u16>>;
 
-trait FirewallRequirements: Default {
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Unsuccessful block check for {0}")]
+    UnsuccessfulBlockCheck(IpAddr),
+    #[error("Unsuccessful ban for {0}")]
+    UnsuccessfulBan(IpAddr),
+    #[error("Unsuccessful unban for {0}")]
+    UnsuccessfulUnban(IpAddr),
+    #[error("Unsuccessful listing of the blocked ips")]
+    UnsuccessfulList,
+    #[error("Unsuccessful deprioritizing of {0}")]
+    UnsuccessfulDeprioritize(IpAddr),
+    #[error("Unsuccessful restore of {0}")]
+    UnsuccessfulRestore(IpAddr),
+    #[error("Deprioritizing requires traffic shaping with tc (Linux only)")]
+    DeprioritizeUnsupported,
+    #[error("No IPv6 firewall (ip6tables) for {0}, IPv6 may be disabled")]
+    NoIpv6Firewall(IpAddr),
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// Firewall backend, see [`Firewall::with_backend()`].
+pub trait FirewallRequirements: Send + Sync {
     /// Checks if ip exists in the firewall and thus is blocked
-    fn is_blocked(&self, ip: Ipv4Addr) -> Result<bool, Error>;
+    fn is_blocked(&self, ip: IpAddr) -> Result<bool, Error>;
 
     /// Ban the ip by adding it to the firewall
-    fn ban_ip(&self, ip: Ipv4Addr) -> Result<(), Error>;
+    fn ban_ip(&self, ip: IpAddr) -> Result<(), Error>;
 
     /// Unban the ip by removing it from the firewall if it was
     /// blocked previously
-    fn unban_ip(&self, ip: Ipv4Addr) -> Result<(), Error>;
+    fn unban_ip(&self, ip: IpAddr) -> Result<(), Error>;
+
+    /// List all the ips blocked in the firewall, with a single query
+    /// of the firewall
+    fn list_blocked(&self) -> Result<HashSet<IpAddr>, Error>;
+
+    /// Ban all the ips, stops at the first error. Backends with a
+    /// costly setup per call should do it once for all the ips.
+    fn ban_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+        ips.iter().try_for_each(|ip| self.ban_ip(*ip))
+    }
+
+    /// Unban all the ips, stops at the first error, see
+    /// [`Self::ban_ips()`].
+    fn unban_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+        ips.iter().try_for_each(|ip| self.unban_ip(*ip))
+    }
+
+    /// Set up the rules of the app again if they were removed outside
+    /// of the app (eg: `iptables -F`, `nft flush ruleset`, a firewalld
+    /// reload), returns `true` if they were: the ips blocked before
+    /// must be banned again. Unbanning all the ips keeps the setup, so
+    /// it is not mistaken for a removal. Never reports a removal by
+    /// default.
+    fn restore_rules(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    /// Set the UDP port ranges of the relay IPs, replacing the
+    /// previous ones. The rules of the IPs banned afterwards are
+    /// restricted to them with [`FirewallOptions::relay_ports_only`].
+    /// Ignored by default.
+    fn set_port_ranges(&self, _port_ranges: PortRanges) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
-pub struct Firewall {
+/// Soft block: the traffic of the deprioritized IPs is delayed (and
+/// optionally dropped in part) instead of blocked, so that matchmaking
+/// avoids them but can still connect to them as the last resort, see
+/// [`Firewall::with_shaper()`].
+pub trait TrafficShaper: Send + Sync {
+    /// Deprioritize all the ips, the deprioritized ips are kept as is.
+    fn deprioritize_ips(&self, ips: &[IpAddr]) -> Result<(), Error>;
+
+    /// Restore the traffic of all the ips, the ips not deprioritized
+    /// are ignored.
+    fn restore_ips(&self, ips: &[IpAddr]) -> Result<(), Error>;
+
+    /// List all the deprioritized ips.
+    fn list_deprioritized(&self) -> Result<HashSet<IpAddr>, Error>;
+}
+
+/// System firewall used on Linux, ignored on Windows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallBackend {
+    /// The backend already holding the blocks of an earlier run,
+    /// otherwise iptables if it is installed, otherwise nftables.
+    #[default]
+    Auto,
+    Iptables,
+    /// Through the `nft` command, for the distros that have deprecated
+    /// iptables.
+    Nftables,
+    /// iptables rules matching an ipset of the blocked IPs, blocking
+    /// many IPs is a single `ipset` call.
+    Ipset,
+}
+
+impl FirewallBackend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FirewallBackend::Auto => "Auto",
+            FirewallBackend::Iptables => "iptables",
+            FirewallBackend::Nftables => "nftables",
+            FirewallBackend::Ipset => "ipset",
+        }
+    }
+
+    /// Resolve [`FirewallBackend::Auto`] to the backend to use, the
+    /// nftables table and the ipset of the options are where the
+    /// blocks of an earlier run are.
     #[cfg(unix)]
-    unix_firewall: unix::Firewall,
-    #[cfg(windows)]
-    windows_firewall: windows::Firewall,
+    fn resolve(self, options: &FirewallOptions) -> Self {
+        if self != FirewallBackend::Auto {
+            return self;
+        }
+        let succeeds = |program: &str, args: &[&str]| {
+            std::process::Command::new(program)
+                .args(args)
+                .output()
+                .is_ok_and(|output| output.status.success())
+        };
+        let backend = if succeeds("nft", &["list", "table", "inet", &options.nft_table]) {
+            FirewallBackend::Nftables
+        } else if succeeds("ipset", &["list", "-name", &options.ipset_name]) {
+            FirewallBackend::Ipset
+        } else if succeeds("iptables", &["--version"]) {
+            FirewallBackend::Iptables
+        } else if succeeds("nft", &["--version"]) {
+            FirewallBackend::Nftables
+        } else {
+            // reports that iptables is missing
+            FirewallBackend::Iptables
+        };
+        log::info!("detected the {} firewall backend", backend);
+        backend
+    }
 }
 
-impl Default for Firewall {
-    fn default() -> Self {
-        Self::new()
+impl std::fmt::Display for FirewallBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
     }
 }
 
-impl Firewall {
-    pub fn new() -> Self {
-        Self {
-            #[cfg(unix)]
-            unix_firewall: unix::Firewall::default(),
-            #[cfg(windows)]
-            windows_firewall: windows::Firewall::default(),
+/// Traffic blocked by the firewall rules.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RuleScope {
+    /// The traffic of all the apps and users of the machine.
+    #[default]
+    System,
+    /// Only the traffic of the processes of the user, eg: the user
+    /// running Steam.
+    Uid { uid: u32 },
+    /// Only the traffic of the processes of the cgroup (v2), eg: the
+    /// cgroup of Steam from `/proc/<pid of steam>/cgroup`.
+    Cgroup { path: String },
+}
+
+impl RuleScope {
+    /// Get the iptables match of the scope, empty for
+    /// [`RuleScope::System`].
+    pub fn iptables_match(&self) -> String {
+        match self {
+            RuleScope::System => String::new(),
+            RuleScope::Uid { uid } => format!("-m owner --uid-owner {}", uid),
+            RuleScope::Cgroup { path } => format!("-m cgroup --path {}", path),
         }
     }
+}
 
-    pub fn is_blocked(&self, ip: Ipv4Addr) -> Result<bool, Error> {
-        #[cfg(unix)]
-        {
-            self.unix_firewall.is_blocked(ip)
+/// Backend specific options of the system firewall, so that the
+/// rules can coexist with a bespoke firewall setup. Stored at
+/// [`file_ops::get_firewall_config_file_path()`], the missing fields
+/// are the defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FirewallOptions {
+    /// System firewall of the rules (Linux only).
+    pub backend: FirewallBackend,
+    /// iptables table of the rules.
+    pub iptables_table: String,
+    /// iptables chain of the rules, a custom chain must exist
+    /// already (and be jumped to).
+    pub iptables_chain: String,
+    /// If not empty, the iptables rules are tagged with the comment
+    /// `<prefix><ip>` and only the tagged rules are considered ours.
+    /// Must not contain whitespace.
+    pub rule_comment_prefix: String,
+    /// Prefix of the names of the Windows firewall rules, the name is
+    /// `<prefix><ip>`.
+    pub windows_rule_name_prefix: String,
+    /// nftables table (of the `inet` family) of the rules, it is
+    /// created and managed by the app.
+    pub nft_table: String,
+    /// Name of the ipset of the blocked IPv4s, the IPv6s are in
+    /// `<ipset_name>6`. The sets are created and managed by the app.
+    pub ipset_name: String,
+    /// Only drop the UDP traffic of the port range of the relays (when
+    /// known) instead of all the traffic of their IPs, so the other
+    /// services of the IPs are not affected (iptables and Windows
+    /// only). Applies to the IPs blocked afterwards.
+    pub relay_ports_only: bool,
+    /// Traffic blocked by the rules (Linux only). The scoped rules
+    /// match the outgoing packets, so with iptables `iptables_chain`
+    /// must be `OUTPUT` (or a chain jumped to from it).
+    pub scope: RuleScope,
+    /// Network interface whose outgoing traffic to the deprioritized
+    /// IPs is delayed (Linux only), the interface of the default
+    /// route if empty. Its root qdisc is replaced while IPs are
+    /// deprioritized.
+    pub shaping_interface: String,
+    /// Delay added to the packets sent to the deprioritized IPs.
+    pub deprioritize_delay_ms: u32,
+    /// Percentage of the packets sent to the deprioritized IPs that
+    /// are dropped.
+    pub deprioritize_loss_percent: u32,
+}
+
+impl FirewallOptions {
+    /// Load the options, the defaults if the file does not exist.
+    pub fn load() -> Result<Self, Error> {
+        let file_path = file_ops::get_firewall_config_file_path()
+            .map_err(|err| Error::Custom(err.to_string()))?;
+        let mut options = if file_path.exists() {
+            file_ops::load_versioned(&file_path, MIGRATIONS)
+                .map_err(|err| Error::Custom(format!("`{}`: {}", file_path.display(), err)))?
+        } else {
+            Self::default()
+        };
+        if let Some(backend) = *BACKEND_OVERRIDE.lock().unwrap() {
+            options.backend = backend;
         }
-        #[cfg(windows)]
-        {
-            self.windows_firewall.is_blocked(ip)
+        Ok(options)
+    }
+
+    /// Use the backend instead of the one of the config file for the
+    /// rest of the process, eg: `--firewall-backend`.
+    pub fn override_backend(backend: FirewallBackend) {
+        *BACKEND_OVERRIDE.lock().unwrap() = Some(backend);
+    }
+}
+
+impl Default for FirewallOptions {
+    fn default() -> Self {
+        Self {
+            backend: FirewallBackend::Auto,
+            iptables_table: "filter".to_string(),
+            iptables_chain: "INPUT".to_string(),
+            rule_comment_prefix: String::new(),
+            windows_rule_name_prefix: "IP_BLOCK_".to_string(),
+            nft_table: "steam_server_disable".to_string(),
+            ipset_name: "steam_server_disable".to_string(),
+            relay_ports_only: false,
+            scope: RuleScope::System,
+            shaping_interface: String::new(),
+            deprioritize_delay_ms: 100,
+            deprioritize_loss_percent: 0,
         }
     }
+}
 
-    pub fn ban_ip(&self, ip: Ipv4Addr) -> Result<(), Error> {
+pub struct Firewall {
+    backend: Box<dyn FirewallRequirements>,
+    /// Deprioritizes the IPs, [`None`] if not supported.
+    shaper: Option<Box<dyn TrafficShaper>>,
+}
+
+impl Firewall {
+    /// Create the [`Firewall`] with the system firewall as the
+    /// backend, configured with the given options.
+    pub fn new(options: &FirewallOptions) -> Result<Self, Error> {
         #[cfg(unix)]
         {
-            self.unix_firewall.ban_ip(ip)
+            let backend = options.backend.resolve(options);
+            if options.relay_ports_only
+                && matches!(backend, FirewallBackend::Nftables | FirewallBackend::Ipset)
+            {
+                log::warn!(
+                    "the {} backend does not restrict the rules to the relay ports, \
+                     all the traffic of the IPs is blocked",
+                    backend
+                );
+            }
+            let firewall = match backend {
+                FirewallBackend::Nftables => Self::with_backend(nftables::Firewall::new(options)?),
+                FirewallBackend::Ipset => Self::with_backend(ipset::Firewall::new(options)?),
+                FirewallBackend::Auto | FirewallBackend::Iptables => {
+                    Self::with_backend(unix::Firewall::new(options)?)
+                }
+            };
+            #[cfg(target_os = "linux")]
+            {
+                // blocking still works without traffic shaping
+                match tc::Shaper::new(options) {
+                    Ok(shaper) => Ok(firewall.with_shaper(shaper)),
+                    Err(err) => {
+                        log::warn!("deprioritizing is not available: {}", err);
+                        Ok(firewall)
+                    }
+                }
+            }
+            #[cfg(not(target_os = "linux"))]
+            {
+                Ok(firewall)
+            }
         }
         #[cfg(windows)]
         {
-            self.windows_firewall.ban_ip(ip)
+            Ok(Self::with_backend(windows::Firewall::new(options)))
         }
     }
 
-    pub fn unban_ip(&self, ip: Ipv4Addr) -> Result<(), Error> {
-        #[cfg(unix)]
-        {
-            self.unix_firewall.unban_ip(ip)
-        }
-        #[cfg(windows)]
-        {
-            self.windows_firewall.unban_ip(ip)
+    /// Create the [`Firewall`] with the system firewall as the
+    /// backend, configured with the options of the config file, see
+    /// [`FirewallOptions::load()`].
+    pub fn from_config() -> Result<Self, Error> {
+        Self::new(&FirewallOptions::load()?)
+    }
+
+    /// Create the [`Firewall`] with the given backend.
+    pub fn with_backend(backend: impl FirewallRequirements + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+            shaper: None,
         }
     }
+
+    /// Deprioritize the IPs with the given shaper, see
+    /// [`Self::deprioritize_ips()`].
+    pub fn with_shaper(mut self, shaper: impl TrafficShaper + 'static) -> Self {
+        self.shaper = Some(Box::new(shaper));
+        self
+    }
+
+    /// Can the IPs be deprioritized?
+    pub fn can_deprioritize(&self) -> bool {
+        self.shaper.is_some()
+    }
+
+    pub fn is_blocked(&self, ip: IpAddr) -> Result<bool, Error> {
+        let _timer = timings::timer("firewall: is blocked");
+        self.backend.is_blocked(ip)
+    }
+
+    pub fn ban_ip(&self, ip: IpAddr) -> Result<(), Error> {
+        let _timer = timings::timer("firewall: ban ip");
+        self.backend.ban_ip(ip)
+    }
+
+    pub fn unban_ip(&self, ip: IpAddr) -> Result<(), Error> {
+        let _timer = timings::timer("firewall: unban ip");
+        self.backend.unban_ip(ip)
+    }
+
+    /// Ban all the ips, prefer this over [`Self::ban_ip()`] when
+    /// banning many ips.
+    pub fn ban_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+        let _timer = timings::timer("firewall: ban ips");
+        self.backend.ban_ips(ips)
+    }
+
+    /// Unban all the ips, prefer this over [`Self::unban_ip()`] when
+    /// unbanning many ips.
+    pub fn unban_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+        let _timer = timings::timer("firewall: unban ips");
+        self.backend.unban_ips(ips)
+    }
+
+    /// Get a snapshot of all the blocked ips, prefer this over
+    /// [`Self::is_blocked()`] when checking many ips.
+    pub fn list_blocked(&self) -> Result<HashSet<IpAddr>, Error> {
+        let _timer = timings::timer("firewall: list blocked");
+        self.backend.list_blocked()
+    }
+
+    /// Set up the rules of the app again if they were removed outside
+    /// of the app, see [`FirewallRequirements::restore_rules()`].
+    pub fn restore_rules(&self) -> Result<bool, Error> {
+        let _timer = timings::timer("firewall: restore rules");
+        self.backend.restore_rules()
+    }
+
+    /// Set the UDP port ranges of the relay IPs, done whenever the
+    /// server list is (re)loaded, see
+    /// [`FirewallRequirements::set_port_ranges()`].
+    pub fn set_port_ranges(&self, port_ranges: PortRanges) -> Result<(), Error> {
+        let _timer = timings::timer("firewall: set port ranges");
+        self.backend.set_port_ranges(port_ranges)
+    }
+
+    /// Deprioritize all the ips instead of blocking them, see
+    /// [`TrafficShaper`].
+    pub fn deprioritize_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+        let _timer = timings::timer("firewall: deprioritize ips");
+        self.shaper
+            .as_ref()
+            .ok_or(Error::DeprioritizeUnsupported)?
+            .deprioritize_ips(ips)
+    }
+
+    /// Restore the traffic of all the deprioritized ips.
+    pub fn restore_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+        let _timer = timings::timer("firewall: restore ips");
+        self.shaper
+            .as_ref()
+            .ok_or(Error::DeprioritizeUnsupported)?
+            .restore_ips(ips)
+    }
+
+    /// Get a snapshot of all the deprioritized ips, none if
+    /// deprioritizing is not supported.
+    pub fn list_deprioritized(&self) -> Result<HashSet<IpAddr>, Error> {
+        let _timer = timings::timer("firewall: list deprioritized");
+        self.shaper
+            .as_ref()
+            .map_or(Ok(HashSet::new()), |shaper| shaper.list_deprioritized())
+    }
+}
+
+/// Rules of the app compared against the server list, see
+/// [`audit()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Audit {
+    /// Blocked IPs of the server list.
+    pub known: Vec<IpAddr>,
+    /// Blocked IPs not in the server list, eg: relays removed from the
+    /// network datagram config since they were blocked. Nothing
+    /// removes their rules otherwise.
+    pub orphaned: Vec<IpAddr>,
+}
+
+/// Enumerate the rules created by the app (the iptables DROP rules,
+/// the nftables or ipset elements, the `IP_BLOCK_*` Windows rules) and
+/// compare their IPs against the IPs of the server list.
+pub fn audit(firewall: &Firewall, known_ips: &HashSet<IpAddr>) -> Result<Audit, Error> {
+    let (mut known, mut orphaned): (Vec<_>, Vec<_>) = firewall
+        .list_blocked()?
+        .into_iter()
+        .partition(|ip| known_ips.contains(ip));
+    known.sort_unstable();
+    orphaned.sort_unstable();
+    Ok(Audit { known, orphaned })
+}
+
+/// In memory firewall backend, nothing is actually blocked. Useful
+/// for testing and embedding without elevated privileges.
+#[derive(Debug, Default)]
+pub struct MockFirewall {
+    blocked: Mutex<HashSet<IpAddr>>,
+}
+
+impl MockFirewall {
+    /// Create a new [`MockFirewall`] with nothing blocked.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl FirewallRequirements for MockFirewall {
+    fn is_blocked(&self, ip: IpAddr) -> Result<bool, Error> {
+        Ok(self.blocked.lock().unwrap().contains(&ip))
+    }
+
+    fn ban_ip(&self, ip: IpAddr) -> Result<(), Error> {
+        self.blocked.lock().unwrap().insert(ip);
+        Ok(())
+    }
+
+    fn unban_ip(&self, ip: IpAddr) -> Result<(), Error> {
+        self.blocked.lock().unwrap().remove(&ip);
+        Ok(())
+    }
+
+    fn list_blocked(&self) -> Result<HashSet<IpAddr>, Error> {
+        Ok(self.blocked.lock().unwrap().clone())
+    }
 }
 
 #[cfg(unix)]
 mod unix {
-    use super::{Error, FirewallRequirements};
+    use std::{collections::HashSet, net::IpAddr, sync::RwLock};
+
+    use super::{Error, FirewallOptions, FirewallRequirements, PortRanges, RuleScope};
+
+    /// Comment (after the comment prefix) of the rule marking the
+    /// chain as set up by the app, see [`Firewall::marker_rule()`].
+    const MARKER_COMMENT: &str = "steam_server_disable";
 
     pub struct Firewall {
         ipt: iptables::IPTables,
+        /// ip6tables, for the IPv6 addresses, [`None`] if it is not
+        /// available (eg: IPv6 is disabled).
+        ipt6: Option<iptables::IPTables>,
+        table: String,
+        chain: String,
+        comment_prefix: String,
+        scope: RuleScope,
+        relay_ports_only: bool,
+        /// See [`FirewallRequirements::set_port_ranges()`].
+        port_ranges: RwLock<PortRanges>,
+    }
+
+    /// Check that the scope can be matched by the rules of the
+    /// iptables chain.
+    pub(super) fn check_scope(options: &FirewallOptions) -> Result<(), Error> {
+        if options.scope != RuleScope::System {
+            if options.iptables_chain == "INPUT" {
+                return Err(Error::Custom(
+                    "the scoped rules match the outgoing packets, the iptables chain \
+                     must be `OUTPUT` (or a chain jumped to from it)"
+                        .to_string(),
+                ));
+            }
+            if let RuleScope::Cgroup { path } = &options.scope {
+                if path.is_empty() || path.contains(char::is_whitespace) {
+                    return Err(Error::Custom(format!(
+                        "cgroup path `{}` is empty or contains whitespace",
+                        path
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Initialize iptables (or ip6tables), the chain must exist.
+    pub(super) fn init(ipv6: bool, options: &FirewallOptions) -> Result<iptables::IPTables, Error> {
+        let name = if ipv6 { "ip6tables" } else { "iptables" };
+        let ipt = iptables::new(ipv6)
+            .map_err(|err| Error::Custom(format!("unable to initialize {}: {}", name, err)))?;
+        let chain_exists = ipt
+            .chain_exists(&options.iptables_table, &options.iptables_chain)
+            .map_err(|err| {
+                Error::Custom(format!(
+                    "unable to check the {} chain `{}`: {}",
+                    name, options.iptables_chain, err
+                ))
+            })?;
+        if !chain_exists {
+            return Err(Error::Custom(format!(
+                "{} chain `{}` does not exist in table `{}`",
+                name, options.iptables_chain, options.iptables_table
+            )));
+        }
+        Ok(ipt)
+    }
+
+    /// Initialize ip6tables, [`None`] with a warning if it is not
+    /// available so that the IPv4s can still be blocked.
+    pub(super) fn init_ipv6(options: &FirewallOptions) -> Option<iptables::IPTables> {
+        init(true, options)
+            .map_err(|err| log::warn!("{}, the IPv6 relays cannot be blocked", err))
+            .ok()
+    }
+
+    /// Check that the ips can be blocked, the IPv6s need ip6tables.
+    pub(super) fn check_ipv6(has_ipv6: bool, ips: &[IpAddr]) -> Result<(), Error> {
+        match ips.iter().find(|ip| ip.is_ipv6()) {
+            Some(ip) if !has_ipv6 => Err(Error::NoIpv6Firewall(*ip)),
+            _ => Ok(()),
+        }
     }
 
     impl Firewall {
-        pub fn new() -> Self {
-            Self {
-                ipt: iptables::new(false).unwrap(),
+        pub fn new(options: &FirewallOptions) -> Result<Self, Error> {
+            if options.rule_comment_prefix.contains(char::is_whitespace) {
+                return Err(Error::Custom(format!(
+                    "rule comment prefix `{}` contains whitespace",
+                    options.rule_comment_prefix
+                )));
+            }
+            check_scope(options)?;
+            let ipt = init(false, options)?;
+            let ipt6 = init_ipv6(options);
+            let firewall = Self {
+                ipt,
+                ipt6,
+                table: options.iptables_table.clone(),
+                chain: options.iptables_chain.clone(),
+                comment_prefix: options.rule_comment_prefix.clone(),
+                scope: options.scope.clone(),
+                relay_ports_only: options.relay_ports_only,
+                port_ranges: RwLock::default(),
+            };
+            firewall.restore_rules()?;
+            Ok(firewall)
+        }
+
+        /// Get the rule marking the chain as set up by the app, it
+        /// matches every packet without a target so it does nothing.
+        /// A flush of the chain removes it, unlike unbanning all the
+        /// ips.
+        fn marker_rule(&self) -> String {
+            format!(
+                "-m comment --comment {}{}",
+                self.comment_prefix, MARKER_COMMENT
+            )
+        }
+
+        /// Get the iptables and ip6tables (if available).
+        fn ipts(&self) -> impl Iterator<Item = &iptables::IPTables> {
+            std::iter::once(&self.ipt).chain(&self.ipt6)
+        }
+
+        /// Get the rule that blocks the ip.
+        fn rule(&self, ip: IpAddr) -> String {
+            let mut rule = format!("{} {}", address_flag(&self.scope), ip);
+            let port_range = self.port_ranges.read().unwrap().get(&ip).cloned();
+            if let Some(port_range) = port_range.filter(|_| self.relay_ports_only) {
+                // the port of the relay is the source port of the
+                // incoming packets
+                let port_flag = match self.scope {
+                    RuleScope::System => "--sport",
+                    RuleScope::Uid { .. } | RuleScope::Cgroup { .. } => "--dport",
+                };
+                rule = format!(
+                    "{} -p udp {} {}:{}",
+                    rule,
+                    port_flag,
+                    port_range.start(),
+                    port_range.end()
+                );
+            }
+            if self.scope != RuleScope::System {
+                rule = format!("{} {}", rule, self.scope.iptables_match());
+            }
+            if !self.comment_prefix.is_empty() {
+                rule = format!(
+                    "{} -m comment --comment {}{}",
+                    rule, self.comment_prefix, ip
+                );
+            }
+            format!("{} -j DROP", rule)
+        }
+
+        /// Get the iptables of the IP version of the ip.
+        fn ipt(&self, ip: IpAddr) -> Result<&iptables::IPTables, Error> {
+            match ip {
+                IpAddr::V4(_) => Ok(&self.ipt),
+                IpAddr::V6(_) => self.ipt6.as_ref().ok_or(Error::NoIpv6Firewall(ip)),
+            }
+        }
+
+        /// List the rules of the chain that block an ip, along with
+        /// the rule without the `-A <chain>`, see
+        /// [`parse_listed_rule()`].
+        fn listed_rules(&self) -> Result<Vec<(IpAddr, String)>, Error> {
+            let mut rules = Vec::new();
+            for ipt in self.ipts() {
+                rules.extend(
+                    ipt.list(&self.table, &self.chain)
+                        .map_err(|_| Error::UnsuccessfulList)?,
+                );
+            }
+            Ok(rules
+                .iter()
+                .filter_map(|rule| parse_listed_rule(rule, &self.scope, &self.comment_prefix))
+                .collect())
+        }
+    }
+
+    /// Get the flag of the address of the relay in the rules, the
+    /// scoped rules match the outgoing packets.
+    fn address_flag(scope: &RuleScope) -> &'static str {
+        match scope {
+            RuleScope::System => "-s",
+            RuleScope::Uid { .. } | RuleScope::Cgroup { .. } => "-d",
+        }
+    }
+
+    /// Check that the listed rule has the match of the scope, eg:
+    /// `--uid-owner 1000`.
+    fn is_in_scope(scope: &RuleScope, rule: &str) -> bool {
+        let (option, value) = match scope {
+            RuleScope::System => return true,
+            RuleScope::Uid { uid } => ("--uid-owner", uid.to_string()),
+            RuleScope::Cgroup { path } => ("--path", path.clone()),
+        };
+        rule.split_whitespace()
+            .skip_while(|part| *part != option)
+            .nth(1)
+            .is_some_and(|part| part.trim_matches('"') == value)
+    }
+
+    /// Get the ip blocked by the listed rule of the app, along with
+    /// the rule without the `-A <chain>`, [`None`] for the other
+    /// rules.
+    fn parse_listed_rule(
+        rule: &str,
+        scope: &RuleScope,
+        comment_prefix: &str,
+    ) -> Option<(IpAddr, String)> {
+        // rules are listed as `-A INPUT -s 1.2.3.4/32 -j DROP`
+        // (`/128` by ip6tables, with `-p udp -m udp --sport
+        // 27015:27050` if restricted to the relay ports, `-m comment
+        // --comment <prefix>1.2.3.4` if tagged, `-d` and the match of
+        // the scope if scoped)
+        if !is_in_scope(scope, rule) {
+            return None;
+        }
+        let address_flag = address_flag(scope);
+        let mut parts = rule.split_whitespace();
+        let address = parts
+            .by_ref()
+            .skip_while(|part| *part != address_flag)
+            .nth(1)?;
+        let ip: IpAddr = address.split('/').next()?.parse().ok()?;
+        let comment = format!("{}{}", comment_prefix, ip);
+        let is_ours = comment_prefix.is_empty()
+            || rule
+                .split_whitespace()
+                .any(|part| part.trim_matches('"') == comment);
+        let is_drop = parts
+            .skip_while(|part| *part != "-j")
+            .nth(1)
+            .is_some_and(|target| target == "DROP");
+        let (_, rule) = rule.strip_prefix("-A ")?.split_once(' ')?;
+        (is_ours && is_drop).then(|| (ip, rule.to_string()))
+    }
+
+    impl FirewallRequirements for Firewall {
+        fn is_blocked(&self, ip: IpAddr) -> Result<bool, Error> {
+            Ok(self
+                .listed_rules()
+                .map_err(|_| Error::UnsuccessfulBlockCheck(ip))?
+                .iter()
+                .any(|(listed_ip, _)| *listed_ip == ip))
+        }
+
+        fn ban_ip(&self, ip: IpAddr) -> Result<(), Error> {
+            self.ban_ips(&[ip])
+        }
+
+        fn unban_ip(&self, ip: IpAddr) -> Result<(), Error> {
+            self.unban_ips(&[ip])
+        }
+
+        fn list_blocked(&self) -> Result<HashSet<IpAddr>, Error> {
+            Ok(self.listed_rules()?.into_iter().map(|(ip, _)| ip).collect())
+        }
+
+        fn ban_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+            // the ips with a rule are kept as is, even if the rule is
+            // of other ports, the IPv4s are blocked even without
+            // ip6tables
+            let blocked = self.list_blocked()?;
+            ips.iter()
+                .filter(|ip| !blocked.contains(ip))
+                .filter_map(|ip| Some((*ip, self.ipt(*ip).ok()?)))
+                .try_for_each(|(ip, ipt)| {
+                    ipt.append(&self.table, &self.chain, &self.rule(ip))
+                        .map_err(|_| Error::UnsuccessfulBan(ip))
+                })?;
+            check_ipv6(self.ipt6.is_some(), ips)
+        }
+
+        fn unban_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+            // the listed rules are deleted, so the rules of other
+            // ports (or from before `relay_ports_only` changed) are
+            // deleted too
+            let unbanned: HashSet<_> = ips.iter().copied().collect();
+            self.listed_rules()?
+                .into_iter()
+                .filter(|(ip, _)| unbanned.contains(ip))
+                .try_for_each(|(ip, rule)| {
+                    self.ipt(ip)?
+                        .delete(&self.table, &self.chain, &rule)
+                        .map_err(|_| Error::UnsuccessfulUnban(ip))
+                })?;
+            check_ipv6(self.ipt6.is_some(), ips)
+        }
+
+        fn restore_rules(&self) -> Result<bool, Error> {
+            let marker_rule = self.marker_rule();
+            let mut restored = false;
+            for ipt in self.ipts() {
+                let error = |err| {
+                    Error::Custom(format!(
+                        "unable to restore the marker rule of the chain `{}`: {}",
+                        self.chain, err
+                    ))
+                };
+                if !ipt
+                    .exists(&self.table, &self.chain, &marker_rule)
+                    .map_err(error)?
+                {
+                    ipt.append(&self.table, &self.chain, &marker_rule)
+                        .map_err(error)?;
+                    restored = true;
+                }
+            }
+            Ok(restored)
+        }
+
+        fn set_port_ranges(&self, port_ranges: PortRanges) -> Result<(), Error> {
+            *self.port_ranges.write().unwrap() = port_ranges;
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn parses_the_rules_of_the_app() {
+            let rule = "-A INPUT -s 155.133.226.75/32 -j DROP";
+            assert_eq!(
+                parse_listed_rule(rule, &RuleScope::System, ""),
+                Some((
+                    "155.133.226.75".parse().unwrap(),
+                    "-s 155.133.226.75/32 -j DROP".to_string()
+                ))
+            );
+
+            let rule = "-A INPUT -s 2a01:4f8::1/128 -p udp -m udp --sport 27015:27060 -j DROP";
+            assert_eq!(
+                parse_listed_rule(rule, &RuleScope::System, "").map(|(ip, _)| ip),
+                Some("2a01:4f8::1".parse().unwrap())
+            );
+        }
+
+        #[test]
+        fn skips_the_other_rules() {
+            // not a drop
+            let rule = "-A INPUT -s 155.133.226.75/32 -j ACCEPT";
+            assert_eq!(parse_listed_rule(rule, &RuleScope::System, ""), None);
+            // no address
+            let rule = "-A INPUT -p udp -j DROP";
+            assert_eq!(parse_listed_rule(rule, &RuleScope::System, ""), None);
+            // tagged by another prefix
+            let rule = "-A INPUT -s 155.133.226.75/32 -m comment --comment \"other_155.133.226.75\" -j DROP";
+            assert_eq!(parse_listed_rule(rule, &RuleScope::System, "ssd_"), None);
+        }
+
+        #[test]
+        fn matches_the_comment_and_the_scope() {
+            let rule = "-A OUTPUT -d 155.133.226.75/32 -m owner --uid-owner 1000 \
+                        -m comment --comment \"ssd_155.133.226.75\" -j DROP";
+            let ip = Some("155.133.226.75".parse().unwrap());
+            let uid = |uid| RuleScope::Uid { uid };
+            assert_eq!(
+                parse_listed_rule(rule, &uid(1000), "ssd_").map(|(ip, _)| ip),
+                ip
+            );
+            assert_eq!(parse_listed_rule(rule, &uid(1001), "ssd_"), None);
+            // the system rules match the source address
+            assert_eq!(parse_listed_rule(rule, &RuleScope::System, "ssd_"), None);
+        }
+    }
+}
+
+/// nftables backend, through the `nft` command. The app owns the
+/// table: the blocked IPs are the elements of a set per IP version,
+/// dropped by the rules of the chain.
+#[cfg(unix)]
+mod nftables {
+    use std::{collections::HashSet, net::IpAddr, process::Command};
+
+    use super::{Error, FirewallOptions, FirewallRequirements, RuleScope};
+
+    /// Set of the blocked IPv4s.
+    const SET: &str = "blocked";
+    /// Set of the blocked IPv6s.
+    const SET6: &str = "blocked6";
+    /// Chain of the rules dropping the traffic of the sets.
+    const CHAIN: &str = "filter";
+
+    pub struct Firewall {
+        table: String,
+        /// Hook of the chain, `input` or `output`.
+        hook: &'static str,
+        /// Rules of the chain dropping the traffic of the sets.
+        rules: [String; 2],
+    }
+
+    impl Firewall {
+        pub fn new(options: &FirewallOptions) -> Result<Self, Error> {
+            let table = &options.nft_table;
+            let is_valid_table = table.starts_with(|c: char| c.is_ascii_alphabetic())
+                && table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !is_valid_table {
+                return Err(Error::Custom(format!(
+                    "nftables table `{}` is not a valid name",
+                    table
+                )));
             }
+
+            // the scoped rules match the outgoing packets
+            let (hook, address, scope_match) = match &options.scope {
+                RuleScope::System => ("input", "saddr", String::new()),
+                RuleScope::Uid { uid } => ("output", "daddr", format!("meta skuid {} ", uid)),
+                RuleScope::Cgroup { path } => {
+                    let path = path.trim_matches('/');
+                    if path.is_empty() || path.contains(|c: char| c.is_whitespace() || c == '"') {
+                        return Err(Error::Custom(format!(
+                            "cgroup path `{}` is empty or contains whitespace",
+                            path
+                        )));
+                    }
+                    (
+                        "output",
+                        "daddr",
+                        format!(
+                            "socket cgroupv2 level {} \"{}\" ",
+                            path.split('/').count(),
+                            path
+                        ),
+                    )
+                }
+            };
+            let firewall = Self {
+                table: table.clone(),
+                hook,
+                rules: [
+                    format!("{}ip {} @{} drop", scope_match, address, SET),
+                    format!("{}ip6 {} @{} drop", scope_match, address, SET6),
+                ],
+            };
+            firewall.setup()?;
+            Ok(firewall)
+        }
+
+        /// Create the table, the sets and the chain, the table and the
+        /// sets are kept if they exist already, the chain if it is up
+        /// to date.
+        fn setup(&self) -> Result<(), Error> {
+            let table = self.table.as_str();
+            // `add` keeps the table and the sets if they exist already,
+            // so the blocks of an earlier run are kept
+            nft(&["add", "table", "inet", table])?;
+            nft(&["add", "set", "inet", table, SET, "{ type ipv4_addr; }"])?;
+            nft(&["add", "set", "inet", table, SET6, "{ type ipv6_addr; }"])?;
+            let chain = nft(&["list", "chain", "inet", table, CHAIN]).ok();
+            if !chain
+                .as_deref()
+                .is_some_and(|chain| self.is_chain_up_to_date(chain))
+            {
+                if chain.is_some() {
+                    nft(&["delete", "chain", "inet", table, CHAIN])?;
+                }
+                nft(&[
+                    "add",
+                    "chain",
+                    "inet",
+                    table,
+                    CHAIN,
+                    &format!(
+                        "{{ type filter hook {} priority 0; policy accept; }}",
+                        self.hook
+                    ),
+                ])?;
+                self.rules.iter().try_for_each(|rule| {
+                    nft(&["add", "rule", "inet", table, CHAIN, rule, &comment(rule)]).map(|_| ())
+                })?;
+            }
+            Ok(())
+        }
+
+        /// Check that the listed chain has the hook and the rules.
+        fn is_chain_up_to_date(&self, chain: &str) -> bool {
+            chain.contains(&format!("hook {} ", self.hook))
+                && self.rules.iter().all(|rule| chain.contains(&comment(rule)))
+        }
+
+        /// List the elements of the set.
+        fn list_set(&self, set: &str) -> Result<HashSet<IpAddr>, Error> {
+            // the set is listed as `table inet <table> { set blocked {
+            // type ipv4_addr elements = { 1.2.3.4, 5.6.7.8 } } }` over
+            // multiple lines
+            let set = nft(&["list", "set", "inet", &self.table, set])?;
+            Ok(set
+                .split_once("elements")
+                .and_then(|(_, elements)| elements.split_once('{'))
+                .and_then(|(_, elements)| elements.split_once('}'))
+                .map(|(elements, _)| {
+                    elements
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .filter_map(|ip| ip.parse().ok())
+                        .collect()
+                })
+                .unwrap_or_default())
+        }
+    }
+
+    /// Get the set of the IP version of the ip.
+    fn set(ip: IpAddr) -> &'static str {
+        match ip {
+            IpAddr::V4(_) => SET,
+            IpAddr::V6(_) => SET6,
         }
     }
 
-    impl Default for Firewall {
-        fn default() -> Self {
-            Self::new()
+    /// Get the comment of the rule, the rules are tagged with
+    /// themselves to spot a change of the scope.
+    fn comment(rule: &str) -> String {
+        format!("comment \"{}\"", rule.replace('"', "'"))
+    }
+
+    /// Run `nft` with the arguments, returns its output.
+    fn nft(args: &[&str]) -> Result<String, Error> {
+        let output = Command::new("nft")
+            .args(args)
+            .output()
+            .map_err(|err| Error::Custom(format!("unable to run nft: {}", err)))?;
+        if !output.status.success() {
+            return Err(Error::Custom(format!(
+                "`nft {}` failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
         }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
     }
 
     impl FirewallRequirements for Firewall {
-        fn is_blocked(&self, ip: std::net::Ipv4Addr) -> Result<bool, Error> {
-            let rule = format!("-s {} -j DROP", ip);
-            self.ipt
-                .exists("filter", "INPUT", &rule)
+        fn is_blocked(&self, ip: IpAddr) -> Result<bool, Error> {
+            self.list_blocked()
+                .map(|blocked| blocked.contains(&ip))
                 .map_err(|_| Error::UnsuccessfulBlockCheck(ip))
         }
 
-        fn ban_ip(&self, ip: std::net::Ipv4Addr) -> Result<(), Error> {
-            let rule = format!("-s {} -j DROP", ip);
-            self.ipt
-                .append_replace("filter", "INPUT", &rule)
+        fn ban_ip(&self, ip: IpAddr) -> Result<(), Error> {
+            // adding an element of the set is a no-op
+            nft(&[
+                "add",
+                "element",
+                "inet",
+                &self.table,
+                set(ip),
+                &format!("{{ {} }}", ip),
+            ])
+            .map(|_| ())
+            .map_err(|_| Error::UnsuccessfulBan(ip))
+        }
+
+        fn unban_ip(&self, ip: IpAddr) -> Result<(), Error> {
+            // deleting a missing element fails
+            if !self.is_blocked(ip)? {
+                return Ok(());
+            }
+            nft(&[
+                "delete",
+                "element",
+                "inet",
+                &self.table,
+                set(ip),
+                &format!("{{ {} }}", ip),
+            ])
+            .map(|_| ())
+            .map_err(|_| Error::UnsuccessfulUnban(ip))
+        }
+
+        fn list_blocked(&self) -> Result<HashSet<IpAddr>, Error> {
+            let mut blocked = self.list_set(SET).map_err(|_| Error::UnsuccessfulList)?;
+            blocked.extend(self.list_set(SET6).map_err(|_| Error::UnsuccessfulList)?);
+            Ok(blocked)
+        }
+
+        fn restore_rules(&self) -> Result<bool, Error> {
+            // listing a missing table, set or chain fails
+            let is_set_up = self.list_set(SET).is_ok()
+                && self.list_set(SET6).is_ok()
+                && nft(&["list", "chain", "inet", &self.table, CHAIN])
+                    .is_ok_and(|chain| self.is_chain_up_to_date(&chain));
+            if is_set_up {
+                return Ok(false);
+            }
+            self.setup()?;
+            Ok(true)
+        }
+    }
+}
+
+/// Traffic shaping with `tc`: the root qdisc of the interface is
+/// replaced by a `prio` qdisc whose extra band has a `netem` qdisc
+/// delaying the packets. Every deprioritized IP has a `flower` filter
+/// sending its packets to the band, the default qdisc is restored when
+/// no IP is deprioritized.
+#[cfg(target_os = "linux")]
+mod tc {
+    use std::{collections::HashSet, net::IpAddr, process::Command};
+
+    use super::{Error, FirewallOptions, TrafficShaper};
+
+    /// Handle of the root `prio` qdisc.
+    const ROOT: &str = "1:";
+    /// Band (class) of the deprioritized packets, the default priomap
+    /// only uses the first 3 bands.
+    const BAND: &str = "1:4";
+
+    pub struct Shaper {
+        interface: String,
+        delay_ms: u32,
+        loss_percent: u32,
+    }
+
+    /// Filter sending the packets of an ip to [`BAND`].
+    struct Filter {
+        ip: IpAddr,
+        handle: String,
+    }
+
+    impl Shaper {
+        pub fn new(options: &FirewallOptions) -> Result<Self, Error> {
+            tc(&["-V"])?;
+            let interface = if options.shaping_interface.is_empty() {
+                default_interface()?
+            } else {
+                options.shaping_interface.clone()
+            };
+            Ok(Self {
+                interface,
+                delay_ms: options.deprioritize_delay_ms,
+                loss_percent: options.deprioritize_loss_percent.min(100),
+            })
+        }
+
+        /// Replace the root qdisc of the interface by the `prio` and
+        /// `netem` qdiscs, unless already done.
+        fn setup(&self) -> Result<(), Error> {
+            let qdiscs = tc(&["qdisc", "show", "dev", &self.interface])?;
+            if qdiscs.contains("qdisc prio 1: root") {
+                return Ok(());
+            }
+            tc(&[
+                "qdisc",
+                "replace",
+                "dev",
+                &self.interface,
+                "root",
+                "handle",
+                ROOT,
+                "prio",
+                "bands",
+                "4",
+            ])?;
+            tc(&[
+                "qdisc",
+                "replace",
+                "dev",
+                &self.interface,
+                "parent",
+                BAND,
+                "handle",
+                "40:",
+                "netem",
+                "delay",
+                &format!("{}ms", self.delay_ms),
+                "loss",
+                &format!("{}%", self.loss_percent),
+            ])?;
+            Ok(())
+        }
+
+        /// Restore the default root qdisc of the interface.
+        fn teardown(&self) -> Result<(), Error> {
+            tc(&["qdisc", "del", "dev", &self.interface, "root"])?;
+            Ok(())
+        }
+
+        /// List the filters of the deprioritized ips.
+        fn filters(&self) -> Result<Vec<Filter>, Error> {
+            // filters are listed as
+            //
+            // filter protocol ip pref 1 flower chain 0 handle 0x1 classid 1:4
+            //   eth_type ipv4
+            //   dst_ip 1.2.3.4
+            //   not_in_hw
+            let output = match tc(&["filter", "show", "dev", &self.interface, "parent", ROOT]) {
+                Ok(output) => output,
+                // no filters without the root qdisc
+                Err(_) => return Ok(Vec::new()),
+            };
+            let mut handle = None;
+            Ok(output
+                .lines()
+                .filter_map(|line| {
+                    if line.starts_with("filter") {
+                        handle = line
+                            .split_whitespace()
+                            .skip_while(|part| *part != "handle")
+                            .nth(1)
+                            .map(str::to_string);
+                        return None;
+                    }
+                    let ip = line.trim().strip_prefix("dst_ip ")?;
+                    let ip = ip.split('/').next()?.parse().ok()?;
+                    Some(Filter {
+                        ip,
+                        handle: handle.clone()?,
+                    })
+                })
+                .collect())
+        }
+    }
+
+    /// Get the protocol and the preference of the filters of the IP
+    /// version of the ip, the filters of a preference must all be of
+    /// the same protocol.
+    fn protocol(ip: IpAddr) -> (&'static str, &'static str) {
+        match ip {
+            IpAddr::V4(_) => ("ip", "1"),
+            IpAddr::V6(_) => ("ipv6", "2"),
+        }
+    }
+
+    /// Get the interface of the default route.
+    fn default_interface() -> Result<String, Error> {
+        // `default via 192.168.1.1 dev eth0 proto dhcp metric 100`
+        let output = Command::new("ip")
+            .args(["route", "show", "default"])
+            .output()
+            .map_err(|err| Error::Custom(format!("unable to run ip: {}", err)))?;
+        String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .skip_while(|part| *part != "dev")
+            .nth(1)
+            .map(str::to_string)
+            .ok_or_else(|| Error::Custom("no default route to shape the traffic of".to_string()))
+    }
+
+    /// Run `tc` with the arguments, returns its output.
+    fn tc(args: &[&str]) -> Result<String, Error> {
+        let output = Command::new("tc")
+            .args(args)
+            .output()
+            .map_err(|err| Error::Custom(format!("unable to run tc: {}", err)))?;
+        if !output.status.success() {
+            return Err(Error::Custom(format!(
+                "`tc {}` failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    impl TrafficShaper for Shaper {
+        fn deprioritize_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+            self.setup()?;
+            let deprioritized = self.list_deprioritized()?;
+            ips.iter()
+                .filter(|ip| !deprioritized.contains(ip))
+                .try_for_each(|ip| {
+                    let (protocol, pref) = protocol(*ip);
+                    tc(&[
+                        "filter",
+                        "add",
+                        "dev",
+                        &self.interface,
+                        "parent",
+                        ROOT,
+                        "protocol",
+                        protocol,
+                        "pref",
+                        pref,
+                        "flower",
+                        "dst_ip",
+                        &ip.to_string(),
+                        "classid",
+                        BAND,
+                    ])
+                    .map(|_| ())
+                    .map_err(|_| Error::UnsuccessfulDeprioritize(*ip))
+                })
+        }
+
+        fn restore_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+            let filters = self.filters()?;
+            filters
+                .iter()
+                .filter(|filter| ips.contains(&filter.ip))
+                .try_for_each(|filter| {
+                    let (protocol, pref) = protocol(filter.ip);
+                    tc(&[
+                        "filter",
+                        "del",
+                        "dev",
+                        &self.interface,
+                        "parent",
+                        ROOT,
+                        "protocol",
+                        protocol,
+                        "pref",
+                        pref,
+                        "handle",
+                        &filter.handle,
+                        "flower",
+                    ])
+                    .map(|_| ())
+                    .map_err(|_| Error::UnsuccessfulRestore(filter.ip))
+                })?;
+            if !filters.is_empty() && filters.iter().all(|filter| ips.contains(&filter.ip)) {
+                self.teardown()?;
+            }
+            Ok(())
+        }
+
+        fn list_deprioritized(&self) -> Result<HashSet<IpAddr>, Error> {
+            Ok(self
+                .filters()?
+                .into_iter()
+                .map(|filter| filter.ip)
+                .collect())
+        }
+    }
+}
+
+/// ipset backend: the blocked IPs are the members of a set per IP
+/// version, dropped by a single iptables (and ip6tables) rule per set
+/// in the chain of the options.
+#[cfg(unix)]
+mod ipset {
+    use std::{
+        collections::HashSet,
+        io::Write,
+        net::IpAddr,
+        process::{Command, Stdio},
+    };
+
+    use super::{unix, Error, FirewallOptions, FirewallRequirements, RuleScope};
+
+    /// Maximum length of the name of an ipset.
+    const MAX_SET_NAME_LEN: usize = 31;
+
+    pub struct Firewall {
+        ipt: iptables::IPTables,
+        /// ip6tables, [`None`] along with [`Self::set6`].
+        ipt6: Option<iptables::IPTables>,
+        table: String,
+        chain: String,
+        /// Set of the blocked IPv4s.
+        set: String,
+        /// Set of the blocked IPv6s, [`None`] if ip6tables is not
+        /// available (eg: IPv6 is disabled).
+        set6: Option<String>,
+        /// Direction of the match of the sets and the match of the
+        /// scope, eg: `src`.
+        set_match: String,
+    }
+
+    impl Firewall {
+        pub fn new(options: &FirewallOptions) -> Result<Self, Error> {
+            let set = options.ipset_name.clone();
+            let set6 = format!("{}6", set);
+            let is_valid_set = !set.is_empty()
+                && set6.len() <= MAX_SET_NAME_LEN
+                && set
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !is_valid_set {
+                return Err(Error::Custom(format!(
+                    "ipset `{}` is not a valid name",
+                    set
+                )));
+            }
+            unix::check_scope(options)?;
+
+            // the scoped rules match the outgoing packets
+            let set_match = match options.scope {
+                RuleScope::System => "src".to_string(),
+                RuleScope::Uid { .. } | RuleScope::Cgroup { .. } => {
+                    format!("dst {}", options.scope.iptables_match())
+                }
+            };
+            let mut firewall = Self {
+                ipt: unix::init(false, options)?,
+                ipt6: unix::init_ipv6(options),
+                table: options.iptables_table.clone(),
+                chain: options.iptables_chain.clone(),
+                set,
+                set6: Some(set6),
+                set_match,
+            };
+            firewall.setup(&firewall.ipt, &firewall.set, "inet")?;
+            let setup6 = match (&firewall.ipt6, &firewall.set6) {
+                (Some(ipt6), Some(set6)) => firewall.setup(ipt6, set6, "inet6"),
+                _ => Err(Error::Custom("ip6tables is not available".to_string())),
+            };
+            if let Err(err) = setup6 {
+                log::warn!("{}, the IPv6 relays cannot be blocked", err);
+                firewall.ipt6 = None;
+                firewall.set6 = None;
+            }
+
+            Ok(firewall)
+        }
+
+        /// Get the rule dropping the traffic of the set.
+        fn rule(&self, set: &str) -> String {
+            format!("-m set --match-set {} {} -j DROP", set, self.set_match)
+        }
+
+        /// Create the set (of the `family`) and add its rule to the
+        /// chain, the set and the rule are kept if they exist already.
+        fn setup(&self, ipt: &iptables::IPTables, set: &str, family: &str) -> Result<(), Error> {
+            // `-exist` keeps the set if it exists already, so the
+            // blocks of an earlier run are kept
+            ipset(
+                &["create", set, "hash:ip", "family", family, "-exist"],
+                None,
+            )?;
+
+            let rule = self.rule(set);
+            let (table, chain) = (&self.table, &self.chain);
+            let rules = ipt.list(table, chain).map_err(|err| {
+                Error::Custom(format!("unable to list the chain `{}`: {}", chain, err))
+            })?;
+            // delete the rules of the set with another scope, listed
+            // as `-A OUTPUT -m set --match-set <set> dst ... -j DROP`
+            let match_set = format!("--match-set {} ", set);
+            rules
+                .iter()
+                .filter(|listed| listed.contains(&match_set) && !listed.ends_with(&rule))
+                .filter_map(|listed| Some(listed.split_once(' ')?.1.split_once(' ')?.1))
+                .try_for_each(|stale_rule| {
+                    ipt.delete(table, chain, stale_rule).map_err(|err| {
+                        Error::Custom(format!(
+                            "unable to delete the rule `{}`: {}",
+                            stale_rule, err
+                        ))
+                    })
+                })?;
+            ipt.append_replace(table, chain, &rule).map_err(|err| {
+                Error::Custom(format!(
+                    "unable to add the rule of the ipset `{}`: {}",
+                    set, err
+                ))
+            })
+        }
+
+        /// Check that the set exists and is dropped by its rule.
+        fn is_set_up(&self, ipt: &iptables::IPTables, set: &str) -> Result<bool, Error> {
+            let rule_exists = ipt
+                .exists(&self.table, &self.chain, &self.rule(set))
+                .map_err(|err| {
+                    Error::Custom(format!(
+                        "unable to check the rule of the ipset `{}`: {}",
+                        set, err
+                    ))
+                })?;
+            // listing a missing set fails
+            Ok(rule_exists && ipset(&["list", "-name", set], None).is_ok())
+        }
+
+        /// Get the set of the IP version of the ip.
+        fn set(&self, ip: IpAddr) -> Result<&str, Error> {
+            match ip {
+                IpAddr::V4(_) => Ok(&self.set),
+                IpAddr::V6(_) => self.set6.as_deref().ok_or(Error::NoIpv6Firewall(ip)),
+            }
+        }
+
+        /// Add (or delete) all the ips with a single `ipset restore`,
+        /// the IPv4s are added even without the set of the IPv6s.
+        fn restore(&self, command: &str, ips: &[IpAddr]) -> Result<(), Error> {
+            let commands: String = ips
+                .iter()
+                .filter_map(|ip| Some(format!("{} {} {}\n", command, self.set(*ip).ok()?, ip)))
+                .collect();
+            // `-exist` ignores the ips already added (or already
+            // deleted)
+            ipset(&["restore", "-exist"], Some(&commands))?;
+            unix::check_ipv6(self.set6.is_some(), ips)
+        }
+
+        /// List the members of the set.
+        fn list_set(&self, set: &str) -> Result<HashSet<IpAddr>, Error> {
+            // the members are listed one per line after the `Members:`
+            // line of the header
+            let set = ipset(&["list", set], None)?;
+            Ok(set
+                .lines()
+                .skip_while(|line| !line.starts_with("Members:"))
+                .skip(1)
+                .filter_map(|member| member.split_whitespace().next()?.parse().ok())
+                .collect())
+        }
+    }
+
+    /// Run `ipset` with the arguments and the input, returns its
+    /// output.
+    fn ipset(args: &[&str], input: Option<&str>) -> Result<String, Error> {
+        let run_error =
+            |err: std::io::Error| Error::Custom(format!("unable to run ipset: {}", err));
+        let mut child = Command::new("ipset")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(run_error)?;
+        // dropping stdin closes it
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(input.unwrap_or_default().as_bytes())
+                .map_err(run_error)?;
+        }
+        let output = child.wait_with_output().map_err(run_error)?;
+        if !output.status.success() {
+            return Err(Error::Custom(format!(
+                "`ipset {}` failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    impl FirewallRequirements for Firewall {
+        fn is_blocked(&self, ip: IpAddr) -> Result<bool, Error> {
+            // `ipset test` fails with `... is NOT in set ...` for the
+            // missing members
+            let Ok(set) = self.set(ip) else {
+                return Ok(false);
+            };
+            match ipset(&["test", set, &ip.to_string()], None) {
+                Ok(_) => Ok(true),
+                Err(Error::Custom(err)) if err.contains("is NOT in set") => Ok(false),
+                Err(_) => Err(Error::UnsuccessfulBlockCheck(ip)),
+            }
+        }
+
+        fn ban_ip(&self, ip: IpAddr) -> Result<(), Error> {
+            self.set(ip)?;
+            self.restore("add", &[ip])
                 .map_err(|_| Error::UnsuccessfulBan(ip))
         }
 
-        fn unban_ip(&self, ip: std::net::Ipv4Addr) -> Result<(), Error> {
-            let rule = format!("-s {} -j DROP", ip);
-            self.ipt
-                .delete_all("filter", "INPUT", &rule)
+        fn unban_ip(&self, ip: IpAddr) -> Result<(), Error> {
+            self.set(ip)?;
+            self.restore("del", &[ip])
                 .map_err(|_| Error::UnsuccessfulUnban(ip))
         }
+
+        fn list_blocked(&self) -> Result<HashSet<IpAddr>, Error> {
+            let mut blocked = self
+                .list_set(&self.set)
+                .map_err(|_| Error::UnsuccessfulList)?;
+            if let Some(set6) = &self.set6 {
+                blocked.extend(self.list_set(set6).map_err(|_| Error::UnsuccessfulList)?);
+            }
+            Ok(blocked)
+        }
+
+        fn ban_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+            self.restore("add", ips).map_err(|err| match err {
+                Error::NoIpv6Firewall(_) => err,
+                err => Error::Custom(format!("unable to block {} IPs: {}", ips.len(), err)),
+            })
+        }
+
+        fn unban_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+            self.restore("del", ips).map_err(|err| match err {
+                Error::NoIpv6Firewall(_) => err,
+                err => Error::Custom(format!("unable to unblock {} IPs: {}", ips.len(), err)),
+            })
+        }
+
+        fn restore_rules(&self) -> Result<bool, Error> {
+            let sets = [
+                Some((&self.ipt, self.set.as_str(), "inet")),
+                self.ipt6
+                    .as_ref()
+                    .zip(self.set6.as_deref())
+                    .map(|(ipt6, set6)| (ipt6, set6, "inet6")),
+            ];
+            let mut restored = false;
+            for (ipt, set, family) in sets.into_iter().flatten() {
+                if !self.is_set_up(ipt, set)? {
+                    self.setup(ipt, set, family)?;
+                    restored = true;
+                }
+            }
+            Ok(restored)
+        }
     }
 }
 
 #[cfg(windows)]
 mod windows {
-    use std::process::Command;
+    //! Rules managed in-process through the firewall COM API
+    //! (`INetFwPolicy2`), one rule per IP named
+    //! `<rule_name_prefix><ip>`, same as the rules earlier versions
+    //! added with `netsh`.
+
+    use std::{collections::HashSet, net::IpAddr, sync::RwLock};
+
+    use ::windows::{
+        core::{Interface, BSTR},
+        Win32::{
+            Foundation::{RPC_E_CHANGED_MODE, VARIANT_FALSE, VARIANT_TRUE},
+            NetworkManagement::WindowsFirewall::{
+                INetFwPolicy2, INetFwRule, INetFwRules, NetFwPolicy2, NetFwRule,
+                NET_FW_ACTION_BLOCK, NET_FW_IP_PROTOCOL_UDP, NET_FW_PROFILE2_ALL,
+                NET_FW_RULE_DIR_OUT,
+            },
+            System::{
+                Com::{
+                    CoCreateInstance, CoInitializeEx, CoUninitialize, IDispatch,
+                    CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED,
+                },
+                Ole::IEnumVARIANT,
+                Variant::VARIANT,
+            },
+        },
+    };
 
-    use super::{Error, FirewallRequirements};
+    use super::{Error, FirewallOptions, FirewallRequirements, PortRanges};
 
-    pub struct Firewall {}
+    /// Name (after the rule name prefix) of the disabled rule marking
+    /// the rules as set up by the app, a reset of the firewall removes
+    /// it unlike unbanning all the ips.
+    const MARKER_NAME: &str = "steam_server_disable";
 
-    impl Default for Firewall {
-        fn default() -> Self {
-            Self {}
+    /// Keeps COM initialized on the current thread, uninitializes it
+    /// on drop if it was initialized by [`ComGuard::new()`].
+    struct ComGuard {
+        uninitialize: bool,
+    }
+
+    impl ComGuard {
+        fn new() -> ::windows::core::Result<Self> {
+            let result = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+            // already initialized by the thread with another
+            // concurrency model, which works just as well
+            if result == RPC_E_CHANGED_MODE {
+                return Ok(Self {
+                    uninitialize: false,
+                });
+            }
+            result.ok()?;
+            Ok(Self { uninitialize: true })
         }
     }
 
-    impl FirewallRequirements for Firewall {
-        fn is_blocked(&self, ip: std::net::Ipv4Addr) -> Result<bool, Error> {
-            let output = Command::new("netsh")
-                .arg("advfirewall")
-                .arg("firewall")
-                .arg("show")
-                .arg("rule")
-                .arg(format!("name=\"IP_BLOCK_{}\"", ip))
-                .output()
-                .unwrap();
-            Ok(output.status.success())
+    impl Drop for ComGuard {
+        fn drop(&mut self) {
+            if self.uninitialize {
+                unsafe { CoUninitialize() };
+            }
         }
+    }
 
-        fn ban_ip(&self, ip: std::net::Ipv4Addr) -> Result<(), Error> {
-            if self.is_blocked(ip).unwrap() {
-                return Ok(());
+    /// Run `f` with the rules of the firewall policy, the policy is
+    /// created once per call so the operations on many ips should be
+    /// done within a single call.
+    fn with_rules<T>(
+        f: impl FnOnce(&INetFwRules) -> ::windows::core::Result<T>,
+    ) -> ::windows::core::Result<T> {
+        let _com = ComGuard::new()?;
+        let policy: INetFwPolicy2 =
+            unsafe { CoCreateInstance(&NetFwPolicy2, None, CLSCTX_INPROC_SERVER)? };
+        let rules = unsafe { policy.Rules()? };
+        f(&rules)
+    }
+
+    pub struct Firewall {
+        rule_name_prefix: String,
+        relay_ports_only: bool,
+        /// See [`FirewallRequirements::set_port_ranges()`].
+        port_ranges: RwLock<PortRanges>,
+    }
+
+    impl Firewall {
+        pub fn new(options: &FirewallOptions) -> Self {
+            Self {
+                rule_name_prefix: options.windows_rule_name_prefix.clone(),
+                relay_ports_only: options.relay_ports_only,
+                port_ranges: RwLock::default(),
             }
+        }
 
-            let output = Command::new("netsh")
-                .arg("advfirewall")
-                .arg("firewall")
-                .arg("add")
-                .arg("rule")
-                .arg(format!("name=\"IP_BLOCK_{}\"", ip))
-                .arg("dir=out")
-                .arg("interface=any")
-                .arg("action=block")
-                .arg(format!("remoteip={}/32", ip))
-                .output()
-                .unwrap();
-            if !output.status.success() {
-                Err(Error::UnsuccessfulBan(ip))
-            } else {
-                Ok(())
+        /// Get the name of the rule that blocks the ip (or of the
+        /// marker rule).
+        fn rule_name(&self, ip: impl std::fmt::Display) -> BSTR {
+            BSTR::from(format!("{}{}", self.rule_name_prefix, ip))
+        }
+
+        /// Get the ips of the rules named with the prefix.
+        fn rule_ips(&self, rules: &INetFwRules) -> ::windows::core::Result<HashSet<IpAddr>> {
+            let rules: IEnumVARIANT = unsafe { rules._NewEnum()? }.cast()?;
+            let mut ips = HashSet::new();
+            loop {
+                let mut variants = [VARIANT::default()];
+                let mut fetched = 0;
+                // `S_FALSE` once there are no more rules
+                let result = unsafe { rules.Next(&mut variants, &mut fetched) };
+                if result.is_err() || fetched == 0 {
+                    result.ok()?;
+                    return Ok(ips);
+                }
+                let rule: INetFwRule = IDispatch::try_from(&variants[0])?.cast()?;
+                let name = unsafe { rule.Name()? }.to_string();
+                if let Some(ip) = name
+                    .strip_prefix(self.rule_name_prefix.as_str())
+                    .and_then(|ip| ip.parse().ok())
+                {
+                    ips.insert(ip);
+                }
             }
         }
 
-        fn unban_ip(&self, ip: std::net::Ipv4Addr) -> Result<(), Error> {
-            if !self.is_blocked(ip).unwrap() {
-                return Ok(());
+        /// Add the rule blocking the outgoing traffic to the ip.
+        fn add_rule(&self, rules: &INetFwRules, ip: IpAddr) -> ::windows::core::Result<()> {
+            let remote_address = match ip {
+                IpAddr::V4(ip) => format!("{}/32", ip),
+                IpAddr::V6(ip) => format!("{}/128", ip),
+            };
+            unsafe {
+                let rule: INetFwRule = CoCreateInstance(&NetFwRule, None, CLSCTX_INPROC_SERVER)?;
+                rule.SetName(&self.rule_name(ip))?;
+                rule.SetDirection(NET_FW_RULE_DIR_OUT)?;
+                rule.SetAction(NET_FW_ACTION_BLOCK)?;
+                rule.SetRemoteAddresses(&BSTR::from(remote_address))?;
+                let port_range = self.port_ranges.read().unwrap().get(&ip).cloned();
+                if let Some(port_range) = port_range.filter(|_| self.relay_ports_only) {
+                    // the protocol must be set before the ports
+                    rule.SetProtocol(NET_FW_IP_PROTOCOL_UDP.0)?;
+                    rule.SetRemotePorts(&BSTR::from(format!(
+                        "{}-{}",
+                        port_range.start(),
+                        port_range.end()
+                    )))?;
+                }
+                rule.SetInterfaceTypes(&BSTR::from("All"))?;
+                rule.SetProfiles(NET_FW_PROFILE2_ALL.0)?;
+                rule.SetEnabled(VARIANT_TRUE)?;
+                rules.Add(&rule)
             }
+        }
 
-            let output = Command::new("netsh")
-                .arg("advfirewall")
-                .arg("firewall")
-                .arg("delete")
-                .arg("rule")
-                .arg(format!("name=\"IP_BLOCK_{}\"", ip))
-                .output()
-                .unwrap();
-            if !output.status.success() {
-                Err(Error::UnsuccessfulUnban(ip))
-            } else {
-                Ok(())
+        /// Add the marker rule, see [`MARKER_NAME`].
+        fn add_marker_rule(&self, rules: &INetFwRules) -> ::windows::core::Result<()> {
+            unsafe {
+                let rule: INetFwRule = CoCreateInstance(&NetFwRule, None, CLSCTX_INPROC_SERVER)?;
+                rule.SetName(&self.rule_name(MARKER_NAME))?;
+                rule.SetEnabled(VARIANT_FALSE)?;
+                rules.Add(&rule)
             }
         }
     }
+
+    impl FirewallRequirements for Firewall {
+        fn is_blocked(&self, ip: IpAddr) -> Result<bool, Error> {
+            // `Item()` fails if there is no rule with the name
+            with_rules(|rules| Ok(unsafe { rules.Item(&self.rule_name(ip)) }.is_ok())).map_err(
+                |err| {
+                    log::error!("unable to check the rule of {}: {}", ip, err);
+                    Error::UnsuccessfulBlockCheck(ip)
+                },
+            )
+        }
+
+        fn ban_ip(&self, ip: IpAddr) -> Result<(), Error> {
+            self.ban_ips(&[ip])
+        }
+
+        fn unban_ip(&self, ip: IpAddr) -> Result<(), Error> {
+            self.unban_ips(&[ip])
+        }
+
+        fn list_blocked(&self) -> Result<HashSet<IpAddr>, Error> {
+            with_rules(|rules| self.rule_ips(rules)).map_err(|err| {
+                log::error!("unable to list the rules: {}", err);
+                Error::UnsuccessfulList
+            })
+        }
+
+        fn ban_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+            let Some(first_ip) = ips.first().copied() else {
+                return Ok(());
+            };
+            let mut failed_ip = first_ip;
+            with_rules(|rules| {
+                let blocked = self.rule_ips(rules)?;
+                ips.iter()
+                    .filter(|ip| !blocked.contains(ip))
+                    .try_for_each(|ip| {
+                        failed_ip = *ip;
+                        self.add_rule(rules, *ip)
+                    })
+            })
+            .map_err(|err| {
+                log::error!("unable to add the rule of {}: {}", failed_ip, err);
+                Error::UnsuccessfulBan(failed_ip)
+            })
+        }
+
+        fn unban_ips(&self, ips: &[IpAddr]) -> Result<(), Error> {
+            let Some(first_ip) = ips.first().copied() else {
+                return Ok(());
+            };
+            let mut failed_ip = first_ip;
+            with_rules(|rules| {
+                let blocked = self.rule_ips(rules)?;
+                ips.iter()
+                    .filter(|ip| blocked.contains(ip))
+                    .try_for_each(|ip| {
+                        failed_ip = *ip;
+                        unsafe { rules.Remove(&self.rule_name(*ip)) }
+                    })
+            })
+            .map_err(|err| {
+                log::error!("unable to remove the rule of {}: {}", failed_ip, err);
+                Error::UnsuccessfulUnban(failed_ip)
+            })
+        }
+
+        fn restore_rules(&self) -> Result<bool, Error> {
+            // the marker rule is only added by the first check, there
+            // is nothing to block again at that point
+            with_rules(|rules| {
+                if unsafe { rules.Item(&self.rule_name(MARKER_NAME)) }.is_ok() {
+                    return Ok(false);
+                }
+                self.add_marker_rule(rules)?;
+                Ok(true)
+            })
+            .map_err(|err| Error::Custom(format!("unable to add the marker rule: {}", err)))
+        }
+
+        fn set_port_ranges(&self, port_ranges: PortRanges) -> Result<(), Error> {
+            *self.port_ranges.write().unwrap() = port_ranges;
+            Ok(())
+        }
+    }
 }
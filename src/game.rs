@@ -0,0 +1,121 @@
+//! Detection of the Steam game that is running, used to guard against
+//! blocking every region or the home region (the nearest one) in the
+//! middle of a match, which disconnects the game.
+//!
+//! The game is detected from its process, or from the running state
+//! recorded by Steam (see [`steam::running_app_id()`]) if the
+//! processes cannot be listed.
+
+use crate::{steam, steam_server::KNOWN_APPS};
+
+/// Process names (without the `.exe` suffix) of the games using the
+/// Steam Datagram Relay, along with their app ids.
+const GAME_PROCESSES: &[(&str, u32)] = &[
+    ("cs2", 730),
+    ("dota2", 570),
+    ("tf_linux64", 440),
+    ("tf_win64", 440),
+    ("project8", 1422450),
+];
+
+/// What to do when blocking every region or the home region while a
+/// game is running.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MatchGuard {
+    /// Block them.
+    Off,
+    /// Ask for confirmation before blocking them.
+    #[default]
+    Warn,
+    /// Never block them.
+    Refuse,
+}
+
+impl MatchGuard {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MatchGuard::Off => "Off",
+            MatchGuard::Warn => "Warn",
+            MatchGuard::Refuse => "Refuse",
+        }
+    }
+}
+
+impl std::fmt::Display for MatchGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Get the names of the running processes, [`None`] if they cannot
+/// be listed.
+#[cfg(target_os = "linux")]
+fn process_names() -> Option<Vec<String>> {
+    let names = std::fs::read_dir("/proc")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.bytes().all(|byte| byte.is_ascii_digit()))
+        })
+        // the process may have exited since
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("comm")).ok())
+        .map(|comm| comm.trim_end().to_string())
+        .collect();
+    Some(names)
+}
+
+/// Get the names of the running processes, [`None`] if they cannot
+/// be listed.
+#[cfg(windows)]
+fn process_names() -> Option<Vec<String>> {
+    let output = std::process::Command::new("tasklist")
+        .args(["/FO", "CSV", "/NH"])
+        .output()
+        .ok()?;
+    // `"cs2.exe","1234","Console","1","1,234,567 K"`
+    let names = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split(',').next())
+        .map(|name| name.trim_matches('"'))
+        .map(|name| {
+            name.get(..name.len().saturating_sub(4))
+                .filter(|_| name.to_ascii_lowercase().ends_with(".exe"))
+                .unwrap_or(name)
+                .to_string()
+        })
+        .collect();
+    Some(names)
+}
+
+/// Get the names of the running processes, [`None`] if they cannot
+/// be listed.
+#[cfg(not(any(target_os = "linux", windows)))]
+fn process_names() -> Option<Vec<String>> {
+    None
+}
+
+/// Get the app id of the game using the Steam Datagram Relay that is
+/// running, [`None`] if there is none.
+pub fn running_game() -> Option<u32> {
+    match process_names() {
+        Some(names) => GAME_PROCESSES.iter().find_map(|(process, app_id)| {
+            names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(process))
+                .then_some(*app_id)
+        }),
+        None => steam::running_app_id()
+            .filter(|app_id| KNOWN_APPS.iter().any(|(known, _)| known == app_id)),
+    }
+}
+
+/// Get the name of the game, its app id if it is not known.
+pub fn name(app_id: u32) -> String {
+    KNOWN_APPS
+        .iter()
+        .find(|(known, _)| *known == app_id)
+        .map_or_else(|| format!("app {}", app_id), |(_, name)| name.to_string())
+}
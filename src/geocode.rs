@@ -0,0 +1,80 @@
+//! Offline reverse geocoding of the region locations to a "City,
+//! Country" label, for the regions whose `geo` is known but have no
+//! description. Uses the city table bundled in the binary
+//! (`data/cities.csv`).
+
+use lazy_static::lazy_static;
+
+use crate::geoip;
+
+/// Cities farther than this from the location are not used for its
+/// label.
+pub const MAX_DISTANCE_KM: f32 = 250.0;
+
+const CITIES_CSV: &str = include_str!("../data/cities.csv");
+
+lazy_static! {
+    static ref CITIES: Vec<City> = parse_cities(CITIES_CSV);
+}
+
+/// City of the bundled table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct City {
+    pub name: String,
+    pub country: String,
+    /// `[longitude, latitude]`, same as the network datagram config.
+    pub geo: [f32; 2],
+}
+
+impl std::fmt::Display for City {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, {}", self.name, self.country)
+    }
+}
+
+/// Parse the `name,country,latitude,longitude` lines, the empty and
+/// `#` comment lines are skipped. The bundled table is known to be
+/// valid, invalid lines are logged and skipped.
+fn parse_cities(csv: &str) -> Vec<City> {
+    csv.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let city = match line.split(',').collect::<Vec<_>>()[..] {
+                [name, country, latitude, longitude] => {
+                    match (latitude.parse::<f32>(), longitude.parse::<f32>()) {
+                        (Ok(latitude), Ok(longitude)) => Some(City {
+                            name: name.to_string(),
+                            country: country.to_string(),
+                            geo: [longitude, latitude],
+                        }),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            if city.is_none() {
+                log::warn!("invalid city `{}`", line);
+            }
+            city
+        })
+        .collect()
+}
+
+/// Get the city of the bundled table nearest to the `[longitude,
+/// latitude]` location, [`None`] if there is none within
+/// [`MAX_DISTANCE_KM`].
+pub fn nearest_city(geo: [f32; 2]) -> Option<&'static City> {
+    CITIES
+        .iter()
+        .map(|city| (city, geoip::distance_km(geo, city.geo)))
+        .filter(|(_, distance)| *distance <= MAX_DISTANCE_KM)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(city, _)| city)
+}
+
+/// Get the "City, Country" label of the `[longitude, latitude]`
+/// location, see [`nearest_city()`].
+pub fn label(geo: [f32; 2]) -> Option<String> {
+    nearest_city(geo).map(City::to_string)
+}
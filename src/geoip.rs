@@ -0,0 +1,65 @@
+//! Approximate location of the user from their public IP, used to
+//! suggest which regions to block.
+
+use serde::Deserialize;
+
+use crate::{
+    block_list::BlockList, downloader, recommend::Recommendation, steam_server::Servers, Error,
+};
+
+/// Approximate location of the user.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Location {
+    pub latitude: f32,
+    pub longitude: f32,
+    pub city: Option<String>,
+    #[serde(rename = "country_name")]
+    pub country: Option<String>,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.city, &self.country) {
+            (Some(city), Some(country)) => write!(f, "{}, {}", city, country),
+            (Some(place), None) | (None, Some(place)) => write!(f, "{}", place),
+            (None, None) => write!(f, "{:.2}, {:.2}", self.latitude, self.longitude),
+        }
+    }
+}
+
+/// Detect the location of the user from their public IP.
+///
+/// note: this sends a request to a third party geo-IP service, it
+/// must only be done when the user opts in.
+pub fn detect_location() -> Result<Location, Error> {
+    let data = downloader::Download::to_bytes("https://ipapi.co/json/")?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+/// Great circle distance in kilometers between two `[longitude,
+/// latitude]` points.
+pub fn distance_km(a: [f32; 2], b: [f32; 2]) -> f32 {
+    const EARTH_RADIUS_KM: f32 = 6371.0;
+
+    let (a_lon, a_lat) = (a[0].to_radians(), a[1].to_radians());
+    let (b_lon, b_lat) = (b[0].to_radians(), b[1].to_radians());
+    let h = ((b_lat - a_lat) / 2.0).sin().powi(2)
+        + a_lat.cos() * b_lat.cos() * ((b_lon - a_lon) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+/// Suggest the regions to block from the given location, the nearest
+/// `keep_nearest` regions are kept and all the others are blocked.
+/// Regions without a known location are never blocked.
+pub fn suggest_block_list(
+    servers: &Servers,
+    location: &Location,
+    keep_nearest: usize,
+) -> BlockList {
+    Recommendation::by_location(
+        servers,
+        [location.longitude, location.latitude],
+        keep_nearest,
+    )
+    .block_list()
+}
@@ -0,0 +1,184 @@
+//! gRPC control API (`--grpc <ADDRESS>`), for remote dashboards. The
+//! service is defined in `proto/steam_server_disable.proto`, the
+//! operations are the same as the ones of [`crate::rpc`].
+
+use std::{net::SocketAddr, sync::Arc};
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use crate::{controller::Controller, event::Event, steam_server::ServerState, Error};
+
+/// Code generated from `proto/steam_server_disable.proto`.
+pub mod proto {
+    tonic::include_proto!("steam_server_disable");
+}
+
+use proto::region_control_server::{RegionControl, RegionControlServer};
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::UnknownRegion(_) => Status::not_found(err.to_string()),
+            err => Status::internal(err.to_string()),
+        }
+    }
+}
+
+impl From<&ServerState> for proto::RegionState {
+    fn from(state: &ServerState) -> Self {
+        match state {
+            ServerState::AllDisabled => Self::AllDisabled,
+            ServerState::SomeDisabled(_) => Self::SomeDisabled,
+            ServerState::NoneDisabled => Self::NoneDisabled,
+            ServerState::Unknown => Self::Unknown,
+        }
+    }
+}
+
+impl From<&Event> for proto::Event {
+    fn from(event: &Event) -> Self {
+        let (kind, region) = match event {
+            Event::RegionDisabled { region, .. } => {
+                (proto::EventKind::RegionDisabled, region.as_str())
+            }
+            Event::RegionEnabled { region, .. } => {
+                (proto::EventKind::RegionEnabled, region.as_str())
+            }
+            _ => (proto::EventKind::Other, ""),
+        };
+        Self {
+            kind: kind.into(),
+            region: region.to_string(),
+            message: event.to_string(),
+        }
+    }
+}
+
+/// [`RegionControl`] service of the [`Controller`].
+struct RegionControlService {
+    controller: Arc<Controller>,
+}
+
+impl RegionControlService {
+    /// Run the blocking operation of the controller on tokio's
+    /// blocking thread pool.
+    async fn run<T: Send + 'static>(
+        &self,
+        op: impl FnOnce(&Controller) -> Result<T, Error> + Send + 'static,
+    ) -> Result<T, Status> {
+        let controller = self.controller.clone();
+        tokio::task::spawn_blocking(move || op(&controller))
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?
+            .map_err(Status::from)
+    }
+
+    /// Block or unblock the regions of the request.
+    async fn set_blocked(
+        &self,
+        request: Request<proto::RegionsRequest>,
+        blocked: bool,
+    ) -> Result<Response<proto::RegionsResponse>, Status> {
+        let regions = request.into_inner().regions;
+        if regions.is_empty() {
+            return Err(Status::invalid_argument("no regions given"));
+        }
+        self.run(move |controller| {
+            regions
+                .iter()
+                .try_for_each(|region| controller.set_blocked(region, blocked))
+        })
+        .await?;
+        Ok(Response::new(proto::RegionsResponse {}))
+    }
+}
+
+#[tonic::async_trait]
+impl RegionControl for RegionControlService {
+    type SubscribeEventsStream = ReceiverStream<Result<proto::Event, Status>>;
+
+    async fn list_regions(
+        &self,
+        _request: Request<proto::ListRegionsRequest>,
+    ) -> Result<Response<proto::ListRegionsResponse>, Status> {
+        let regions = self
+            .controller
+            .regions()
+            .into_iter()
+            .map(|region| proto::Region {
+                region: region.region,
+                description: region.description,
+                longitude: region.geo.map(|geo| geo[0]),
+                latitude: region.geo.map(|geo| geo[1]),
+                num_ips: u32::try_from(region.num_ips).unwrap_or(u32::MAX),
+            })
+            .collect();
+        Ok(Response::new(proto::ListRegionsResponse { regions }))
+    }
+
+    async fn get_status(
+        &self,
+        request: Request<proto::RegionsRequest>,
+    ) -> Result<Response<proto::StatusResponse>, Status> {
+        let regions = request.into_inner().regions;
+        let states = self
+            .run(move |controller| controller.states(&regions))
+            .await?;
+        Ok(Response::new(proto::StatusResponse {
+            statuses: states
+                .iter()
+                .map(|(region, state)| proto::RegionStatus {
+                    region: region.clone(),
+                    state: proto::RegionState::from(state).into(),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn enable(
+        &self,
+        request: Request<proto::RegionsRequest>,
+    ) -> Result<Response<proto::RegionsResponse>, Status> {
+        self.set_blocked(request, false).await
+    }
+
+    async fn disable(
+        &self,
+        request: Request<proto::RegionsRequest>,
+    ) -> Result<Response<proto::RegionsResponse>, Status> {
+        self.set_blocked(request, true).await
+    }
+
+    async fn subscribe_events(
+        &self,
+        _request: Request<proto::SubscribeEventsRequest>,
+    ) -> Result<Response<Self::SubscribeEventsStream>, Status> {
+        let event_receiver = self.controller.subscribe();
+        let (sender, receiver) = mpsc::channel(16);
+        // forward until the controller is dropped or the client
+        // closed the stream (noticed on the next event)
+        tokio::task::spawn_blocking(move || {
+            while let Ok(event) = event_receiver.recv() {
+                if sender.blocking_send(Ok((&event).into())).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+/// Serve the gRPC API of the controller on the given address until
+/// the server fails.
+pub async fn serve(controller: Controller, address: SocketAddr) -> Result<(), Error> {
+    log::info!("serving the gRPC API on {}", address);
+    tonic::transport::Server::builder()
+        .add_service(RegionControlServer::new(RegionControlService {
+            controller: Arc::new(controller),
+        }))
+        .serve(address)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! Health of the background threads, so that a thread that panicked
+//! or stalled is noticed instead of the UI silently no longer
+//! updating.
+
+use std::{
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Status of a background thread, see [`Health::status()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadStatus {
+    Running,
+    /// No activity for longer than expected, the thread is likely
+    /// blocked.
+    Stalled,
+    Panicked,
+}
+
+impl ThreadStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThreadStatus::Running => "Running",
+            ThreadStatus::Stalled => "Stalled",
+            ThreadStatus::Panicked => "Panicked",
+        }
+    }
+}
+
+impl std::fmt::Display for ThreadStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug)]
+struct HealthState {
+    last_activity: Instant,
+    panicked: bool,
+}
+
+/// Health of a background thread, shared between the thread and its
+/// handle. The thread calls [`Health::beat()`] on every iteration of
+/// its loop and keeps a [`HealthGuard`] alive.
+#[derive(Debug, Clone)]
+pub struct Health {
+    state: Arc<Mutex<HealthState>>,
+    /// The thread is stalled after this long without activity.
+    stall_timeout: Duration,
+}
+
+impl Health {
+    pub fn new(stall_timeout: Duration) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(HealthState {
+                last_activity: Instant::now(),
+                panicked: false,
+            })),
+            stall_timeout,
+        }
+    }
+
+    /// Lock the state, a poisoned lock is still usable since the
+    /// state is always consistent.
+    fn state(&self) -> MutexGuard<'_, HealthState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Record activity of the thread.
+    pub fn beat(&self) {
+        self.state().last_activity = Instant::now();
+    }
+
+    /// Get the guard that marks the thread as panicked if it is
+    /// dropped while unwinding, create it at the start of the thread.
+    pub fn guard(&self) -> HealthGuard {
+        HealthGuard {
+            health: self.clone(),
+        }
+    }
+
+    /// Time of the last activity of the thread.
+    pub fn last_activity(&self) -> Instant {
+        self.state().last_activity
+    }
+
+    pub fn status(&self) -> ThreadStatus {
+        let state = self.state();
+        if state.panicked {
+            ThreadStatus::Panicked
+        } else if state.last_activity.elapsed() > self.stall_timeout {
+            ThreadStatus::Stalled
+        } else {
+            ThreadStatus::Running
+        }
+    }
+}
+
+/// Marks the thread as panicked when dropped while unwinding, see
+/// [`Health::guard()`].
+pub struct HealthGuard {
+    health: Health,
+}
+
+impl Drop for HealthGuard {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.health.state().panicked = true;
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Journal of the blocks and unblocks of the regions, kept across
+//! sessions so that the match quality can be correlated with the
+//! configuration changes.
+//!
+//! The journal is a file of JSON lines at
+//! [`file_ops::get_history_file_path()`], entries are only ever
+//! appended (the oldest entries are dropped on load once there are
+//! more than [`History::MAX_ENTRIES`]).
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{file_ops, Error};
+
+/// Block or unblock of a region.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Unix time (seconds) of the change.
+    pub time: u64,
+    /// Unix time (seconds) the session the change happened in
+    /// started at.
+    pub session: u64,
+    /// Abreviation of the region.
+    pub region: String,
+    /// If the region got blocked, otherwise it got unblocked.
+    pub blocked: bool,
+    /// Why the region was (un)blocked, [`None`] if by the user.
+    pub reason: Option<String>,
+}
+
+impl std::fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            format_unix_time(self.time),
+            if self.blocked { "blocked" } else { "unblocked" },
+            self.region
+        )?;
+        self.reason
+            .as_ref()
+            .map_or(Ok(()), |reason| write!(f, " due to {}", reason))
+    }
+}
+
+/// Journal of the blocks and unblocks, see the module documentation.
+#[derive(Debug)]
+pub struct History {
+    /// [`None`] without a home directory, the entries are then only
+    /// kept in memory.
+    file_path: Option<PathBuf>,
+    /// Unix time (seconds) the current session started at.
+    session: u64,
+    /// Oldest first.
+    entries: Vec<HistoryEntry>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            file_path: file_ops::get_history_file_path().ok(),
+            session: unix_time_now(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl History {
+    /// Maximum number of entries kept.
+    pub const MAX_ENTRIES: usize = 10_000;
+
+    /// Load the journal and start a new session, no entries if the
+    /// file does not exist yet. Invalid lines are skipped.
+    pub fn load() -> Result<Self, Error> {
+        let file_path = file_ops::get_history_file_path()?;
+        let mut res = Self::default();
+        if !file_path.exists() {
+            return Ok(res);
+        }
+
+        let contents = fs::read_to_string(&file_path)?;
+        res.entries = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|err| log::warn!("skipping invalid history entry: {}", err))
+                    .ok()
+            })
+            .collect();
+
+        if res.entries.len() > Self::MAX_ENTRIES {
+            res.entries.drain(..res.entries.len() - Self::MAX_ENTRIES);
+            let mut contents = Vec::new();
+            res.entries.iter().try_for_each(|entry| {
+                serde_json::to_writer(&mut contents, entry)?;
+                contents.push(b'\n');
+                Ok::<_, Error>(())
+            })?;
+            file_ops::write_atomic(&file_path, contents)?;
+        }
+
+        Ok(res)
+    }
+
+    /// Record a block or unblock of the region now, the entry is
+    /// appended to the file.
+    pub fn record(&mut self, region: impl Into<String>, blocked: bool, reason: Option<String>) {
+        let entry = HistoryEntry {
+            time: unix_time_now(),
+            session: self.session,
+            region: region.into(),
+            blocked,
+            reason,
+        };
+        if let Some(file_path) = &self.file_path {
+            if let Err(err) = Self::append(file_path, &entry) {
+                log::error!(
+                    "unable to write the history to `{}`: {}",
+                    file_path.display(),
+                    err
+                );
+            }
+        }
+        self.entries.push(entry);
+    }
+
+    /// Append the entry to the file.
+    fn append(file_path: &Path, entry: &HistoryEntry) -> Result<(), Error> {
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(file_path)?
+            .write_all(&line)?;
+        Ok(())
+    }
+
+    /// Get the unix time (seconds) the current session started at.
+    pub fn session(&self) -> u64 {
+        self.session
+    }
+
+    /// Get a reference to the entries, oldest first.
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+}
+
+/// Get the current unix time in seconds.
+pub fn unix_time_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Format the unix time (seconds) as `YYYY-MM-DD hh:mm:ss` in UTC.
+pub fn format_unix_time(time: u64) -> String {
+    let (days, secs) = (time / 86400, time % 86400);
+    // civil date from the days since 1970-01-01, see
+    // <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,175 @@
+//! User hooks run on the [`Event`]s, eg: when a region is blocked or
+//! its ping goes above the alert threshold (`--ping-alert-ms`). A hook
+//! runs a command or posts to a webhook, the arguments and the body are
+//! templates where `{kind}`, `{title}`, `{message}`, `{region}`,
+//! `{ips}`, `{ping_ms}` and `{threshold_ms}` are replaced by the values
+//! of the event (empty if the event has none).
+//!
+//! The hooks are stored at [`file_ops::get_hooks_file_path()`].
+//!
+//! note: the commands run with the privileges of the app, which
+//! usually runs elevated.
+
+use std::process;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    downloader,
+    event::{Event, Notifier},
+    file_ops::{self, Migration},
+    Error,
+};
+
+/// Migrations of the hooks file, see [`file_ops::migrate()`].
+const MIGRATIONS: &[Migration] = &[];
+
+/// What a hook does.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HookAction {
+    /// Run the program with the templated arguments.
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// Post the templated body as JSON to the URL, the event as JSON
+    /// if the body is empty.
+    Webhook {
+        url: String,
+        #[serde(default)]
+        body: String,
+    },
+}
+
+/// Hook run on the events of the given kinds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hook {
+    /// Kinds of the events running the hook (eg: `region_disabled`,
+    /// `ping_threshold_crossed`), all the events if empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+    pub action: HookAction,
+}
+
+impl Hook {
+    /// Does the event run the hook?
+    pub fn matches(&self, event: &Event) -> bool {
+        self.events.is_empty() || self.events.iter().any(|kind| kind == event.kind())
+    }
+
+    /// Run the hook for the event.
+    pub fn run(&self, event: &Event) -> Result<(), Error> {
+        match &self.action {
+            HookAction::Command { program, args } => {
+                let status = process::Command::new(program)
+                    .args(args.iter().map(|arg| template(arg, event, false)))
+                    .status()?;
+                if !status.success() {
+                    log::warn!("hook `{}` exited with {}", program, status);
+                }
+            }
+            HookAction::Webhook { url, body } => {
+                let body = if body.is_empty() {
+                    serde_json::to_string(event)?
+                } else {
+                    template(body, event, true)
+                };
+                downloader::Download::post_json(url, body.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replace the placeholders of the template by the values of the
+/// event, escaped for a JSON string if `json`.
+fn template(template: &str, event: &Event, json: bool) -> String {
+    let (region, ips, ping_ms, threshold_ms) = match event {
+        Event::RegionDisabled { region, ips, .. } | Event::RegionEnabled { region, ips, .. } => (
+            region.as_str(),
+            ips.iter()
+                .map(|ip| ip.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            String::new(),
+            String::new(),
+        ),
+        Event::PingThresholdCrossed {
+            region,
+            ping_ms,
+            threshold_ms,
+            ..
+        } => (
+            region.as_str(),
+            String::new(),
+            ping_ms.to_string(),
+            threshold_ms.to_string(),
+        ),
+        Event::LossThresholdCrossed { region, .. } | Event::FirewallError { region, .. } => {
+            (region.as_str(), String::new(), String::new(), String::new())
+        }
+        _ => ("", String::new(), String::new(), String::new()),
+    };
+    let escape = |value: &str| {
+        if json {
+            let quoted = serde_json::Value::from(value).to_string();
+            quoted[1..quoted.len() - 1].to_string()
+        } else {
+            value.to_string()
+        }
+    };
+    [
+        ("{kind}", event.kind().to_string()),
+        ("{title}", event.title().to_string()),
+        ("{message}", event.to_string()),
+        ("{region}", region.to_string()),
+        ("{ips}", ips),
+        ("{ping_ms}", ping_ms),
+        ("{threshold_ms}", threshold_ms),
+    ]
+    .into_iter()
+    .fold(template.to_string(), |text, (placeholder, value)| {
+        text.replace(placeholder, &escape(&value))
+    })
+}
+
+/// Hooks of the user.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hooks {
+    pub hooks: Vec<Hook>,
+}
+
+impl Hooks {
+    /// Load the hooks, none if the file does not exist yet.
+    pub fn load() -> Result<Self, Error> {
+        let file_path = file_ops::get_hooks_file_path()?;
+        if !file_path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(file_ops::load_versioned(file_path, MIGRATIONS)?)
+    }
+
+    /// Save the hooks.
+    pub fn save(&self) -> Result<(), Error> {
+        file_ops::save_versioned(file_ops::get_hooks_file_path()?, self, MIGRATIONS)?;
+        Ok(())
+    }
+}
+
+impl Notifier for Hooks {
+    fn notify(&mut self, event: &Event) -> Result<(), Error> {
+        // a failing hook does not keep the others from running
+        self.hooks
+            .iter()
+            .filter(|hook| hook.matches(event))
+            .for_each(|hook| {
+                if let Err(err) = hook.run(event) {
+                    log::error!("unable to run hook {:?}: {}", hook.action, err);
+                }
+            });
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+//! System wide hotkey, works even when another window (the game) has
+//! the focus.
+//!
+//! On Windows the hotkey events are delivered through the event loop
+//! of the thread that registered the hotkey, so [`Hotkey`] must be
+//! created on the thread that polls the window events.
+
+use std::sync::mpsc;
+
+use global_hotkey::{hotkey::HotKey, GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+
+use crate::{waker::Waker, Error};
+
+/// Registered system wide hotkey.
+pub struct Hotkey {
+    manager: GlobalHotKeyManager,
+    hotkey: HotKey,
+    press_receiver: mpsc::Receiver<()>,
+}
+
+impl Hotkey {
+    /// Register the hotkey, eg: `ctrl+alt+s`. `waker` is woken up
+    /// when the hotkey is pressed.
+    pub fn new(hotkey: &str, waker: Waker) -> Result<Self, Error> {
+        let hotkey: HotKey = hotkey.parse().map_err(|err| Error::InvalidHotkey {
+            hotkey: hotkey.to_string(),
+            reason: format!("{}", err),
+        })?;
+        let manager = GlobalHotKeyManager::new()?;
+        manager.register(hotkey)?;
+
+        let (press_sender, press_receiver) = mpsc::channel();
+        let hotkey_id = hotkey.id();
+        GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+            if event.id() == hotkey_id && event.state() == HotKeyState::Pressed {
+                // the receiver is gone only when the hotkey is
+                // unregistered
+                let _ = press_sender.send(());
+                waker.wake();
+            }
+        }));
+
+        log::info!("registered hotkey `{}`", hotkey.into_string());
+
+        Ok(Self {
+            manager,
+            hotkey,
+            press_receiver,
+        })
+    }
+
+    /// Check if the hotkey was pressed since the last call, returns
+    /// the number of presses.
+    pub fn presses(&self) -> usize {
+        self.press_receiver.try_iter().count()
+    }
+}
+
+impl Drop for Hotkey {
+    fn drop(&mut self) {
+        GlobalHotKeyEvent::set_event_handler(None::<fn(GlobalHotKeyEvent)>);
+        if let Err(err) = self.manager.unregister(self.hotkey) {
+            log::error!("unable to unregister the hotkey: {}", err);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Parsing of a pasted list of IPs and CIDRs (eg: from a console log
+//! or a support thread) so that they can be blocked in one batch.
+//!
+//! The entries are separated by whitespace, commas or semicolons, a
+//! `#` starts a comment until the end of the line. A port after the
+//! IP (`1.2.3.4:27015`, `[2001:db8::1]:27015`) is ignored. The CIDRs
+//! are IPv4 only.
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use crate::steam_server::{ServerInfo, Servers};
+
+/// Shortest CIDR prefix accepted, so that a typo cannot block a large
+/// part of the internet.
+pub const MIN_CIDR_PREFIX: u32 = 24;
+
+/// Entry of the list that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidEntry {
+    pub entry: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for InvalidEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}`: {}", self.entry, self.reason)
+    }
+}
+
+/// Parsed list of IPs, see [`parse()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IpList {
+    /// IPs of the list with the CIDRs expanded, without duplicates,
+    /// in the order of the list.
+    pub ips: Vec<IpAddr>,
+    pub invalid: Vec<InvalidEntry>,
+}
+
+/// Parse the list of IPs and CIDRs, see the module documentation.
+pub fn parse(text: &str) -> IpList {
+    let mut res = IpList::default();
+    let mut seen = HashSet::new();
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ',' || c == ';'))
+        .filter(|entry| !entry.is_empty())
+        .for_each(|entry| match parse_entry(entry) {
+            Ok(ips) => res
+                .ips
+                .extend(ips.into_iter().filter(|ip| seen.insert(*ip))),
+            Err(reason) => res.invalid.push(InvalidEntry {
+                entry: entry.to_string(),
+                reason,
+            }),
+        });
+    res
+}
+
+/// Parse an IP, an IP with a port or a CIDR.
+fn parse_entry(entry: &str) -> Result<Vec<IpAddr>, String> {
+    let ipv6 = entry
+        .strip_prefix('[')
+        .and_then(|entry| entry.split_once(']'))
+        .map_or(entry, |(ip, _)| ip);
+    if let Ok(ip) = ipv6.parse::<Ipv6Addr>() {
+        return Ok(vec![ip.into()]);
+    }
+
+    let (ip, prefix) = match entry.split_once('/') {
+        Some((ip, prefix)) => {
+            let prefix: u32 = prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= 32)
+                .ok_or_else(|| format!("invalid prefix length `{}`", prefix))?;
+            if prefix < MIN_CIDR_PREFIX {
+                return Err(format!(
+                    "prefix length {} is shorter than the allowed /{}",
+                    prefix, MIN_CIDR_PREFIX
+                ));
+            }
+            (ip, prefix)
+        }
+        None => (entry.split_once(':').map_or(entry, |(ip, _)| ip), 32),
+    };
+    let ip: Ipv4Addr = ip.parse().map_err(|err| format!("{}", err))?;
+    let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+    let network = u32::from(ip) & mask;
+    Ok((network..=network | !mask)
+        .map(|ip| Ipv4Addr::from(ip).into())
+        .collect())
+}
+
+/// IPs of the list grouped by the region they belong to.
+#[derive(Clone, Default)]
+pub struct RegionGroups<'a> {
+    /// Regions with their IPs of the list, in the order of the
+    /// servers.
+    pub regions: Vec<(&'a ServerInfo, Vec<IpAddr>)>,
+    /// IPs of the list that belong to no known region.
+    pub unknown: Vec<IpAddr>,
+}
+
+/// Group the IPs by the region they belong to.
+pub fn group_by_region<'a>(servers: &'a Servers, ips: &[IpAddr]) -> RegionGroups<'a> {
+    let ip_servers: HashMap<IpAddr, usize> = servers
+        .get_servers()
+        .iter()
+        .enumerate()
+        .flat_map(|(index, server)| server.get_ips().iter().map(move |ip| (*ip, index)))
+        .collect();
+    let mut regions: Vec<Vec<IpAddr>> = vec![Vec::new(); servers.get_servers().len()];
+    let mut unknown = Vec::new();
+    ips.iter().for_each(|ip| match ip_servers.get(ip) {
+        Some(index) => regions[*index].push(*ip),
+        None => unknown.push(*ip),
+    });
+    RegionGroups {
+        regions: servers
+            .get_servers()
+            .iter()
+            .zip(regions)
+            .filter(|(_, ips)| !ips.is_empty())
+            .collect(),
+        unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ips(ips: &[&str]) -> Vec<IpAddr> {
+        ips.iter().map(|ip| ip.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn parses_the_separators_and_the_comments() {
+        let list = parse("1.2.3.4, 1.2.3.5;1.2.3.6\n# 1.2.3.7\n1.2.3.8 # 1.2.3.9\n");
+        assert_eq!(list.ips, ips(&["1.2.3.4", "1.2.3.5", "1.2.3.6", "1.2.3.8"]));
+        assert!(list.invalid.is_empty());
+    }
+
+    #[test]
+    fn ignores_the_ports() {
+        let list = parse("1.2.3.4:27015 [2001:db8::1]:27015 2001:db8::2");
+        assert_eq!(list.ips, ips(&["1.2.3.4", "2001:db8::1", "2001:db8::2"]));
+    }
+
+    #[test]
+    fn expands_the_cidrs() {
+        let list = parse("10.0.0.0/30 10.0.0.1");
+        assert_eq!(
+            list.ips,
+            ips(&["10.0.0.0", "10.0.0.1", "10.0.0.2", "10.0.0.3"])
+        );
+        assert!(list.invalid.is_empty());
+    }
+
+    #[test]
+    fn reports_the_invalid_entries() {
+        let list = parse("1.2.3.4 10.0.0.0/8 10.0.0.0/33 2001:db8::/64 nope");
+        assert_eq!(list.ips, ips(&["1.2.3.4"]));
+        let invalid: Vec<_> = list
+            .invalid
+            .iter()
+            .map(|entry| entry.entry.as_str())
+            .collect();
+        assert_eq!(
+            invalid,
+            ["10.0.0.0/8", "10.0.0.0/33", "2001:db8::/64", "nope"]
+        );
+    }
+}
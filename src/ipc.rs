@@ -0,0 +1,295 @@
+//! Named pipe between the CLI and a running elevated instance on
+//! Windows, so that the CLI works from a normal (non elevated)
+//! terminal while the app runs.
+//!
+//! The elevated instance serves the pipe with [`PipeServer`], the CLI
+//! forwards its commands with [`forward()`]. The requests and the
+//! responses are the JSON-RPC lines of [`crate::rpc`], every
+//! connection gets its own [`Controller`]. The pipe only accepts
+//! local clients of the user that started the app, and the next
+//! instance of the pipe is created before the served one is closed so
+//! that another process cannot take over the name in between.
+
+use std::{
+    ffi::c_void,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    os::windows::io::FromRawHandle,
+    path::PathBuf,
+    ptr,
+    sync::mpsc,
+    thread,
+};
+
+use serde_json::{json, Value};
+use windows_sys::Win32::{
+    Foundation::{
+        CloseHandle, GetLastError, LocalFree, ERROR_PIPE_CONNECTED, HANDLE, INVALID_HANDLE_VALUE,
+    },
+    Security::{
+        Authorization::{
+            ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW,
+            SDDL_REVISION_1,
+        },
+        GetTokenInformation, TokenUser, SECURITY_ATTRIBUTES, TOKEN_QUERY, TOKEN_USER,
+    },
+    Storage::FileSystem::{FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX},
+    System::{
+        Pipes::{
+            ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+        },
+        Threading::{GetCurrentProcess, OpenProcessToken},
+    },
+};
+
+use crate::{controller::Controller, firewall::Firewall, rpc, steam_server::Servers, Error};
+
+/// Name of the pipe.
+pub const PIPE_NAME: &str = r"\\.\pipe\steam_server_disable";
+
+/// Access of the pipe for the user that started the app: read/write
+/// without `FILE_CREATE_PIPE_INSTANCE` (`GW` includes it), so that
+/// its non elevated processes cannot create instances of the pipe.
+const PIPE_USER_ACCESS: &str = "0x12019b";
+
+enum PipeServerMessage {
+    KillThread,
+}
+
+/// Serves the forwarded commands on [`PIPE_NAME`] on a separate
+/// thread, one connection at a time (the next client waits on the
+/// next instance of the pipe).
+pub struct PipeServer {
+    message_sender: mpsc::Sender<PipeServerMessage>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+}
+
+impl PipeServer {
+    /// Start serving the pipe, `network_datagram_config` is the
+    /// config the servers of the connections are loaded from (the
+    /// cached one if [`None`]).
+    pub fn new(network_datagram_config: Option<PathBuf>) -> Result<Self, Error> {
+        let security_descriptor = security_descriptor()?;
+        // fail early if another instance serves the pipe already
+        let mut pipe = create_pipe(security_descriptor, true)?;
+        let (message_sender, message_receiver) = mpsc::channel();
+
+        let thread_handle = thread::spawn(move || loop {
+            if let Err(err) = connect_pipe(&pipe) {
+                log::error!("unable to accept a pipe connection: {}", err);
+                break;
+            }
+            if let Ok(PipeServerMessage::KillThread) = message_receiver.try_recv() {
+                break;
+            }
+
+            // create the next instance before the served one is
+            // closed, the name is never free for another process
+            let next_pipe = match create_pipe(security_descriptor, false) {
+                Ok(pipe) => pipe,
+                Err(err) => {
+                    log::error!("unable to create the pipe: {}", err);
+                    break;
+                }
+            };
+            let served_pipe = std::mem::replace(&mut pipe, next_pipe);
+            if let Err(err) = serve_connection(served_pipe, network_datagram_config.as_ref()) {
+                log::error!("pipe connection: {}", err);
+            }
+        });
+
+        log::info!("serving the CLI commands on `{}`", PIPE_NAME);
+        Ok(Self {
+            message_sender,
+            thread_handle: Some(thread_handle),
+        })
+    }
+}
+
+impl Drop for PipeServer {
+    fn drop(&mut self) {
+        let _ = self.message_sender.send(PipeServerMessage::KillThread);
+        // connect to unblock the thread waiting for a connection
+        let _ = OpenOptions::new().read(true).write(true).open(PIPE_NAME);
+        if let Some(thread_handle) = self.thread_handle.take() {
+            if thread_handle.join().is_err() {
+                log::error!("pipe server thread panicked");
+            }
+        }
+    }
+}
+
+/// Forward the JSON-RPC request to the running elevated instance,
+/// returns the result.
+pub fn forward(method: &str, params: Value) -> Result<Value, Error> {
+    let mut pipe = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(PIPE_NAME)
+        .map_err(|err| {
+            Error::Ipc(format!(
+                "no elevated instance is running ({}), start the app or run the \
+                 command as administrator",
+                err
+            ))
+        })?;
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    serde_json::to_writer(&mut pipe, &request)?;
+    pipe.write_all(b"\n")?;
+    pipe.flush()?;
+
+    let mut line = String::new();
+    BufReader::new(pipe).read_line(&mut line)?;
+    let mut response: Value = serde_json::from_str(&line)?;
+    match response.get("error") {
+        Some(error) => Err(Error::Ipc(
+            error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error")
+                .to_string(),
+        )),
+        None => Ok(response
+            .get_mut("result")
+            .map(Value::take)
+            .unwrap_or_default()),
+    }
+}
+
+/// Serve the JSON-RPC requests of the connected pipe until the client
+/// disconnects.
+fn serve_connection(pipe: File, network_datagram_config: Option<&PathBuf>) -> Result<(), Error> {
+    let controller = Controller::new(
+        Servers::new(network_datagram_config)?,
+        Firewall::from_config()?,
+    );
+    let input = BufReader::new(pipe.try_clone()?);
+    rpc::serve(controller, input, pipe)
+}
+
+/// Get the SID of the user of the process as a string, eg:
+/// `S-1-5-21-..-1001`. It is the same for the elevated and the non
+/// elevated processes of the user.
+fn current_user_sid() -> Result<String, Error> {
+    let mut token: HANDLE = 0;
+    // SAFETY: the pseudo handle of the process is always valid
+    if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    let mut len = 0;
+    // SAFETY: querying the length only, the call fails with
+    // `ERROR_INSUFFICIENT_BUFFER`
+    unsafe { GetTokenInformation(token, TokenUser, ptr::null_mut(), 0, &mut len) };
+    // `u64` for the alignment of `TOKEN_USER`
+    let mut buffer = vec![0_u64; (len as usize).div_ceil(std::mem::size_of::<u64>())];
+    // SAFETY: the buffer is at least `len` bytes long
+    let res =
+        unsafe { GetTokenInformation(token, TokenUser, buffer.as_mut_ptr().cast(), len, &mut len) };
+    let err = std::io::Error::last_os_error();
+    // SAFETY: the token is opened above and not used afterwards
+    unsafe { CloseHandle(token) };
+    if res == 0 {
+        return Err(err.into());
+    }
+
+    // SAFETY: the buffer is filled with a `TOKEN_USER` whose SID
+    // points into the buffer
+    let sid = unsafe { (*buffer.as_ptr().cast::<TOKEN_USER>()).User.Sid };
+    let mut sid_string = ptr::null_mut();
+    // SAFETY: the SID is valid, the string is allocated by the call
+    if unsafe { ConvertSidToStringSidW(sid, &mut sid_string) } == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    // SAFETY: the string is nul terminated
+    let sid = unsafe {
+        let len = (0..).take_while(|i| *sid_string.add(*i) != 0).count();
+        String::from_utf16_lossy(std::slice::from_raw_parts(sid_string, len))
+    };
+    // SAFETY: the string was allocated with `LocalAlloc`
+    unsafe { LocalFree(sid_string.cast()) };
+    Ok(sid)
+}
+
+/// Get the access of the pipe: [`PIPE_USER_ACCESS`] for the user,
+/// full access for the administrators (the elevated instance) and
+/// the system, nothing for the other users.
+fn pipe_sddl(user_sid: &str) -> String {
+    format!(
+        "D:P(A;;{};;;{})(A;;GA;;;BA)(A;;GA;;;SY)",
+        PIPE_USER_ACCESS, user_sid
+    )
+}
+
+/// Create the security descriptor of [`pipe_sddl()`] for the current
+/// user, it is kept for the lifetime of the process.
+fn security_descriptor() -> Result<usize, Error> {
+    let sddl: Vec<u16> = pipe_sddl(&current_user_sid()?)
+        .encode_utf16()
+        .chain(Some(0))
+        .collect();
+    let mut security_descriptor: *mut c_void = ptr::null_mut();
+    // SAFETY: `sddl` is nul terminated, the descriptor is allocated
+    // by the call
+    let res = unsafe {
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            sddl.as_ptr(),
+            SDDL_REVISION_1,
+            &mut security_descriptor,
+            ptr::null_mut(),
+        )
+    };
+    if res == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(security_descriptor as usize)
+}
+
+/// Create an instance of the pipe, `first` fails if the pipe exists
+/// already.
+fn create_pipe(security_descriptor: usize, first: bool) -> Result<File, Error> {
+    let name: Vec<u16> = PIPE_NAME.encode_utf16().chain(Some(0)).collect();
+    let security_attributes = SECURITY_ATTRIBUTES {
+        nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+        lpSecurityDescriptor: security_descriptor as *mut c_void,
+        bInheritHandle: 0,
+    };
+    let open_mode = if first {
+        PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE
+    } else {
+        PIPE_ACCESS_DUPLEX
+    };
+    // SAFETY: `name` is nul terminated and the security attributes
+    // outlive the call
+    let handle = unsafe {
+        CreateNamedPipeW(
+            name.as_ptr(),
+            open_mode,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            4096,
+            4096,
+            0,
+            &security_attributes,
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    // SAFETY: the handle is valid and owned by the file from now on
+    Ok(unsafe { File::from_raw_handle(handle as _) })
+}
+
+/// Wait for a client to connect to the pipe.
+fn connect_pipe(pipe: &File) -> Result<(), Error> {
+    use std::os::windows::io::AsRawHandle;
+
+    // SAFETY: the handle is a valid pipe handle
+    let res = unsafe { ConnectNamedPipe(pipe.as_raw_handle() as _, ptr::null_mut()) };
+    // the client may connect between the creation and the call
+    if res == 0 && unsafe { GetLastError() } != ERROR_PIPE_CONNECTED {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
@@ -1,7 +1,109 @@
+//! Block the access to the Steam Datagram Relay regions through the
+//! system firewall.
+//!
+//! The library works without any graphics dependencies when the
+//! default `gui` feature is disabled: load the regions with
+//! [`Servers`], block or unblock their IPs with [`Firewall`] (or a
+//! region at a time with [`Controller`]), measure them with
+//! [`Pinger`], keep sets of blocked regions in [`Profiles`] and block
+//! the regions automatically with [`Rules`].
+//!
+//! ``` no_run
+//! use steam_server_disable::{Controller, Firewall, Servers};
+//!
+//! # fn main() -> Result<(), steam_server_disable::Error> {
+//! // the cached network datagram config
+//! let servers = Servers::new(None::<&str>)?;
+//! let controller = Controller::new(servers, Firewall::from_config()?);
+//! controller.set_blocked("sgp", true)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The modules behind the `gui` feature ([`app`], [`config`],
+//! [`command_palette`], [`map_tiles`], [`view_state`], [`world_map`])
+//! are the GUI of the binary, they are not meant to be embedded.
+
+#[cfg(feature = "gui")]
 pub mod app;
+pub mod asn;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub mod block_list;
+pub mod block_suggestions;
+pub mod cli;
+#[cfg(feature = "gui")]
+pub mod command_palette;
+#[cfg(feature = "gui")]
+pub mod config;
+pub mod config_refresh;
+pub mod connections;
+pub mod controller;
+pub mod crash;
+pub mod cs2;
+#[cfg(feature = "notify")]
+pub mod desktop_notification;
+pub mod discord;
 pub mod downloader;
+pub mod error;
+pub mod event;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod file_ops;
 pub mod firewall;
+pub mod game;
+pub mod geocode;
+pub mod geoip;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod health;
+pub mod history;
+pub mod hooks;
+#[cfg(feature = "hotkey")]
+pub mod hotkey;
+pub mod ip_list;
+#[cfg(windows)]
+pub mod ipc;
 pub mod logger;
+#[cfg(feature = "gui")]
+pub mod map_tiles;
 pub mod ping;
+pub mod port_check;
+pub mod presets;
+#[cfg(unix)]
+pub mod privileged;
+pub mod profiles;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod recommend;
+pub mod regions;
+pub mod report;
+pub mod rpc;
+pub mod rules;
+pub mod score;
+pub mod session;
+pub mod steam;
+#[cfg(feature = "cdn")]
+pub mod steam_cdn;
+pub mod steam_cm;
+pub mod steam_pings;
 pub mod steam_server;
+pub mod steam_status;
+pub mod timings;
+pub mod trial_block;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "gui")]
+pub mod view_state;
+pub mod waker;
+pub mod worker;
+#[cfg(feature = "gui")]
+pub mod world_map;
+
+pub use controller::Controller;
+pub use error::{Error, Result};
+pub use firewall::{Firewall, FirewallOptions};
+pub use ping::Pinger;
+pub use profiles::{Profile, Profiles};
+pub use rules::{Rule, Rules};
+pub use steam_server::{ServerInfo, Servers};
@@ -1,39 +1,64 @@
 use std::{
     collections::VecDeque,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Mutex,
     },
 };
 
-use egui_glfw::egui;
 use lazy_static::lazy_static;
 use log::{Level, LevelFilter, Log, SetLoggerError};
 
 lazy_static! {
     /// Logger used for the project.
     pub static ref LOGGER: CombineLoggers<EguiLogger, env_logger::Logger> = CombineLoggers::new(
-        EguiLogger {
-            records: Mutex::new(VecDeque::new()),
-            previous_ui_sizes: Mutex::new(None),
-            force_open_logging_window: AtomicBool::new(false),
+        EguiLogger::new(),
+        LevelFilter::Trace,
+        // the level is controlled by `CombineLoggers`, `RUST_LOG` is
+        // still parsed so that module specific filters work
+        env_logger::Builder::new()
+            .filter_level(LevelFilter::Trace)
+            .parse_default_env()
+            .build(),
+        if std::env::var_os("RUST_LOG").is_some() {
+            LevelFilter::Trace
+        } else {
+            LevelFilter::Info
         },
-        env_logger::Builder::from_env(
-            env_logger::Env::default().default_filter_or("info")
-        ).build(),
     );
 }
 
 /// Combine the two loggers.
+///
+/// Each logger has its own [`LevelFilter`] that can be changed at
+/// runtime, a record is only forwarded to the loggers that have it
+/// enabled.
 pub struct CombineLoggers<T, U> {
     first: T,
     second: U,
+
+    /// Max level of the first logger, stored as [`LevelFilter`] as
+    /// [`usize`].
+    first_max_level: AtomicUsize,
+    /// Max level of the second logger, stored as [`LevelFilter`] as
+    /// [`usize`].
+    second_max_level: AtomicUsize,
 }
 
 impl<T, U> CombineLoggers<T, U> {
     /// Create a new [`CombineLoggers`].
-    pub fn new(first: T, second: U) -> Self {
-        Self { first, second }
+    pub fn new(
+        first: T,
+        first_max_level: LevelFilter,
+        second: U,
+        second_max_level: LevelFilter,
+    ) -> Self {
+        Self {
+            first,
+            second,
+            first_max_level: AtomicUsize::new(first_max_level as usize),
+            second_max_level: AtomicUsize::new(second_max_level as usize),
+        }
     }
 
     /// Get a reference to the first logger.
@@ -45,20 +70,56 @@ impl<T, U> CombineLoggers<T, U> {
     pub fn second(&self) -> &U {
         &self.second
     }
+
+    /// Get the max level of the first logger.
+    pub fn first_max_level(&self) -> LevelFilter {
+        level_filter_from_usize(self.first_max_level.load(Ordering::Relaxed))
+    }
+
+    /// Set the max level of the first logger.
+    pub fn set_first_max_level(&self, level: LevelFilter) {
+        self.first_max_level
+            .store(level as usize, Ordering::Relaxed);
+    }
+
+    /// Get the max level of the second logger.
+    pub fn second_max_level(&self) -> LevelFilter {
+        level_filter_from_usize(self.second_max_level.load(Ordering::Relaxed))
+    }
+
+    /// Set the max level of the second logger.
+    pub fn set_second_max_level(&self, level: LevelFilter) {
+        self.second_max_level
+            .store(level as usize, Ordering::Relaxed);
+    }
+}
+
+impl<T: Log, U: Log> CombineLoggers<T, U> {
+    /// Check if the first logger should receive the record with the
+    /// given metadata.
+    fn first_enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.first_max_level() && self.first.enabled(metadata)
+    }
+
+    /// Check if the second logger should receive the record with the
+    /// given metadata.
+    fn second_enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.second_max_level() && self.second.enabled(metadata)
+    }
 }
 
 impl<T: Log, U: Log> Log for CombineLoggers<T, U> {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        let first = self.first.enabled(metadata);
-        let second = self.second.enabled(metadata);
-        first || second
+        self.first_enabled(metadata) || self.second_enabled(metadata)
     }
 
     fn log(&self, record: &log::Record) {
-        // TODO: ideally, based on what is enabled, it should log only
-        // to that
-        self.first.log(record);
-        self.second.log(record);
+        if self.first_enabled(record.metadata()) {
+            self.first.log(record);
+        }
+        if self.second_enabled(record.metadata()) {
+            self.second.log(record);
+        }
     }
 
     fn flush(&self) {
@@ -67,10 +128,73 @@ impl<T: Log, U: Log> Log for CombineLoggers<T, U> {
     }
 }
 
+#[cfg(feature = "gui")]
+impl CombineLoggers<EguiLogger, env_logger::Logger> {
+    /// Draw the logging window, this includes the UI to change the
+    /// max level of each of the loggers.
+    pub fn draw_ui(&self, ctx: &egui::Context, open_logging_window: &mut bool) {
+        self.first.draw_ui(ctx, open_logging_window, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("GUI level:");
+                let mut first_max_level = self.first_max_level();
+                level_filter_ui(ui, &mut first_max_level, "gui_log_level");
+                self.set_first_max_level(first_max_level);
+
+                ui.separator();
+
+                ui.label("Terminal level:");
+                let mut second_max_level = self.second_max_level();
+                level_filter_ui(ui, &mut second_max_level, "terminal_log_level");
+                self.set_second_max_level(second_max_level);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Max records:");
+                let mut max_records = self.first.max_records();
+                ui.add(egui::DragValue::new(&mut max_records).range(1..=usize::MAX));
+                self.first.set_max_records(max_records);
+
+                ui.separator();
+
+                ui.label("Max size (MiB):");
+                let mut max_mib = self.first.max_bytes() / (1024 * 1024);
+                ui.add(egui::DragValue::new(&mut max_mib).range(1..=usize::MAX));
+                self.first.set_max_bytes(max_mib * 1024 * 1024);
+            });
+            ui.separator();
+        });
+    }
+}
+
+/// Convert the [`usize`] representation of the [`LevelFilter`] back
+/// to [`LevelFilter`].
+fn level_filter_from_usize(level: usize) -> LevelFilter {
+    LevelFilter::iter().nth(level).unwrap_or(LevelFilter::Trace)
+}
+
+/// Create a combo box to select a [`LevelFilter`].
+#[cfg(feature = "gui")]
+fn level_filter_ui(ui: &mut egui::Ui, level: &mut LevelFilter, id_source: impl std::hash::Hash) {
+    egui::ComboBox::from_id_source(id_source)
+        .selected_text(level.as_str())
+        .show_ui(ui, |ui| {
+            LevelFilter::iter().for_each(|level_filter| {
+                ui.selectable_value(level, level_filter, level_filter.as_str());
+            });
+        });
+}
+
 pub struct EguiLogger {
-    records: Mutex<VecDeque<Record>>,
+    records: Mutex<Records>,
+    #[cfg(feature = "gui")]
     previous_ui_sizes: Mutex<Option<UiSizes>>,
     force_open_logging_window: AtomicBool,
+
+    /// Maximum number of records to keep, the oldest records are
+    /// dropped first.
+    max_records: AtomicUsize,
+    /// Approximate maximum number of bytes used by the records, the
+    /// oldest records are dropped first.
+    max_bytes: AtomicUsize,
 }
 
 pub fn init() -> Result<(), SetLoggerError> {
@@ -78,7 +202,90 @@ pub fn init() -> Result<(), SetLoggerError> {
 }
 
 impl EguiLogger {
-    pub fn draw_ui(&self, ctx: &egui::Context, open_logging_window: &mut bool) {
+    /// Default maximum number of records.
+    pub const DEFAULT_MAX_RECORDS: usize = 10000;
+
+    /// Default approximate maximum number of bytes used by the
+    /// records.
+    pub const DEFAULT_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+    /// Create a new [`EguiLogger`].
+    pub fn new() -> Self {
+        Self {
+            records: Mutex::new(Records::default()),
+            #[cfg(feature = "gui")]
+            previous_ui_sizes: Mutex::new(None),
+            force_open_logging_window: AtomicBool::new(false),
+            max_records: AtomicUsize::new(Self::DEFAULT_MAX_RECORDS),
+            max_bytes: AtomicUsize::new(Self::DEFAULT_MAX_BYTES),
+        }
+    }
+
+    /// Get the maximum number of records.
+    pub fn max_records(&self) -> usize {
+        self.max_records.load(Ordering::Relaxed)
+    }
+
+    /// Set the maximum number of records, trims the oldest records
+    /// if needed.
+    pub fn set_max_records(&self, max_records: usize) {
+        self.max_records.store(max_records, Ordering::Relaxed);
+        self.records
+            .lock()
+            .unwrap()
+            .trim(max_records, self.max_bytes());
+    }
+
+    /// Get the approximate maximum number of bytes used by the
+    /// records.
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Set the approximate maximum number of bytes used by the
+    /// records, trims the oldest records if needed.
+    pub fn set_max_bytes(&self, max_bytes: usize) {
+        self.max_bytes.store(max_bytes, Ordering::Relaxed);
+        self.records
+            .lock()
+            .unwrap()
+            .trim(self.max_records(), max_bytes);
+    }
+
+    /// Get the newest `n` records formatted as strings, newest first.
+    pub fn format_newest_records(&self, n: usize) -> Vec<String> {
+        self.records
+            .lock()
+            .unwrap()
+            .records
+            .iter()
+            .take(n)
+            .map(|record| record.to_string())
+            .collect()
+    }
+
+    /// Get the level and the message of the newest record at
+    /// `max_level` or more severe, eg: the last warning or error for
+    /// [`LevelFilter::Warn`].
+    pub fn newest_record(&self, max_level: LevelFilter) -> Option<(Level, String)> {
+        self.records
+            .lock()
+            .unwrap()
+            .records
+            .iter()
+            .find(|record| record.level <= max_level)
+            .map(|record| (record.level, record.args.clone()))
+    }
+
+    /// Draw the logging window, `add_contents` is drawn above the
+    /// records.
+    #[cfg(feature = "gui")]
+    pub fn draw_ui(
+        &self,
+        ctx: &egui::Context,
+        open_logging_window: &mut bool,
+        add_contents: impl FnOnce(&mut egui::Ui),
+    ) {
         if self.force_open_logging_window.swap(false, Ordering::SeqCst) {
             *open_logging_window = true;
         }
@@ -87,18 +294,21 @@ impl EguiLogger {
             .scroll([true, true])
             .open(open_logging_window)
             .show(ctx, |ui| {
+                add_contents(ui);
+
                 let records = self.records.lock().unwrap();
 
                 egui::Grid::new("logging window grid")
                     .striped(true)
                     .show(ui, |ui| {
-                        let ui_sizes = records.iter().fold(UiSizes::zero(), |acc, record| {
-                            let ui_sizes =
-                                record.draw_ui(ui, self.previous_ui_sizes.lock().unwrap().as_ref());
-                            ui.end_row();
+                        let ui_sizes =
+                            records.records.iter().fold(UiSizes::zero(), |acc, record| {
+                                let ui_sizes = record
+                                    .draw_ui(ui, self.previous_ui_sizes.lock().unwrap().as_ref());
+                                ui.end_row();
 
-                            acc.max(&ui_sizes)
-                        });
+                                acc.max(&ui_sizes)
+                            });
 
                         *self.previous_ui_sizes.lock().unwrap() = Some(ui_sizes);
                     });
@@ -112,25 +322,53 @@ impl Log for EguiLogger {
     }
 
     fn log(&self, record: &log::Record) {
-        let max_number_of_records = 10000;
-
         if record.level() == Level::Error {
             self.force_open_logging_window.swap(true, Ordering::SeqCst);
         }
 
         if self.enabled(record.metadata()) {
             let mut records = self.records.lock().unwrap();
-            records.push_front(Record::new(record));
-
-            if records.len() > max_number_of_records {
-                records.truncate(max_number_of_records);
-            }
+            records.push(Record::new(record));
+            records.trim(self.max_records(), self.max_bytes());
         }
     }
 
     fn flush(&self) {}
 }
 
+impl Default for EguiLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Records of the [`EguiLogger`], newest first.
+#[derive(Default)]
+struct Records {
+    records: VecDeque<Record>,
+    /// Approximate number of bytes used by `records`.
+    num_bytes: usize,
+}
+
+impl Records {
+    /// Push the record as the newest record.
+    fn push(&mut self, record: Record) {
+        self.num_bytes += record.num_bytes();
+        self.records.push_front(record);
+    }
+
+    /// Drop the oldest records until there are at most `max_records`
+    /// records using at most approximately `max_bytes`.
+    fn trim(&mut self, max_records: usize, max_bytes: usize) {
+        while self.records.len() > max_records || self.num_bytes > max_bytes {
+            match self.records.pop_back() {
+                Some(record) => self.num_bytes -= record.num_bytes(),
+                None => break,
+            }
+        }
+    }
+}
+
 struct Record {
     level: log::Level,
     file: Option<String>,
@@ -148,6 +386,14 @@ impl Record {
         }
     }
 
+    /// Approximate number of bytes used by the record.
+    pub fn num_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.file.as_ref().map_or(0, |file| file.len())
+            + self.args.len()
+    }
+
+    #[cfg(feature = "gui")]
     pub fn draw_ui(&self, ui: &mut egui::Ui, previous_sizes: Option<&UiSizes>) -> UiSizes {
         ui.horizontal(|ui| {
             let color = match self.level {
@@ -208,6 +454,17 @@ impl Record {
     }
 }
 
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.level)?;
+        if let (Some(file), Some(line)) = (&self.file, &self.line) {
+            write!(f, " {}:{}", file, line)?;
+        }
+        write!(f, " {}", self.args)
+    }
+}
+
+#[cfg(feature = "gui")]
 #[derive(Debug)]
 struct UiSizes {
     level: egui::Vec2,
@@ -215,6 +472,7 @@ struct UiSizes {
     args: egui::Vec2,
 }
 
+#[cfg(feature = "gui")]
 impl UiSizes {
     pub fn new(level: egui::Vec2, file_line: egui::Vec2, args: egui::Vec2) -> Self {
         Self {
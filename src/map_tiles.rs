@@ -0,0 +1,54 @@
+//! Tiles of the map, downloaded from OpenStreetMap or the tile server
+//! of the config (eg: a self-hosted one) and cached on disk at
+//! [`file_ops::get_map_tiles_cache_dir()`], so that the tiles already
+//! seen are not downloaded again at every start.
+
+use crate::file_ops;
+
+/// Tile server whose URL is a template where `{z}`, `{x}` and `{y}`
+/// are replaced by the coordinates of the tile, eg:
+/// `https://tiles.example.com/{z}/{x}/{y}.png`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileServer {
+    pub url: String,
+}
+
+impl walkers::sources::TileSource for TileServer {
+    fn tile_url(&self, tile_id: walkers::TileId) -> String {
+        self.url
+            .replace("{z}", &tile_id.zoom.to_string())
+            .replace("{x}", &tile_id.x.to_string())
+            .replace("{y}", &tile_id.y.to_string())
+    }
+
+    fn attribution(&self) -> walkers::sources::Attribution {
+        // the tile servers are expected to serve OpenStreetMap data
+        walkers::sources::Attribution {
+            text: "OpenStreetMap contributors",
+            url: "https://www.openstreetmap.org/copyright",
+            logo_light: None,
+            logo_dark: None,
+        }
+    }
+}
+
+/// Create the cached tiles of the tile server at the URL (see
+/// [`TileServer`]), OpenStreetMap if the URL is empty.
+pub fn http_tiles(url: &str, ctx: egui::Context) -> walkers::HttpTiles {
+    let options = walkers::HttpOptions {
+        cache: file_ops::get_map_tiles_cache_dir().ok(),
+        ..Default::default()
+    };
+    if url.trim().is_empty() {
+        walkers::HttpTiles::with_options(walkers::sources::OpenStreetMap, options, ctx)
+    } else {
+        log::info!("map tile server: {}", url);
+        walkers::HttpTiles::with_options(
+            TileServer {
+                url: url.trim().to_string(),
+            },
+            options,
+            ctx,
+        )
+    }
+}
@@ -1,52 +1,360 @@
 use std::{
-    fmt::Display,
-    net::Ipv4Addr,
+    collections::{HashMap, HashSet},
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket},
+    sync::Arc,
     time::{Duration, Instant},
 };
 
-use icmp_socket::{packet::WithEchoRequest, IcmpSocket, IcmpSocket4, Icmpv4Message, Icmpv4Packet};
+use icmp_socket::{
+    packet::WithEchoRequest, IcmpSocket, IcmpSocket4, IcmpSocket6, Icmpv4Message, Icmpv4Packet,
+    Icmpv6Message, Icmpv6Packet,
+};
+
+use crate::firewall::PortRanges;
 
-#[derive(Debug)]
+/// Payload of the echo requests.
+const PAYLOAD: [u8; 56] = [
+    0x20, 0x20, 0x75, 0x73, 0x74, 0x20, 0x61, 0x20, 0x66, 0x6c, 0x65, 0x73, 0x68, 0x20, 0x77, 0x6f,
+    0x75, 0x6e, 0x64, 0x20, 0x20, 0x74, 0x69, 0x73, 0x20, 0x62, 0x75, 0x74, 0x20, 0x61, 0x20, 0x73,
+    0x63, 0x72, 0x61, 0x74, 0x63, 0x68, 0x20, 0x20, 0x6b, 0x6e, 0x69, 0x67, 0x68, 0x74, 0x73, 0x20,
+    0x6f, 0x66, 0x20, 0x6e, 0x69, 0x20, 0x20, 0x20,
+];
+
+#[derive(Debug, thiserror::Error)]
 pub enum Error {
+    /// The destination (or the network) is unreachable, or the time
+    /// to live was exceeded on the way.
+    #[error("Unreachable")]
     Unreachable,
-    IoError(std::io::Error),
+    /// The destination is unreachable because a firewall on the way
+    /// rejected the request (administratively prohibited).
+    #[error("Prohibited")]
+    Prohibited,
+    #[error("{0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Send Error")]
     SendError,
-    UnknownReturnAddress(Ipv4Addr),
+    #[error("Unknown Return Address {0}")]
+    UnknownReturnAddress(IpAddr),
+    /// The ICMP socket could not be created (eg: without the privilege
+    /// to open raw sockets), the IPv4 addresses cannot be pinged with
+    /// ICMP.
+    #[error("ICMP Unavailable")]
+    IcmpUnavailable,
+    /// The ICMPv6 socket could not be created (eg: IPv6 is disabled),
+    /// the IPv6 addresses cannot be pinged.
+    #[error("IPv6 Unavailable")]
+    Ipv6Unavailable,
+    /// The received packet or its return address could not be
+    /// interpreted.
+    #[error("Invalid Packet")]
+    InvalidPacket,
+    /// No reply within the timeout.
+    #[error("Timeout")]
+    Timeout,
+    /// The TCP and UDP probes need the relay port of the IP, it is
+    /// not in the network datagram config.
+    #[error("No Relay Port")]
+    NoRelayPort,
+}
+
+impl Error {
+    /// Create the error for an ICMP destination unreachable message
+    /// with the given code.
+    fn from_unreachable_code(code: u8) -> Self {
+        match code {
+            // network/host administratively prohibited, communication
+            // administratively prohibited
+            9 | 10 | 13 => Error::Prohibited,
+            _ => Error::Unreachable,
+        }
+    }
+
+    /// Create the error for an ICMPv6 destination unreachable message
+    /// with the given code.
+    fn from_unreachable_code_v6(code: u8) -> Self {
+        match code {
+            // administratively prohibited, source address failed
+            // ingress/egress policy, reject route to destination
+            1 | 5 | 6 => Error::Prohibited,
+            _ => Error::Unreachable,
+        }
+    }
+
+    /// Get the [`FailureCause`] of the error.
+    pub fn cause(&self) -> FailureCause {
+        match self {
+            Error::Timeout => FailureCause::Timeout,
+            Error::IoError(error)
+                if matches!(
+                    error.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                FailureCause::Timeout
+            }
+            Error::Unreachable => FailureCause::Unreachable,
+            Error::Prohibited => FailureCause::Prohibited,
+            Error::IoError(_)
+            | Error::SendError
+            | Error::UnknownReturnAddress(_)
+            | Error::IcmpUnavailable
+            | Error::Ipv6Unavailable
+            | Error::InvalidPacket
+            | Error::NoRelayPort => FailureCause::Socket,
+        }
+    }
+}
+
+/// Cause of a failed ping, see [`Error::cause()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FailureCause {
+    /// No reply within the timeout, the relay may be down or drop
+    /// ICMP.
+    Timeout,
+    /// A router reported the destination as unreachable.
+    Unreachable,
+    /// A firewall rejected the request, possibly the firewall of this
+    /// machine.
+    Prohibited,
+    /// The request could not be sent or the reply could not be read.
+    Socket,
+}
+
+impl FailureCause {
+    /// Get the name of the [`FailureCause`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            FailureCause::Timeout => "Timeout",
+            FailureCause::Unreachable => "Unreachable",
+            FailureCause::Prohibited => "Prohibited",
+            FailureCause::Socket => "Socket Error",
+        }
+    }
+
+    /// Get a description of the [`FailureCause`] for the user.
+    pub fn description(&self) -> &'static str {
+        match self {
+            FailureCause::Timeout => "No reply in time, the relay may be down or drop pings",
+            FailureCause::Unreachable => "A router reported the relay as unreachable",
+            FailureCause::Prohibited => {
+                "A firewall rejected the ping, possibly the firewall of this machine"
+            }
+            FailureCause::Socket => "The ping could not be sent or its reply could not be read",
+        }
+    }
 }
 
-impl Display for Error {
+impl std::fmt::Display for FailureCause {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Get the most common [`FailureCause`] of the failed pings, [`None`]
+/// if no ping failed. Ties go to the cause listed first in
+/// [`FailureCause`].
+pub fn dominant_failure_cause<'a>(
+    pings: impl IntoIterator<Item = &'a Result<PingInfo, Error>>,
+) -> Option<FailureCause> {
+    let mut counts: HashMap<FailureCause, usize> = HashMap::new();
+    pings
+        .into_iter()
+        .filter_map(|ping| ping.as_ref().err())
+        .for_each(|error| *counts.entry(error.cause()).or_default() += 1);
+    counts
+        .into_iter()
+        .max_by(|(cause_a, count_a), (cause_b, count_b)| {
+            count_a.cmp(count_b).then(cause_b.cmp(cause_a))
+        })
+        .map(|(cause, _)| cause)
+}
+
+/// Kind of probe that measured a ping.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ProbeKind {
+    /// ICMP echo request.
+    #[default]
+    Icmp,
+    /// TCP connection to a relay port, the connection being refused
+    /// is a reply too.
+    Tcp,
+    /// UDP datagram to a relay port, answered by the relay or by an
+    /// ICMP port unreachable.
+    Udp,
+}
+
+impl ProbeKind {
+    /// Get the name of the [`ProbeKind`].
+    pub fn name(&self) -> &'static str {
         match self {
-            Error::Unreachable => write!(f, "Unreachable"),
-            Error::IoError(error) => write!(f, "{}", error),
-            Error::SendError => write!(f, "Send Error"),
-            Error::UnknownReturnAddress(ipv4) => write!(f, "Unknown Return Address {}", ipv4),
+            ProbeKind::Icmp => "ICMP",
+            ProbeKind::Tcp => "TCP",
+            ProbeKind::Udp => "UDP",
         }
     }
+
+    /// Get the probe to fall back to when this one fails.
+    fn fallback(&self) -> Self {
+        match self {
+            ProbeKind::Icmp => ProbeKind::Tcp,
+            ProbeKind::Tcp => ProbeKind::Udp,
+            ProbeKind::Udp => ProbeKind::Icmp,
+        }
+    }
+}
+
+impl std::fmt::Display for ProbeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
 }
 
-impl std::error::Error for Error {}
+/// Probes used by [`Pinger::ping_many()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProbeMode {
+    /// ICMP, an IP whose ICMP pings fail falls back to TCP and then
+    /// UDP probes, for the networks that drop ICMP.
+    #[default]
+    Auto,
+    /// Only ICMP.
+    Icmp,
+    /// Only TCP probes to the relay ports.
+    Tcp,
+    /// Only UDP probes to the relay ports.
+    Udp,
+}
 
-impl From<std::io::Error> for Error {
-    fn from(error: std::io::Error) -> Self {
-        Self::IoError(error)
+impl ProbeMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProbeMode::Auto => "Auto",
+            ProbeMode::Icmp => "ICMP",
+            ProbeMode::Tcp => "TCP",
+            ProbeMode::Udp => "UDP",
+        }
+    }
+}
+
+impl std::fmt::Display for ProbeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Get the probe of the newest successful ping, the pings are ordered
+/// from the newest.
+pub fn latest_probe<'a>(
+    pings: impl IntoIterator<Item = &'a Result<PingInfo, Error>>,
+) -> Option<ProbeKind> {
+    pings
+        .into_iter()
+        .find_map(|ping| ping.as_ref().ok())
+        .map(PingInfo::probe)
+}
+
+/// Measure the round trip time to the relay port (the address) with a
+/// TCP connection or a UDP datagram, see [`ProbeKind`].
+fn probe_port(address: SocketAddr, probe: ProbeKind, timeout: Duration) -> Result<PingInfo, Error> {
+    let ip = address.ip();
+    let (send_time, res) = match probe {
+        ProbeKind::Icmp => return Err(Error::SendError),
+        ProbeKind::Tcp => {
+            let send_time = Instant::now();
+            let res = TcpStream::connect_timeout(&address, timeout).map(|_| ());
+            (send_time, res)
+        }
+        ProbeKind::Udp => {
+            let local: IpAddr = match ip {
+                IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+                IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+            };
+            let socket = UdpSocket::bind((local, 0))?;
+            crate::connections::register_probe_socket(&socket);
+            socket.connect(address)?;
+            socket.set_read_timeout(Some(timeout))?;
+            let send_time = Instant::now();
+            socket.send(&PAYLOAD).map_err(|_| Error::SendError)?;
+            (send_time, socket.recv(&mut [0; 64]).map(|_| ()))
+        }
+    };
+    match res {
+        Ok(()) => Ok(PingInfo::new(send_time.elapsed()).with_probe(probe)),
+        // a refused connection (or the port unreachable of the
+        // datagram) is a reply from the relay too
+        Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => {
+            Ok(PingInfo::new(send_time.elapsed()).with_probe(probe))
+        }
+        Err(error)
+            if matches!(
+                error.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Err(Error::Timeout)
+        }
+        Err(error) => Err(error.into()),
     }
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct PingInfo {
     rtt: Duration,
+    /// Number of echo requests of the sample, see
+    /// [`Pinger::ping_burst()`].
+    sent: u16,
+    /// Number of replies of the sample, at least one.
+    received: u16,
+    probe: ProbeKind,
 }
 
 impl PingInfo {
     pub fn new(rtt: Duration) -> Self {
-        Self { rtt }
+        Self {
+            rtt,
+            sent: 1,
+            received: 1,
+            probe: ProbeKind::Icmp,
+        }
+    }
+
+    /// Set the probe that measured the ping, ICMP by default.
+    pub fn with_probe(mut self, probe: ProbeKind) -> Self {
+        self.probe = probe;
+        self
+    }
+
+    /// Create the sample of a burst of `sent` echo requests that got
+    /// `received` replies, `rtt` is the mean of the replies.
+    pub fn from_burst(rtt: Duration, sent: u16, received: u16) -> Self {
+        Self {
+            rtt,
+            sent,
+            received,
+            probe: ProbeKind::Icmp,
+        }
     }
 
     /// Get ping info's rtt.
     pub fn get_rtt(&self) -> Duration {
         self.rtt
     }
+
+    /// Get the number of echo requests of the sample.
+    pub fn sent(&self) -> u16 {
+        self.sent
+    }
+
+    /// Get the number of replies of the sample.
+    pub fn received(&self) -> u16 {
+        self.received
+    }
+
+    /// Get the probe that measured the ping.
+    pub fn probe(&self) -> ProbeKind {
+        self.probe
+    }
 }
 
 impl std::fmt::Display for PingInfo {
@@ -55,19 +363,277 @@ impl std::fmt::Display for PingInfo {
     }
 }
 
+/// ICMP message received, the parts of the ICMPv4 and ICMPv6 messages
+/// the pingers use.
+enum Message {
+    EchoReply {
+        identifier: u16,
+        sequence: u16,
+    },
+    /// ICMP error, along with the echo request that caused it if it
+    /// could be read.
+    Error {
+        error: Error,
+        request: Option<EchoRequest>,
+    },
+    /// Any other message, eg: the echo requests of the other pingers.
+    Other,
+}
+
+impl Message {
+    /// Get the identifier of the echo request the message is about,
+    /// [`None`] for the other messages.
+    fn identifier(&self) -> Option<u16> {
+        match self {
+            Message::EchoReply { identifier, .. } => Some(*identifier),
+            Message::Error { request, .. } => request.map(|request| request.identifier),
+            Message::Other => None,
+        }
+    }
+
+    /// Get the pinged IP the message is about, the errors come from
+    /// the router that dropped the request so it is the destination of
+    /// their request. `address` is the address the message came from.
+    fn target(&self, address: IpAddr) -> IpAddr {
+        match self {
+            Message::Error {
+                request: Some(request),
+                ..
+            } => request.destination,
+            _ => address,
+        }
+    }
+}
+
+/// Echo request included in an ICMP error message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EchoRequest {
+    destination: IpAddr,
+    identifier: u16,
+    sequence: u16,
+}
+
+/// ICMP socket of either IP version.
+enum Socket {
+    V4(IcmpSocket4),
+    V6(IcmpSocket6),
+}
+
+impl Socket {
+    fn new_v4() -> Result<Self, Error> {
+        let mut socket = IcmpSocket4::new()?;
+        socket.bind(Ipv4Addr::UNSPECIFIED)?;
+        Ok(Socket::V4(socket))
+    }
+
+    fn new_v6() -> Result<Self, Error> {
+        let mut socket = IcmpSocket6::new()?;
+        socket.bind(Ipv6Addr::UNSPECIFIED)?;
+        Ok(Socket::V6(socket))
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        match self {
+            Socket::V4(socket) => socket.set_timeout(Some(timeout)),
+            Socket::V6(socket) => socket.set_timeout(Some(timeout)),
+        }
+    }
+
+    /// Send an echo request to the IP, which must be of the IP
+    /// version of the socket.
+    fn send_echo_request(
+        &mut self,
+        ip: IpAddr,
+        identifier: u16,
+        sequence: u16,
+    ) -> Result<(), Error> {
+        match (self, ip) {
+            (Socket::V4(socket), IpAddr::V4(ip)) => {
+                let packet =
+                    Icmpv4Packet::with_echo_request(identifier, sequence, PAYLOAD.to_vec())
+                        .map_err(|_| Error::SendError)?;
+                socket.send_to(ip, packet).map_err(|_| Error::SendError)
+            }
+            (Socket::V6(socket), IpAddr::V6(ip)) => {
+                let packet =
+                    Icmpv6Packet::with_echo_request(identifier, sequence, PAYLOAD.to_vec())
+                        .map_err(|_| Error::SendError)?;
+                socket.send_to(ip, packet).map_err(|_| Error::SendError)
+            }
+            _ => Err(Error::SendError),
+        }
+    }
+
+    /// Receive a message, returns it along with the address it came
+    /// from.
+    fn receive(&mut self) -> Result<(Message, IpAddr), Error> {
+        match self {
+            Socket::V4(socket) => {
+                let (packet, address) = socket.rcv_from()?;
+                let address = *address.as_socket_ipv4().ok_or(Error::InvalidPacket)?.ip();
+                let message = match packet.message {
+                    Icmpv4Message::EchoReply {
+                        identifier,
+                        sequence,
+                        ..
+                    } => Message::EchoReply {
+                        identifier,
+                        sequence,
+                    },
+                    // the header of the request is included in the
+                    // errors
+                    Icmpv4Message::Unreachable { header, .. } => Message::Error {
+                        error: Error::from_unreachable_code(packet.code),
+                        request: echo_request(&header),
+                    },
+                    Icmpv4Message::TimeExceeded { header, .. } => Message::Error {
+                        error: Error::Unreachable,
+                        request: echo_request(&header),
+                    },
+                    // echo requests, redirects, timestamps, ...
+                    _ => Message::Other,
+                };
+                Ok((message, address.into()))
+            }
+            Socket::V6(socket) => {
+                let (packet, address) = socket.rcv_from()?;
+                let address = *address.as_socket_ipv6().ok_or(Error::InvalidPacket)?.ip();
+                let message = match packet.message {
+                    Icmpv6Message::EchoReply {
+                        identifier,
+                        sequence,
+                        ..
+                    } => Message::EchoReply {
+                        identifier,
+                        sequence,
+                    },
+                    Icmpv6Message::Unreachable {
+                        invoking_packet, ..
+                    } => Message::Error {
+                        error: Error::from_unreachable_code_v6(packet.code),
+                        request: echo_request_v6(&invoking_packet),
+                    },
+                    Icmpv6Message::TimeExceeded {
+                        invoking_packet, ..
+                    } => Message::Error {
+                        error: Error::Unreachable,
+                        request: echo_request_v6(&invoking_packet),
+                    },
+                    Icmpv6Message::PacketTooBig { .. }
+                    | Icmpv6Message::ParameterProblem { .. }
+                    | Icmpv6Message::PrivateExperimental { .. }
+                    | Icmpv6Message::EchoRequest { .. } => Message::Other,
+                };
+                Ok((message, address.into()))
+            }
+        }
+    }
+}
+
+/// Create the ICMPv6 socket, [`None`] if IPv6 is not available, only
+/// the IPv4 addresses can be pinged then.
+fn new_v4_socket() -> Option<Socket> {
+    Socket::new_v4()
+        .map_err(|err| log::warn!("unable to create the ICMP socket: {}", err))
+        .ok()
+}
+
+fn new_v6_socket() -> Option<Socket> {
+    Socket::new_v6()
+        .map_err(|err| log::warn!("unable to create the ICMPv6 socket: {}", err))
+        .ok()
+}
+
+/// Echo requests of an IP sent by [`Pinger::ping_bursts()`].
+#[derive(Debug, Default)]
+struct Burst {
+    /// Send time of the requests waiting for their reply, by sequence
+    /// number.
+    send_times: HashMap<u16, Instant>,
+    sent: u16,
+    rtts: Vec<Duration>,
+    first_error: Option<Error>,
+}
+
+impl Burst {
+    /// Get the sample of the burst, see [`Pinger::ping_burst()`].
+    fn into_result(self) -> Result<PingInfo, Error> {
+        if self.rtts.is_empty() {
+            return Err(self.first_error.unwrap_or(Error::Timeout));
+        }
+        let received = self.rtts.len() as u16;
+        Ok(PingInfo::from_burst(
+            self.rtts.iter().sum::<Duration>() / u32::from(received),
+            self.sent,
+            received,
+        ))
+    }
+}
+
 pub struct Pinger {
-    socket: IcmpSocket4,
+    /// [`None`] if ICMP is not available (eg: running unprivileged),
+    /// the IPs then fall back to the port probes with
+    /// [`ProbeMode::Auto`].
+    socket: Option<Socket>,
+    /// [`None`] if IPv6 is not available.
+    socket6: Option<Socket>,
 
     timeout: Duration,
+    /// Sequence number of the next echo request of every IP, see
+    /// [`Self::ping_many()`].
+    sequences: HashMap<IpAddr, u16>,
+    /// Identifier of the echo requests of the next batch, see
+    /// [`Self::ping_bursts()`].
+    next_identifier: u16,
+    mode: ProbeMode,
+    /// Probe of the IPs that fell back from ICMP with
+    /// [`ProbeMode::Auto`].
+    fallbacks: HashMap<IpAddr, ProbeKind>,
+    /// Relay ports of the IPs, probed by the TCP and UDP probes, see
+    /// [`Self::set_port_ranges()`].
+    port_ranges: PortRanges,
 }
 
 impl Pinger {
-    pub fn new() -> Self {
-        let mut socket = IcmpSocket4::new().unwrap();
-        socket.bind("0.0.0.0".parse::<Ipv4Addr>().unwrap()).unwrap();
-        Self {
-            socket,
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            socket: new_v4_socket(),
+            socket6: new_v6_socket(),
             timeout: Duration::from_secs(2),
+            sequences: HashMap::new(),
+            // distinct from the identifiers of the other pingers
+            // running on the system, most of them use their process id
+            next_identifier: std::process::id() as u16,
+            mode: ProbeMode::default(),
+            fallbacks: HashMap::new(),
+            port_ranges: PortRanges::new(),
+        })
+    }
+
+    /// Set the probes used by [`Self::ping_many()`].
+    pub fn set_mode(&mut self, mode: ProbeMode) {
+        self.mode = mode;
+        self.fallbacks.clear();
+    }
+
+    pub fn mode(&self) -> ProbeMode {
+        self.mode
+    }
+
+    /// Set the UDP port ranges of the relay IPs, replacing the
+    /// previous ones. The TCP and UDP probes of an IP need its port
+    /// range, see [`crate::steam_server::Servers::port_ranges()`].
+    pub fn set_port_ranges(&mut self, port_ranges: PortRanges) {
+        self.port_ranges = port_ranges;
+    }
+
+    /// Get the probe of the next ping of the IP.
+    fn probe_kind(&self, ip: IpAddr) -> ProbeKind {
+        match self.mode {
+            ProbeMode::Auto => self.fallbacks.get(&ip).copied().unwrap_or_default(),
+            ProbeMode::Icmp => ProbeKind::Icmp,
+            ProbeMode::Tcp => ProbeKind::Tcp,
+            ProbeMode::Udp => ProbeKind::Udp,
         }
     }
 
@@ -75,50 +641,434 @@ impl Pinger {
         self.timeout = timeout;
     }
 
-    pub fn ping(&mut self, ipv4: impl Into<Ipv4Addr>, sequence: u16) -> Result<PingInfo, Error> {
-        let ipv4 = ipv4.into();
-        let packet = Icmpv4Packet::with_echo_request(
-            42,
-            sequence,
-            vec![
-                0x20, 0x20, 0x75, 0x73, 0x74, 0x20, 0x61, 0x20, 0x66, 0x6c, 0x65, 0x73, 0x68, 0x20,
-                0x77, 0x6f, 0x75, 0x6e, 0x64, 0x20, 0x20, 0x74, 0x69, 0x73, 0x20, 0x62, 0x75, 0x74,
-                0x20, 0x61, 0x20, 0x73, 0x63, 0x72, 0x61, 0x74, 0x63, 0x68, 0x20, 0x20, 0x6b, 0x6e,
-                0x69, 0x67, 0x68, 0x74, 0x73, 0x20, 0x6f, 0x66, 0x20, 0x6e, 0x69, 0x20, 0x20, 0x20,
-            ],
-        )
-        .unwrap();
-
-        let send_time = Instant::now();
-        self.socket
-            .send_to(ipv4, packet)
-            .map_err(|_| Error::SendError)?;
-
-        self.socket.set_timeout(Some(self.timeout));
-
-        self.socket
-            .rcv_from()
-            .map_err(|error| error.into())
-            .and_then(|(packet, address)| {
-                let address = *address.as_socket_ipv4().unwrap().ip();
-                if address == ipv4 {
-                    Ok(packet)
-                } else {
-                    Err(Error::UnknownReturnAddress(address))
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Get the socket of the IP version of the IP.
+    fn socket(&mut self, ip: IpAddr) -> Result<&mut Socket, Error> {
+        match ip {
+            IpAddr::V4(_) => self.socket.as_mut().ok_or(Error::IcmpUnavailable),
+            IpAddr::V6(_) => self.socket6.as_mut().ok_or(Error::Ipv6Unavailable),
+        }
+    }
+
+    /// Send an echo request to the IP and wait for its reply.
+    pub fn ping(&mut self, ip: impl Into<IpAddr>, sequence: u16) -> Result<PingInfo, Error> {
+        self.ping_burst(ip, 1, sequence)
+    }
+
+    /// Send `count` echo requests to the IP as a burst, with the
+    /// sequence numbers from `first_sequence` on, and aggregate the
+    /// replies into one sample with the mean rtt of the replies. Fails
+    /// only if none of the requests got a reply.
+    pub fn ping_burst(
+        &mut self,
+        ip: impl Into<IpAddr>,
+        count: u16,
+        first_sequence: u16,
+    ) -> Result<PingInfo, Error> {
+        let ip = ip.into();
+        self.ping_bursts(&[(ip, first_sequence)], count)
+            .pop()
+            .map_or(Err(Error::Timeout), |(_, res)| res)
+    }
+
+    /// Ping all the IPs concurrently with the probes of the
+    /// [`ProbeMode`]. With [`ProbeMode::Auto`], an IP whose ping fails
+    /// is probed with the next [`ProbeKind`] the next time, until one
+    /// gets replies. Only the ICMP pings are bursts of `count`
+    /// requests, see [`Self::ping_icmp_many()`].
+    pub fn ping_many(
+        &mut self,
+        ips: &[IpAddr],
+        count: u16,
+    ) -> Vec<(IpAddr, Result<PingInfo, Error>)> {
+        let (icmp_ips, port_ips): (Vec<_>, Vec<_>) = ips
+            .iter()
+            .copied()
+            .partition(|ip| self.probe_kind(*ip) == ProbeKind::Icmp);
+        let mut results = self.ping_icmp_many(&icmp_ips, count);
+
+        // the port probes block, one thread per probe
+        let timeout = self.timeout;
+        let probes: Vec<_> = port_ips
+            .into_iter()
+            .map(|ip| {
+                let port = self
+                    .port_ranges
+                    .get(&ip)
+                    .map(|port_range| *port_range.start());
+                (ip, port, self.probe_kind(ip))
+            })
+            .collect();
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = probes
+                .into_iter()
+                .map(|(ip, port, probe)| {
+                    let handle = scope.spawn(move || {
+                        let port = port.ok_or(Error::NoRelayPort)?;
+                        probe_port(SocketAddr::new(ip, port), probe, timeout)
+                    });
+                    (ip, handle)
+                })
+                .collect();
+            results.extend(handles.into_iter().map(|(ip, handle)| {
+                let res = handle.join().unwrap_or(Err(Error::SendError));
+                (ip, res)
+            }));
+        });
+
+        if self.mode == ProbeMode::Auto {
+            results.iter().for_each(|(ip, res)| {
+                if let Err(error) = res {
+                    let probe = self.probe_kind(*ip);
+                    // without a relay port, only ICMP is left
+                    let fallback = match error {
+                        Error::NoRelayPort => ProbeKind::Icmp,
+                        _ => probe.fallback(),
+                    };
+                    if fallback != probe {
+                        log::debug!("pinging {} with {} probes", ip, fallback);
+                    }
+                    if fallback == ProbeKind::Icmp {
+                        self.fallbacks.remove(ip);
+                    } else {
+                        self.fallbacks.insert(*ip, fallback);
+                    }
                 }
+            });
+        }
+        results
+    }
+
+    /// Ping all the IPs concurrently, `count` echo requests each
+    /// aggregated into one sample (see [`Self::ping_burst()`]). All
+    /// the requests are sent before waiting for the replies, so
+    /// pinging many IPs takes about as long as pinging one. Every IP
+    /// has its own sequence numbers.
+    ///
+    /// note: the requests are multiplexed on the blocking socket of
+    /// each IP version rather than awaited by a tokio task per IP, the
+    /// worker thread that pings is not async and one socket needs no
+    /// runtime, see `asynchronous::ping_stream()` (with the `async`
+    /// feature) for the async API.
+    pub fn ping_icmp_many(
+        &mut self,
+        ips: &[IpAddr],
+        count: u16,
+    ) -> Vec<(IpAddr, Result<PingInfo, Error>)> {
+        let count = count.max(1);
+        let mut seen = HashSet::new();
+        let bursts: Vec<_> = ips
+            .iter()
+            .copied()
+            .filter(|ip| seen.insert(*ip))
+            .map(|ip| {
+                let next_sequence = self.sequences.entry(ip).or_insert(0);
+                let first_sequence = *next_sequence;
+                *next_sequence = next_sequence.wrapping_add(count);
+                (ip, first_sequence)
             })
-            .and_then(|packet| {
-                if let Icmpv4Message::EchoReply { .. } = packet.message {
-                    Ok(PingInfo::new(send_time.elapsed()))
-                } else {
-                    Err(Error::Unreachable)
+            .collect();
+        self.ping_bursts(&bursts, count)
+    }
+
+    /// Get the identifier of the echo requests of the next batch.
+    fn new_identifier(&mut self) -> u16 {
+        let identifier = self.next_identifier;
+        self.next_identifier = self.next_identifier.wrapping_add(1);
+        identifier
+    }
+
+    /// Send a burst of `count` echo requests to every IP, with the
+    /// sequence numbers from the first sequence number of the IP on,
+    /// then wait for the replies. The requests of the batch share an
+    /// identifier of their own, the replies are matched to the
+    /// requests by their identifier, address and sequence number.
+    fn ping_bursts(
+        &mut self,
+        ips: &[(IpAddr, u16)],
+        count: u16,
+    ) -> Vec<(IpAddr, Result<PingInfo, Error>)> {
+        // time slice of a socket while the replies of both IP
+        // versions are awaited
+        const SOCKET_SLICE: Duration = Duration::from_millis(10);
+
+        let identifier = self.new_identifier();
+        let mut bursts: HashMap<IpAddr, Burst> = HashMap::new();
+        for (ip, first_sequence) in ips.iter().copied() {
+            let mut burst = Burst::default();
+            match self.socket(ip) {
+                Ok(socket) => {
+                    for index in 0..count {
+                        let sequence = first_sequence.wrapping_add(index);
+                        let send_time = Instant::now();
+                        if let Err(error) = socket.send_echo_request(ip, identifier, sequence) {
+                            burst.first_error = Some(error);
+                            break;
+                        }
+                        burst.send_times.insert(sequence, send_time);
+                        burst.sent += 1;
+                    }
                 }
+                Err(error) => burst.first_error = Some(error),
+            }
+            bursts.insert(ip, burst);
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let waiting = |is_ipv4: bool| {
+                bursts
+                    .iter()
+                    .any(|(ip, burst)| ip.is_ipv4() == is_ipv4 && !burst.send_times.is_empty())
+            };
+            let (waiting_v4, waiting_v6) = (waiting(true), waiting(false));
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if (!waiting_v4 && !waiting_v6) || remaining.is_zero() {
+                break;
+            }
+            let socket_timeout = if waiting_v4 && waiting_v6 {
+                remaining.min(SOCKET_SLICE)
+            } else {
+                remaining
+            };
+
+            let sockets = [
+                self.socket.as_mut().filter(|_| waiting_v4),
+                self.socket6.as_mut().filter(|_| waiting_v6),
+            ];
+            for socket in sockets.into_iter().flatten() {
+                socket.set_timeout(socket_timeout);
+                let (message, address) = match socket.receive() {
+                    Ok(message) => message,
+                    // no message within the time slice
+                    Err(Error::IoError(error))
+                        if matches!(
+                            error.kind(),
+                            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                        ) =>
+                    {
+                        continue;
+                    }
+                    Err(error) => {
+                        // the socket is unusable, its IPs get no more
+                        // replies
+                        let is_ipv4 = matches!(socket, Socket::V4(_));
+                        let mut error = Some(error);
+                        bursts
+                            .iter_mut()
+                            .filter(|(ip, _)| ip.is_ipv4() == is_ipv4)
+                            .for_each(|(_, burst)| {
+                                burst.send_times.clear();
+                                if burst.first_error.is_none() {
+                                    burst.first_error = error.take();
+                                }
+                            });
+                        continue;
+                    }
+                };
+                // replies to the requests of the other batches and
+                // pingers
+                if message.identifier() != Some(identifier) {
+                    continue;
+                }
+                let Some(burst) = bursts.get_mut(&message.target(address)) else {
+                    continue;
+                };
+                match message {
+                    Message::EchoReply { sequence, .. } => {
+                        if let Some(send_time) = burst.send_times.remove(&sequence) {
+                            burst.rtts.push(send_time.elapsed());
+                        }
+                    }
+                    // no reply follows the error
+                    Message::Error {
+                        error,
+                        request: Some(request),
+                    } => {
+                        burst.first_error.get_or_insert(error);
+                        burst.send_times.remove(&request.sequence);
+                    }
+                    Message::Error { request: None, .. } | Message::Other => {}
+                }
+            }
+        }
+
+        ips.iter()
+            .filter_map(|(ip, _)| Some((*ip, bursts.remove(ip)?.into_result())))
+            .collect()
+    }
+}
+
+/// Pinger of the worker thread, see
+/// [`crate::worker::Worker::new()`].
+pub trait PingerRequirements: Send {
+    fn set_timeout(&mut self, timeout: Duration);
+
+    fn timeout(&self) -> Duration;
+
+    /// Set the probes used by [`Self::ping_many()`].
+    fn set_mode(&mut self, mode: ProbeMode);
+
+    fn mode(&self) -> ProbeMode;
+
+    /// Set the UDP port ranges of the relay IPs, see
+    /// [`Pinger::set_port_ranges()`]. Ignored by default.
+    fn set_port_ranges(&mut self, _port_ranges: PortRanges) {}
+
+    /// Ping all the IPs concurrently, `count` requests each aggregated
+    /// into one sample, see [`Pinger::ping_many()`].
+    fn ping_many(&mut self, ips: &[IpAddr], count: u16) -> Vec<(IpAddr, Result<PingInfo, Error>)>;
+}
+
+impl PingerRequirements for Pinger {
+    fn set_timeout(&mut self, timeout: Duration) {
+        Pinger::set_timeout(self, timeout)
+    }
+
+    fn timeout(&self) -> Duration {
+        Pinger::timeout(self)
+    }
+
+    fn set_mode(&mut self, mode: ProbeMode) {
+        Pinger::set_mode(self, mode)
+    }
+
+    fn mode(&self) -> ProbeMode {
+        Pinger::mode(self)
+    }
+
+    fn set_port_ranges(&mut self, port_ranges: PortRanges) {
+        Pinger::set_port_ranges(self, port_ranges)
+    }
+
+    fn ping_many(&mut self, ips: &[IpAddr], count: u16) -> Vec<(IpAddr, Result<PingInfo, Error>)> {
+        Pinger::ping_many(self, ips, count)
+    }
+}
+
+/// Creates the pinger of the worker thread, called again when the
+/// worker thread is restarted.
+pub type PingerFactory = Arc<dyn Fn() -> Result<Box<dyn PingerRequirements>, Error> + Send + Sync>;
+
+/// Get the [`PingerFactory`] of [`Pinger`].
+pub fn pinger_factory() -> PingerFactory {
+    Arc::new(|| Ok(Box::new(Pinger::new()?)))
+}
+
+/// Pinger whose pings all succeed with the same round trip time,
+/// nothing is sent. Useful for testing and embedding without the
+/// network or elevated privileges.
+#[derive(Debug, Clone)]
+pub struct MockPinger {
+    rtt: Duration,
+    timeout: Duration,
+    mode: ProbeMode,
+}
+
+impl MockPinger {
+    /// Create a new [`MockPinger`] replying after `rtt`.
+    pub fn new(rtt: Duration) -> Self {
+        Self {
+            rtt,
+            timeout: Duration::from_secs(2),
+            mode: ProbeMode::default(),
+        }
+    }
+}
+
+impl PingerRequirements for MockPinger {
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    fn set_mode(&mut self, mode: ProbeMode) {
+        self.mode = mode;
+    }
+
+    fn mode(&self) -> ProbeMode {
+        self.mode
+    }
+
+    fn ping_many(&mut self, ips: &[IpAddr], count: u16) -> Vec<(IpAddr, Result<PingInfo, Error>)> {
+        ips.iter()
+            .map(|ip| {
+                let res = if self.rtt < self.timeout {
+                    Ok(PingInfo::from_burst(self.rtt, count.max(1), count.max(1)))
+                } else {
+                    Err(Error::Timeout)
+                };
+                (*ip, res)
             })
+            .collect()
+    }
+}
+
+/// Get the echo request from the IP header (and the start of the
+/// payload) included in an ICMP error message.
+fn echo_request(header: &[u8]) -> Option<EchoRequest> {
+    let ip_header_len = usize::from(header.first()? & 0x0f) * 4;
+    let destination: [u8; 4] = header.get(16..20)?.try_into().ok()?;
+    let icmp = header.get(ip_header_len..ip_header_len + 8)?;
+    // type 8 is an echo request
+    if icmp[0] != 8 {
+        return None;
     }
+    Some(EchoRequest {
+        destination: Ipv4Addr::from(destination).into(),
+        identifier: u16::from_be_bytes([icmp[4], icmp[5]]),
+        sequence: u16::from_be_bytes([icmp[6], icmp[7]]),
+    })
 }
 
-impl Default for Pinger {
-    fn default() -> Self {
-        Self::new()
+/// Get the echo request from the invoking packet included in an
+/// ICMPv6 error message. The extension headers are not followed, the
+/// echo requests have none.
+fn echo_request_v6(invoking_packet: &[u8]) -> Option<EchoRequest> {
+    const IPV6_HEADER_LEN: usize = 40;
+    let destination: [u8; 16] = invoking_packet.get(24..40)?.try_into().ok()?;
+    let icmp = invoking_packet.get(IPV6_HEADER_LEN..IPV6_HEADER_LEN + 8)?;
+    // type 128 is an echo request
+    if icmp[0] != 128 {
+        return None;
+    }
+    Some(EchoRequest {
+        destination: Ipv6Addr::from(destination).into(),
+        identifier: u16::from_be_bytes([icmp[4], icmp[5]]),
+        sequence: u16::from_be_bytes([icmp[6], icmp[7]]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_request_of_the_errors() {
+        // IPv4 header (no options) to 155.133.226.75 and the echo
+        // request with the identifier 42 and the sequence number 7
+        let mut header = vec![0x45, 0, 0, 84, 0, 0, 0x40, 0, 64, 1, 0, 0];
+        header.extend([192, 168, 1, 2, 155, 133, 226, 75]);
+        header.extend([8, 0, 0, 0, 0, 42, 0, 7]);
+        assert_eq!(
+            echo_request(&header),
+            Some(EchoRequest {
+                destination: "155.133.226.75".parse().unwrap(),
+                identifier: 42,
+                sequence: 7,
+            })
+        );
+
+        // not an echo request
+        header[20] = 0;
+        assert_eq!(echo_request(&header), None);
+        // truncated
+        assert_eq!(echo_request(&header[..24]), None);
     }
 }
@@ -0,0 +1,322 @@
+//! Reachability check of the UDP ports of the relays.
+//!
+//! A relay can answer ICMP while its game ports are filtered (eg: by
+//! the ISP). A sample of the advertised port range of every IP of the
+//! region is probed with a UDP datagram:
+//!
+//! * the relays do not answer datagrams they do not understand, so no
+//!   response is the expected outcome for a reachable port,
+//! * an ICMP error (port/host unreachable, administratively
+//!   prohibited) for the probe means the port is rejected on the way
+//!   or by the relay.
+//!
+//! A silent drop by a filter cannot be told apart from a reachable
+//! port, so only the rejections are reported.
+
+use std::{
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    ops::RangeInclusive,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{connections, steam_server::ServerInfo, waker::Waker, Error};
+
+/// Number of ports probed in the port range of every IP.
+pub const PORTS_PER_IP: usize = 3;
+
+/// Time to wait for the ICMP errors of the probes.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Result of the probe of a port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortStatus {
+    /// The relay answered the probe.
+    Responded,
+    /// No response, the port is presumed to be reachable.
+    NoResponse,
+    /// The probe was rejected with an ICMP error.
+    Rejected,
+}
+
+impl PortStatus {
+    /// Get the name of the [`PortStatus`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            PortStatus::Responded => "Responded",
+            PortStatus::NoResponse => "No Response",
+            PortStatus::Rejected => "Rejected",
+        }
+    }
+}
+
+impl std::fmt::Display for PortStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Summary of the port checks of a region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionPortStatus {
+    /// No probe was rejected.
+    Reachable,
+    /// Some of the probes were rejected.
+    PartlyRejected,
+    /// All the probes were rejected, the region is effectively
+    /// unusable.
+    Unusable,
+    /// No port could be probed.
+    Unknown,
+}
+
+impl RegionPortStatus {
+    /// Get the name of the [`RegionPortStatus`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            RegionPortStatus::Reachable => "Reachable",
+            RegionPortStatus::PartlyRejected => "Partly Rejected",
+            RegionPortStatus::Unusable => "Unusable",
+            RegionPortStatus::Unknown => "Unknown",
+        }
+    }
+}
+
+impl std::fmt::Display for RegionPortStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Port checks of a region.
+#[derive(Debug, Clone)]
+pub struct RegionPortReport {
+    /// Abreviation of the region.
+    pub region: String,
+    /// Status of every probed port, ports whose probe could not be
+    /// sent are left out.
+    pub ports: Vec<(SocketAddr, PortStatus)>,
+}
+
+impl RegionPortReport {
+    /// Get the [`RegionPortStatus`] of the region.
+    pub fn status(&self) -> RegionPortStatus {
+        let num_rejected = self
+            .ports
+            .iter()
+            .filter(|(_, status)| *status == PortStatus::Rejected)
+            .count();
+        if self.ports.is_empty() {
+            RegionPortStatus::Unknown
+        } else if num_rejected == self.ports.len() {
+            RegionPortStatus::Unusable
+        } else if num_rejected != 0 {
+            RegionPortStatus::PartlyRejected
+        } else {
+            RegionPortStatus::Reachable
+        }
+    }
+
+    /// Get the rejected ports.
+    pub fn rejected(&self) -> impl Iterator<Item = &SocketAddr> {
+        self.ports
+            .iter()
+            .filter(|(_, status)| *status == PortStatus::Rejected)
+            .map(|(addr, _)| addr)
+    }
+}
+
+/// Get `num_samples` ports spread evenly over the range, the ends of
+/// the range are included.
+pub fn sample_ports(port_range: &RangeInclusive<u16>, num_samples: usize) -> Vec<u16> {
+    let (start, end) = (*port_range.start(), *port_range.end());
+    let len = usize::from(end - start) + 1;
+    if num_samples >= len {
+        return port_range.clone().collect();
+    }
+    match num_samples {
+        0 => Vec::new(),
+        1 => vec![start],
+        _ => (0..num_samples)
+            .map(|i| start + u16::try_from(i * (len - 1) / (num_samples - 1)).unwrap())
+            .collect(),
+    }
+}
+
+/// Get the addresses to probe for the server, see [`sample_ports()`].
+pub fn targets(server: &ServerInfo, ports_per_ip: usize) -> Vec<SocketAddr> {
+    server
+        .get_ips()
+        .iter()
+        .filter_map(|ip| Some((ip, server.port_range(ip)?)))
+        .flat_map(|(ip, port_range)| {
+            sample_ports(port_range, ports_per_ip)
+                .into_iter()
+                .map(|port| SocketAddr::new(*ip, port))
+        })
+        .collect()
+}
+
+/// Probe the addresses, all the probes are sent before waiting for
+/// the responses so that it takes about `timeout`.
+pub fn check_ports(targets: &[SocketAddr], timeout: Duration) -> Vec<(SocketAddr, PortStatus)> {
+    let sockets: Vec<_> = targets
+        .iter()
+        .filter_map(|addr| {
+            let send_probe = || -> Result<UdpSocket, Error> {
+                let unspecified = match addr {
+                    SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+                    SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+                };
+                let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0))?;
+                connections::register_probe_socket(&socket);
+                // the ICMP errors are only reported on connected
+                // sockets
+                socket.connect(addr)?;
+                socket.send(&[0; 4])?;
+                Ok(socket)
+            };
+            send_probe()
+                .map_err(|err| log::warn!("unable to probe {}: {}", addr, err))
+                .ok()
+                .map(|socket| (*addr, socket))
+        })
+        .collect();
+
+    let deadline = Instant::now() + timeout;
+    sockets
+        .into_iter()
+        .map(|(addr, socket)| {
+            let remaining = deadline
+                .saturating_duration_since(Instant::now())
+                .max(Duration::from_millis(1));
+            let mut buf = [0; 64];
+            let status = match socket
+                .set_read_timeout(Some(remaining))
+                .and_then(|_| socket.recv(&mut buf))
+            {
+                Ok(_) => PortStatus::Responded,
+                Err(err) => match err.kind() {
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => PortStatus::NoResponse,
+                    io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::HostUnreachable
+                    | io::ErrorKind::NetworkUnreachable
+                    | io::ErrorKind::PermissionDenied => PortStatus::Rejected,
+                    _ => {
+                        log::warn!("unable to check {}: {}", addr, err);
+                        PortStatus::NoResponse
+                    }
+                },
+            };
+            (addr, status)
+        })
+        .collect()
+}
+
+/// Checks the ports of the regions on a separate thread, one region
+/// at a time.
+pub struct PortChecker {
+    kill_sender: mpsc::Sender<()>,
+    /// Is [`Some`] until the [`PortChecker`] is dropped.
+    request_sender: Option<mpsc::Sender<(String, Vec<SocketAddr>)>>,
+    report_receiver: mpsc::Receiver<RegionPortReport>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+}
+
+impl PortChecker {
+    /// Start the thread, `waker` is woken up after every region is
+    /// checked.
+    pub fn new(waker: Waker) -> Self {
+        let (kill_sender, kill_receiver) = mpsc::channel();
+        let (request_sender, request_receiver) = mpsc::channel::<(String, Vec<SocketAddr>)>();
+        let (report_sender, report_receiver) = mpsc::channel();
+
+        let thread_handle = thread::spawn(move || {
+            while let Ok((region, targets)) = request_receiver.recv() {
+                // skip the queued regions once killed
+                if !matches!(kill_receiver.try_recv(), Err(mpsc::TryRecvError::Empty)) {
+                    break;
+                }
+                let report = RegionPortReport {
+                    ports: check_ports(&targets, PROBE_TIMEOUT),
+                    region,
+                };
+                log::info!(
+                    "ports of {}: {} ({} rejected of {})",
+                    report.region,
+                    report.status(),
+                    report.rejected().count(),
+                    report.ports.len()
+                );
+                if report_sender.send(report).is_err() {
+                    break;
+                }
+                waker.wake();
+            }
+        });
+
+        Self {
+            kill_sender,
+            request_sender: Some(request_sender),
+            report_receiver,
+            thread_handle: Some(thread_handle),
+        }
+    }
+
+    /// Queue the check of the ports of the server.
+    pub fn check(&self, server: &ServerInfo) {
+        let request = (server.get_abr().to_string(), targets(server, PORTS_PER_IP));
+        let sent = self
+            .request_sender
+            .as_ref()
+            .is_some_and(|request_sender| request_sender.send(request).is_ok());
+        if !sent {
+            log::error!("port checker thread is not running");
+        }
+    }
+
+    /// Get an iterator over the reports of the regions checked since
+    /// the last call.
+    pub fn try_iter(&self) -> mpsc::TryIter<'_, RegionPortReport> {
+        self.report_receiver.try_iter()
+    }
+}
+
+impl Drop for PortChecker {
+    fn drop(&mut self) {
+        let _ = self.kill_sender.send(());
+        // wakes up the thread if it is waiting for a request
+        self.request_sender.take();
+        if let Some(thread_handle) = self.thread_handle.take() {
+            if thread_handle.join().is_err() {
+                log::error!("port checker thread panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_include_the_ends() {
+        assert_eq!(sample_ports(&(27015..=27060), 0), Vec::<u16>::new());
+        assert_eq!(sample_ports(&(27015..=27060), 1), [27015]);
+        assert_eq!(sample_ports(&(27015..=27060), 2), [27015, 27060]);
+        assert_eq!(
+            sample_ports(&(27015..=27055), 5),
+            [27015, 27025, 27035, 27045, 27055]
+        );
+    }
+
+    #[test]
+    fn samples_at_most_the_whole_range() {
+        assert_eq!(sample_ports(&(27015..=27017), 3), [27015, 27016, 27017]);
+        assert_eq!(sample_ports(&(27015..=27017), 10), [27015, 27016, 27017]);
+        assert_eq!(sample_ports(&(27015..=27015), 4), [27015]);
+    }
+}
@@ -0,0 +1,73 @@
+//! Presets that keep only the regions of some continents (eg: "Only
+//! EU") and block all the others. The defaults can be overridden in
+//! the config file (`[[presets]]` tables of `config.toml`).
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    regions::Continent,
+    steam_server::{ServerCategory, Servers},
+};
+
+/// Preset keeping the regions of the continents and the listed
+/// regions, all the other relay regions are blocked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    /// Continents whose regions are kept.
+    #[serde(default)]
+    pub continents: Vec<Continent>,
+    /// Abreviations of the other regions kept.
+    #[serde(default)]
+    pub regions: Vec<String>,
+}
+
+impl Preset {
+    /// Get the presets shipped with the app.
+    pub fn defaults() -> Vec<Self> {
+        [
+            ("Only EU", Continent::Europe),
+            ("Only NA", Continent::NorthAmerica),
+            ("Only Asia", Continent::Asia),
+        ]
+        .into_iter()
+        .map(|(name, continent)| Self {
+            name: name.to_string(),
+            continents: vec![continent],
+            regions: Vec::new(),
+        })
+        .collect()
+    }
+
+    /// Get the abreviations of the relay regions kept by the preset.
+    pub fn kept_regions<'a>(&'a self, servers: &'a Servers) -> impl Iterator<Item = &'a str> {
+        servers
+            .get_servers_in(ServerCategory::Relay)
+            .filter(|server| {
+                Continent::of_server(server)
+                    .is_some_and(|continent| self.continents.contains(&continent))
+                    || self.regions.iter().any(|abr| abr == server.get_abr())
+            })
+            .map(|server| server.get_abr())
+    }
+
+    /// Get the regex matching exactly the abreviations of the regions
+    /// kept by the preset, for the `--enable`/`--disable` plumbing.
+    pub fn kept_regex(&self, servers: &Servers) -> regex::Regex {
+        let alternatives: Vec<_> = self.kept_regions(servers).map(regex::escape).collect();
+        // `[^\s\S]` matches nothing, for the presets keeping no region
+        let pattern = if alternatives.is_empty() {
+            r"[^\s\S]".to_string()
+        } else {
+            format!("^(?:{})$", alternatives.join("|"))
+        };
+        regex::Regex::new(&pattern).expect("escaped alternatives are a valid regex")
+    }
+}
+
+/// Find the preset by its name, case insensitive.
+pub fn find<'a>(presets: &'a [Preset], name: &str) -> Option<&'a Preset> {
+    presets
+        .iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name.trim()))
+}
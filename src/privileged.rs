@@ -0,0 +1,315 @@
+//! Privileged helper running the firewall operations of an
+//! unprivileged instance, so that the GUI, the pinger and the
+//! downloads (eg: the map tiles) do not run as root.
+//!
+//! The instance starts the helper (`--privileged-helper`) through
+//! polkit's `pkexec`, see [`Helper::spawn()`]. The first line of the
+//! stdin of the helper is the [`FirewallOptions`] of the instance
+//! (the helper runs as root, its config directory is the one of root),
+//! every other line is a [`Request`]. The helper answers every line
+//! with a [`Reply`] on its stdout and exits once its stdin is closed,
+//! ie: when the instance exits. The logs of the helper are written to
+//! stderr.
+
+use std::{
+    collections::HashSet,
+    io::{self, BufRead, BufReader, Write},
+    net::IpAddr,
+    process::{self, Child, ChildStdin, ChildStdout, Stdio},
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    firewall::{self, Firewall, FirewallOptions, FirewallRequirements, PortRanges, TrafficShaper},
+    Error,
+};
+
+/// How the app gets the privileges to change the firewall rules.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Elevation {
+    /// Polkit if `pkexec` is installed, otherwise sudo.
+    #[default]
+    Auto,
+    /// Only the firewall operations run as root, in a helper started
+    /// with `pkexec`.
+    Polkit,
+    /// The whole app is run again as root with `sudo`.
+    Sudo,
+}
+
+impl Elevation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Elevation::Auto => "Auto",
+            Elevation::Polkit => "Polkit",
+            Elevation::Sudo => "Sudo",
+        }
+    }
+
+    /// Resolve [`Elevation::Auto`] to the elevation to use.
+    pub fn resolve(self) -> Self {
+        if self != Elevation::Auto {
+            return self;
+        }
+        let has_pkexec = process::Command::new("pkexec")
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if has_pkexec {
+            Elevation::Polkit
+        } else {
+            Elevation::Sudo
+        }
+    }
+}
+
+impl std::fmt::Display for Elevation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Firewall operation run by the helper.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Request {
+    IsBlocked { ip: IpAddr },
+    Ban { ips: Vec<IpAddr> },
+    Unban { ips: Vec<IpAddr> },
+    ListBlocked,
+    SetPortRanges { port_ranges: PortRanges },
+    Deprioritize { ips: Vec<IpAddr> },
+    Restore { ips: Vec<IpAddr> },
+    ListDeprioritized,
+}
+
+/// Answer of the helper.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reply {
+    /// The firewall was created from the options.
+    Ready {
+        can_deprioritize: bool,
+    },
+    Done,
+    Blocked(bool),
+    Ips(HashSet<IpAddr>),
+    Error(String),
+}
+
+/// Run the firewall operations of the requests of stdin until it is
+/// closed, see the module documentation.
+pub fn serve() -> Result<(), Error> {
+    let mut lines = io::stdin().lock().lines();
+    let mut stdout = io::stdout().lock();
+    let mut reply = |reply: &Reply| -> Result<(), Error> {
+        writeln!(stdout, "{}", serde_json::to_string(reply)?)?;
+        stdout.flush()?;
+        Ok(())
+    };
+
+    let Some(options) = lines.next() else {
+        return Ok(());
+    };
+    let firewall = match serde_json::from_str(&options?)
+        .map_err(Error::from)
+        .and_then(|options| Ok(Firewall::new(&options)?))
+    {
+        Ok(firewall) => firewall,
+        Err(err) => {
+            reply(&Reply::Error(err.to_string()))?;
+            return Err(err);
+        }
+    };
+    reply(&Reply::Ready {
+        can_deprioritize: firewall.can_deprioritize(),
+    })?;
+
+    for line in lines {
+        let res = serde_json::from_str(&line?)
+            .map_err(|err| firewall::Error::Custom(format!("invalid request: {}", err)))
+            .and_then(|request| run(&firewall, request));
+        reply(&res.unwrap_or_else(|err| Reply::Error(err.to_string())))?;
+    }
+    Ok(())
+}
+
+/// Run the firewall operation of the request.
+fn run(firewall: &Firewall, request: Request) -> Result<Reply, firewall::Error> {
+    log::debug!("privileged helper: {:?}", request);
+    Ok(match request {
+        Request::IsBlocked { ip } => Reply::Blocked(firewall.is_blocked(ip)?),
+        Request::Ban { ips } => firewall.ban_ips(&ips).map(|()| Reply::Done)?,
+        Request::Unban { ips } => firewall.unban_ips(&ips).map(|()| Reply::Done)?,
+        Request::ListBlocked => Reply::Ips(firewall.list_blocked()?),
+        Request::SetPortRanges { port_ranges } => firewall
+            .set_port_ranges(port_ranges)
+            .map(|()| Reply::Done)?,
+        Request::Deprioritize { ips } => firewall.deprioritize_ips(&ips).map(|()| Reply::Done)?,
+        Request::Restore { ips } => firewall.restore_ips(&ips).map(|()| Reply::Done)?,
+        Request::ListDeprioritized => Reply::Ips(firewall.list_deprioritized()?),
+    })
+}
+
+/// Pipes to the helper process.
+struct Connection {
+    child: Child,
+    /// [`None`] once dropped, so that the helper exits.
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Connection {
+    /// Send the line and read the reply.
+    fn send(&mut self, line: &str) -> Result<Reply, firewall::Error> {
+        let lost = |err: io::Error| {
+            firewall::Error::Custom(format!("lost the privileged helper: {}", err))
+        };
+        let stdin = self.stdin.as_mut().expect("stdin is only taken on drop");
+        writeln!(stdin, "{}", line).map_err(lost)?;
+        stdin.flush().map_err(lost)?;
+        let mut reply = String::new();
+        if self.stdout.read_line(&mut reply).map_err(lost)? == 0 {
+            return Err(lost(io::ErrorKind::UnexpectedEof.into()));
+        }
+        serde_json::from_str(&reply)
+            .map_err(|err| firewall::Error::Custom(format!("invalid helper reply: {}", err)))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // the helper exits once its stdin is closed
+        drop(self.stdin.take());
+        if let Err(err) = self.child.wait() {
+            log::error!("unable to wait for the privileged helper: {}", err);
+        }
+    }
+}
+
+/// Firewall backend (and traffic shaper) forwarding the operations to
+/// the privileged helper, see the module documentation.
+#[derive(Clone)]
+pub struct Helper {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl Helper {
+    /// Start the helper with `pkexec` (polkit asks the user to
+    /// authenticate), its firewall is configured with the options.
+    /// Returns if the helper can deprioritize the IPs.
+    pub fn spawn(options: &FirewallOptions) -> Result<(Self, bool), Error> {
+        let mut child = process::Command::new("pkexec")
+            .arg(std::env::current_exe()?)
+            .arg("--privileged-helper")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let connection = Connection {
+            stdin: child.stdin.take(),
+            stdout: BufReader::new(child.stdout.take().expect("stdout is piped")),
+            child,
+        };
+        let helper = Self {
+            connection: Arc::new(Mutex::new(connection)),
+        };
+        match helper.send(&serde_json::to_string(options)?)? {
+            Reply::Ready { can_deprioritize } => Ok((helper, can_deprioritize)),
+            reply => Err(unexpected(reply).into()),
+        }
+    }
+
+    /// Create the [`Firewall`] whose operations are run by the
+    /// helper, see [`Self::spawn()`].
+    pub fn firewall(options: &FirewallOptions) -> Result<Firewall, Error> {
+        let (helper, can_deprioritize) = Self::spawn(options)?;
+        let firewall = Firewall::with_backend(helper.clone());
+        Ok(if can_deprioritize {
+            firewall.with_shaper(helper)
+        } else {
+            firewall
+        })
+    }
+
+    fn send(&self, line: &str) -> Result<Reply, firewall::Error> {
+        match self.connection.lock().unwrap().send(line)? {
+            Reply::Error(message) => Err(firewall::Error::Custom(message)),
+            reply => Ok(reply),
+        }
+    }
+
+    fn request(&self, request: &Request) -> Result<Reply, firewall::Error> {
+        let line = serde_json::to_string(request)
+            .map_err(|err| firewall::Error::Custom(err.to_string()))?;
+        self.send(&line)
+    }
+
+    /// Run the request whose reply is [`Reply::Done`].
+    fn done(&self, request: &Request) -> Result<(), firewall::Error> {
+        match self.request(request)? {
+            Reply::Done => Ok(()),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    /// Run the request whose reply is [`Reply::Ips`].
+    fn ips(&self, request: &Request) -> Result<HashSet<IpAddr>, firewall::Error> {
+        match self.request(request)? {
+            Reply::Ips(ips) => Ok(ips),
+            reply => Err(unexpected(reply)),
+        }
+    }
+}
+
+fn unexpected(reply: Reply) -> firewall::Error {
+    firewall::Error::Custom(format!("unexpected helper reply {:?}", reply))
+}
+
+impl FirewallRequirements for Helper {
+    fn is_blocked(&self, ip: IpAddr) -> Result<bool, firewall::Error> {
+        match self.request(&Request::IsBlocked { ip })? {
+            Reply::Blocked(blocked) => Ok(blocked),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn ban_ip(&self, ip: IpAddr) -> Result<(), firewall::Error> {
+        self.ban_ips(&[ip])
+    }
+
+    fn unban_ip(&self, ip: IpAddr) -> Result<(), firewall::Error> {
+        self.unban_ips(&[ip])
+    }
+
+    fn list_blocked(&self) -> Result<HashSet<IpAddr>, firewall::Error> {
+        self.ips(&Request::ListBlocked)
+    }
+
+    fn ban_ips(&self, ips: &[IpAddr]) -> Result<(), firewall::Error> {
+        self.done(&Request::Ban { ips: ips.to_vec() })
+    }
+
+    fn unban_ips(&self, ips: &[IpAddr]) -> Result<(), firewall::Error> {
+        self.done(&Request::Unban { ips: ips.to_vec() })
+    }
+
+    fn set_port_ranges(&self, port_ranges: PortRanges) -> Result<(), firewall::Error> {
+        self.done(&Request::SetPortRanges { port_ranges })
+    }
+}
+
+impl TrafficShaper for Helper {
+    fn deprioritize_ips(&self, ips: &[IpAddr]) -> Result<(), firewall::Error> {
+        self.done(&Request::Deprioritize { ips: ips.to_vec() })
+    }
+
+    fn restore_ips(&self, ips: &[IpAddr]) -> Result<(), firewall::Error> {
+        self.done(&Request::Restore { ips: ips.to_vec() })
+    }
+
+    fn list_deprioritized(&self) -> Result<HashSet<IpAddr>, firewall::Error> {
+        self.ips(&Request::ListDeprioritized)
+    }
+}
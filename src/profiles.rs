@@ -0,0 +1,133 @@
+//! Named sets of blocked regions (profiles), a profile can be bound to
+//! a Steam app so that the blocked regions follow the game being
+//! played.
+//!
+//! The firewall rules do not survive a reboot (or a flush of the
+//! firewall), the last applied profile is remembered so that it can
+//! be applied again on startup (`--restore-profile`).
+
+use std::{collections::BTreeSet, net::IpAddr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block_list::BlockList,
+    file_ops::{self, Migration},
+    Error,
+};
+
+/// Migrations of the profiles file, see [`file_ops::migrate()`].
+const MIGRATIONS: &[Migration] = &[];
+
+/// Named set of blocked regions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// Steam app the profile is for, the network datagram config of
+    /// the app is loaded when the profile is applied. [`None`] if the
+    /// profile is not bound to an app.
+    pub app_id: Option<u32>,
+    /// Abreviations of the blocked regions.
+    pub blocked_regions: BTreeSet<String>,
+    /// IPs blocked in the regions that are not blocked entirely, eg:
+    /// by a partial block.
+    #[serde(default)]
+    pub blocked_ips: BTreeSet<IpAddr>,
+}
+
+impl Profile {
+    pub fn new(
+        name: impl Into<String>,
+        app_id: Option<u32>,
+        block_list: &BlockList,
+        blocked_ips: impl IntoIterator<Item = IpAddr>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            app_id,
+            blocked_regions: block_list.regions().map(str::to_string).collect(),
+            blocked_ips: blocked_ips.into_iter().collect(),
+        }
+    }
+
+    /// Get the [`BlockList`] of the profile.
+    pub fn block_list(&self) -> BlockList {
+        BlockList::new(self.blocked_regions.iter().cloned())
+    }
+}
+
+/// All the [`Profile`]s, stored at
+/// [`file_ops::get_profiles_file_path()`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profiles {
+    profiles: Vec<Profile>,
+    /// Name of the profile applied last.
+    #[serde(default)]
+    last_applied: Option<String>,
+}
+
+impl Profiles {
+    /// Load the profiles, no profiles if the file does not exist yet.
+    pub fn load() -> Result<Self, Error> {
+        let file_path = file_ops::get_profiles_file_path()?;
+        if !file_path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(file_ops::load_versioned(file_path, MIGRATIONS)?)
+    }
+
+    /// Save the profiles.
+    pub fn save(&self) -> Result<(), Error> {
+        file_ops::save_versioned(file_ops::get_profiles_file_path()?, self, MIGRATIONS)?;
+        Ok(())
+    }
+
+    /// Get a reference to the profiles.
+    pub fn profiles(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    /// Get the profile with the given name.
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    /// Get the profile applied last, if it still exists.
+    pub fn last_applied(&self) -> Option<&Profile> {
+        self.get(self.last_applied.as_deref()?)
+    }
+
+    /// Remember the profile as the one applied last.
+    pub fn set_last_applied(&mut self, name: &str) {
+        self.last_applied = Some(name.to_string());
+    }
+
+    /// Get the first profile bound to the given Steam app.
+    pub fn for_app(&self, app_id: u32) -> Option<&Profile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.app_id == Some(app_id))
+    }
+
+    /// Add the profile, replaces the profile with the same name (if
+    /// any).
+    pub fn insert(&mut self, profile: Profile) {
+        match self
+            .profiles
+            .iter_mut()
+            .find(|existing| existing.name == profile.name)
+        {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+    }
+
+    /// Remove the profile with the given name.
+    pub fn remove(&mut self, name: &str) -> Option<Profile> {
+        let index = self
+            .profiles
+            .iter()
+            .position(|profile| profile.name == name)?;
+        Some(self.profiles.remove(index))
+    }
+}
@@ -0,0 +1,223 @@
+//! Python bindings, built as the `steam_server_disable` extension
+//! module with `maturin` (see `pyproject.toml`).
+//!
+//! The blocking operations (downloads, firewall commands, pings)
+//! release the GIL, so that the other Python threads keep running.
+
+use std::{net::IpAddr, path::PathBuf, time::Duration};
+
+use pyo3::{create_exception, exceptions::PyException, prelude::*};
+
+use crate::{
+    firewall::Firewall,
+    ping::Pinger,
+    steam_server::{ServerInfo, ServerState, Servers},
+    Error,
+};
+
+create_exception!(
+    steam_server_disable,
+    SteamServerDisableError,
+    PyException,
+    "Error raised by `steam_server_disable`."
+);
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> Self {
+        SteamServerDisableError::new_err(error.to_string())
+    }
+}
+
+/// Parse the IPv4 or IPv6 address, raising a
+/// [`SteamServerDisableError`] on failure.
+fn parse_ip(ip: &str) -> PyResult<IpAddr> {
+    ip.parse().map_err(|error| {
+        Error::InvalidIp {
+            ip: ip.to_string(),
+            error,
+        }
+        .into()
+    })
+}
+
+/// Servers (regions) from the network datagram config.
+#[pyclass(name = "Servers")]
+pub struct PyServers {
+    servers: Servers,
+}
+
+impl PyServers {
+    /// Get the server with the given abreviation, raising a
+    /// [`SteamServerDisableError`] if it does not exist.
+    fn server(&self, abr: &str) -> PyResult<&ServerInfo> {
+        self.servers
+            .get_server(abr)
+            .ok_or_else(|| SteamServerDisableError::new_err(format!("no region named `{}`", abr)))
+    }
+}
+
+#[pymethods]
+impl PyServers {
+    /// Load the servers from the given network datagram config file,
+    /// the default (downloaded if necessary) file is used if `None`.
+    #[new]
+    #[pyo3(signature = (network_datagram_config_path = None))]
+    fn new(py: Python<'_>, network_datagram_config_path: Option<PathBuf>) -> PyResult<Self> {
+        Ok(Self {
+            servers: py.allow_threads(|| Servers::new(network_datagram_config_path))?,
+        })
+    }
+
+    /// Download the latest network datagram config file.
+    #[staticmethod]
+    fn download_file(py: Python<'_>) -> PyResult<()> {
+        Ok(py.allow_threads(Servers::download_file)?)
+    }
+
+    /// Abreviations of all the regions.
+    fn regions(&self) -> Vec<String> {
+        self.servers
+            .get_servers()
+            .iter()
+            .map(|server| server.get_abr().to_string())
+            .collect()
+    }
+
+    /// IPv4 addresses of the region.
+    fn ipv4s(&self, abr: &str) -> PyResult<Vec<String>> {
+        Ok(self
+            .server(abr)?
+            .get_ips()
+            .iter()
+            .filter(|ip| ip.is_ipv4())
+            .map(|ip| ip.to_string())
+            .collect())
+    }
+
+    /// IPv6 addresses of the region.
+    fn ipv6s(&self, abr: &str) -> PyResult<Vec<String>> {
+        Ok(self
+            .server(abr)?
+            .get_ips()
+            .iter()
+            .filter(|ip| ip.is_ipv6())
+            .map(|ip| ip.to_string())
+            .collect())
+    }
+
+    /// Description of the region.
+    fn description(&self, abr: &str) -> PyResult<Option<String>> {
+        Ok(self.server(abr)?.desc().map(|desc| desc.to_string()))
+    }
+
+    /// `(longitude, latitude)` of the region.
+    fn geo(&self, abr: &str) -> PyResult<Option<(f32, f32)>> {
+        Ok(self.server(abr)?.geo().map(|geo| (geo[0], geo[1])))
+    }
+}
+
+/// System firewall.
+#[pyclass(name = "Firewall")]
+pub struct PyFirewall {
+    firewall: Firewall,
+}
+
+#[pymethods]
+impl PyFirewall {
+    #[new]
+    fn new(py: Python<'_>) -> PyResult<Self> {
+        Ok(Self {
+            firewall: py
+                .allow_threads(Firewall::from_config)
+                .map_err(Error::from)?,
+        })
+    }
+
+    /// Check if the IP is blocked.
+    fn is_blocked(&self, py: Python<'_>, ip: &str) -> PyResult<bool> {
+        let ip = parse_ip(ip)?;
+        Ok(py
+            .allow_threads(|| self.firewall.is_blocked(ip))
+            .map_err(Error::from)?)
+    }
+
+    /// Block the IP.
+    fn ban_ip(&self, py: Python<'_>, ip: &str) -> PyResult<()> {
+        let ip = parse_ip(ip)?;
+        Ok(py
+            .allow_threads(|| self.firewall.ban_ip(ip))
+            .map_err(Error::from)?)
+    }
+
+    /// Unblock the IP.
+    fn unban_ip(&self, py: Python<'_>, ip: &str) -> PyResult<()> {
+        let ip = parse_ip(ip)?;
+        Ok(py
+            .allow_threads(|| self.firewall.unban_ip(ip))
+            .map_err(Error::from)?)
+    }
+
+    /// Block all the IPs of the region.
+    fn ban_region(&self, py: Python<'_>, servers: &PyServers, abr: &str) -> PyResult<()> {
+        let server = servers.server(abr)?;
+        Ok(py.allow_threads(|| {
+            self.firewall
+                .set_port_ranges(servers.servers.port_ranges())?;
+            server.ban(&self.firewall)
+        })?)
+    }
+
+    /// Unblock all the IPs of the region.
+    fn unban_region(&self, py: Python<'_>, servers: &PyServers, abr: &str) -> PyResult<()> {
+        let server = servers.server(abr)?;
+        Ok(py.allow_threads(|| server.unban(&self.firewall))?)
+    }
+
+    /// State of the region, one of `"All Disabled"`, `"Some
+    /// Disabled"`, `"None Disabled"`.
+    fn region_state(&self, py: Python<'_>, servers: &PyServers, abr: &str) -> PyResult<String> {
+        let server = servers.server(abr)?;
+        let state: ServerState = py.allow_threads(|| server.query_state(&self.firewall));
+        Ok(state.to_string())
+    }
+}
+
+/// ICMP pinger.
+#[pyclass(name = "Pinger")]
+pub struct PyPinger {
+    pinger: Pinger,
+}
+
+#[pymethods]
+impl PyPinger {
+    #[new]
+    #[pyo3(signature = (timeout_ms = 2000))]
+    fn new(timeout_ms: u64) -> PyResult<Self> {
+        let mut pinger = Pinger::new().map_err(Error::from)?;
+        pinger.set_timeout(Duration::from_millis(timeout_ms));
+        Ok(Self { pinger })
+    }
+
+    /// Ping the IP, returns the round trip time in milliseconds.
+    #[pyo3(signature = (ip, sequence = 0))]
+    fn ping(&mut self, py: Python<'_>, ip: &str, sequence: u16) -> PyResult<f64> {
+        let ip = parse_ip(ip)?;
+        let pinger = &mut self.pinger;
+        let ping_info = py
+            .allow_threads(|| pinger.ping(ip, sequence))
+            .map_err(Error::from)?;
+        Ok(ping_info.get_rtt().as_secs_f64() * 1000.0)
+    }
+}
+
+#[pymodule]
+fn steam_server_disable(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add(
+        "SteamServerDisableError",
+        m.py().get_type::<SteamServerDisableError>(),
+    )?;
+    m.add_class::<PyServers>()?;
+    m.add_class::<PyFirewall>()?;
+    m.add_class::<PyPinger>()?;
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! Recommendation of the regions to keep: the nearest regions to the
+//! location of the user (see [`crate::geoip`]), or the ones with the
+//! lowest ping when the location is not known. All the other regions
+//! can then be blocked in one go, see [`Recommendation::block_list()`].
+
+use std::time::Duration;
+
+use crate::{
+    block_list::BlockList,
+    geoip,
+    steam_server::{ServerCategory, ServerInfo, Servers},
+};
+
+/// What the regions are ranked by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecommendationBasis {
+    /// Distance from the location of the user.
+    Location,
+    /// Mean ping.
+    Latency,
+}
+
+impl RecommendationBasis {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RecommendationBasis::Location => "Location",
+            RecommendationBasis::Latency => "Latency",
+        }
+    }
+}
+
+impl std::fmt::Display for RecommendationBasis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Nearest relay regions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recommendation {
+    pub basis: RecommendationBasis,
+    /// Abreviations of the recommended regions, the nearest first.
+    pub regions: Vec<String>,
+    /// Abreviations of the ranked regions that are not recommended.
+    others: Vec<String>,
+}
+
+impl Recommendation {
+    /// Recommend the `n` relay regions nearest to the `[longitude,
+    /// latitude]` location. The regions without a known location are
+    /// left out.
+    pub fn by_location(servers: &Servers, location: [f32; 2], n: usize) -> Self {
+        Self::ranked(
+            RecommendationBasis::Location,
+            servers,
+            n,
+            |server| Some(geoip::distance_km(location, *server.geo()?)),
+            f32::total_cmp,
+        )
+    }
+
+    /// Recommend the `n` relay regions with the lowest `ping`. The
+    /// regions without a ping (eg: blocked or unreachable) are never
+    /// recommended.
+    pub fn by_latency(
+        servers: &Servers,
+        ping: impl Fn(&ServerInfo) -> Option<Duration>,
+        n: usize,
+    ) -> Self {
+        let mut recommendation = Self::ranked(
+            RecommendationBasis::Latency,
+            servers,
+            n,
+            &ping,
+            Duration::cmp,
+        );
+        recommendation.others.extend(
+            servers
+                .get_servers_in(ServerCategory::Relay)
+                .filter(|server| !server.get_ips().is_empty() && ping(server).is_none())
+                .map(|server| server.get_abr().to_string()),
+        );
+        recommendation
+    }
+
+    fn ranked<T>(
+        basis: RecommendationBasis,
+        servers: &Servers,
+        n: usize,
+        key: impl Fn(&ServerInfo) -> Option<T>,
+        cmp: impl Fn(&T, &T) -> std::cmp::Ordering,
+    ) -> Self {
+        let mut ranked: Vec<_> = servers
+            .get_servers_in(ServerCategory::Relay)
+            .filter(|server| !server.get_ips().is_empty())
+            .filter_map(|server| Some((server.get_abr().to_string(), key(server)?)))
+            .collect();
+        ranked.sort_by(|(_, a), (_, b)| cmp(a, b));
+        let mut regions: Vec<_> = ranked.into_iter().map(|(abr, _)| abr).collect();
+        let others = regions.split_off(n.min(regions.len()));
+        Self {
+            basis,
+            regions,
+            others,
+        }
+    }
+
+    /// Is the region recommended?
+    pub fn contains(&self, region: &str) -> bool {
+        self.regions.iter().any(|abr| abr == region)
+    }
+
+    /// Get the block list of the regions that are not recommended, the
+    /// regions without a known location are never blocked when
+    /// recommending by [`RecommendationBasis::Location`].
+    pub fn block_list(&self) -> BlockList {
+        BlockList::new(self.others.iter().cloned())
+    }
+}
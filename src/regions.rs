@@ -0,0 +1,218 @@
+//! Metadata of the relay regions (location, relays, ports) for
+//! external tooling and map visualizations.
+
+use std::{collections::BTreeSet, fmt::Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    steam_server::{ServerCategory, ServerInfo, Servers},
+    Error,
+};
+
+/// Output format of the region metadata.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum RegionsFormat {
+    /// Human readable table.
+    #[default]
+    Table,
+    /// JSON array of [`RegionInfo`].
+    Json,
+}
+
+/// Metadata of a region.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegionInfo {
+    /// Abreviation of the region, eg: `sgp`.
+    pub abr: String,
+    /// Short name of the region, the description without the
+    /// details, eg: `Stockholm` for `Stockholm - Kista`.
+    pub name: String,
+    /// Description of the region, the "City, Country" of its location
+    /// if the config has no description.
+    pub desc: Option<String>,
+    pub latitude: Option<f32>,
+    pub longitude: Option<f32>,
+    /// Number of relays (IPv4s) of the region.
+    pub relay_count: usize,
+    /// Number of the relays that also have an IPv6.
+    pub ipv6_count: usize,
+    /// Distinct UDP port ranges of the relays, inclusive.
+    pub port_ranges: Vec<[u16; 2]>,
+}
+
+impl RegionInfo {
+    /// Create the [`RegionInfo`] of the server.
+    pub fn new(server: &ServerInfo) -> Self {
+        let label = server.label();
+        let name = label
+            .as_deref()
+            .and_then(|desc| desc.split([',', '(']).next())
+            .map(|name| name.split(" - ").next().unwrap_or(name).trim())
+            .filter(|name| !name.is_empty())
+            .unwrap_or(server.get_abr())
+            .to_string();
+        let port_ranges: BTreeSet<_> = server
+            .get_ips()
+            .iter()
+            .filter_map(|ip| server.port_range(ip))
+            .map(|port_range| [*port_range.start(), *port_range.end()])
+            .collect();
+        Self {
+            abr: server.get_abr().to_string(),
+            name,
+            desc: label.as_deref().map(str::to_string),
+            // geo is `[longitude, latitude]`
+            latitude: server.geo().map(|geo| geo[1]),
+            longitude: server.geo().map(|geo| geo[0]),
+            relay_count: server.get_ips().len() - server.num_ipv6s(),
+            ipv6_count: server.num_ipv6s(),
+            port_ranges: port_ranges.into_iter().collect(),
+        }
+    }
+}
+
+/// Continent of a region, from a coarse split of the
+/// longitude/latitude, good enough for the Steam relay locations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Continent {
+    NorthAmerica,
+    SouthAmerica,
+    Europe,
+    Africa,
+    Asia,
+    Oceania,
+}
+
+impl Continent {
+    /// Get all the [`Continent`]s.
+    pub const fn all() -> [Self; 6] {
+        [
+            Self::NorthAmerica,
+            Self::SouthAmerica,
+            Self::Europe,
+            Self::Africa,
+            Self::Asia,
+            Self::Oceania,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Continent::NorthAmerica => "North America",
+            Continent::SouthAmerica => "South America",
+            Continent::Europe => "Europe",
+            Continent::Africa => "Africa",
+            Continent::Asia => "Asia",
+            Continent::Oceania => "Oceania",
+        }
+    }
+
+    /// Get the [`Continent`] of the `[longitude, latitude]` location,
+    /// [`None`] for Antarctica.
+    pub fn from_geo(geo: [f32; 2]) -> Option<Self> {
+        let [lon, lat] = geo;
+        if lat < -60.0 {
+            None
+        } else if lon < -30.0 {
+            // Central America is part of North America
+            if lat < 13.0 && lon > -82.0 {
+                Some(Continent::SouthAmerica)
+            } else {
+                Some(Continent::NorthAmerica)
+            }
+        } else if lat < -10.0 && lon > 110.0 {
+            Some(Continent::Oceania)
+        } else if lat < 37.0 && lon < 52.0 && !(lon > 34.5 && lat > 12.0) {
+            // the Middle East is part of Asia
+            Some(Continent::Africa)
+        } else if lon < 45.0 && lat >= 36.0 {
+            Some(Continent::Europe)
+        } else {
+            Some(Continent::Asia)
+        }
+    }
+
+    /// Get the [`Continent`] of the server, [`None`] if it has no
+    /// location.
+    pub fn of_server(server: &ServerInfo) -> Option<Self> {
+        Self::from_geo(*server.geo()?)
+    }
+}
+
+impl std::fmt::Display for Continent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Get the metadata of all the relay regions.
+pub fn region_infos(servers: &Servers) -> Vec<RegionInfo> {
+    servers
+        .get_servers_in(ServerCategory::Relay)
+        .map(RegionInfo::new)
+        .collect()
+}
+
+/// Format the metadata of the regions.
+pub fn format_regions(regions: &[RegionInfo], format: RegionsFormat) -> Result<String, Error> {
+    match format {
+        RegionsFormat::Table => Ok(format_table(regions)),
+        RegionsFormat::Json => Ok(serde_json::to_string_pretty(regions)?),
+    }
+}
+
+/// Format the metadata of the regions as a table with aligned
+/// columns.
+fn format_table(regions: &[RegionInfo]) -> String {
+    let header = [
+        "ABR",
+        "NAME",
+        "LATITUDE",
+        "LONGITUDE",
+        "RELAYS",
+        "PORTS",
+        "DESCRIPTION",
+    ]
+    .map(str::to_string);
+    let rows: Vec<[String; 7]> = regions
+        .iter()
+        .map(|region| {
+            let coordinate = |value: Option<f32>| {
+                value.map_or_else(|| "-".to_string(), |value| format!("{:.2}", value))
+            };
+            [
+                region.abr.clone(),
+                region.name.clone(),
+                coordinate(region.latitude),
+                coordinate(region.longitude),
+                region.relay_count.to_string(),
+                region
+                    .port_ranges
+                    .iter()
+                    .map(|[start, end]| format!("{}-{}", start, end))
+                    .collect::<Vec<_>>()
+                    .join(","),
+                region.desc.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.clone().map(|column| column.len());
+    rows.iter().for_each(|row| {
+        row.iter()
+            .zip(widths.iter_mut())
+            .for_each(|(column, width)| *width = (*width).max(column.chars().count()));
+    });
+
+    let mut table = String::new();
+    std::iter::once(&header).chain(rows.iter()).for_each(|row| {
+        row.iter().zip(widths).for_each(|(column, width)| {
+            write!(table, "{:width$}  ", column, width = width).unwrap();
+        });
+        table.truncate(table.trim_end().len());
+        table.push('\n');
+    });
+    table
+}
@@ -0,0 +1,315 @@
+//! Self-contained HTML or Markdown report of the blocked regions and
+//! the connection statistics of the regions, for sharing with
+//! teammates or in support threads.
+
+use std::{fmt::Write, path::PathBuf, time::Duration};
+
+use crate::{file_ops, history, Error};
+
+/// Format of the report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// Single HTML file with inline graphs.
+    #[default]
+    Html,
+    /// Markdown tables.
+    Markdown,
+}
+
+impl ReportFormat {
+    /// Get the name of the [`ReportFormat`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReportFormat::Html => "HTML",
+            ReportFormat::Markdown => "Markdown",
+        }
+    }
+
+    /// Get the file extension of the [`ReportFormat`].
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Html => "html",
+            ReportFormat::Markdown => "md",
+        }
+    }
+}
+
+impl std::fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Statistics of a region in the [`Report`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionReport {
+    /// Abreviation of the region.
+    pub abr: String,
+    pub desc: Option<String>,
+    /// Blocked state of the region, eg: `All Disabled`.
+    pub state: String,
+    pub mean_ping: Option<Duration>,
+    pub min_ping: Option<Duration>,
+    pub jitter: Duration,
+    /// Loss over the last minute in percent.
+    pub recent_loss_percent: Option<f64>,
+    /// Loss over the session in percent.
+    pub session_loss_percent: Option<f64>,
+    /// Composite score, see [`crate::score`].
+    pub score: Option<f64>,
+    /// Recent pings of the region, oldest first, [`None`] for the
+    /// lost packets.
+    pub ping_history: Vec<Option<Duration>>,
+}
+
+/// Report of the current state, see the module documentation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// Unix time (seconds) the report was generated at.
+    pub generated_at: u64,
+    /// Steam app of the network datagram config.
+    pub app_id: u32,
+    /// Revision of the network datagram config.
+    pub revision: usize,
+    /// Abreviations of the blocked regions.
+    pub blocked_regions: Vec<String>,
+    pub regions: Vec<RegionReport>,
+}
+
+impl Report {
+    /// Render the report in the given format.
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Html => self.render_html(),
+            ReportFormat::Markdown => self.render_markdown(),
+        }
+    }
+
+    /// Render the report and write it to the reports directory,
+    /// returns the path of the written report.
+    pub fn write(&self, format: ReportFormat) -> Result<PathBuf, Error> {
+        let reports_dir = file_ops::get_reports_dir()?;
+        std::fs::create_dir_all(&reports_dir)?;
+        let file_path = reports_dir.join(format!(
+            "report_{}.{}",
+            self.generated_at,
+            format.extension()
+        ));
+        file_ops::write_atomic(&file_path, self.render(format))?;
+        log::info!("wrote the {} report to `{}`", format, file_path.display());
+        Ok(file_path)
+    }
+
+    /// Get the highest ping of the histories, used to scale the
+    /// graphs.
+    fn max_ping(&self) -> Duration {
+        self.regions
+            .iter()
+            .flat_map(|region| region.ping_history.iter().flatten())
+            .chain(
+                self.regions
+                    .iter()
+                    .filter_map(|region| region.mean_ping.as_ref()),
+            )
+            .max()
+            .copied()
+            .unwrap_or(Duration::ZERO)
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut res = String::new();
+        writeln!(res, "# steam_server_disable report").unwrap();
+        writeln!(res).unwrap();
+        self.summary_lines().iter().for_each(|line| {
+            writeln!(res, "* {}", line).unwrap();
+        });
+        writeln!(res).unwrap();
+        writeln!(
+            res,
+            "| Region | Description | State | Ping (ms) | Min (ms) | Jitter (ms) | \
+             Loss 1 min | Loss session | Score |"
+        )
+        .unwrap();
+        writeln!(res, "|---|---|---|---:|---:|---:|---:|---:|---:|").unwrap();
+        self.regions.iter().for_each(|region| {
+            let cells = RegionCells::new(region);
+            writeln!(
+                res,
+                "| {} | {} | {} | {} | {} | {} | {} | {} | {} |",
+                region.abr,
+                region.desc.as_deref().unwrap_or("").replace('|', "\\|"),
+                region.state,
+                cells.mean_ping,
+                cells.min_ping,
+                cells.jitter,
+                cells.recent_loss,
+                cells.session_loss,
+                cells.score
+            )
+            .unwrap();
+        });
+        res
+    }
+
+    fn render_html(&self) -> String {
+        let max_ping = self.max_ping();
+        let mut res = String::new();
+        res.push_str(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>steam_server_disable report</title>\n<style>\n\
+             body { font-family: sans-serif; margin: 2em; }\n\
+             table { border-collapse: collapse; }\n\
+             th, td { border: 1px solid #ccc; padding: 0.2em 0.5em; text-align: right; }\n\
+             th:nth-child(-n+3), td:nth-child(-n+3) { text-align: left; }\n\
+             tr.blocked { color: #888; }\n\
+             </style>\n</head>\n<body>\n<h1>steam_server_disable report</h1>\n<ul>\n",
+        );
+        self.summary_lines().iter().for_each(|line| {
+            writeln!(res, "<li>{}</li>", escape_html(line)).unwrap();
+        });
+        res.push_str(
+            "</ul>\n<table>\n<tr><th>Region</th><th>Description</th><th>State</th>\
+             <th>Ping (ms)</th><th>Min (ms)</th><th>Jitter (ms)</th><th>Loss 1 min</th>\
+             <th>Loss session</th><th>Score</th><th>Recent pings</th></tr>\n",
+        );
+        self.regions.iter().for_each(|region| {
+            let cells = RegionCells::new(region);
+            let blocked = self.blocked_regions.contains(&region.abr);
+            writeln!(
+                res,
+                "<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+                 <td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                if blocked { " class=\"blocked\"" } else { "" },
+                escape_html(&region.abr),
+                escape_html(region.desc.as_deref().unwrap_or("")),
+                escape_html(&region.state),
+                cells.mean_ping,
+                cells.min_ping,
+                cells.jitter,
+                cells.recent_loss,
+                cells.session_loss,
+                cells.score,
+                sparkline_svg(&region.ping_history, max_ping)
+            )
+            .unwrap();
+        });
+        res.push_str("</table>\n</body>\n</html>\n");
+        res
+    }
+
+    /// Lines of the summary at the top of the report.
+    fn summary_lines(&self) -> Vec<String> {
+        vec![
+            format!(
+                "Generated at {} UTC",
+                history::format_unix_time(self.generated_at)
+            ),
+            format!(
+                "Steam app {}, network datagram config revision {}",
+                self.app_id, self.revision
+            ),
+            if self.blocked_regions.is_empty() {
+                "No blocked regions".to_string()
+            } else {
+                format!(
+                    "Blocked regions ({}): {}",
+                    self.blocked_regions.len(),
+                    self.blocked_regions.join(", ")
+                )
+            },
+        ]
+    }
+}
+
+/// Formatted cells of a [`RegionReport`].
+struct RegionCells {
+    mean_ping: String,
+    min_ping: String,
+    jitter: String,
+    recent_loss: String,
+    session_loss: String,
+    score: String,
+}
+
+impl RegionCells {
+    fn new(region: &RegionReport) -> Self {
+        let millis = |ping: Option<Duration>| {
+            ping.map_or_else(
+                || "-".to_string(),
+                |ping| format!("{:.1}", ping.as_secs_f64() * 1000.0),
+            )
+        };
+        let percent = |loss: Option<f64>| {
+            loss.map_or_else(|| "-".to_string(), |loss| format!("{:.1}%", loss))
+        };
+        Self {
+            mean_ping: millis(region.mean_ping),
+            min_ping: millis(region.min_ping),
+            jitter: millis(region.mean_ping.map(|_| region.jitter)),
+            recent_loss: percent(region.recent_loss_percent),
+            session_loss: percent(region.session_loss_percent),
+            score: region
+                .score
+                .map_or_else(|| "-".to_string(), |score| format!("{:.0}", score)),
+        }
+    }
+}
+
+/// Create an inline SVG line graph of the pings, the lost packets are
+/// marked in red.
+fn sparkline_svg(pings: &[Option<Duration>], max_ping: Duration) -> String {
+    const WIDTH: f64 = 160.0;
+    const HEIGHT: f64 = 24.0;
+
+    if pings.is_empty() || max_ping.is_zero() {
+        return String::new();
+    }
+    let step = WIDTH / pings.len().max(2).saturating_sub(1) as f64;
+    let y = |ping: Duration| HEIGHT - ping.as_secs_f64() / max_ping.as_secs_f64() * HEIGHT;
+
+    let mut svg = format!(
+        "<svg width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+        WIDTH, HEIGHT, WIDTH, HEIGHT
+    );
+    let points: Vec<_> = pings
+        .iter()
+        .enumerate()
+        .filter_map(|(i, ping)| Some(format!("{:.1},{:.1}", i as f64 * step, y((*ping)?))))
+        .collect();
+    write!(
+        svg,
+        "<polyline fill=\"none\" stroke=\"#2a7\" stroke-width=\"1.5\" points=\"{}\"/>",
+        points.join(" ")
+    )
+    .unwrap();
+    pings
+        .iter()
+        .enumerate()
+        .filter(|(_, ping)| ping.is_none())
+        .for_each(|(i, _)| {
+            let x = i as f64 * step;
+            write!(
+                svg,
+                "<line x1=\"{:.1}\" y1=\"0\" x2=\"{:.1}\" y2=\"{}\" stroke=\"#d33\"/>",
+                x, x, HEIGHT
+            )
+            .unwrap();
+        });
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Escape the text for HTML.
+fn escape_html(text: &str) -> String {
+    text.chars()
+        .fold(String::with_capacity(text.len()), |mut res, c| {
+            match c {
+                '&' => res.push_str("&amp;"),
+                '<' => res.push_str("&lt;"),
+                '>' => res.push_str("&gt;"),
+                '"' => res.push_str("&quot;"),
+                c => res.push(c),
+            }
+            res
+        })
+}
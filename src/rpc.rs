@@ -0,0 +1,265 @@
+//! [JSON-RPC 2.0](https://www.jsonrpc.org/specification) over
+//! stdin/stdout (`--rpc`), so that launchers and other front-ends can
+//! embed the tool as a child process.
+//!
+//! Every line of the input is a request, every line of the output is
+//! a response or a notification. The logs are written to stderr.
+//!
+//! Methods:
+//!
+//! * `list`: summaries of the regions, see [`RegionSummary`].
+//!
+//! * `status`: `{"regions": [..]}` (optional, all the regions if not
+//!   given), the state of every region, eg: `{"sgp": "All
+//!   Disabled"}`.
+//!
+//! * `enable`, `disable`: `{"regions": [..]}`, unblock or block the
+//!   regions.
+//!
+//! * `subscribe`: the events are sent as `event` notifications from
+//!   then on, eg: `{"jsonrpc": "2.0", "method": "event", "params":
+//!   {"kind": "region_disabled", "region": "sgp", "reason": null}}`.
+//!
+//! [`RegionSummary`]: crate::controller::RegionSummary
+
+use std::{
+    io::{BufRead, Write},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{controller::Controller, Error};
+
+/// Error codes of the specification.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Error of the operation itself (eg: the firewall failed).
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// [`None`] for notifications, they get no response.
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Option<Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RegionsParams {
+    #[serde(default)]
+    regions: Vec<String>,
+}
+
+/// Error response of a request.
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<Error> for RpcError {
+    fn from(err: Error) -> Self {
+        Self::new(SERVER_ERROR, err)
+    }
+}
+
+/// Serve the requests read from `input` until it is closed, the
+/// responses and the notifications are written to `output`.
+pub fn serve(
+    controller: Controller,
+    input: impl BufRead,
+    output: impl Write + Send + 'static,
+) -> Result<(), Error> {
+    let output = Arc::new(Mutex::new(output));
+    let mut event_thread_handle = None;
+
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (id, res) = match parse_request(&line) {
+            Ok(request) => {
+                let res = handle(&controller, &request, &output, &mut event_thread_handle);
+                match request.id {
+                    Some(id) => (Some(id), res),
+                    None => {
+                        if let Err(err) = res {
+                            log::error!("rpc notification `{}`: {}", request.method, err.message);
+                        }
+                        continue;
+                    }
+                }
+            }
+            Err((id, err)) => (Some(id), Err(err)),
+        };
+
+        let response = match res {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": err.code, "message": err.message },
+            }),
+        };
+        write_line(&output, &response)?;
+    }
+
+    // the event thread stops once the controller (and with it the
+    // event sender) is dropped
+    drop(controller);
+    if let Some(thread_handle) = event_thread_handle {
+        if thread_handle.join().is_err() {
+            log::error!("rpc event thread panicked");
+        }
+    }
+    Ok(())
+}
+
+/// Parse the line into a request, on error returns the `id` to
+/// respond with (`null` if it cannot be read) along with the error:
+/// [`PARSE_ERROR`] if the line is not JSON, [`INVALID_REQUEST`] if it
+/// is not a request.
+fn parse_request(line: &str) -> Result<Request, (Value, RpcError)> {
+    let value: Value =
+        serde_json::from_str(line).map_err(|err| (Value::Null, RpcError::new(PARSE_ERROR, err)))?;
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let request = serde_json::from_value::<Request>(value)
+        .map_err(|err| (id.clone(), RpcError::new(INVALID_REQUEST, err)))?;
+    if request.jsonrpc != "2.0" {
+        return Err((
+            id,
+            RpcError::new(INVALID_REQUEST, "`jsonrpc` must be \"2.0\""),
+        ));
+    }
+    Ok(request)
+}
+
+/// Handle the request, returns the result.
+fn handle<W: Write + Send + 'static>(
+    controller: &Controller,
+    request: &Request,
+    output: &Arc<Mutex<W>>,
+    event_thread_handle: &mut Option<thread::JoinHandle<()>>,
+) -> Result<Value, RpcError> {
+    let regions_params = || -> Result<RegionsParams, RpcError> {
+        request
+            .params
+            .clone()
+            .map_or(Ok(RegionsParams::default()), |params| {
+                serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err))
+            })
+    };
+
+    match request.method.as_str() {
+        "list" => Ok(json!(controller.regions())),
+        "status" => {
+            let states = controller.states(&regions_params()?.regions)?;
+            Ok(Value::Object(
+                states
+                    .into_iter()
+                    .map(|(region, state)| (region, json!(state.name())))
+                    .collect(),
+            ))
+        }
+        method @ ("enable" | "disable") => {
+            let regions = regions_params()?.regions;
+            if regions.is_empty() {
+                return Err(RpcError::new(INVALID_PARAMS, "no `regions` given"));
+            }
+            regions
+                .iter()
+                .try_for_each(|region| controller.set_blocked(region, method == "disable"))?;
+            Ok(Value::Null)
+        }
+        "subscribe" => {
+            if event_thread_handle.is_none() {
+                let event_receiver = controller.subscribe();
+                let output = output.clone();
+                *event_thread_handle = Some(thread::spawn(move || {
+                    while let Ok(event) = event_receiver.recv() {
+                        let notification =
+                            json!({ "jsonrpc": "2.0", "method": "event", "params": event });
+                        if let Err(err) = write_line(&output, &notification) {
+                            log::error!("unable to write the rpc event: {}", err);
+                            break;
+                        }
+                    }
+                }));
+            }
+            Ok(Value::Bool(true))
+        }
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("no method named `{}`", method),
+        )),
+    }
+}
+
+/// Write the value as a single line and flush it.
+fn write_line(output: &Mutex<impl Write>, value: &Value) -> Result<(), Error> {
+    let mut output = output.lock().unwrap();
+    serde_json::to_writer(&mut *output, value)?;
+    output.write_all(b"\n")?;
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get the `id` and the code of the error of the line.
+    fn parse_error(line: &str) -> (Value, i64) {
+        match parse_request(line) {
+            Ok(_) => panic!("`{}` is a valid request", line),
+            Err((id, err)) => (id, err.code),
+        }
+    }
+
+    #[test]
+    fn parses_the_request() {
+        let request = parse_request(r#"{"jsonrpc": "2.0", "id": 1, "method": "list"}"#)
+            .unwrap_or_else(|(_, err)| panic!("{}", err.message));
+        assert_eq!(request.id, Some(json!(1)));
+        assert_eq!(request.method, "list");
+    }
+
+    #[test]
+    fn invalid_json_is_a_parse_error() {
+        assert_eq!(
+            parse_error(r#"{"jsonrpc": "2.0", "id": 1"#),
+            (Value::Null, PARSE_ERROR)
+        );
+    }
+
+    #[test]
+    fn invalid_request_echoes_the_id() {
+        assert_eq!(
+            parse_error(r#"{"jsonrpc": "2.0", "id": 7, "method": 1}"#),
+            (json!(7), INVALID_REQUEST)
+        );
+        assert_eq!(
+            parse_error(r#"{"jsonrpc": "1.0", "id": "a", "method": "list"}"#),
+            (json!("a"), INVALID_REQUEST)
+        );
+        assert_eq!(parse_error("[1, 2]"), (Value::Null, INVALID_REQUEST));
+        assert_eq!(parse_error("42"), (Value::Null, INVALID_REQUEST));
+    }
+}
@@ -0,0 +1,158 @@
+//! Rules that block the regions automatically from their recent
+//! pings, eg: "block any region whose mean ping is above 150 ms over
+//! the last 10 samples". The rules are evaluated by the app every time
+//! new pings arrive, the decisions are logged and shown in the GUI.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    file_ops::{self, Migration},
+    Error,
+};
+
+/// Migrations of the rules file, see [`file_ops::migrate()`].
+const MIGRATIONS: &[Migration] = &[];
+
+/// Maximum number of samples a rule can be evaluated over, the app
+/// keeps the last 20 pings of every IP by default.
+pub const MAX_SAMPLES: usize = 20;
+
+/// Condition on the recent pings of a region that blocks it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RuleCondition {
+    /// The mean ping of the last `samples` pings of the IPs of the
+    /// region is above `max_ping_ms`. The failed pings are left out,
+    /// the IPs with fewer than `samples` successful pings too.
+    MeanPingAbove { max_ping_ms: u32, samples: usize },
+}
+
+impl RuleCondition {
+    /// Check the condition against the recent pings of the IPs of a
+    /// region, the successful pings of every IP from newest to
+    /// oldest. Returns the value that met the condition, eg: the mean
+    /// ping.
+    pub fn check<'a>(
+        &self,
+        ip_pings: impl IntoIterator<Item = &'a [Duration]>,
+    ) -> Option<Duration> {
+        match *self {
+            RuleCondition::MeanPingAbove {
+                max_ping_ms,
+                samples,
+            } => {
+                let samples = samples.clamp(1, MAX_SAMPLES);
+                let pings: Vec<_> = ip_pings
+                    .into_iter()
+                    .filter_map(|ip_pings| ip_pings.get(..samples))
+                    .flatten()
+                    .collect();
+                if pings.is_empty() {
+                    return None;
+                }
+                let mean = pings.iter().copied().sum::<Duration>() / pings.len() as u32;
+                (mean > Duration::from_millis(u64::from(max_ping_ms))).then_some(mean)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for RuleCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleCondition::MeanPingAbove {
+                max_ping_ms,
+                samples,
+            } => write!(
+                f,
+                "mean ping above {} ms over the last {} samples",
+                max_ping_ms, samples
+            ),
+        }
+    }
+}
+
+/// Rule blocking the regions that meet its condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rule {
+    /// Only the enabled rules are evaluated.
+    pub enabled: bool,
+    pub condition: RuleCondition,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            condition: RuleCondition::MeanPingAbove {
+                max_ping_ms: 150,
+                samples: 10,
+            },
+        }
+    }
+}
+
+/// Decision of a rule to block a region.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleDecision {
+    /// Abreviation of the region.
+    pub region: String,
+    pub rule: Rule,
+    /// Value that met the condition of the rule, eg: the mean ping.
+    pub value: Duration,
+    /// Unix time (seconds) of the decision.
+    pub time: u64,
+}
+
+impl std::fmt::Display for RuleDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.rule.condition {
+            RuleCondition::MeanPingAbove { samples, .. } => write!(
+                f,
+                "blocked {}: mean ping {} ms over the last {} samples",
+                self.region,
+                self.value.as_millis(),
+                samples
+            ),
+        }
+    }
+}
+
+/// Rules of the user, stored at [`file_ops::get_rules_file_path()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    pub rules: Vec<Rule>,
+}
+
+impl Rules {
+    /// Load the rules, none if the file does not exist yet.
+    pub fn load() -> Result<Self, Error> {
+        let file_path = file_ops::get_rules_file_path()?;
+        if !file_path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(file_ops::load_versioned(file_path, MIGRATIONS)?)
+    }
+
+    /// Save the rules.
+    pub fn save(&self) -> Result<(), Error> {
+        file_ops::save_versioned(file_ops::get_rules_file_path()?, self, MIGRATIONS)?;
+        Ok(())
+    }
+
+    /// Get the first enabled rule whose condition the recent pings of
+    /// the region meet along with the value that met it, see
+    /// [`RuleCondition::check()`].
+    pub fn evaluate(&self, ip_pings: &[Vec<Duration>]) -> Option<(Rule, Duration)> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .find_map(|rule| {
+                let value = rule.condition.check(ip_pings.iter().map(Vec::as_slice))?;
+                Some((*rule, value))
+            })
+    }
+}
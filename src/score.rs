@@ -0,0 +1,147 @@
+//! Composite score of the connection to a region, so that the regions
+//! are not ranked by their mean ping alone.
+//!
+//! `score = ping weight * mean ping + jitter weight * jitter + loss
+//! weight * loss`, with the pings in milliseconds and the loss in
+//! percent. Lower is better.
+
+use std::time::Duration;
+
+use crate::ping::{self, PingInfo};
+
+/// Weights of the components of the score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreWeights {
+    /// Weight of the mean ping in milliseconds.
+    pub ping: f64,
+    /// Weight of the jitter in milliseconds.
+    pub jitter: f64,
+    /// Weight of the loss in percent.
+    pub loss: f64,
+}
+
+impl ScoreWeights {
+    /// Weights used unless configured otherwise.
+    pub const DEFAULT: Self = Self {
+        ping: 1.0,
+        jitter: 4.0,
+        loss: 50.0,
+    };
+}
+
+/// Connection statistics of an IP or a region.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ConnectionStats {
+    /// Mean ping of the received packets, [`None`] if none were
+    /// received.
+    pub mean_ping: Option<Duration>,
+    /// Lowest ping of the received packets.
+    pub min_ping: Option<Duration>,
+    /// Highest ping of the received packets.
+    pub max_ping: Option<Duration>,
+    /// Standard deviation of the pings of the received packets.
+    pub jitter: Duration,
+    pub num_packets: usize,
+    pub lost_packets: usize,
+}
+
+impl ConnectionStats {
+    /// Statistics when no packet was sent.
+    pub const NONE: Self = Self {
+        mean_ping: None,
+        min_ping: None,
+        max_ping: None,
+        jitter: Duration::ZERO,
+        num_packets: 0,
+        lost_packets: 0,
+    };
+
+    /// Compute the statistics of the pings of an IP.
+    pub fn new<'a>(pings: impl IntoIterator<Item = &'a Result<PingInfo, ping::Error>>) -> Self {
+        let mut num_packets = 0;
+        let mut rtts = Vec::new();
+        pings.into_iter().for_each(|ping| {
+            num_packets += 1;
+            if let Ok(info) = ping {
+                rtts.push(info.get_rtt());
+            }
+        });
+
+        let mean_ping = (!rtts.is_empty())
+            .then(|| rtts.iter().sum::<Duration>() / u32::try_from(rtts.len()).unwrap());
+        let jitter = match mean_ping {
+            Some(mean_ping) if rtts.len() >= 2 => {
+                let mean = mean_ping.as_secs_f64();
+                let variance = rtts
+                    .iter()
+                    .map(|rtt| (rtt.as_secs_f64() - mean).powi(2))
+                    .sum::<f64>()
+                    / rtts.len() as f64;
+                Duration::from_secs_f64(variance.sqrt())
+            }
+            _ => Duration::ZERO,
+        };
+        Self {
+            mean_ping,
+            min_ping: rtts.iter().min().copied(),
+            max_ping: rtts.iter().max().copied(),
+            jitter,
+            num_packets,
+            lost_packets: num_packets - rtts.len(),
+        }
+    }
+
+    /// Combine the statistics of the IPs of a region, the mean ping
+    /// is weighted by the received packets and the jitter is the
+    /// mean jitter of the IPs with received packets.
+    pub fn combine(stats: &[Self]) -> Self {
+        let received: Vec<_> = stats
+            .iter()
+            .filter_map(|stats| {
+                let num_received = stats.num_packets - stats.lost_packets;
+                Some((stats.mean_ping?, stats.jitter, num_received))
+            })
+            .collect();
+        let total_received: usize = received.iter().map(|(_, _, num)| num).sum();
+        let mean_ping = (total_received != 0).then(|| {
+            received
+                .iter()
+                .map(|(ping, _, num)| *ping * u32::try_from(*num).unwrap())
+                .sum::<Duration>()
+                / u32::try_from(total_received).unwrap()
+        });
+        let jitter = if received.is_empty() {
+            Duration::ZERO
+        } else {
+            received
+                .iter()
+                .map(|(_, jitter, _)| *jitter)
+                .sum::<Duration>()
+                / u32::try_from(received.len()).unwrap()
+        };
+        Self {
+            mean_ping,
+            min_ping: stats.iter().filter_map(|stats| stats.min_ping).min(),
+            max_ping: stats.iter().filter_map(|stats| stats.max_ping).max(),
+            jitter,
+            num_packets: stats.iter().map(|stats| stats.num_packets).sum(),
+            lost_packets: stats.iter().map(|stats| stats.lost_packets).sum(),
+        }
+    }
+
+    /// Get the loss in percent, [`None`] if no packet was sent.
+    pub fn loss_percent(&self) -> Option<f64> {
+        (self.num_packets != 0).then(|| self.lost_packets as f64 / self.num_packets as f64 * 100.0)
+    }
+
+    /// Get the score, lower is better. [`None`] if no packet was
+    /// received.
+    pub fn score(&self, weights: &ScoreWeights) -> Option<f64> {
+        let mean_ping = self.mean_ping?;
+        Some(
+            weights.ping * mean_ping.as_secs_f64() * 1000.0
+                + weights.jitter * self.jitter.as_secs_f64() * 1000.0
+                + weights.loss * self.loss_percent().unwrap_or(0.0),
+        )
+    }
+}
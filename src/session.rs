@@ -0,0 +1,73 @@
+//! Session mode (`--session`): the blocks made by the app are undone
+//! when it exits, also when it crashes (see [`crate::crash`]), so the
+//! user is never left silently blocked.
+
+use std::{collections::HashSet, net::IpAddr, sync::Mutex};
+
+use lazy_static::lazy_static;
+
+use crate::{firewall::Firewall, Error};
+
+/// State of the active session.
+struct Session {
+    /// IPs blocked before the session started, they are kept blocked.
+    blocked_before: HashSet<IpAddr>,
+    /// IPs blocked during the session.
+    blocked: HashSet<IpAddr>,
+}
+
+lazy_static! {
+    static ref SESSION: Mutex<Option<Session>> = Mutex::new(None);
+}
+
+/// Start the session, the IPs blocked so far are not undone.
+pub fn start(firewall: &Firewall) -> Result<(), Error> {
+    let blocked_before = firewall.list_blocked()?;
+    *SESSION.lock().unwrap() = Some(Session {
+        blocked_before,
+        blocked: HashSet::new(),
+    });
+    log::info!("session mode, the blocks are undone on exit");
+    Ok(())
+}
+
+/// Check if a session is active.
+pub fn is_active() -> bool {
+    SESSION.lock().unwrap().is_some()
+}
+
+/// Record that the IPs were blocked (or unblocked), does nothing if
+/// no session is active.
+pub fn record(ips: &[IpAddr], blocked: bool) {
+    if let Some(session) = SESSION.lock().unwrap().as_mut() {
+        if blocked {
+            session.blocked.extend(ips);
+        } else {
+            ips.iter().for_each(|ip| {
+                session.blocked.remove(ip);
+            });
+        }
+    }
+}
+
+/// Unblock the IPs blocked during the session and end it, returns the
+/// number of unblocked IPs. Does nothing if no session is active.
+pub fn rollback(firewall: &Firewall) -> Result<usize, Error> {
+    // the lock may be poisoned if the app panicked while it was held,
+    // the session is still consistent
+    let session = SESSION
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .take();
+    let Some(session) = session else {
+        return Ok(0);
+    };
+    let ips: Vec<_> = session
+        .blocked
+        .difference(&session.blocked_before)
+        .copied()
+        .collect();
+    firewall.unban_ips(&ips)?;
+    log::info!("session ended, unblocked {} IPs", ips.len());
+    Ok(ips.len())
+}
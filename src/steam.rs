@@ -0,0 +1,303 @@
+//! Locating the Steam installation, its per-user data and the game
+//! that is running.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use crate::waker::Waker;
+
+/// Get the home directory of the user running Steam.
+///
+/// The application generally runs with elevated privileges, so the
+/// home directory of the user that invoked `sudo` is preferred.
+#[cfg(unix)]
+fn get_user_home_dir() -> Option<PathBuf> {
+    if let Some(sudo_user) = std::env::var_os("SUDO_USER") {
+        let home_dir = Path::new("/home").join(sudo_user);
+        if home_dir.is_dir() {
+            return Some(home_dir);
+        }
+    }
+
+    directories::BaseDirs::new().map(|base_dirs| base_dirs.home_dir().to_path_buf())
+}
+
+/// Get the candidate Steam installation directories, in the order of
+/// preference.
+fn get_install_dir_candidates() -> Vec<PathBuf> {
+    #[cfg(unix)]
+    {
+        get_user_home_dir()
+            .map(|home_dir| {
+                [
+                    ".steam/steam",
+                    ".local/share/Steam",
+                    ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+                ]
+                .into_iter()
+                .map(|dir| home_dir.join(dir))
+                .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[cfg(windows)]
+    {
+        ["ProgramFiles(x86)", "ProgramFiles"]
+            .into_iter()
+            .filter_map(std::env::var_os)
+            .map(|program_files| PathBuf::from(program_files).join("Steam"))
+            .collect()
+    }
+}
+
+/// Find the Steam installation directory.
+pub fn find_install_dir() -> Option<PathBuf> {
+    get_install_dir_candidates()
+        .into_iter()
+        .find(|dir| dir.join("userdata").is_dir())
+}
+
+/// Get the per-user data directories (`userdata/<account id>`) of the
+/// given Steam installation.
+pub fn get_userdata_dirs(install_dir: impl AsRef<Path>) -> Vec<PathBuf> {
+    let userdata_dir = install_dir.as_ref().join("userdata");
+    match userdata_dir.read_dir() {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect(),
+        Err(err) => {
+            log::warn!("unable to read `{}`: {}", userdata_dir.display(), err);
+            Vec::new()
+        }
+    }
+}
+
+/// Game (or tool) installed in one of the Steam libraries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledApp {
+    pub app_id: u32,
+    pub name: String,
+}
+
+impl std::fmt::Display for InstalledApp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.app_id)
+    }
+}
+
+/// Name prefixes of the Steam tools that show up as installed apps
+/// but do not connect to any game servers.
+const TOOL_NAME_PREFIXES: &[&str] = &[
+    "Proton",
+    "Steam Linux Runtime",
+    "Steamworks Common Redistributables",
+    "Steamworks Shared",
+];
+
+/// Get the library directories (the ones containing `steamapps`) of
+/// the given Steam installation, listed in
+/// `steamapps/libraryfolders.vdf`. The installation directory is
+/// always the first library.
+pub fn get_library_dirs(install_dir: impl AsRef<Path>) -> Vec<PathBuf> {
+    let install_dir = install_dir.as_ref();
+    let library_folders_file_path = install_dir.join("steamapps/libraryfolders.vdf");
+    let mut library_dirs = vec![install_dir.to_path_buf()];
+    match std::fs::read_to_string(&library_folders_file_path) {
+        Ok(contents) => library_dirs.extend(
+            contents
+                .lines()
+                .filter_map(parse_key_value)
+                .filter(|(key, _)| *key == "path")
+                // backslashes are escaped on windows
+                .map(|(_, path)| PathBuf::from(path.replace("\\\\", "\\"))),
+        ),
+        Err(err) => log::warn!(
+            "unable to read `{}`: {}",
+            library_folders_file_path.display(),
+            err
+        ),
+    }
+    // the installation directory is usually listed as well
+    let mut seen = std::collections::HashSet::new();
+    library_dirs.retain(|dir| seen.insert(dir.canonicalize().unwrap_or_else(|_| dir.clone())));
+    library_dirs
+}
+
+/// Get the games installed in the libraries of the given Steam
+/// installation, sorted by name. They are read from the
+/// `steamapps/appmanifest_<app id>.acf` files, the Steam tools
+/// (Proton, runtimes, ...) are left out.
+///
+/// Whether a game uses the Steam Datagram Relay cannot be known
+/// locally, it is known once its network datagram config is
+/// downloaded.
+pub fn get_installed_apps(install_dir: impl AsRef<Path>) -> Vec<InstalledApp> {
+    let mut apps: Vec<_> = get_library_dirs(install_dir)
+        .into_iter()
+        .filter_map(|library_dir| library_dir.join("steamapps").read_dir().ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|file_name| file_name.to_str())
+                .is_some_and(|file_name| {
+                    file_name.starts_with("appmanifest_") && file_name.ends_with(".acf")
+                })
+        })
+        .filter_map(|path| {
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|err| log::warn!("unable to read `{}`: {}", path.display(), err))
+                .ok()?;
+            let mut app_id = None;
+            let mut name = None;
+            contents
+                .lines()
+                .filter_map(parse_key_value)
+                .for_each(|(key, value)| match key {
+                    "appid" if app_id.is_none() => app_id = value.parse().ok(),
+                    "name" if name.is_none() => name = Some(value.to_string()),
+                    _ => {}
+                });
+            Some(InstalledApp {
+                app_id: app_id?,
+                name: name?,
+            })
+        })
+        .filter(|app| {
+            !TOOL_NAME_PREFIXES
+                .iter()
+                .any(|prefix| app.name.starts_with(prefix))
+        })
+        .collect();
+    apps.sort_by(|a, b| a.name.cmp(&b.name));
+    apps.dedup_by_key(|app| app.app_id);
+    apps
+}
+
+/// Get the Steam app id of the game that is running, [`None`] if no
+/// game is running or it cannot be determined.
+///
+/// Steam records it in its registry, on Linux the registry is the
+/// `registry.vdf` file in the `.steam` directory.
+pub fn running_app_id() -> Option<u32> {
+    #[cfg(unix)]
+    let app_id = {
+        let registry_file_path = get_user_home_dir()?.join(".steam/registry.vdf");
+        std::fs::read_to_string(registry_file_path)
+            .ok()?
+            .lines()
+            .filter_map(parse_key_value)
+            .find(|(key, _)| key.eq_ignore_ascii_case("RunningAppID"))
+            .and_then(|(_, value)| value.parse().ok())?
+    };
+
+    #[cfg(windows)]
+    let app_id = {
+        let output = std::process::Command::new("reg")
+            .args(["query", r"HKCU\Software\Valve\Steam", "/v", "RunningAppID"])
+            .output()
+            .ok()?;
+        // `    RunningAppID    REG_DWORD    0x2da`
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| {
+                let mut parts = line.split_whitespace();
+                if !parts.next()?.eq_ignore_ascii_case("RunningAppID") {
+                    return None;
+                }
+                let value = parts.nth(1)?;
+                u32::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+            })?
+    };
+
+    // 0 when no game is running
+    (app_id != 0).then_some(app_id)
+}
+
+/// Watches the game that is running (see [`running_app_id()`]) on a
+/// separate thread.
+pub struct RunningAppWatcher {
+    kill_sender: mpsc::Sender<()>,
+    app_id_receiver: mpsc::Receiver<Option<u32>>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+}
+
+impl RunningAppWatcher {
+    /// Start checking the running game every `interval`, `waker` is
+    /// woken up when it changes.
+    pub fn new(interval: Duration, waker: Waker) -> Self {
+        Self::with_detector(interval, waker, running_app_id)
+    }
+
+    /// Same as [`Self::new()`] but the running game is detected by
+    /// `detect`, eg: [`crate::game::running_game()`].
+    pub fn with_detector(interval: Duration, waker: Waker, detect: fn() -> Option<u32>) -> Self {
+        let (kill_sender, kill_receiver) = mpsc::channel();
+        let (app_id_sender, app_id_receiver) = mpsc::channel();
+
+        let thread_handle = thread::spawn(move || {
+            let mut prev_app_id = None;
+            loop {
+                let app_id = detect();
+                if app_id != prev_app_id {
+                    if app_id_sender.send(app_id).is_err() {
+                        break;
+                    }
+                    waker.wake();
+                    prev_app_id = app_id;
+                }
+
+                match kill_receiver.recv_timeout(interval) {
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    Ok(()) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+
+        Self {
+            kill_sender,
+            app_id_receiver,
+            thread_handle: Some(thread_handle),
+        }
+    }
+
+    /// Get the running game if it changed since the last call,
+    /// `Some(None)` if no game is running anymore.
+    pub fn try_recv_latest(&self) -> Option<Option<u32>> {
+        self.app_id_receiver.try_iter().last()
+    }
+}
+
+impl Drop for RunningAppWatcher {
+    fn drop(&mut self) {
+        let _ = self.kill_sender.send(());
+        if let Some(thread_handle) = self.thread_handle.take() {
+            if thread_handle.join().is_err() {
+                log::error!("running app watcher thread panicked");
+            }
+        }
+    }
+}
+
+/// Parse a `"key" "value"` line.
+pub(crate) fn parse_key_value(line: &str) -> Option<(&str, &str)> {
+    let mut parts = line.split('"');
+    // before the key
+    if !parts.next()?.trim().is_empty() {
+        return None;
+    }
+    let key = parts.next()?;
+    if !parts.next()?.trim().is_empty() {
+        return None;
+    }
+    let value = parts.next()?;
+    Some((key, value))
+}
@@ -0,0 +1,121 @@
+//! Steam content (CDN) servers, the servers the Steam client
+//! downloads the games and updates from, from
+//! `IContentServerDirectoryService/GetServersForSteamPipe`.
+//!
+//! Blocking them only affects the downloads, eg: to move the
+//! downloads off a bad cache. They are given the
+//! [`ServerCategory::Content`] category and are never part of the
+//! block lists, the abreviations are prefixed with [`ABR_PREFIX`].
+//!
+//! The servers are listed by host name, they are resolved to their
+//! IPs when loaded.
+
+use std::{collections::BTreeMap, net::ToSocketAddrs, path::Path};
+
+use serde::Deserialize;
+
+use crate::{
+    downloader, file_ops,
+    steam_server::{ServerCategory, ServerInfo, Servers},
+    Error,
+};
+
+/// Prefix of the abreviations of the content servers.
+pub const ABR_PREFIX: &str = "cdn-";
+
+const CDN_LIST_URL: &str =
+    "https://api.steampowered.com/IContentServerDirectoryService/GetServersForSteamPipe/v1/?cell_id=0";
+
+#[derive(Debug, Deserialize)]
+struct CdnListResponse {
+    response: CdnList,
+}
+
+#[derive(Debug, Deserialize)]
+struct CdnList {
+    servers: Vec<CdnServer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CdnServer {
+    /// Host name of the server, eg: `cache1-fra1.steamcontent.com`.
+    host: String,
+    /// Type of the server, eg: `SteamCache`, `CDN`.
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+/// Download the content server list to
+/// [`file_ops::get_cdn_list_file_path()`].
+pub fn download_file() -> Result<(), Error> {
+    downloader::Download::from_url(CDN_LIST_URL, file_ops::get_cdn_list_file_path()?)?;
+    Ok(())
+}
+
+/// Load the content servers from the given file, the file at
+/// [`file_ops::get_cdn_list_file_path()`] is used (and downloaded if
+/// it does not exist) if no file is given.
+///
+/// note: this resolves the host names of the servers, it blocks until
+/// all of them are resolved.
+pub fn load(cdn_list_file_path: Option<impl AsRef<Path>>) -> Result<Vec<ServerInfo>, Error> {
+    let data = match cdn_list_file_path {
+        Some(path) => std::fs::read(path)?,
+        None => {
+            let file_path = file_ops::get_cdn_list_file_path()?;
+            if !file_path.exists() {
+                log::info!("{} doesn't exist, downloading it", file_path.display());
+                download_file()?;
+            }
+            std::fs::read(file_path)?
+        }
+    };
+    from_json(&data)
+}
+
+/// Load the Steam content (CDN) servers into their category of `servers`,
+/// errors are logged.
+pub fn load_into(servers: &mut Servers) {
+    match load(None::<&Path>) {
+        Ok(loaded) => servers.replace_category(ServerCategory::Content, loaded),
+        Err(err) => log::error!("unable to load the content servers: {}", err),
+    }
+}
+
+/// Parse the content servers from the response of
+/// `GetServersForSteamPipe`, the host names are resolved to their
+/// IPs. Hosts that cannot be resolved are skipped.
+pub fn from_json(json_data: &[u8]) -> Result<Vec<ServerInfo>, Error> {
+    let cdn_list: CdnListResponse = serde_json::from_slice(json_data)?;
+
+    let hosts: BTreeMap<_, _> = cdn_list
+        .response
+        .servers
+        .iter()
+        .map(|server| (server.host.as_str(), server.kind.as_str()))
+        .collect();
+
+    Ok(hosts
+        .into_iter()
+        .filter_map(|(host, kind)| {
+            let ips: Vec<_> = match (host, 443).to_socket_addrs() {
+                Ok(addresses) => addresses.map(|address| address.ip()).collect(),
+                Err(err) => {
+                    log::warn!("unable to resolve `{}`: {}", host, err);
+                    return None;
+                }
+            };
+            if ips.is_empty() {
+                return None;
+            }
+            let name = host.strip_suffix(".steamcontent.com").unwrap_or(host);
+            Some(ServerInfo::new(
+                format!("{}{}", ABR_PREFIX, name),
+                Some(format!("{} ({})", host, kind)),
+                ips,
+                None,
+                ServerCategory::Content,
+            ))
+        })
+        .collect())
+}
@@ -0,0 +1,112 @@
+//! Steam connection manager (CM) servers, the servers the Steam
+//! client connects to, from `ISteamDirectory/GetCMListForConnect`.
+//!
+//! The servers are grouped by their datacenter and are given the
+//! [`ServerCategory::ConnectionManager`] category, the abreviations
+//! are prefixed with [`ABR_PREFIX`] so that they do not clash with
+//! the relays.
+
+use std::{collections::BTreeMap, net::SocketAddr, path::Path};
+
+use serde::Deserialize;
+
+use crate::{
+    downloader, file_ops,
+    steam_server::{ServerCategory, ServerInfo, Servers},
+    Error,
+};
+
+/// Prefix of the abreviations of the CM servers.
+pub const ABR_PREFIX: &str = "cm-";
+
+const CM_LIST_URL: &str =
+    "https://api.steampowered.com/ISteamDirectory/GetCMListForConnect/v1/?cellid=0&cmtype=netfilter";
+
+#[derive(Debug, Deserialize)]
+struct CmListResponse {
+    response: CmList,
+}
+
+#[derive(Debug, Deserialize)]
+struct CmList {
+    serverlist: Vec<CmServer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CmServer {
+    /// `ip:port` for the `netfilter` servers, host name for the
+    /// `websockets` servers.
+    endpoint: String,
+    #[serde(rename = "type")]
+    kind: String,
+    /// Datacenter of the server, eg: `fra1`.
+    dc: String,
+}
+
+/// Download the CM server list to [`file_ops::get_cm_list_file_path()`].
+pub fn download_file() -> Result<(), Error> {
+    downloader::Download::from_url(CM_LIST_URL, file_ops::get_cm_list_file_path()?)?;
+    Ok(())
+}
+
+/// Load the CM servers from the given file, the file at
+/// [`file_ops::get_cm_list_file_path()`] is used (and downloaded if
+/// it does not exist) if no file is given.
+pub fn load(cm_list_file_path: Option<impl AsRef<Path>>) -> Result<Vec<ServerInfo>, Error> {
+    let data = match cm_list_file_path {
+        Some(path) => std::fs::read(path)?,
+        None => {
+            let file_path = file_ops::get_cm_list_file_path()?;
+            if !file_path.exists() {
+                log::info!("{} doesn't exist, downloading it", file_path.display());
+                download_file()?;
+            }
+            std::fs::read(file_path)?
+        }
+    };
+    from_json(&data)
+}
+
+/// Load the Steam connection manager (CM) servers into their category of `servers`,
+/// errors are logged.
+pub fn load_into(servers: &mut Servers) {
+    match load(None::<&Path>) {
+        Ok(loaded) => servers.replace_category(ServerCategory::ConnectionManager, loaded),
+        Err(err) => log::error!("unable to load the cm servers: {}", err),
+    }
+}
+
+/// Parse the CM servers from the response of
+/// `GetCMListForConnect`.
+pub fn from_json(json_data: &[u8]) -> Result<Vec<ServerInfo>, Error> {
+    let cm_list: CmListResponse = serde_json::from_slice(json_data)?;
+
+    let mut datacenters: BTreeMap<String, Vec<_>> = BTreeMap::new();
+    cm_list
+        .response
+        .serverlist
+        .iter()
+        .filter(|server| server.kind == "netfilter")
+        .for_each(|server| match server.endpoint.parse::<SocketAddr>() {
+            Ok(address) => {
+                let ips = datacenters.entry(server.dc.clone()).or_default();
+                if !ips.contains(&address.ip()) {
+                    ips.push(address.ip());
+                }
+            }
+            Err(_) => log::warn!("{}: invalid cm endpoint `{}`", server.dc, server.endpoint),
+        });
+
+    Ok(datacenters
+        .into_iter()
+        .map(|(dc, ips)| {
+            ServerInfo::new(
+                format!("{}{}", ABR_PREFIX, dc),
+                Some(format!("Steam connection managers in {}", dc)),
+                ips,
+                None,
+                ServerCategory::ConnectionManager,
+            )
+        })
+        .collect())
+}
@@ -0,0 +1,167 @@
+//! Pings to the relays measured by Steam itself, read from the logs
+//! of the Steam client.
+//!
+//! Steam measures the ping to the relays through the relay protocol
+//! (not ICMP) and logs its "ping location", a comma separated list of
+//! `<region>=<ping>+<front ping>` entries, eg:
+//! `sto=22+2,fra=34+3/33+2`. Alternative routes to a region are
+//! separated by `/`, the first one is the best route. The regions are
+//! the abreviations of the relays.
+//!
+//! ICMP may be deprioritized or blocked by some routers, so the ping
+//! measured by Steam can differ a lot from the ICMP ping.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use lazy_static::lazy_static;
+
+use crate::Error;
+
+lazy_static! {
+    /// `<region>=<ping>+<front ping>` entry of a ping location.
+    static ref PING_LOCATION_ENTRY_REGEX: regex::Regex =
+        regex::Regex::new(r"^([a-z0-9]+)=(\d+)\+\d+").unwrap();
+}
+
+/// Prefix of the names of the log files that the ping location is
+/// logged to.
+const LOG_FILE_NAME_PREFIX: &str = "connection_log";
+
+/// Minimum interval between the checks for new log entries.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Parse the ping location, returns the ping of the best route to
+/// every region.
+pub fn parse_ping_location(ping_location: &str) -> HashMap<String, Duration> {
+    ping_location
+        .split(',')
+        .filter_map(|entry| {
+            let captures = PING_LOCATION_ENTRY_REGEX.captures(entry.trim())?;
+            let ping = captures[2].parse().ok()?;
+            Some((captures[1].to_string(), Duration::from_millis(ping)))
+        })
+        .collect()
+}
+
+/// Find the last ping location logged in the log file contents.
+fn find_last_ping_location(contents: &str) -> Option<&str> {
+    contents.lines().rev().find_map(|line| {
+        let start = line.to_ascii_lowercase().find("ping location")? + "ping location".len();
+        let ping_location = line[start..]
+            .trim_start_matches(|c: char| c == ':' || c == '=' || c.is_whitespace())
+            .split(|c: char| c.is_whitespace() || c == '"' || c == '\'')
+            .next()?;
+        (!ping_location.is_empty()).then_some(ping_location)
+    })
+}
+
+/// Get the log files that may contain the ping location, the most
+/// recently modified first.
+fn get_log_files(logs_dir: &Path) -> Result<Vec<(PathBuf, SystemTime)>, Error> {
+    let mut log_files = logs_dir
+        .read_dir()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|file_name| file_name.starts_with(LOG_FILE_NAME_PREFIX))
+        })
+        .map(|entry| Ok((entry.path(), entry.metadata()?.modified()?)))
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    log_files.sort_by(|(_, a), (_, b)| b.cmp(a));
+    Ok(log_files)
+}
+
+/// Pings measured by Steam, see the module documentation.
+#[derive(Debug)]
+pub struct SteamPings {
+    logs_dir: PathBuf,
+    pings: HashMap<String, Duration>,
+    /// Modification time of the log file the pings were read from.
+    modified: Option<SystemTime>,
+    last_check: Option<Instant>,
+}
+
+impl SteamPings {
+    /// Read the pings from the logs of the given Steam installation.
+    pub fn new(steam_install_dir: impl AsRef<Path>) -> Self {
+        let mut res = Self {
+            logs_dir: steam_install_dir.as_ref().join("logs"),
+            pings: HashMap::new(),
+            modified: None,
+            last_check: None,
+        };
+        res.update();
+        res
+    }
+
+    /// Re-read the pings if the logs changed, the logs are checked
+    /// at most every [`CHECK_INTERVAL`]. Returns `true` if the pings
+    /// changed.
+    pub fn update(&mut self) -> bool {
+        if self
+            .last_check
+            .is_some_and(|last_check| last_check.elapsed() < CHECK_INTERVAL)
+        {
+            return false;
+        }
+        self.last_check = Some(Instant::now());
+
+        match self.read() {
+            Ok(changed) => changed,
+            Err(err) => {
+                log::warn!(
+                    "unable to read Steam's pings from `{}`: {}",
+                    self.logs_dir.display(),
+                    err
+                );
+                false
+            }
+        }
+    }
+
+    /// Read the last ping location from the most recently modified
+    /// log file that has one, nothing is read if the logs did not
+    /// change since the last read. Returns `true` if the pings
+    /// changed.
+    fn read(&mut self) -> Result<bool, Error> {
+        for (path, modified) in get_log_files(&self.logs_dir)? {
+            if self
+                .modified
+                .is_some_and(|prev_modified| modified <= prev_modified)
+            {
+                return Ok(false);
+            }
+            // the logs are not always valid utf-8
+            let contents = fs::read(&path)?;
+            let contents = String::from_utf8_lossy(&contents);
+            if let Some(ping_location) = find_last_ping_location(&contents) {
+                let pings = parse_ping_location(ping_location);
+                self.modified = Some(modified);
+                if pings == self.pings {
+                    return Ok(false);
+                }
+                log::info!("read Steam's pings to {} regions", pings.len());
+                self.pings = pings;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Get the ping to the region measured by Steam.
+    pub fn get(&self, abr: &str) -> Option<Duration> {
+        self.pings.get(abr).copied()
+    }
+
+    /// Get a reference to the pings measured by Steam, by region.
+    pub fn pings(&self) -> &HashMap<String, Duration> {
+        &self.pings
+    }
+}
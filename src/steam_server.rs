@@ -1,11 +1,15 @@
 use std::{
-    net::Ipv4Addr,
-    path::{Path, PathBuf},
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    net::IpAddr,
+    ops::RangeInclusive,
+    path::Path,
 };
 
 use crate::{
-    downloader,
-    firewall::{self, Firewall},
+    file_ops,
+    firewall::{Firewall, PortRanges},
+    geocode, Error,
 };
 
 use self::parse::ServerObject;
@@ -13,14 +17,9 @@ use self::parse::ServerObject;
 mod parse {
     use serde::{Deserialize, Serialize};
 
-    use std::fs::File;
-    use std::io::prelude::*;
-    use std::path::PathBuf;
     use std::{collections::HashMap, path::Path};
 
-    use crate::{downloader, file_ops};
-
-    use super::Error;
+    use crate::{downloader, file_ops, timings, Error};
 
     #[derive(Serialize, Deserialize)]
     pub struct ServerObject {
@@ -50,6 +49,9 @@ mod parse {
     #[derive(Serialize, Deserialize)]
     pub(crate) struct RelayInfo {
         pub ipv4: String,
+        /// Only some of the relays have an IPv6.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub ipv6: Option<String>,
         pub port_range: Vec<usize>,
     }
 
@@ -58,73 +60,88 @@ mod parse {
         pub(crate) fn get_ipv4(&self) -> &str {
             self.ipv4.as_ref()
         }
-    }
 
-    impl Default for ServerObject {
-        fn default() -> Self {
-            Self::new(None::<PathBuf>)
+        /// Get a reference to the relay info's ipv6, if it has one.
+        pub(crate) fn get_ipv6(&self) -> Option<&str> {
+            self.ipv6.as_deref()
+        }
+
+        /// Get the UDP port range of the relay, [`None`] if it is not
+        /// a valid range.
+        pub(crate) fn get_port_range(&self) -> Option<std::ops::RangeInclusive<u16>> {
+            match self.port_range.as_slice() {
+                [start, end] if start <= end => {
+                    Some(u16::try_from(*start).ok()?..=u16::try_from(*end).ok()?)
+                }
+                _ => None,
+            }
         }
     }
 
     impl ServerObject {
-        pub fn new(network_datagram_config_file_path: Option<impl AsRef<Path>>) -> Self {
-            let network_datagram_config_file_path = network_datagram_config_file_path
-                .as_ref()
-                .map(|path| path.as_ref());
-            let file_path = if let Some(path) = network_datagram_config_file_path {
-                path
-            } else {
-                file_ops::get_network_datagram_config_file_path()
+        pub fn new(
+            network_datagram_config_file_path: Option<impl AsRef<Path>>,
+            app_id: u32,
+        ) -> Result<Self, Error> {
+            let file_path = match network_datagram_config_file_path {
+                Some(path) => path.as_ref().to_path_buf(),
+                None => file_ops::get_network_datagram_config_file_path_for_app(app_id)?,
             };
-            let mut file = File::open(file_path)
-                .or_else(|_| {
-                    match Self::download_file() {
-                        Ok(_) => {}
-                        Err(error) => {
-                            panic!(
-                                "{} didn't exist, tried to download, \
-                                 check your internet connection? {}",
-                                file_path.to_str().unwrap(),
-                                error
-                            )
-                        }
-                    }
-                    File::open(file_path)
-                })
-                .expect(
-                    "didn't find the file, tried to download, \
-                     but even that might have failed",
-                );
-            let mut json_data = String::new();
-            file.read_to_string(&mut json_data).unwrap();
-
-            serde_json::from_str(&json_data).expect(
-                "network datagram config file \
-                 json structure might have changed, \
-                 unable to parse, contact developer",
-            )
+
+            if !file_path.exists() {
+                log::info!("{} doesn't exist, downloading it", file_path.display());
+                Self::download_file(app_id)?;
+            }
+
+            Self::from_json(&std::fs::read(file_path)?)
+        }
+
+        pub fn from_json(json_data: &[u8]) -> Result<Self, Error> {
+            let _timer = timings::timer("network datagram config parsing");
+            // error here likely means that the network datagram
+            // config file json structure has changed
+            Ok(serde_json::from_slice(json_data)?)
         }
 
-        pub fn download_file() -> Result<(), Error> {
-            let file_path = file_ops::get_network_datagram_config_file_path();
+        pub fn download_file(app_id: u32) -> Result<(), Error> {
+            let file_path = file_ops::get_network_datagram_config_file_path_for_app(app_id)?;
+            file_ops::write_atomic(&file_path, Self::download(app_id)?)?;
+            log::info!(
+                "downloaded the network datagram config of app {} to `{}`",
+                app_id,
+                file_path.display()
+            );
+            Ok(())
+        }
+
+        /// Download the contents of the network datagram config of
+        /// the given Steam app.
+        pub fn download(app_id: u32) -> Result<Vec<u8>, Error> {
+            let download_res = downloader::Download::to_bytes(&format!(
+                "https://api.steampowered.com/ISteamApps/GetSDRConfig/v1/?appid={}",
+                app_id
+            ));
+            if app_id != super::DEFAULT_APP_ID {
+                return Ok(download_res?);
+            }
+
             // `NetworkDatagramConfig.json` is no longer available on
             // the master branch of `SteamDatabase`, so use the latest
             // available version as a fallback if the json file is not
             // available on the steam website
-            downloader::Download::from_url(
-                "https://api.steampowered.com/ISteamApps/GetSDRConfig/v1/?appid=730",
-                file_path,
-            )
-            .or_else(|err1| {
-                downloader::Download::from_url(
+            download_res.or_else(|err1| {
+                downloader::Download::to_bytes(
                     "https://raw.githubusercontent.com/SteamDatabase/\
                      SteamTracking/0ae12036fceb607d31a2cecb504f4ffa6f52d306/\
                      Random/NetworkDatagramConfig.json",
-                    file_path,
                 )
-                .map_err(|err2| Error::DownloaderMultiple(vec![err1, err2]))
-            })?;
-            Ok(())
+                .map_err(|err2| Error::DownloadMultiple(vec![err1, err2]))
+            })
+        }
+
+        /// Get the revision of the network datagram config.
+        pub(crate) fn get_revision(&self) -> usize {
+            self.revision
         }
 
         /// Get a reference to the server object's pops.
@@ -139,55 +156,120 @@ pub enum ServerState {
     AllDisabled,
     /// Some IPs of the server are disabled. IPs that are disabled are
     /// passed along.
-    SomeDisabled(Vec<Ipv4Addr>),
+    SomeDisabled(Vec<IpAddr>),
     NoneDisabled,
     Unknown,
 }
 
-impl std::fmt::Display for ServerState {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                ServerState::AllDisabled => "All Disabled",
-                ServerState::SomeDisabled(_) => "Some Disabled",
-                ServerState::NoneDisabled => "None Disabled",
-                ServerState::Unknown => "Unknown",
-            }
-        )
+impl ServerState {
+    /// Get the [`ServerState`] of a server with `num_ips` IPs of
+    /// which `blocked_ips` are blocked.
+    pub fn from_blocked_ips(num_ips: usize, blocked_ips: Vec<IpAddr>) -> Self {
+        // a server without IPs has nothing to block
+        if blocked_ips.is_empty() {
+            ServerState::NoneDisabled
+        } else if blocked_ips.len() == num_ips {
+            ServerState::AllDisabled
+        } else {
+            ServerState::SomeDisabled(blocked_ips)
+        }
     }
 }
 
-#[derive(Debug)]
-pub enum Error {
-    Downloader(downloader::Error),
-    DownloaderMultiple(Vec<downloader::Error>),
-    NoServer,
-    NoRelay,
-    Firewall(firewall::Error),
-    ServerUnreachable,
+impl ServerState {
+    /// Get the name of the [`ServerState`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerState::AllDisabled => "All Disabled",
+            ServerState::SomeDisabled(_) => "Some Disabled",
+            ServerState::NoneDisabled => "None Disabled",
+            ServerState::Unknown => "Unknown",
+        }
+    }
+
+    /// Get the fraction of the IPs of a server with `num_ips` IPs that
+    /// are blocked, [`None`] if the state is unknown.
+    pub fn blocked_fraction(&self, num_ips: usize) -> Option<f32> {
+        match self {
+            ServerState::AllDisabled => Some(1.0),
+            ServerState::SomeDisabled(blocked_ips) => {
+                Some(blocked_ips.len() as f32 / num_ips.max(1) as f32)
+            }
+            ServerState::NoneDisabled => Some(0.0),
+            ServerState::Unknown => None,
+        }
+    }
+
+    /// Get the label of the state of a server with `num_ips` IPs, the
+    /// number of blocked IPs is included when some are blocked.
+    pub fn label(&self, num_ips: usize) -> Cow<'static, str> {
+        match self {
+            ServerState::SomeDisabled(blocked_ips) => Cow::Owned(format!(
+                "{} ({}/{})",
+                self.name(),
+                blocked_ips.len(),
+                num_ips
+            )),
+            _ => Cow::Borrowed(self.name()),
+        }
+    }
 }
 
-impl std::fmt::Display for Error {
+impl std::fmt::Display for ServerState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        write!(f, "{}", self.name())
     }
 }
 
-impl From<downloader::Error> for Error {
-    fn from(error: downloader::Error) -> Self {
-        Error::Downloader(error)
-    }
+/// Category of a server, the servers of each category are managed
+/// separately.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub enum ServerCategory {
+    /// Steam Datagram Relay (SDR) points of presence from the network
+    /// datagram config.
+    #[default]
+    Relay,
+    /// Steam connection manager (CM) servers, see
+    /// [`crate::steam_cm`].
+    ConnectionManager,
+    /// Steam content (CDN) servers used for the downloads, only
+    /// available with the `cdn` feature.
+    Content,
 }
 
-impl From<firewall::Error> for Error {
-    fn from(error: firewall::Error) -> Self {
-        Error::Firewall(error)
+impl ServerCategory {
+    /// Get all the [`ServerCategory`]s.
+    pub const fn all() -> [Self; 3] {
+        [Self::Relay, Self::ConnectionManager, Self::Content]
+    }
+
+    /// Get the name of the [`ServerCategory`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerCategory::Relay => "Relays",
+            ServerCategory::ConnectionManager => "Connection Managers",
+            ServerCategory::Content => "Content Servers",
+        }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::fmt::Display for ServerCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
 
 /// Server info.
 pub struct ServerInfo {
@@ -195,32 +277,100 @@ pub struct ServerInfo {
     abr: String,
     /// Description of the server.
     desc: Option<String>,
-    /// [`Ipv4Addr`]s of the server.
-    ipv4s: Vec<Ipv4Addr>,
+    /// IPv4 and IPv6 addresses of the server, the IPv4s first.
+    ips: Vec<IpAddr>,
     /// Geo location.
     geo: Option<[f32; 2]>,
+    /// Category of the server.
+    category: ServerCategory,
+    /// Groups of the server (eg: the continent or country) given by
+    /// the network datagram config, only known for the relays.
+    groups: Vec<String>,
+    /// UDP port ranges of the IPs, only known for the relays.
+    port_ranges: HashMap<IpAddr, RangeInclusive<u16>>,
 }
 
 impl ServerInfo {
+    pub(crate) fn new(
+        abr: String,
+        desc: Option<String>,
+        ips: Vec<IpAddr>,
+        geo: Option<[f32; 2]>,
+        category: ServerCategory,
+    ) -> Self {
+        Self {
+            abr,
+            desc,
+            ips,
+            geo,
+            category,
+            groups: Vec::new(),
+            port_ranges: HashMap::new(),
+        }
+    }
+
     pub fn ban(&self, firewall: &Firewall) -> Result<(), Error> {
         log::info!("banned {}", self.get_abr());
-        Ok(self
-            .get_ipv4s()
-            .iter()
-            .try_for_each(|ip| firewall.ban_ip(*ip))?)
+        Ok(firewall.ban_ips(self.get_ips())?)
     }
 
     pub fn unban(&self, firewall: &Firewall) -> Result<(), Error> {
         log::info!("unbanned {}", self.get_abr());
-        Ok(self
-            .get_ipv4s()
+        Ok(firewall.unban_ips(self.get_ips())?)
+    }
+
+    /// Split the IPs into the ones to block and the ones to unblock
+    /// to block `percent` percent of the IPs. The blocked subset is
+    /// deterministic and spread over the IPs, so the same percentage
+    /// always blocks the same IPs.
+    pub fn partial_block_ips(&self, percent: u8) -> (Vec<IpAddr>, Vec<IpAddr>) {
+        let num_ips = self.ips.len();
+        let num_blocked = (num_ips * usize::from(percent.min(100)) + 50) / 100;
+        let (blocked, unblocked): (Vec<_>, Vec<_>) = self
+            .ips
             .iter()
-            .try_for_each(|ip| firewall.unban_ip(*ip))?)
+            .enumerate()
+            .partition(|(i, _)| (i + 1) * num_blocked / num_ips > i * num_blocked / num_ips);
+        (
+            blocked.into_iter().map(|(_, ip)| *ip).collect(),
+            unblocked.into_iter().map(|(_, ip)| *ip).collect(),
+        )
     }
 
-    /// Get a reference to the server info's ipv4s.
-    pub fn get_ipv4s(&self) -> &[Ipv4Addr] {
-        self.ipv4s.as_ref()
+    /// Query the firewall for the [`ServerState`] of the server, IPs
+    /// whose block check fails are considered to not be blocked.
+    pub fn query_state(&self, firewall: &Firewall) -> ServerState {
+        ServerState::from_blocked_ips(
+            self.ips.len(),
+            self.ips
+                .iter()
+                .copied()
+                .filter(|ip| firewall.is_blocked(*ip).unwrap_or(false))
+                .collect(),
+        )
+    }
+
+    /// Get the [`ServerState`] of the server from a snapshot of the
+    /// blocked IPs, see [`Firewall::list_blocked()`].
+    pub fn state_from_blocked(&self, blocked: &HashSet<IpAddr>) -> ServerState {
+        ServerState::from_blocked_ips(
+            self.ips.len(),
+            self.ips
+                .iter()
+                .copied()
+                .filter(|ip| blocked.contains(ip))
+                .collect(),
+        )
+    }
+
+    /// Get a reference to the server info's IPv4 and IPv6 addresses.
+    pub fn get_ips(&self) -> &[IpAddr] {
+        self.ips.as_ref()
+    }
+
+    /// Get the number of IPv6 addresses of the server.
+    pub fn num_ipv6s(&self) -> usize {
+        self.ips.iter().filter(|ip| ip.is_ipv6()).count()
     }
 
     /// Get a reference to the server info's abr.
@@ -237,30 +387,314 @@ impl ServerInfo {
     pub fn geo(&self) -> Option<&[f32; 2]> {
         self.geo.as_ref()
     }
+
+    /// Get the description of the server, or the "City, Country"
+    /// label of its location if it has no description, see
+    /// [`crate::geocode`].
+    pub fn label(&self) -> Option<Cow<'_, str>> {
+        match (self.desc(), self.geo()) {
+            (Some(desc), _) => Some(Cow::Borrowed(desc)),
+            (None, Some(geo)) => geocode::label(*geo).map(Cow::Owned),
+            (None, None) => None,
+        }
+    }
+
+    /// Get the [`ServerCategory`] of the server.
+    pub fn category(&self) -> ServerCategory {
+        self.category
+    }
+
+    /// Get the groups of the server.
+    pub fn groups(&self) -> &[String] {
+        self.groups.as_ref()
+    }
+
+    /// Get the first of the groups of the server, the one it is
+    /// listed under when the grid is grouped.
+    pub fn group(&self) -> Option<&str> {
+        self.groups.first().map(String::as_str)
+    }
+
+    /// Get the UDP port range of the IP of the server, if known.
+    pub fn port_range(&self, ip: &IpAddr) -> Option<&RangeInclusive<u16>> {
+        self.port_ranges.get(ip)
+    }
 }
 
+/// Steam app whose network datagram config is used by default
+/// (Counter-Strike 2).
+pub const DEFAULT_APP_ID: u32 = 730;
+
+/// Steam apps known to use the SDR relays, along with their names.
+/// They can be picked even if they are not installed, their network
+/// datagram configs are downloaded on demand.
+pub const KNOWN_APPS: &[(u32, &str)] = &[
+    (DEFAULT_APP_ID, "Counter-Strike 2"),
+    (570, "Dota 2"),
+    (440, "Team Fortress 2"),
+    (1422450, "Deadlock"),
+];
+
+/// Snapshot of the network datagram config of [`DEFAULT_APP_ID`]
+/// embedded at compile time (`data/NetworkDatagramConfig.json`, see
+/// `build.rs`), used when no config is cached and it cannot be
+/// downloaded. The file is fetched by the release workflow, it is
+/// [`None`] for the builds without it.
+#[cfg(embedded_network_datagram_config)]
+const EMBEDDED_NETWORK_DATAGRAM_CONFIG: Option<&[u8]> = Some(include_bytes!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/data/NetworkDatagramConfig.json"
+)));
+#[cfg(not(embedded_network_datagram_config))]
+const EMBEDDED_NETWORK_DATAGRAM_CONFIG: Option<&[u8]> = None;
+
 pub struct Servers {
+    revision: usize,
     servers: Vec<ServerInfo>,
+    /// Steam app the network datagram config is of.
+    app_id: u32,
+    /// Loaded from the snapshot embedded in the binary, the data may
+    /// be stale.
+    embedded: bool,
 }
 
 impl Servers {
-    pub fn new(network_datagram_config_file_path: Option<impl AsRef<Path>>) -> Self {
-        ServerObject::new(network_datagram_config_file_path).into()
+    pub fn new(network_datagram_config_file_path: Option<impl AsRef<Path>>) -> Result<Self, Error> {
+        Self::new_for_app(network_datagram_config_file_path, DEFAULT_APP_ID)
+    }
+
+    /// Load the servers of the network datagram config of the given
+    /// Steam app, the config is downloaded if it is not cached yet
+    /// and no file is given.
+    ///
+    /// If the config of [`DEFAULT_APP_ID`] can neither be loaded nor
+    /// downloaded, the snapshot embedded in the binary (if any) is
+    /// used, see [`Self::is_embedded()`].
+    pub fn new_for_app(
+        network_datagram_config_file_path: Option<impl AsRef<Path>>,
+        app_id: u32,
+    ) -> Result<Self, Error> {
+        let file_given = network_datagram_config_file_path.is_some();
+        let mut servers: Self = match ServerObject::new(network_datagram_config_file_path, app_id) {
+            Ok(server_object) => server_object.into(),
+            Err(err) => match EMBEDDED_NETWORK_DATAGRAM_CONFIG {
+                Some(json_data) if !file_given && app_id == DEFAULT_APP_ID => {
+                    let mut servers = Self::from_json(json_data)?;
+                    log::error!(
+                        "unable to load the network datagram config: {}, using the \
+                         snapshot embedded in the binary (revision {}), the data may be stale",
+                        err,
+                        servers.get_revision()
+                    );
+                    servers.embedded = true;
+                    servers
+                }
+                _ => return Err(err),
+            },
+        };
+        servers.app_id = app_id;
+        Ok(servers)
+    }
+
+    /// Create [`Servers`] from the contents of a network datagram
+    /// config file.
+    pub fn from_json(json_data: &[u8]) -> Result<Self, Error> {
+        Ok(ServerObject::from_json(json_data)?.into())
     }
 
     pub fn download_file() -> Result<(), Error> {
-        ServerObject::download_file()
+        Self::download_file_for_app(DEFAULT_APP_ID)
+    }
+
+    /// Download the network datagram config of the given Steam app.
+    pub fn download_file_for_app(app_id: u32) -> Result<(), Error> {
+        ServerObject::download_file(app_id)
+    }
+
+    /// Download the network datagram config of the given Steam app,
+    /// the cached config is replaced only if the downloaded one is
+    /// valid (it parses and has relays).
+    pub fn download_validated_for_app(app_id: u32) -> Result<Self, Error> {
+        let json_data = ServerObject::download(app_id)?;
+        let mut servers = Self::from_json(&json_data)?;
+        if !servers
+            .get_servers_in(ServerCategory::Relay)
+            .any(|server| !server.get_ips().is_empty())
+        {
+            return Err(Error::InvalidNetworkDatagramConfig("no relays".to_string()));
+        }
+        servers.app_id = app_id;
+        file_ops::write_atomic(
+            file_ops::get_network_datagram_config_file_path_for_app(app_id)?,
+            json_data,
+        )?;
+        Ok(servers)
+    }
+
+    /// Check if the servers are from the snapshot embedded in the
+    /// binary instead of a downloaded config, the data may be stale.
+    pub fn is_embedded(&self) -> bool {
+        self.embedded
+    }
+
+    /// Get the Steam app the network datagram config is of.
+    pub fn get_app_id(&self) -> u32 {
+        self.app_id
+    }
+
+    /// Get the revision of the network datagram config the servers
+    /// were loaded from.
+    pub fn get_revision(&self) -> usize {
+        self.revision
     }
 
     /// Get a reference to the servers's servers.
     pub fn get_servers(&self) -> &[ServerInfo] {
         self.servers.as_ref()
     }
+
+    /// Get the UDP port ranges of the IPs of all the servers, see
+    /// [`Firewall::set_port_ranges()`].
+    pub fn port_ranges(&self) -> PortRanges {
+        self.servers
+            .iter()
+            .flat_map(|server| &server.port_ranges)
+            .map(|(ip, port_range)| (*ip, port_range.clone()))
+            .collect()
+    }
+
+    /// Get the server with the given abreviation.
+    pub fn get_server(&self, abr: &str) -> Option<&ServerInfo> {
+        self.servers.iter().find(|server| server.get_abr() == abr)
+    }
+
+    /// Get an iterator over the servers of the given category.
+    pub fn get_servers_in(&self, category: ServerCategory) -> impl Iterator<Item = &ServerInfo> {
+        self.servers
+            .iter()
+            .filter(move |server| server.category() == category)
+    }
+
+    /// Check if there are servers of the given category.
+    pub fn has_category(&self, category: ServerCategory) -> bool {
+        self.get_servers_in(category).next().is_some()
+    }
+
+    /// Remove the servers of the given category, returns the removed
+    /// servers.
+    pub fn take_category(&mut self, category: ServerCategory) -> Vec<ServerInfo> {
+        let (taken, kept) = std::mem::take(&mut self.servers)
+            .into_iter()
+            .partition(|server| server.category() == category);
+        self.servers = kept;
+        taken
+    }
+
+    /// Replace the servers of the category of `servers` with
+    /// `servers`. The abreviations of the servers must be unique
+    /// across the categories.
+    pub fn replace_category(&mut self, category: ServerCategory, servers: Vec<ServerInfo>) {
+        self.servers.retain(|server| server.category() != category);
+        self.servers.extend(
+            servers
+                .into_iter()
+                .filter(|server| server.category() == category),
+        );
+        self.servers
+            .sort_unstable_by(|a, b| (a.category, &a.abr).cmp(&(b.category, &b.abr)));
+    }
+
+    /// Get the regions and IPs added and removed in `new` compared to
+    /// `self`.
+    pub fn diff(&self, new: &Servers) -> ServersDiff {
+        let regions = |servers: &Servers| -> HashSet<String> {
+            servers
+                .get_servers()
+                .iter()
+                .map(|server| server.get_abr().to_string())
+                .collect()
+        };
+        let ips = |servers: &Servers| -> HashSet<(String, IpAddr)> {
+            servers
+                .get_servers()
+                .iter()
+                .flat_map(|server| {
+                    server
+                        .get_ips()
+                        .iter()
+                        .map(|ip| (server.get_abr().to_string(), *ip))
+                })
+                .collect()
+        };
+        fn sorted<T: Ord>(mut items: Vec<T>) -> Vec<T> {
+            items.sort_unstable();
+            items
+        }
+        let (old_regions, new_regions) = (regions(self), regions(new));
+        let (old_ips, new_ips) = (ips(self), ips(new));
+        ServersDiff {
+            added_regions: sorted(new_regions.difference(&old_regions).cloned().collect()),
+            removed_regions: sorted(old_regions.difference(&new_regions).cloned().collect()),
+            added_ips: sorted(new_ips.difference(&old_ips).cloned().collect()),
+            removed_ips: sorted(old_ips.difference(&new_ips).cloned().collect()),
+        }
+    }
 }
 
-impl Default for Servers {
-    fn default() -> Self {
-        Self::new(None::<PathBuf>)
+/// Regions and IPs added and removed between two server lists, see
+/// [`Servers::diff()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServersDiff {
+    /// Abreviations of the regions only in the new list.
+    pub added_regions: Vec<String>,
+    /// Abreviations of the regions only in the old list.
+    pub removed_regions: Vec<String>,
+    /// IPs only in the new list, along with the abreviation of their
+    /// region.
+    pub added_ips: Vec<(String, IpAddr)>,
+    /// IPs only in the old list, along with the abreviation of their
+    /// region.
+    pub removed_ips: Vec<(String, IpAddr)>,
+}
+
+impl ServersDiff {
+    /// Are the server lists the same?
+    pub fn is_empty(&self) -> bool {
+        self.added_regions.is_empty()
+            && self.removed_regions.is_empty()
+            && self.added_ips.is_empty()
+            && self.removed_ips.is_empty()
+    }
+
+    /// Get the added IPs grouped by the abreviation of their region.
+    pub fn added_ips_by_region(&self) -> BTreeMap<&str, Vec<IpAddr>> {
+        ips_by_region(&self.added_ips)
+    }
+
+    /// Get the removed IPs grouped by the abreviation of their region.
+    pub fn removed_ips_by_region(&self) -> BTreeMap<&str, Vec<IpAddr>> {
+        ips_by_region(&self.removed_ips)
+    }
+}
+
+fn ips_by_region(ips: &[(String, IpAddr)]) -> BTreeMap<&str, Vec<IpAddr>> {
+    let mut by_region: BTreeMap<&str, Vec<IpAddr>> = BTreeMap::new();
+    ips.iter().for_each(|(region, ip)| {
+        by_region.entry(region.as_str()).or_default().push(*ip);
+    });
+    by_region
+}
+
+impl std::fmt::Display for ServersDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} regions added, {} regions removed, {} IPs added, {} IPs removed",
+            self.added_regions.len(),
+            self.removed_regions.len(),
+            self.added_ips.len(),
+            self.removed_ips.len()
+        )
     }
 }
 
@@ -269,27 +703,60 @@ impl From<ServerObject> for Servers {
         let mut servers: Vec<_> = server_object
             .get_pops()
             .iter()
-            .filter_map(|(server, info)| {
-                let ipv4s = info
-                    .get_relays()?
-                    .iter()
-                    .map(|info| info.get_ipv4().parse().unwrap())
+            .map(|(server, info)| {
+                // pops without relays are kept as regions without IPs,
+                // hidden in the UI by default
+                let parse_ip = |ip: &str| {
+                    ip.parse::<IpAddr>()
+                        .map_err(|error| {
+                            log::warn!(
+                                "{}: {}",
+                                server,
+                                Error::InvalidIp {
+                                    ip: ip.to_string(),
+                                    error
+                                }
+                            )
+                        })
+                        .ok()
+                };
+                let relays = info.get_relays().into_iter().flatten();
+                // the IPv6 of a relay shares the port range of its IPv4
+                let relays: Vec<(IpAddr, _)> = relays
+                    .clone()
+                    .filter_map(|info| Some((parse_ip(info.get_ipv4())?, info.get_port_range())))
+                    .chain(relays.filter_map(|info| {
+                        Some((parse_ip(info.get_ipv6()?)?, info.get_port_range()))
+                    }))
                     .collect();
-                Some(ServerInfo {
+                let ips = relays.iter().map(|(ip, _)| *ip).collect();
+                let port_ranges = relays
+                    .into_iter()
+                    .filter_map(|(ip, port_range)| Some((ip, port_range?)))
+                    .collect();
+                ServerInfo {
                     abr: server.to_string(),
                     desc: info.desc.clone(),
-                    ipv4s,
+                    ips,
                     geo: info
                         .geo
                         .as_ref()
                         .and_then(|geo| <&[f32; 2]>::try_from(geo.as_slice()).ok())
                         .cloned(),
-                })
+                    category: ServerCategory::Relay,
+                    groups: info.groups.clone().unwrap_or_default(),
+                    port_ranges,
+                }
             })
             .collect();
 
         servers.sort_unstable_by_key(|info| info.abr.to_string());
 
-        Servers { servers }
+        Servers {
+            revision: server_object.get_revision(),
+            servers,
+            app_id: DEFAULT_APP_ID,
+            embedded: false,
+        }
     }
 }
@@ -0,0 +1,232 @@
+//! Counter-Strike datacenter status (capacity and load) published by
+//! Valve through the Steam Web API.
+
+use std::{collections::HashMap, sync::mpsc, thread, time::Duration};
+
+use serde::Deserialize;
+
+use crate::{
+    downloader,
+    health::{Health, ThreadStatus},
+    steam_server::{ServerCategory, Servers},
+    waker::Waker,
+    Error,
+};
+
+/// Status of a datacenter.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DatacenterStatus {
+    /// Capacity of the datacenter, eg: `full`, `near_full`, `low`.
+    pub capacity: String,
+    /// Load of the datacenter, eg: `idle`, `low`, `medium`, `high`,
+    /// `full`.
+    pub load: String,
+}
+
+impl DatacenterStatus {
+    /// Health of the datacenter based on its load.
+    pub fn health(&self) -> DatacenterHealth {
+        match self.load.as_str() {
+            "idle" | "low" => DatacenterHealth::Good,
+            "medium" => DatacenterHealth::Busy,
+            "high" | "full" => DatacenterHealth::Overloaded,
+            _ => DatacenterHealth::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for DatacenterStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "capacity: {}, load: {}", self.capacity, self.load)
+    }
+}
+
+/// Health of a datacenter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatacenterHealth {
+    Good,
+    Busy,
+    Overloaded,
+    Unknown,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    result: ResponseResult,
+}
+
+#[derive(Deserialize)]
+struct ResponseResult {
+    datacenters: HashMap<String, DatacenterStatus>,
+}
+
+/// Datacenter names that cannot be matched against the description of
+/// the regions, mapped to the region abreviations.
+const DATACENTER_ALIASES: &[(&str, &str)] = &[
+    ("EU West", "lux"),
+    ("EU East", "vie"),
+    ("EU North", "sto"),
+    ("Sweden", "sto"),
+    ("Germany", "fra"),
+    ("Poland", "waw"),
+    ("Spain", "mad"),
+    ("US Northwest", "sea"),
+    ("US Southwest", "lax"),
+    ("US California", "lax"),
+    ("US Northcentral", "ord"),
+    ("US Southeast", "atl"),
+    ("US Northeast", "iad"),
+    ("US South", "dfw"),
+    ("Brazil", "gru"),
+    ("Peru", "lim"),
+    ("Chile", "scl"),
+    ("Emirates", "dxb"),
+    ("South Africa", "jnb"),
+    ("India", "bom"),
+    ("India East", "maa"),
+    ("Australia", "syd"),
+    ("Japan", "tyo"),
+    ("Hong Kong", "hkg"),
+    ("Singapore", "sgp"),
+];
+
+/// Fetch the status of all the datacenters, keyed by the datacenter
+/// name.
+pub fn fetch(api_key: &str) -> Result<HashMap<String, DatacenterStatus>, Error> {
+    let data = downloader::Download::to_bytes(&format!(
+        "https://api.steampowered.com/ICSGOServers_730/GetGameServersStatus/v1/?key={}",
+        api_key
+    ))?;
+    let response: Response = serde_json::from_slice(&data)?;
+    Ok(response.result.datacenters)
+}
+
+/// Get the abreviation of the region of the given datacenter. Known
+/// aliases are used first, otherwise the region whose description
+/// contains the datacenter name.
+pub fn region_for_datacenter<'a>(datacenter: &str, servers: &'a Servers) -> Option<&'a str> {
+    DATACENTER_ALIASES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(datacenter))
+        .and_then(|(_, abr)| servers.get_server(abr))
+        .or_else(|| {
+            let datacenter = datacenter.to_lowercase();
+            servers
+                .get_servers_in(ServerCategory::Relay)
+                .find(|server| {
+                    server
+                        .desc()
+                        .is_some_and(|desc| desc.to_lowercase().contains(&datacenter))
+                })
+        })
+        .map(|server| server.get_abr())
+}
+
+/// Map the datacenter statuses to the regions of `servers`, keyed by
+/// the region abreviation.
+pub fn statuses_by_region(
+    statuses: HashMap<String, DatacenterStatus>,
+    servers: &Servers,
+) -> HashMap<String, DatacenterStatus> {
+    statuses
+        .into_iter()
+        .filter_map(|(datacenter, status)| {
+            Some((
+                region_for_datacenter(&datacenter, servers)?.to_string(),
+                status,
+            ))
+        })
+        .collect()
+}
+
+/// Periodically fetches the datacenter statuses on a separate thread.
+pub struct SteamStatusFetcher {
+    kill_sender: mpsc::Sender<()>,
+    status_receiver: mpsc::Receiver<Result<HashMap<String, DatacenterStatus>, Error>>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+    health: Health,
+
+    /// Needed to restart the fetcher, see
+    /// [`SteamStatusFetcher::restart()`].
+    api_key: String,
+    interval: Duration,
+    waker: Waker,
+}
+
+impl SteamStatusFetcher {
+    /// Time a fetch may take before the thread is considered stalled.
+    const MAX_FETCH_DURATION: Duration = Duration::from_secs(60);
+
+    /// Start fetching the statuses every `interval`, `waker` is woken
+    /// up after every fetch.
+    pub fn new(api_key: String, interval: Duration, waker: Waker) -> Self {
+        let (kill_sender, kill_receiver) = mpsc::channel();
+        let (status_sender, status_receiver) = mpsc::channel();
+        let health = Health::new(interval + Self::MAX_FETCH_DURATION);
+
+        let thread_handle = {
+            let api_key = api_key.clone();
+            let waker = waker.clone();
+            let health = health.clone();
+            thread::spawn(move || {
+                let _health_guard = health.guard();
+                loop {
+                    health.beat();
+                    if status_sender.send(fetch(&api_key)).is_err() {
+                        break;
+                    }
+                    health.beat();
+                    waker.wake();
+
+                    match kill_receiver.recv_timeout(interval) {
+                        Err(mpsc::RecvTimeoutError::Timeout) => {}
+                        Ok(()) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+            })
+        };
+
+        Self {
+            kill_sender,
+            status_receiver,
+            thread_handle: Some(thread_handle),
+            health,
+            api_key,
+            interval,
+            waker,
+        }
+    }
+
+    /// Get the health of the fetcher thread.
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+
+    /// Replace the fetcher thread with a new one, eg: after it
+    /// panicked or stalled.
+    pub fn restart(&mut self) {
+        *self = Self::new(self.api_key.clone(), self.interval, self.waker.clone());
+        log::info!("restarted the steam status fetcher thread");
+    }
+
+    /// Get the latest fetched statuses, if any were fetched since the
+    /// last call.
+    pub fn try_recv_latest(&self) -> Option<Result<HashMap<String, DatacenterStatus>, Error>> {
+        self.status_receiver.try_iter().last()
+    }
+}
+
+impl Drop for SteamStatusFetcher {
+    fn drop(&mut self) {
+        let _ = self.kill_sender.send(());
+        if self.health.status() == ThreadStatus::Stalled {
+            log::error!("steam status fetcher thread is stalled, not waiting for it to stop");
+            return;
+        }
+        if let Some(thread_handle) = self.thread_handle.take() {
+            if thread_handle.join().is_err() {
+                log::error!("steam status fetcher thread panicked");
+            }
+        }
+    }
+}
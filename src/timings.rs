@@ -0,0 +1,120 @@
+//! Lightweight timing of the potentially slow operations (firewall
+//! calls, config parsing, downloads), to diagnose why eg: disabling
+//! all the regions takes seconds on some systems. Shown in the
+//! diagnostics window and printed by `doctor --timings`.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref TIMINGS: Mutex<HashMap<&'static str, Timing>> = Mutex::new(HashMap::new());
+}
+
+/// Timing of all the runs of an operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timing {
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+    pub last: Duration,
+}
+
+impl Timing {
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        self.total / u32::try_from(self.count).unwrap_or(u32::MAX)
+    }
+}
+
+/// Records the time from its creation until it is dropped as a run of
+/// the operation, see [`timer()`].
+pub struct Timer {
+    operation: &'static str,
+    start: Instant,
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        record(self.operation, self.start.elapsed());
+    }
+}
+
+/// Start timing a run of the operation, the run ends when the
+/// returned [`Timer`] is dropped.
+pub fn timer(operation: &'static str) -> Timer {
+    Timer {
+        operation,
+        start: Instant::now(),
+    }
+}
+
+/// Record a run of the operation that took `duration`.
+pub fn record(operation: &'static str, duration: Duration) {
+    let mut timings = TIMINGS.lock().unwrap();
+    let timing = timings.entry(operation).or_default();
+    timing.count += 1;
+    timing.total += duration;
+    timing.max = timing.max.max(duration);
+    timing.last = duration;
+}
+
+/// Get the timings of all the operations run so far, the largest
+/// total time first.
+pub fn snapshot() -> Vec<(&'static str, Timing)> {
+    let mut timings: Vec<_> = TIMINGS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(operation, timing)| (*operation, *timing))
+        .collect();
+    timings.sort_by_key(|(_, timing)| std::cmp::Reverse(timing.total));
+    timings
+}
+
+/// Forget the timings recorded so far.
+pub fn reset() {
+    TIMINGS.lock().unwrap().clear();
+}
+
+/// Format the timings as a plain text table.
+pub fn format_table(timings: &[(&str, Timing)]) -> String {
+    let width = timings
+        .iter()
+        .map(|(operation, _)| operation.len())
+        .chain(std::iter::once("operation".len()))
+        .max()
+        .unwrap_or_default();
+    let mut res = format!(
+        "{:width$}  {:>6}  {:>10}  {:>10}  {:>10}\n",
+        "operation",
+        "count",
+        "total",
+        "mean",
+        "max",
+        width = width
+    );
+    timings.iter().for_each(|(operation, timing)| {
+        res.push_str(&format!(
+            "{:width$}  {:>6}  {:>10}  {:>10}  {:>10}\n",
+            operation,
+            timing.count,
+            format_duration(timing.total),
+            format_duration(timing.mean()),
+            format_duration(timing.max),
+            width = width
+        ));
+    });
+    res
+}
+
+/// Format the duration in milliseconds, eg: `12.3 ms`.
+pub fn format_duration(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,191 @@
+//! Trial blocks: block some regions for a limited time to compare the
+//! latency before and during the block, then revert.
+//!
+//! Matchmaking picks among the regions that are not blocked, so the
+//! latency that matters is the one of the best reachable region
+//! (along with the spread of the other reachable regions).
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use crate::block_list::BlockList;
+
+/// Ping of the regions that are reachable at some point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencySnapshot {
+    /// Ping by region, only the regions that are not blocked and
+    /// answered.
+    pub pings: BTreeMap<String, Duration>,
+}
+
+impl LatencySnapshot {
+    /// Create a snapshot from the ping of every region, the regions
+    /// in `blocked` are left out.
+    pub fn new(pings: impl IntoIterator<Item = (String, Duration)>, blocked: &BlockList) -> Self {
+        Self {
+            pings: pings
+                .into_iter()
+                .filter(|(region, _)| !blocked.contains(region))
+                .collect(),
+        }
+    }
+
+    /// Get the region with the lowest ping.
+    pub fn best(&self) -> Option<(&str, Duration)> {
+        self.pings
+            .iter()
+            .min_by_key(|(_, ping)| **ping)
+            .map(|(region, ping)| (region.as_str(), *ping))
+    }
+
+    /// Get the median ping of the regions.
+    pub fn median(&self) -> Option<Duration> {
+        let mut pings: Vec<_> = self.pings.values().copied().collect();
+        pings.sort_unstable();
+        pings.get(pings.len() / 2).copied()
+    }
+}
+
+impl std::fmt::Display for LatencySnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.best(), self.median()) {
+            (Some((region, best)), Some(median)) => write!(
+                f,
+                "best {} {} ms, median {} ms over {} regions",
+                region,
+                best.as_millis(),
+                median.as_millis(),
+                self.pings.len()
+            ),
+            _ => write!(f, "no reachable region"),
+        }
+    }
+}
+
+/// Trial block in progress.
+#[derive(Debug, Clone)]
+pub struct TrialBlock {
+    /// Regions blocked for the trial.
+    regions: BlockList,
+    /// Regions blocked before the trial, restored after the trial.
+    previous: BlockList,
+    duration: Duration,
+    started_at: Instant,
+    before: LatencySnapshot,
+    steam_before: Option<LatencySnapshot>,
+}
+
+impl TrialBlock {
+    /// Start the trial of blocking `regions` for `duration`.
+    /// `previous` are the regions blocked before the trial, `before`
+    /// and `steam_before` are the ICMP pings and the pings measured
+    /// by Steam (if available) before the trial.
+    pub fn new(
+        regions: BlockList,
+        previous: BlockList,
+        duration: Duration,
+        before: LatencySnapshot,
+        steam_before: Option<LatencySnapshot>,
+    ) -> Self {
+        Self {
+            regions,
+            previous,
+            duration,
+            started_at: Instant::now(),
+            before,
+            steam_before,
+        }
+    }
+
+    /// Get the regions to block during the trial, the previously
+    /// blocked regions stay blocked.
+    pub fn trial_block_list(&self) -> BlockList {
+        BlockList::new(
+            self.previous
+                .regions()
+                .chain(self.regions.regions())
+                .map(str::to_string),
+        )
+    }
+
+    /// Get a reference to the regions blocked before the trial.
+    pub fn previous(&self) -> &BlockList {
+        &self.previous
+    }
+
+    /// Get the time left in the trial.
+    pub fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.started_at.elapsed())
+    }
+
+    /// Check if the trial is over.
+    pub fn is_done(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// End the trial with the pings measured during the trial.
+    pub fn finish(
+        self,
+        after: LatencySnapshot,
+        steam_after: Option<LatencySnapshot>,
+    ) -> TrialReport {
+        TrialReport {
+            regions: self.regions.regions().map(str::to_string).collect(),
+            duration: self.started_at.elapsed(),
+            before: self.before,
+            after,
+            steam: self.steam_before.zip(steam_after),
+        }
+    }
+}
+
+/// Comparison of the latency before and during a [`TrialBlock`].
+#[derive(Debug, Clone)]
+pub struct TrialReport {
+    /// Regions blocked for the trial.
+    pub regions: Vec<String>,
+    /// Time the trial lasted.
+    pub duration: Duration,
+    /// ICMP pings before the trial.
+    pub before: LatencySnapshot,
+    /// ICMP pings during the trial.
+    pub after: LatencySnapshot,
+    /// Pings measured by Steam before and during the trial, if
+    /// available.
+    pub steam: Option<(LatencySnapshot, LatencySnapshot)>,
+}
+
+impl TrialReport {
+    /// Get the change of the ping of the best region, positive if the
+    /// ping increased during the trial.
+    pub fn best_ping_change_millis(&self) -> Option<i128> {
+        let before = self.before.best()?.1.as_millis();
+        let after = self.after.best()?.1.as_millis();
+        Some(after as i128 - before as i128)
+    }
+}
+
+impl std::fmt::Display for TrialReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Blocked {} for {}s",
+            self.regions.join(", "),
+            self.duration.as_secs()
+        )?;
+        writeln!(f, "Before: {}", self.before)?;
+        writeln!(f, "During: {}", self.after)?;
+        if let Some((steam_before, steam_after)) = &self.steam {
+            writeln!(f, "Steam before: {}", steam_before)?;
+            writeln!(f, "Steam during: {}", steam_after)?;
+        }
+        match self.best_ping_change_millis() {
+            Some(change) if change > 0 => write!(f, "The best ping got worse by {} ms", change),
+            Some(change) if change < 0 => write!(f, "The best ping improved by {} ms", -change),
+            Some(_) => write!(f, "The best ping did not change"),
+            None => write!(f, "Not enough pings to compare"),
+        }
+    }
+}
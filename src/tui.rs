@@ -0,0 +1,148 @@
+//! Terminal front-end (`--tui`) showing the grid of the GUI, for the
+//! machines managed over SSH where the GUI cannot run. It runs the
+//! same [`App::update()`] loop as the GUI.
+//!
+//! Keys: `↑`/`↓` (or `k`/`j`) select a region, `d` disables it, `e`
+//! enables it, `q` (or `Esc`) quits.
+//!
+//! The terminal logger is disabled while the TUI runs so that it does
+//! not draw over it, the last warning or error is shown in a status
+//! line instead.
+
+use std::time::Duration;
+
+use log::{Level, LevelFilter};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Cell, Paragraph, Row, Table, TableState},
+    DefaultTerminal, Frame,
+};
+
+use crate::{
+    app::{App, RegionRow},
+    logger::LOGGER,
+    steam_server::ServerState,
+    Error,
+};
+
+/// Interval between the updates of the app while no key is pressed.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Run the terminal front-end until the user quits, the terminal
+/// (and the terminal logger) is restored even if it fails.
+pub fn run(app: &mut App) -> Result<(), Error> {
+    let terminal_log_level = LOGGER.second_max_level();
+    LOGGER.set_second_max_level(LevelFilter::Off);
+    let mut terminal = ratatui::init();
+    let res = run_loop(&mut terminal, app);
+    ratatui::restore();
+    LOGGER.set_second_max_level(terminal_log_level);
+    res
+}
+
+fn run_loop(terminal: &mut DefaultTerminal, app: &mut App) -> Result<(), Error> {
+    let mut table_state = TableState::default().with_selected(Some(0));
+    loop {
+        app.update();
+        let rows = app.region_rows();
+        let status = LOGGER.first().newest_record(LevelFilter::Warn);
+        terminal.draw(|frame| draw(frame, &rows, status.as_ref(), &mut table_state))?;
+
+        if !event::poll(UPDATE_INTERVAL)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let selected = table_state.selected().and_then(|index| rows.get(index));
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Down | KeyCode::Char('j') => table_state.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => table_state.select_previous(),
+            KeyCode::Char(key @ ('d' | 'e')) => {
+                if let Some(row) = selected {
+                    // shown in the status line
+                    if let Err(err) = app.set_region_blocked(&row.region, key == 'd') {
+                        log::error!("unable to update `{}`: {}", row.region, err);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Draw the grid, the status line and the keys.
+fn draw(
+    frame: &mut Frame,
+    rows: &[RegionRow],
+    status: Option<&(Level, String)>,
+    table_state: &mut TableState,
+) {
+    let [table_area, status_area, keys_area] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let header = Row::new(["Region", "State", "Ping", "Loss", "Jitter", "Score"])
+        .style(Style::new().add_modifier(Modifier::BOLD));
+    let table_rows = rows.iter().map(|row| {
+        let state_color = match row.state {
+            ServerState::AllDisabled => Color::Red,
+            ServerState::SomeDisabled(_) => Color::Yellow,
+            ServerState::NoneDisabled => Color::Green,
+            ServerState::Unknown => Color::Gray,
+        };
+        let region = match &row.description {
+            Some(description) => format!("{} ({})", row.region, description),
+            None => row.region.clone(),
+        };
+        Row::new([
+            Cell::from(region),
+            Cell::from(row.state_label.as_str()).style(Style::new().fg(state_color)),
+            Cell::from(row.ping_label.as_str()),
+            Cell::from(row.loss_label.as_str()),
+            Cell::from(row.jitter_label.as_str()),
+            Cell::from(row.score_label.as_str()),
+        ])
+    });
+    let table = Table::new(
+        table_rows,
+        [
+            Constraint::Min(20),
+            Constraint::Length(22),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+        ],
+    )
+    .header(header)
+    .block(Block::bordered().title(" Steam Server Disable "))
+    .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(table, table_area, table_state);
+
+    if let Some((level, message)) = status {
+        let color = match level {
+            Level::Error => Color::Red,
+            _ => Color::Yellow,
+        };
+        frame.render_widget(
+            Paragraph::new(format!("{}: {}", level, message)).style(Style::new().fg(color)),
+            status_area,
+        );
+    }
+
+    frame.render_widget(
+        Paragraph::new("↑/↓ select   d disable   e enable   q quit"),
+        keys_area,
+    );
+}
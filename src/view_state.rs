@@ -0,0 +1,190 @@
+//! Sort and filter preferences of each view (grid, map), remembered
+//! across the restarts.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    file_ops::{self, Migration},
+    steam_server::ServerCategory,
+    Error,
+};
+
+/// Migrations of the view state file, see [`file_ops::migrate()`].
+const MIGRATIONS: &[Migration] = &[migrate_sort_by_score];
+
+/// Replace `grid.sort_by_score` by `grid.sort_by`.
+fn migrate_sort_by_score(mut value: serde_json::Value) -> Result<serde_json::Value, String> {
+    if let Some(grid) = value
+        .get_mut("grid")
+        .and_then(serde_json::Value::as_object_mut)
+    {
+        if grid.remove("sort_by_score").and_then(|sort| sort.as_bool()) == Some(true) {
+            grid.insert("sort_by".to_string(), "score".into());
+        }
+    }
+    Ok(value)
+}
+
+/// Column the grid can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GridSortColumn {
+    /// Abreviation of the region.
+    Region,
+    /// Fraction of the IPs of the region that are blocked.
+    State,
+    Ping,
+    /// Loss over the last minute.
+    Loss,
+    Jitter,
+    Score,
+}
+
+impl GridSortColumn {
+    /// Get all the [`GridSortColumn`]s.
+    pub const fn all() -> [Self; 6] {
+        [
+            Self::Region,
+            Self::State,
+            Self::Ping,
+            Self::Loss,
+            Self::Jitter,
+            Self::Score,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GridSortColumn::Region => "Region",
+            GridSortColumn::State => "State",
+            GridSortColumn::Ping => "Ping",
+            GridSortColumn::Loss => "Loss",
+            GridSortColumn::Jitter => "Jitter",
+            GridSortColumn::Score => "Score",
+        }
+    }
+}
+
+impl std::fmt::Display for GridSortColumn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Preferences of the grid view.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GridViewState {
+    /// Category of the servers shown.
+    pub category: ServerCategory,
+    /// Column the regions are sorted by, in the order of the server
+    /// list if [`None`].
+    pub sort_by: Option<GridSortColumn>,
+    /// Sort from the highest to the lowest.
+    pub sort_descending: bool,
+    /// Show the regions without any IPs.
+    pub show_empty_regions: bool,
+    /// List the regions under their group, see
+    /// [`crate::steam_server::ServerInfo::group()`].
+    pub group_regions: bool,
+}
+
+impl Default for GridViewState {
+    fn default() -> Self {
+        Self {
+            category: ServerCategory::Relay,
+            sort_by: None,
+            sort_descending: false,
+            show_empty_regions: false,
+            group_regions: false,
+        }
+    }
+}
+
+impl GridViewState {
+    /// Sort by the column when its header is clicked: ascending first,
+    /// then descending, then back to the order of the server list.
+    pub fn toggle_sort(&mut self, column: GridSortColumn) {
+        match self.sort_by {
+            Some(sort_by) if sort_by == column && !self.sort_descending => {
+                self.sort_descending = true;
+            }
+            Some(sort_by) if sort_by == column => {
+                self.sort_by = None;
+                self.sort_descending = false;
+            }
+            _ => {
+                self.sort_by = Some(column);
+                self.sort_descending = false;
+            }
+        }
+    }
+}
+
+/// What the color of the markers of the map shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MapColoring {
+    /// Fraction of the IPs of the region that are blocked.
+    #[default]
+    Status,
+    /// Average ping of the region.
+    Latency,
+}
+
+impl MapColoring {
+    /// Get all the [`MapColoring`]s.
+    pub const fn all() -> [Self; 2] {
+        [Self::Status, Self::Latency]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MapColoring::Status => "Status",
+            MapColoring::Latency => "Latency",
+        }
+    }
+}
+
+impl std::fmt::Display for MapColoring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Preferences of the map view.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapViewState {
+    /// Draw the servers without any IPs.
+    pub show_empty_regions: bool,
+    /// What the color of the markers shows.
+    pub coloring: MapColoring,
+}
+
+/// Preferences of all the views, stored at
+/// [`file_ops::get_view_state_file_path()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewState {
+    pub grid: GridViewState,
+    pub map: MapViewState,
+}
+
+impl ViewState {
+    /// Load the view state, the defaults if the file does not exist
+    /// yet.
+    pub fn load() -> Result<Self, Error> {
+        let file_path = file_ops::get_view_state_file_path()?;
+        if !file_path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(file_ops::load_versioned(file_path, MIGRATIONS)?)
+    }
+
+    /// Save the view state.
+    pub fn save(&self) -> Result<(), Error> {
+        file_ops::save_versioned(file_ops::get_view_state_file_path()?, self, MIGRATIONS)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+//! Wakes up the GUI when a background thread has new data, so that it
+//! does not need to redraw continuously to pick it up.
+
+use std::sync::{Arc, RwLock};
+
+type WakeFn = Box<dyn Fn() + Send + Sync>;
+
+/// Shared handle to the wake up callback, the callback can be set
+/// after the handle is given to the background threads.
+#[derive(Clone, Default)]
+pub struct Waker {
+    wake_fn: Arc<RwLock<Option<WakeFn>>>,
+}
+
+impl Waker {
+    /// Set the callback called on [`Self::wake()`], it must be cheap
+    /// and callable from any thread.
+    pub fn set(&self, wake_fn: impl Fn() + Send + Sync + 'static) {
+        *self.wake_fn.write().unwrap() = Some(Box::new(wake_fn));
+    }
+
+    /// Wake up the GUI, does nothing if no callback is set.
+    pub fn wake(&self) {
+        if let Some(wake_fn) = self.wake_fn.read().unwrap().as_ref() {
+            wake_fn();
+        }
+    }
+}
+
+impl std::fmt::Debug for Waker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Waker").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,862 @@
+//! Background worker that pings the servers, checks their status and
+//! applies the firewall changes, driven by [`Command`]s.
+//!
+//! The worker owns the list of IPs to ping, it is derived from the
+//! status of the servers: only the IPs that are not blocked are
+//! pinged. All the [`Response`]s are sent on a single channel in the
+//! order they happen, so once [`Response::PingStopped`] is received
+//! no more [`Response::Pings`] for those IPs follow, and once
+//! [`Response::Acked`] is received the firewall changes sent before it
+//! are applied.
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    crash,
+    firewall::{self, Firewall, PortRanges},
+    health::{Health, ThreadStatus},
+    ping::{self, PingInfo, PingerFactory, PingerRequirements, ProbeMode},
+    session,
+    steam_server::ServerState,
+    waker::Waker,
+};
+
+/// Priority of a server status check, higher priority checks are
+/// done first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StatusPriority {
+    /// Periodic recheck.
+    Background,
+    /// Server is visible in the UI.
+    Visible,
+    /// Server was just changed (enabled/disabled).
+    Changed,
+}
+
+impl StatusPriority {
+    /// Get all the [`StatusPriority`]s, highest priority first.
+    pub const fn all() -> [Self; 3] {
+        [Self::Changed, Self::Visible, Self::Background]
+    }
+}
+
+/// Command sent to the [`Worker`].
+#[derive(Debug)]
+pub enum Command {
+    /// Add the servers (with their IPs) to the servers whose status
+    /// is checked and whose unblocked IPs are pinged, a status check
+    /// is queued with the given priority.
+    WatchServers(Vec<(String, Vec<IpAddr>)>, StatusPriority),
+    /// Stop watching all the servers.
+    ClearServers,
+    /// Watch only the given servers (with their IPs), eg: after the
+    /// server list is reloaded. The IPs no longer watched stop being
+    /// pinged, the others are pinged without interruption. A status
+    /// check of every server is queued.
+    ReplaceServers(Vec<(String, Vec<IpAddr>)>),
+    /// Servers currently visible in the UI, they are rechecked before
+    /// the others.
+    SetVisible(Vec<String>),
+    /// Block (or unblock) the IPs of the server.
+    SetBlocked {
+        server: String,
+        ips: Vec<IpAddr>,
+        blocked: bool,
+    },
+    /// Deprioritize (or restore) the IPs of the server, see
+    /// [`firewall::TrafficShaper`].
+    SetDeprioritized {
+        server: String,
+        ips: Vec<IpAddr>,
+        deprioritized: bool,
+    },
+    /// IPs the game traffic is flowing through, blocking them is
+    /// refused with [`Response::BlockRefused`], see
+    /// [`crate::connections`].
+    SetGuarded(HashSet<IpAddr>),
+    /// Refuse (with [`Response::BlockRefused`]) the blocks that would
+    /// leave every watched server with IPs blocked, eg: while a game is
+    /// running, see [`crate::game`].
+    SetLastServerGuarded(bool),
+    /// Enable (default) or disable the watchdog that applies the rules
+    /// again when they were removed outside of the app, see
+    /// [`Firewall::restore_rules()`].
+    SetWatchdog(bool),
+    /// Timeout of a single ping, see [`PingerRequirements::set_timeout()`].
+    SetPingTimeout(Duration),
+    /// UDP port ranges of the watched relays, see
+    /// [`Firewall::set_port_ranges()`] and
+    /// [`PingerRequirements::set_port_ranges()`].
+    SetPortRanges(PortRanges),
+    /// Reply once all the previous commands are applied.
+    Flush(mpsc::Sender<()>),
+    /// Respond with [`Response::Acked`] once all the previous commands
+    /// are applied, unlike [`Command::Flush`] the sender does not wait
+    /// for it, see [`Worker::ack()`].
+    Ack(u64),
+    Shutdown,
+}
+
+/// Result of pinging an IP.
+pub type PingResult = (IpAddr, Result<PingInfo, ping::Error>);
+
+/// Response of the [`Worker`].
+#[derive(Debug)]
+pub enum Response {
+    /// Batch of ping results, in the order the pings were done.
+    Pings(Vec<PingResult>),
+    ServerState(String, ServerState),
+    /// The IPs are no longer pinged, no [`Response::Pings`] for them
+    /// follows until they are pinged again.
+    PingStopped(Vec<IpAddr>),
+    /// Blocking or unblocking an IP of the server failed.
+    FirewallError {
+        server: String,
+        error: firewall::Error,
+    },
+    /// The IPs of the server were not blocked since they are guarded,
+    /// see [`Command::SetGuarded`] and
+    /// [`Command::SetLastServerGuarded`].
+    BlockRefused {
+        server: String,
+        ips: Vec<IpAddr>,
+    },
+    /// The IPs were deprioritized (or restored).
+    Deprioritized {
+        ips: Vec<IpAddr>,
+        deprioritized: bool,
+    },
+    /// The rules of the app were removed outside of the app (eg: the
+    /// firewall was flushed), the IPs were blocked again.
+    RulesRestored(Vec<IpAddr>),
+    /// All the commands sent before the [`Command::Ack`] with this
+    /// sequence number are applied.
+    Acked(u64),
+}
+
+/// Queue of the servers whose status must be checked.
+#[derive(Debug, Default)]
+struct StatusQueue {
+    /// IPs of all the servers whose status is checked.
+    servers: HashMap<String, Vec<IpAddr>>,
+    /// Queued servers along with their priority.
+    queued: HashMap<String, StatusPriority>,
+    /// Servers visible in the UI.
+    visible: HashSet<String>,
+}
+
+impl StatusQueue {
+    /// Number of the servers checked per cycle, at most.
+    const MAX_CHECKS_PER_CYCLE: usize = 16;
+
+    fn append(&mut self, servers: Vec<(String, Vec<IpAddr>)>, priority: StatusPriority) {
+        servers.into_iter().for_each(|(server, ips)| {
+            self.push(server.clone(), priority);
+            self.servers.insert(server, ips);
+        });
+    }
+
+    /// Queue the server for a check, a server already queued keeps the
+    /// higher of the two priorities.
+    fn push(&mut self, server: String, priority: StatusPriority) {
+        let queued_priority = self.queued.entry(server).or_insert(priority);
+        *queued_priority = (*queued_priority).max(priority);
+    }
+
+    /// Queue all the servers for a recheck.
+    fn push_all(&mut self) {
+        let servers: Vec<_> = self.servers.keys().cloned().collect();
+        servers.into_iter().for_each(|server| {
+            let priority = if self.visible.contains(&server) {
+                StatusPriority::Visible
+            } else {
+                StatusPriority::Background
+            };
+            self.push(server, priority);
+        });
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.servers.clear();
+        self.queued.clear();
+    }
+
+    /// Take the highest priority servers for the next cycle.
+    fn pop_cycle(&mut self) -> Vec<(String, Vec<IpAddr>)> {
+        let servers: Vec<String> = StatusPriority::all()
+            .into_iter()
+            .flat_map(|priority| {
+                self.queued
+                    .iter()
+                    .filter(move |(_, queued_priority)| **queued_priority == priority)
+                    .map(|(server, _)| server.clone())
+            })
+            .take(Self::MAX_CHECKS_PER_CYCLE)
+            .collect();
+
+        servers
+            .into_iter()
+            .filter_map(|server| {
+                self.queued.remove(&server);
+                let ips = self.servers.get(&server)?.clone();
+                Some((server, ips))
+            })
+            .collect()
+    }
+}
+
+/// State of the worker thread.
+struct WorkerState {
+    firewall: Arc<Firewall>,
+    pinger: Box<dyn PingerRequirements>,
+    ping_interval: Duration,
+    response_sender: mpsc::Sender<Response>,
+    waker: Waker,
+
+    queue: StatusQueue,
+    /// All the servers are rechecked periodically to reflect the
+    /// changes made outside of the app.
+    next_recheck: Instant,
+    /// The firewall is not queried again until then after a failure.
+    retry_at: Instant,
+
+    ping_targets: Vec<IpAddr>,
+    ping_index: usize,
+    next_ping: Instant,
+    /// Echo requests per IP per round, see [`ping::Pinger::ping_burst()`].
+    probes_per_round: u16,
+    /// Targets pinged at the same time, see [`PingerRequirements::ping_many()`].
+    concurrent_pings: usize,
+    /// Ping results not sent yet, they are sent as a single
+    /// [`Response::Pings`] at the end of the round over the targets or
+    /// once the oldest result is [`Self::PING_BATCH_MAX_AGE`] old.
+    ping_batch: Vec<PingResult>,
+    ping_batch_started: Instant,
+    /// IPs that are never blocked, see [`Command::SetGuarded`].
+    guarded: HashSet<IpAddr>,
+    /// See [`Command::SetLastServerGuarded`].
+    last_server_guarded: bool,
+    /// IPs that should be blocked: blocked in the last firewall
+    /// snapshot or by the app since, [`None`] until the first
+    /// snapshot.
+    desired_blocked: Option<HashSet<IpAddr>>,
+    /// See [`Command::SetWatchdog`].
+    watchdog: bool,
+}
+
+impl WorkerState {
+    const RECHECK_INTERVAL: Duration = Duration::from_secs(5);
+    const PING_BATCH_MAX_AGE: Duration = Duration::from_millis(250);
+
+    /// Send the response, returns `false` if the receiver no longer
+    /// exists.
+    ///
+    /// The pending ping results are sent first to keep the responses
+    /// in order.
+    fn respond(&mut self, response: Response) -> bool {
+        if !self.send_ping_batch() || self.response_sender.send(response).is_err() {
+            return false;
+        }
+        self.waker.wake();
+        true
+    }
+
+    /// Send the pending ping results (if any), returns `false` if the
+    /// receiver no longer exists.
+    fn send_ping_batch(&mut self) -> bool {
+        if self.ping_batch.is_empty() {
+            return true;
+        }
+        let ping_batch = std::mem::take(&mut self.ping_batch);
+        if self
+            .response_sender
+            .send(Response::Pings(ping_batch))
+            .is_err()
+        {
+            return false;
+        }
+        self.waker.wake();
+        true
+    }
+
+    /// Apply the command, returns `false` if the worker must stop.
+    fn apply(&mut self, command: Command) -> bool {
+        match command {
+            Command::WatchServers(servers, priority) => self.queue.append(servers, priority),
+            Command::ClearServers => {
+                self.queue.clear();
+                let ping_targets = std::mem::take(&mut self.ping_targets);
+                if !ping_targets.is_empty() {
+                    return self.respond(Response::PingStopped(ping_targets));
+                }
+            }
+            Command::ReplaceServers(servers) => {
+                let ips: HashSet<_> = servers
+                    .iter()
+                    .flat_map(|(_, ips)| ips.iter().copied())
+                    .collect();
+                self.queue.clear();
+                self.queue.append(servers, StatusPriority::Background);
+                let removed: Vec<_> = self
+                    .ping_targets
+                    .iter()
+                    .copied()
+                    .filter(|ip| !ips.contains(ip))
+                    .collect();
+                return self.remove_ping_targets(&removed);
+            }
+            Command::SetVisible(visible) => {
+                self.queue.visible = visible.into_iter().collect();
+            }
+            Command::SetBlocked {
+                server,
+                ips,
+                blocked,
+            } => {
+                let (ips, refused): (Vec<_>, Vec<_>) = if blocked && self.blocks_last_server(&ips) {
+                    log::warn!(
+                        "not blocking {}, every server would be blocked while a game is running",
+                        server
+                    );
+                    (Vec::new(), ips)
+                } else {
+                    ips.into_iter()
+                        .partition(|ip| !blocked || !self.guarded.contains(ip))
+                };
+                if !refused.is_empty() {
+                    log::warn!(
+                        "not blocking {} IPs of {}, they are guarded",
+                        refused.len(),
+                        server
+                    );
+                    if !self.respond(Response::BlockRefused {
+                        server: server.clone(),
+                        ips: refused,
+                    }) {
+                        return false;
+                    }
+                }
+
+                if blocked {
+                    // stop pinging before blocking, the pings would
+                    // only time out
+                    if !self.remove_ping_targets(&ips) {
+                        return false;
+                    }
+                }
+
+                let _operation = crash::begin_operation(format!(
+                    "{} {} IPs of {}",
+                    if blocked { "blocking" } else { "unblocking" },
+                    ips.len(),
+                    server
+                ));
+                let res = if blocked {
+                    self.firewall.ban_ips(&ips)
+                } else {
+                    self.firewall.unban_ips(&ips)
+                };
+                let changed: Vec<_> = match &res {
+                    Ok(()) => ips.clone(),
+                    // the ips may have been changed up to the failure
+                    Err(_) => match self.firewall.list_blocked() {
+                        Ok(now_blocked) => ips
+                            .iter()
+                            .copied()
+                            .filter(|ip| now_blocked.contains(ip) == blocked)
+                            .collect(),
+                        Err(_) => Vec::new(),
+                    },
+                };
+                if let Some(desired_blocked) = &mut self.desired_blocked {
+                    if blocked {
+                        desired_blocked.extend(changed.iter().copied());
+                    } else {
+                        changed.iter().for_each(|ip| {
+                            desired_blocked.remove(ip);
+                        });
+                    }
+                }
+                session::record(&changed, blocked);
+                log::info!(
+                    "{} {} ips of {}",
+                    if blocked { "blocked" } else { "unblocked" },
+                    ips.len(),
+                    server
+                );
+
+                self.queue.push(server.clone(), StatusPriority::Changed);
+
+                if let Err(error) = res {
+                    return self.respond(Response::FirewallError { server, error });
+                }
+            }
+            Command::SetDeprioritized {
+                server,
+                ips,
+                deprioritized,
+            } => {
+                let res = if deprioritized {
+                    self.firewall.deprioritize_ips(&ips)
+                } else {
+                    self.firewall.restore_ips(&ips)
+                };
+                log::info!(
+                    "{} {} ips of {}",
+                    if deprioritized {
+                        "deprioritized"
+                    } else {
+                        "restored"
+                    },
+                    ips.len(),
+                    server
+                );
+                return match res {
+                    Ok(()) => self.respond(Response::Deprioritized { ips, deprioritized }),
+                    Err(error) => self.respond(Response::FirewallError { server, error }),
+                };
+            }
+            Command::SetGuarded(guarded) => {
+                self.guarded = guarded;
+            }
+            Command::SetLastServerGuarded(last_server_guarded) => {
+                self.last_server_guarded = last_server_guarded;
+            }
+            Command::SetWatchdog(watchdog) => {
+                self.watchdog = watchdog;
+            }
+            Command::SetPingTimeout(timeout) => self.pinger.set_timeout(timeout),
+            Command::SetPortRanges(port_ranges) => {
+                if let Err(err) = self.firewall.set_port_ranges(port_ranges.clone()) {
+                    log::error!("unable to set the port ranges of the firewall: {}", err);
+                }
+                self.pinger.set_port_ranges(port_ranges);
+            }
+            Command::Flush(sender) => {
+                // the receiver may have given up waiting
+                let _ = sender.send(());
+            }
+            Command::Ack(sequence) => return self.respond(Response::Acked(sequence)),
+            Command::Shutdown => return false,
+        }
+        true
+    }
+
+    /// Would blocking the IPs leave every watched server with IPs
+    /// blocked while [`Self::last_server_guarded`]? Unknown until the
+    /// first firewall snapshot.
+    fn blocks_last_server(&self, ips: &[IpAddr]) -> bool {
+        let Some(desired_blocked) = &self.desired_blocked else {
+            return false;
+        };
+        self.last_server_guarded
+            && self
+                .queue
+                .servers
+                .values()
+                .filter(|server_ips| !server_ips.is_empty())
+                .all(|server_ips| {
+                    server_ips
+                        .iter()
+                        .all(|ip| desired_blocked.contains(ip) || ips.contains(ip))
+                })
+    }
+
+    /// Remove the IPs from the ping targets, returns `false` if the
+    /// receiver no longer exists.
+    fn remove_ping_targets(&mut self, ips: &[IpAddr]) -> bool {
+        let removed: Vec<_> = ips
+            .iter()
+            .copied()
+            .filter(|ip| self.ping_targets.contains(ip))
+            .collect();
+        if removed.is_empty() {
+            return true;
+        }
+        self.ping_targets.retain(|ip| !removed.contains(ip));
+        self.respond(Response::PingStopped(removed))
+    }
+
+    /// Time until there is work to do.
+    fn timeout(&self) -> Duration {
+        let mut deadline = self.next_recheck;
+        if !self.queue.is_empty() {
+            deadline = deadline.min(self.retry_at);
+        }
+        if !self.ping_targets.is_empty() {
+            deadline = deadline.min(self.next_ping);
+        }
+        if !self.ping_batch.is_empty() {
+            deadline = deadline.min(self.ping_batch_started + Self::PING_BATCH_MAX_AGE);
+        }
+        deadline.saturating_duration_since(Instant::now())
+    }
+
+    /// Check the status of the queued servers (if due), returns
+    /// `false` if the receiver no longer exists.
+    fn check_status(&mut self) -> bool {
+        let now = Instant::now();
+        if now >= self.next_recheck {
+            self.queue.push_all();
+            self.next_recheck = now + Self::RECHECK_INTERVAL;
+        }
+        if now < self.retry_at {
+            return true;
+        }
+
+        let list = self.queue.pop_cycle();
+        if list.is_empty() {
+            return true;
+        }
+
+        // the rules of the app are set up again before the snapshot,
+        // which fails without them (eg: without the nftables table)
+        let rules_removed = self.watchdog
+            && self.firewall.restore_rules().unwrap_or_else(|err| {
+                log::error!("{}", err);
+                false
+            });
+
+        // single snapshot of the firewall for all the servers of this
+        // cycle
+        let mut blocked = match self.firewall.list_blocked() {
+            Ok(blocked) => blocked,
+            Err(err) => {
+                log::error!("{}", err);
+                list.into_iter().for_each(|(server, _)| {
+                    self.queue.push(server, StatusPriority::Background);
+                });
+                self.retry_at = now + Duration::from_millis(500);
+                return true;
+            }
+        };
+        if !self.restore_flushed_rules(rules_removed, &mut blocked) {
+            return false;
+        }
+
+        list.into_iter().all(|(server, ips)| {
+            let (blocked_ips, unblocked_ips): (Vec<_>, Vec<_>) =
+                ips.iter().copied().partition(|ip| blocked.contains(ip));
+
+            unblocked_ips.iter().for_each(|ip| {
+                if !self.ping_targets.contains(ip) {
+                    self.ping_targets.push(*ip);
+                }
+            });
+
+            self.remove_ping_targets(&blocked_ips)
+                && self.respond(Response::ServerState(
+                    server,
+                    ServerState::from_blocked_ips(ips.len(), blocked_ips),
+                ))
+        })
+    }
+
+    /// Block the desired IPs missing from the `blocked` snapshot again
+    /// if the rules of the app were removed outside of the app (eg:
+    /// Docker restarts, `iptables -F`, firewalld reloads), see
+    /// [`Firewall::restore_rules()`]. Otherwise the snapshot becomes
+    /// the desired state, the IPs unblocked outside of the app (eg: by
+    /// the CLI) stay unblocked. Returns `false` if the receiver no
+    /// longer exists.
+    fn restore_flushed_rules(
+        &mut self,
+        rules_removed: bool,
+        blocked: &mut HashSet<IpAddr>,
+    ) -> bool {
+        let flushed: Vec<_> = match &self.desired_blocked {
+            Some(desired_blocked) if rules_removed => {
+                desired_blocked.difference(blocked).copied().collect()
+            }
+            _ => Vec::new(),
+        };
+        if flushed.is_empty() {
+            self.desired_blocked = Some(blocked.clone());
+            return true;
+        }
+
+        log::warn!(
+            "the rules of the app were removed outside of the app, blocking the {} IPs again",
+            flushed.len()
+        );
+        let _operation =
+            crash::begin_operation(format!("restoring {} flushed IP blocks", flushed.len()));
+        let restored: Vec<_> = flushed
+            .into_iter()
+            .filter(|ip| match self.firewall.ban_ip(*ip) {
+                Ok(()) => true,
+                Err(err) => {
+                    log::error!("unable to block {} again: {}", ip, err);
+                    false
+                }
+            })
+            .collect();
+        blocked.extend(restored.iter().copied());
+        self.desired_blocked = Some(blocked.clone());
+        self.respond(Response::RulesRestored(restored))
+    }
+
+    /// Ping the next [`Self::concurrent_pings`] targets (if due),
+    /// returns `false` if the receiver no longer exists.
+    fn ping(&mut self) -> bool {
+        if !self.ping_batch.is_empty()
+            && self.ping_batch_started.elapsed() >= Self::PING_BATCH_MAX_AGE
+            && !self.send_ping_batch()
+        {
+            return false;
+        }
+
+        if self.ping_targets.is_empty() || Instant::now() < self.next_ping {
+            return true;
+        }
+
+        if self.ping_index >= self.ping_targets.len() {
+            self.ping_index = 0;
+        }
+        let end = (self.ping_index + self.concurrent_pings.max(1)).min(self.ping_targets.len());
+        let ips = self.ping_targets[self.ping_index..end].to_vec();
+        self.ping_index = end;
+
+        let results = self.pinger.ping_many(&ips, self.probes_per_round);
+        self.next_ping = Instant::now() + self.ping_interval;
+        if results
+            .iter()
+            .all(|(_, ping_data)| matches!(ping_data, Err(ping::Error::SendError)))
+        {
+            // not logged here since it fails for every ping while the
+            // network is down, the error is part of the ping data
+            self.next_ping = Instant::now() + Duration::from_secs(1);
+        }
+        if self.ping_batch.is_empty() {
+            self.ping_batch_started = Instant::now();
+        }
+        self.ping_batch.extend(results);
+
+        // end of the round
+        if self.ping_index >= self.ping_targets.len() {
+            return self.send_ping_batch();
+        }
+        true
+    }
+}
+
+/// Handle to the worker thread, the thread is stopped when dropped.
+pub struct Worker {
+    command_sender: mpsc::Sender<Command>,
+    response_receiver: mpsc::Receiver<Response>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+    health: Health,
+
+    /// Needed to restart the worker, see [`Worker::restart()`].
+    firewall: Arc<Firewall>,
+    new_pinger: PingerFactory,
+    ping_timeout: Duration,
+    probe_mode: ProbeMode,
+    ping_interval: Duration,
+    probes_per_round: u16,
+    concurrent_pings: usize,
+    waker: Waker,
+
+    /// Sequence number of the last [`Command::Ack`], kept across
+    /// restarts.
+    ack_sequence: u64,
+}
+
+impl Worker {
+    /// The worker is stalled after this long without completing an
+    /// iteration, it wakes up at least every
+    /// [`WorkerState::RECHECK_INTERVAL`].
+    pub const STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// Start the worker, `concurrent_pings` IPs are pinged at the same
+    /// time at most once every `ping_interval`, each as a burst of
+    /// `probes_per_round` echo requests aggregated into one sample.
+    /// `waker` is woken up whenever responses are sent. The worker
+    /// thread is restarted with a pinger of `new_pinger`, see
+    /// [`Self::restart()`].
+    pub fn new(
+        firewall: Arc<Firewall>,
+        pinger: Box<dyn PingerRequirements>,
+        new_pinger: PingerFactory,
+        ping_interval: Duration,
+        probes_per_round: u16,
+        concurrent_pings: usize,
+        waker: Waker,
+    ) -> Self {
+        let (command_sender, command_receiver) = mpsc::channel::<Command>();
+        let (response_sender, response_receiver) = mpsc::channel();
+        let health = Health::new(Self::STALL_TIMEOUT);
+        let ping_timeout = pinger.timeout();
+        let probe_mode = pinger.mode();
+
+        let thread_handle = {
+            let firewall = firewall.clone();
+            let waker = waker.clone();
+            let health = health.clone();
+            thread::spawn(move || {
+                let _health_guard = health.guard();
+                Self::run(
+                    WorkerState {
+                        firewall,
+                        pinger,
+                        ping_interval,
+                        response_sender,
+                        waker,
+                        queue: StatusQueue::default(),
+                        next_recheck: Instant::now() + WorkerState::RECHECK_INTERVAL,
+                        retry_at: Instant::now(),
+                        ping_targets: Vec::new(),
+                        ping_index: 0,
+                        next_ping: Instant::now(),
+                        probes_per_round,
+                        concurrent_pings,
+                        ping_batch: Vec::new(),
+                        ping_batch_started: Instant::now(),
+                        guarded: HashSet::new(),
+                        last_server_guarded: false,
+                        desired_blocked: None,
+                        watchdog: true,
+                    },
+                    command_receiver,
+                    health,
+                )
+            })
+        };
+
+        Self {
+            command_sender,
+            response_receiver,
+            thread_handle: Some(thread_handle),
+            health,
+            firewall,
+            new_pinger,
+            ping_timeout,
+            probe_mode,
+            ping_interval,
+            probes_per_round,
+            concurrent_pings,
+            waker,
+            ack_sequence: 0,
+        }
+    }
+
+    /// Loop of the worker thread.
+    fn run(mut state: WorkerState, command_receiver: mpsc::Receiver<Command>, health: Health) {
+        loop {
+            health.beat();
+
+            // wait for a command or for the next scheduled work,
+            // whichever comes first
+            match command_receiver.recv_timeout(state.timeout()) {
+                Ok(command) => {
+                    let keep_running = std::iter::once(command)
+                        .chain(command_receiver.try_iter())
+                        .all(|command| state.apply(command));
+                    if !keep_running {
+                        break;
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+
+            // status checks first, they are cheap and reflect the
+            // user's actions
+            if !state.check_status() || !state.ping() {
+                // receiver no longer exists
+                break;
+            }
+        }
+    }
+
+    /// Get the health of the worker thread.
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+
+    /// Replace the worker thread with a new one with a new
+    /// pinger, eg: after it panicked or stalled. The servers must be
+    /// watched again.
+    pub fn restart(&mut self) -> Result<(), ping::Error> {
+        let mut pinger = (self.new_pinger)()?;
+        pinger.set_timeout(self.ping_timeout);
+        pinger.set_mode(self.probe_mode);
+        let ack_sequence = self.ack_sequence;
+        *self = Self::new(
+            self.firewall.clone(),
+            pinger,
+            self.new_pinger.clone(),
+            self.ping_interval,
+            self.probes_per_round,
+            self.concurrent_pings,
+            self.waker.clone(),
+        );
+        self.ack_sequence = ack_sequence;
+        log::info!("restarted the worker thread");
+        Ok(())
+    }
+
+    /// Send the command to the worker.
+    pub fn send(&self, command: Command) {
+        if self.command_sender.send(command).is_err() {
+            log::error!("worker thread is not running");
+        }
+    }
+
+    /// Wait until all the previously sent commands are applied.
+    pub fn flush(&self) {
+        let (sender, receiver) = mpsc::channel();
+        self.send(Command::Flush(sender));
+        // fails only if the worker is not running
+        let _ = receiver.recv();
+    }
+
+    /// Set the timeout of a single ping, it is kept across restarts.
+    pub fn set_ping_timeout(&mut self, ping_timeout: Duration) {
+        self.ping_timeout = ping_timeout;
+        self.send(Command::SetPingTimeout(ping_timeout));
+    }
+
+    /// Request a [`Response::Acked`] once all the previously sent
+    /// commands are applied, returns its sequence number. Unlike
+    /// [`Self::flush()`] this does not block, the acknowledgements of
+    /// a worker that is restarted are never received.
+    pub fn ack(&mut self) -> u64 {
+        self.ack_sequence += 1;
+        self.send(Command::Ack(self.ack_sequence));
+        self.ack_sequence
+    }
+
+    /// Get an iterator over the responses received so far, does not
+    /// block.
+    pub fn try_iter(&self) -> mpsc::TryIter<'_, Response> {
+        self.response_receiver.try_iter()
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.command_sender.send(Command::Shutdown);
+        if self.health.status() == ThreadStatus::Stalled {
+            // joining would block until the thread is unstuck, it
+            // stops on its own once it notices the shutdown
+            log::error!("worker thread is stalled, not waiting for it to stop");
+            return;
+        }
+        if let Some(thread_handle) = self.thread_handle.take() {
+            if thread_handle.join().is_err() {
+                log::error!("worker thread panicked");
+            }
+        }
+    }
+}
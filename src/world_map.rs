@@ -0,0 +1,353 @@
+//! Offline world map drawn with the egui painter, used instead of the
+//! OpenStreetMap tiles when they cannot (or should not) be
+//! downloaded.
+//!
+//! The coastlines are coarse hand simplified outlines of the
+//! continents and the larger islands, only meant to give the markers
+//! some geographic context.
+
+/// Spacing in degrees of the latitude and longitude lines.
+const GRATICULE_SPACING: i32 = 30;
+
+/// Maximum latitude shown by the map (web mercator).
+const MAX_LATITUDE: f64 = 85.0;
+
+/// [`walkers::Plugin`] that draws the offline world map, it must be
+/// the first plugin so that the other plugins are drawn above it.
+pub struct WorldOutline;
+
+impl WorldOutline {
+    fn project(projector: &walkers::Projector, lon: f64, lat: f64) -> egui::Pos2 {
+        projector
+            .project(walkers::Position::from_lon_lat(lon, lat))
+            .to_pos2()
+    }
+}
+
+impl walkers::Plugin for WorldOutline {
+    fn run(
+        &mut self,
+        response: &egui::Response,
+        painter: egui::Painter,
+        projector: &walkers::Projector,
+    ) {
+        let visuals = painter.ctx().style().visuals.clone();
+        painter.rect_filled(response.rect, 0.0, visuals.extreme_bg_color);
+
+        let graticule_stroke = egui::Stroke::new(1.0, visuals.faint_bg_color);
+        (-180..=180)
+            .step_by(GRATICULE_SPACING as usize)
+            .for_each(|lon| {
+                let lon = f64::from(lon);
+                painter.line_segment(
+                    [
+                        Self::project(projector, lon, -MAX_LATITUDE),
+                        Self::project(projector, lon, MAX_LATITUDE),
+                    ],
+                    graticule_stroke,
+                );
+            });
+        (-60..=60)
+            .step_by(GRATICULE_SPACING as usize)
+            .for_each(|lat| {
+                let lat = f64::from(lat);
+                painter.line_segment(
+                    [
+                        Self::project(projector, -180.0, lat),
+                        Self::project(projector, 180.0, lat),
+                    ],
+                    graticule_stroke,
+                );
+            });
+
+        let coastline_stroke = egui::Stroke::new(1.0, visuals.weak_text_color());
+        COASTLINES.iter().for_each(|coastline| {
+            painter.add(egui::Shape::closed_line(
+                coastline
+                    .iter()
+                    .map(|[lon, lat]| Self::project(projector, f64::from(*lon), f64::from(*lat)))
+                    .collect(),
+                coastline_stroke,
+            ));
+        });
+    }
+}
+
+/// Coastlines as closed `[longitude, latitude]` outlines (the first
+/// point is not repeated).
+#[rustfmt::skip]
+const COASTLINES: &[&[[f32; 2]]] = &[
+    // North America
+    &[
+        [-168.0, 65.5], [-164.0, 67.0], [-156.0, 71.3], [-141.0, 69.6], [-128.0, 70.0],
+        [-115.0, 68.5], [-95.0, 68.5], [-94.0, 72.0], [-82.0, 69.0], [-80.0, 63.0], [-88.0, 64.0],
+        [-94.0, 60.0], [-92.0, 57.0], [-82.0, 55.0], [-79.0, 51.5], [-77.0, 60.0], [-70.0, 61.0],
+        [-64.0, 60.0], [-61.0, 56.0], [-56.0, 52.0], [-59.0, 48.0], [-65.0, 49.0], [-70.0, 47.0],
+        [-66.0, 45.0], [-70.0, 43.0], [-70.0, 41.5], [-74.0, 40.5], [-76.0, 38.0], [-75.5, 35.5],
+        [-78.0, 34.0], [-81.0, 31.5], [-80.0, 27.0], [-80.5, 25.2], [-82.0, 26.5], [-83.0, 29.0],
+        [-85.0, 30.0], [-89.0, 30.3], [-90.0, 29.0], [-94.0, 29.6], [-97.4, 27.5], [-97.5, 22.0],
+        [-96.0, 19.0], [-94.5, 18.2], [-91.0, 18.8], [-90.5, 21.0], [-87.0, 21.5], [-88.0, 18.0],
+        [-88.5, 16.0], [-84.0, 15.8], [-83.4, 11.0], [-81.5, 9.0], [-79.5, 9.5], [-77.3, 8.6],
+        [-78.0, 7.5], [-80.5, 7.3], [-83.5, 8.5], [-85.8, 11.0], [-87.5, 13.0], [-91.5, 14.0],
+        [-94.5, 16.1], [-97.0, 15.8], [-101.0, 17.3], [-105.5, 20.5], [-105.3, 22.5],
+        [-109.0, 25.5], [-112.5, 29.0], [-114.7, 31.6], [-113.0, 28.0], [-112.0, 25.0],
+        [-110.0, 23.0], [-112.0, 24.7], [-114.0, 27.5], [-115.5, 30.0], [-117.0, 32.5],
+        [-120.5, 34.5], [-122.5, 37.5], [-124.0, 40.5], [-124.2, 43.0], [-124.0, 46.3],
+        [-124.7, 48.4], [-123.0, 49.0], [-128.0, 51.0], [-130.5, 54.5], [-134.0, 58.0],
+        [-140.0, 59.8], [-147.0, 60.8], [-152.0, 59.0], [-157.0, 57.5], [-162.0, 55.0],
+        [-158.0, 58.5], [-162.0, 60.0], [-165.0, 61.0], [-166.0, 64.0],
+    ],
+    // South America
+    &[
+        [-77.3, 8.6], [-75.5, 10.5], [-72.0, 12.0], [-67.0, 10.6], [-62.0, 10.7], [-60.0, 8.5],
+        [-57.0, 6.0], [-52.0, 5.0], [-50.0, 1.8], [-48.5, -1.0], [-44.0, -2.5], [-39.5, -3.5],
+        [-35.0, -5.5], [-35.0, -9.0], [-37.0, -11.0], [-39.0, -15.0], [-39.5, -19.0],
+        [-41.5, -22.5], [-44.5, -23.2], [-48.5, -26.0], [-48.7, -28.5], [-51.0, -31.0],
+        [-53.0, -34.0], [-56.0, -34.8], [-58.5, -34.5], [-57.0, -36.5], [-57.5, -38.2],
+        [-62.0, -39.0], [-62.3, -40.8], [-65.0, -41.0], [-64.5, -42.5], [-65.5, -45.0],
+        [-67.5, -46.5], [-65.8, -47.8], [-69.0, -50.5], [-68.5, -52.3], [-70.0, -53.5],
+        [-71.5, -54.0], [-74.0, -52.5], [-75.5, -48.0], [-74.0, -44.0], [-73.5, -41.0],
+        [-73.5, -37.0], [-71.5, -32.0], [-71.4, -28.0], [-70.5, -23.5], [-70.2, -18.5],
+        [-75.2, -15.5], [-76.5, -13.5], [-79.5, -7.5], [-81.2, -5.5], [-80.0, -2.5], [-80.5, 0.0],
+        [-79.0, 1.8], [-77.5, 4.0], [-77.5, 7.0],
+    ],
+    // Africa
+    &[
+        [-17.0, 21.0], [-16.0, 24.0], [-13.0, 27.8], [-9.8, 30.0], [-9.5, 32.5], [-6.8, 34.0],
+        [-5.9, 35.8], [-2.0, 35.1], [1.0, 36.5], [6.0, 37.0], [10.2, 37.2], [11.0, 35.5],
+        [10.0, 34.0], [11.5, 33.0], [15.2, 32.3], [19.0, 30.3], [20.0, 32.0], [23.0, 32.6],
+        [25.0, 31.7], [29.0, 30.9], [32.3, 31.3], [34.2, 31.2], [33.5, 28.0], [35.0, 24.5],
+        [37.0, 21.0], [38.5, 18.0], [41.5, 14.0], [43.3, 12.5], [44.0, 10.5], [51.0, 11.8],
+        [51.0, 10.0], [48.0, 4.5], [44.0, 0.0], [41.0, -2.0], [39.5, -5.0], [39.0, -8.0],
+        [40.5, -11.0], [40.5, -15.0], [37.0, -18.0], [35.0, -22.0], [35.5, -24.0], [33.0, -26.0],
+        [32.5, -29.0], [30.0, -31.5], [27.0, -33.7], [25.0, -34.0], [20.0, -34.8], [18.4, -34.2],
+        [18.2, -31.5], [16.5, -28.6], [15.0, -26.5], [14.5, -22.5], [11.8, -17.0], [12.3, -13.5],
+        [13.5, -11.0], [12.2, -6.0], [9.5, -2.0], [9.5, 2.5], [9.5, 4.0], [8.5, 4.5], [6.0, 4.3],
+        [4.0, 6.3], [1.0, 6.0], [-2.0, 4.8], [-4.0, 5.2], [-7.5, 4.4], [-11.0, 6.5], [-13.2, 8.5],
+        [-15.0, 11.0], [-16.8, 13.0], [-17.5, 14.7], [-16.5, 16.5], [-16.2, 19.2],
+    ],
+    // Madagascar
+    &[
+        [49.3, -12.0], [50.5, -15.5], [49.8, -17.2], [48.8, -20.0], [47.5, -24.0], [45.2, -25.5],
+        [43.7, -23.5], [43.3, -21.8], [44.3, -19.5], [44.0, -16.8], [46.3, -15.7], [48.0, -13.5],
+    ],
+    // Eurasia
+    &[
+        [-9.5, 37.0], [-8.8, 41.0], [-9.0, 43.0], [-8.0, 43.7], [-2.0, 43.4], [-1.3, 44.5],
+        [-1.2, 46.0], [-2.3, 47.2], [-4.7, 48.4], [-1.5, 48.7], [-1.5, 49.6], [1.5, 50.1],
+        [1.6, 50.9], [3.5, 51.4], [4.5, 52.5], [5.0, 53.3], [7.0, 53.6], [8.7, 53.9], [8.6, 55.5],
+        [8.2, 56.8], [10.5, 57.7], [10.5, 56.0], [10.9, 54.4], [14.0, 54.0], [18.5, 54.6],
+        [21.0, 55.2], [21.0, 57.0], [24.0, 57.3], [23.5, 59.2], [28.0, 59.6], [30.0, 60.0],
+        [26.5, 60.4], [22.9, 60.0], [21.5, 61.5], [21.2, 63.0], [25.5, 65.0], [22.5, 65.8],
+        [21.0, 64.5], [18.5, 63.0], [17.3, 61.0], [18.8, 59.8], [16.6, 57.4], [14.3, 55.5],
+        [12.9, 55.5], [11.8, 58.0], [10.6, 59.3], [8.0, 58.0], [5.6, 58.5], [5.0, 61.0],
+        [5.5, 62.5], [8.5, 63.5], [11.0, 64.8], [14.0, 67.0], [16.0, 68.5], [19.0, 70.0],
+        [23.0, 70.7], [28.0, 71.0], [31.0, 70.0], [33.0, 69.3], [41.0, 67.5], [40.0, 66.0],
+        [36.0, 66.5], [34.5, 64.5], [37.5, 64.0], [44.0, 66.2], [44.0, 68.5], [53.5, 68.8],
+        [58.0, 68.6], [60.8, 69.8], [68.0, 68.5], [68.5, 72.5], [72.5, 72.7], [72.0, 68.0],
+        [75.0, 72.5], [80.0, 72.2], [80.5, 73.5], [87.0, 74.0], [95.0, 76.0], [104.0, 77.7],
+        [113.0, 76.0], [113.5, 73.5], [123.0, 73.0], [128.8, 72.5], [131.0, 71.0], [140.0, 72.5],
+        [150.0, 71.5], [159.0, 70.8], [161.0, 69.5], [170.0, 70.0], [176.0, 69.8], [180.0, 68.9],
+        [180.0, 65.0], [178.5, 64.5], [177.0, 62.5], [172.0, 60.8], [164.0, 59.9], [163.0, 58.0],
+        [162.0, 56.0], [160.0, 53.5], [158.5, 53.0], [156.5, 51.2], [156.0, 54.0], [155.5, 57.7],
+        [161.0, 60.5], [158.0, 61.7], [152.0, 59.0], [143.0, 59.3], [137.5, 54.2], [141.5, 53.0],
+        [140.5, 48.0], [138.0, 45.0], [135.0, 43.0], [132.0, 43.0], [129.7, 40.9], [129.3, 37.5],
+        [129.4, 35.2], [126.5, 34.4], [126.2, 37.7], [124.8, 39.6], [121.5, 39.0], [122.2, 40.5],
+        [121.0, 40.9], [118.0, 39.0], [117.6, 38.3], [119.0, 37.2], [122.5, 37.0], [119.5, 35.0],
+        [121.5, 32.0], [121.8, 30.8], [121.8, 29.0], [119.6, 25.6], [116.5, 22.9], [113.3, 22.2],
+        [110.5, 21.0], [110.2, 20.3], [108.7, 21.6], [106.7, 20.5], [105.8, 19.0], [107.5, 16.2],
+        [109.3, 13.0], [109.0, 11.5], [107.0, 10.4], [104.8, 8.6], [104.8, 10.3], [103.0, 11.0],
+        [101.0, 12.7], [100.0, 13.4], [99.2, 10.0], [100.3, 7.4], [101.5, 6.8], [103.4, 4.6],
+        [104.2, 1.4], [103.5, 1.3], [101.4, 2.7], [100.3, 5.5], [98.3, 8.0], [98.5, 13.0],
+        [97.8, 16.5], [95.3, 15.8], [94.3, 16.3], [94.6, 19.0], [92.3, 21.0], [91.8, 22.5],
+        [90.5, 22.0], [88.8, 21.6], [87.0, 21.4], [85.0, 19.5], [82.2, 16.6], [80.3, 15.9],
+        [80.0, 11.5], [79.9, 10.3], [78.0, 8.3], [76.6, 8.9], [75.2, 11.9], [73.5, 16.0],
+        [72.8, 19.2], [72.6, 21.4], [70.5, 20.9], [68.9, 22.4], [67.5, 23.8], [66.6, 25.4],
+        [61.5, 25.2], [57.3, 25.8], [56.6, 27.1], [54.7, 26.5], [51.5, 27.9], [50.1, 30.1],
+        [48.0, 30.0], [48.5, 28.0], [50.0, 26.0], [51.6, 25.3], [51.6, 24.2], [54.0, 24.2],
+        [56.0, 26.3], [56.4, 24.9], [58.7, 23.6], [59.8, 22.4], [57.5, 18.9], [55.3, 17.3],
+        [52.2, 15.9], [48.7, 14.0], [45.0, 12.8], [43.5, 12.7], [42.7, 16.4], [40.9, 19.5],
+        [39.1, 21.3], [38.5, 23.7], [37.1, 25.1], [35.2, 28.0], [34.6, 28.0], [34.3, 31.2],
+        [35.0, 33.0], [35.9, 35.4], [36.2, 36.6], [34.7, 36.8], [32.5, 36.1], [30.6, 36.7],
+        [28.7, 36.7], [27.2, 37.8], [26.3, 39.4], [26.5, 40.2], [29.0, 41.0], [31.0, 41.1],
+        [35.0, 42.0], [38.2, 40.9], [41.5, 41.5], [41.7, 42.6], [39.9, 43.4], [38.0, 44.4],
+        [36.7, 45.2], [35.3, 44.9], [33.5, 44.6], [32.5, 45.4], [33.6, 46.0], [31.7, 46.5],
+        [30.7, 46.5], [29.6, 45.3], [28.7, 44.3], [27.7, 42.6], [28.0, 41.6], [26.4, 40.8],
+        [23.7, 40.7], [22.8, 40.5], [23.3, 39.2], [22.5, 38.4], [23.2, 38.0], [22.9, 36.4],
+        [21.7, 36.8], [21.1, 38.4], [20.2, 39.6], [19.4, 40.3], [19.5, 41.7], [18.4, 42.5],
+        [16.0, 43.5], [15.1, 44.2], [13.7, 45.1], [13.6, 45.8], [12.3, 45.4], [12.6, 44.1],
+        [13.8, 43.0], [15.9, 41.9], [18.0, 40.6], [18.5, 40.2], [16.9, 40.4], [16.6, 38.8],
+        [15.7, 38.0], [15.9, 38.8], [15.4, 40.1], [14.1, 40.8], [12.2, 41.8], [10.5, 42.9],
+        [10.2, 44.0], [8.9, 44.4], [7.4, 43.7], [6.5, 43.1], [4.6, 43.4], [3.1, 43.1], [3.2, 41.9],
+        [1.0, 41.0], [0.0, 39.9], [-0.3, 38.5], [-2.1, 36.7], [-4.4, 36.7], [-5.6, 36.0],
+        [-6.5, 36.9], [-7.5, 37.2], [-8.9, 37.0],
+    ],
+    // Great Britain
+    &[
+        [-5.7, 50.0], [-3.0, 50.6], [1.4, 51.2], [1.7, 52.7], [0.3, 53.4], [-0.2, 54.3],
+        [-1.6, 55.6], [-2.5, 56.3], [-1.8, 57.6], [-3.9, 57.6], [-3.0, 58.6], [-5.0, 58.6],
+        [-6.2, 57.5], [-5.6, 56.3], [-4.8, 55.0], [-3.1, 54.9], [-3.2, 54.2], [-3.0, 53.4],
+        [-4.6, 53.3], [-4.2, 52.3], [-5.3, 51.8], [-3.2, 51.4], [-4.5, 51.1],
+    ],
+    // Ireland
+    &[
+        [-6.2, 53.9], [-6.0, 52.2], [-7.0, 52.1], [-9.5, 51.6], [-10.2, 52.2], [-9.0, 53.2],
+        [-10.0, 54.1], [-8.3, 55.2], [-6.0, 55.2], [-5.5, 54.4],
+    ],
+    // Iceland
+    &[
+        [-22.0, 64.0], [-24.0, 65.5], [-22.5, 66.4], [-16.0, 66.5], [-13.6, 65.2], [-15.0, 64.2],
+        [-18.7, 63.4],
+    ],
+    // Greenland
+    &[
+        [-73.0, 78.5], [-66.0, 80.7], [-55.0, 82.2], [-40.0, 83.5], [-25.0, 83.0], [-20.0, 82.0],
+        [-12.0, 81.5], [-18.0, 77.0], [-19.0, 74.0], [-22.0, 70.5], [-26.0, 68.5], [-32.0, 68.0],
+        [-38.0, 65.5], [-41.0, 63.0], [-43.5, 60.0], [-48.0, 61.0], [-51.0, 64.0], [-53.5, 67.0],
+        [-53.0, 70.0], [-55.0, 71.0], [-57.0, 74.5], [-61.0, 76.2], [-68.0, 76.0],
+    ],
+    // Baffin Island
+    &[
+        [-80.0, 73.7], [-71.0, 70.5], [-66.5, 68.0], [-62.0, 66.5], [-65.0, 63.0], [-68.0, 62.5],
+        [-74.0, 64.5], [-77.5, 65.4], [-73.5, 68.0], [-82.0, 69.7], [-88.0, 70.5], [-85.0, 73.0],
+    ],
+    // Victoria Island
+    &[
+        [-118.0, 71.0], [-111.0, 67.8], [-101.0, 68.5], [-104.0, 71.0], [-110.0, 73.0],
+    ],
+    // Ellesmere Island
+    &[
+        [-90.0, 76.5], [-78.0, 76.0], [-75.0, 79.5], [-63.0, 82.0], [-78.0, 83.0], [-91.0, 81.0],
+    ],
+    // Svalbard
+    &[
+        [11.0, 78.5], [16.5, 76.5], [22.0, 77.5], [27.0, 79.5], [18.0, 80.5], [11.0, 79.7],
+    ],
+    // Novaya Zemlya
+    &[
+        [52.0, 71.3], [55.5, 70.6], [57.5, 70.7], [56.0, 73.0], [60.0, 75.5], [68.5, 76.8],
+        [63.0, 76.5], [55.0, 75.5], [53.0, 73.0],
+    ],
+    // Cuba
+    &[
+        [-84.9, 21.9], [-82.5, 23.1], [-80.0, 23.1], [-77.5, 21.8], [-74.2, 20.2], [-77.7, 19.9],
+        [-78.5, 21.5], [-81.8, 22.2],
+    ],
+    // Hispaniola
+    &[
+        [-74.4, 18.4], [-72.8, 19.9], [-70.0, 19.7], [-68.3, 18.6], [-71.4, 17.6],
+    ],
+    // Sicily
+    &[
+        [12.4, 37.8], [15.6, 38.3], [15.1, 36.6],
+    ],
+    // Sardinia
+    &[
+        [8.4, 39.0], [9.7, 39.2], [9.8, 41.0], [8.2, 41.0],
+    ],
+    // Honshu
+    &[
+        [130.2, 31.3], [131.5, 31.4], [132.0, 33.5], [135.0, 33.5], [136.8, 34.3], [138.8, 34.7],
+        [140.0, 35.1], [140.9, 36.9], [141.0, 38.3], [142.0, 39.6], [141.4, 41.4], [140.0, 40.7],
+        [139.9, 39.5], [139.4, 38.0], [137.0, 37.0], [136.7, 36.3], [135.5, 35.6], [132.5, 35.4],
+        [131.0, 34.3], [130.0, 33.5],
+    ],
+    // Hokkaido
+    &[
+        [140.0, 41.5], [141.2, 41.8], [143.2, 41.9], [145.5, 43.3], [144.8, 44.3], [141.9, 45.5],
+        [141.4, 43.3], [140.0, 42.6],
+    ],
+    // Sakhalin
+    &[
+        [142.0, 46.0], [143.5, 46.5], [143.0, 49.0], [144.0, 49.3], [143.2, 52.5], [142.7, 54.3],
+        [142.0, 53.6], [142.2, 51.0], [141.9, 48.0],
+    ],
+    // Taiwan
+    &[
+        [120.1, 23.0], [120.8, 21.9], [121.9, 24.9], [121.5, 25.3], [120.2, 23.8],
+    ],
+    // Hainan
+    &[
+        [108.6, 19.2], [110.5, 18.2], [111.0, 19.7], [110.3, 20.1],
+    ],
+    // Sri Lanka
+    &[
+        [79.8, 8.0], [80.0, 6.0], [81.7, 6.3], [81.9, 7.8], [80.1, 9.8],
+    ],
+    // Luzon
+    &[
+        [120.6, 18.5], [122.2, 18.5], [122.2, 17.0], [121.5, 15.8], [122.5, 14.2], [124.0, 13.0],
+        [123.3, 13.0], [121.7, 13.9], [120.6, 14.3], [120.0, 16.0],
+    ],
+    // Mindanao
+    &[
+        [122.0, 7.0], [123.7, 7.8], [125.0, 9.0], [126.5, 9.0], [126.5, 7.0], [125.5, 6.0],
+        [124.0, 6.4],
+    ],
+    // Sumatra
+    &[
+        [95.3, 5.6], [97.5, 5.2], [100.3, 2.3], [103.8, -1.0], [106.0, -3.2], [105.8, -5.8],
+        [104.5, -5.9], [102.0, -4.0], [101.0, -2.4], [99.0, 0.2], [98.5, 1.7], [96.0, 3.8],
+    ],
+    // Java
+    &[
+        [105.2, -6.8], [106.0, -5.9], [108.3, -6.3], [110.5, -6.9], [112.6, -6.9], [114.5, -7.8],
+        [114.6, -8.7], [111.0, -8.2], [108.7, -7.7], [106.4, -7.4],
+    ],
+    // Borneo
+    &[
+        [109.0, 1.3], [109.6, 2.0], [111.2, 2.6], [113.0, 3.2], [115.5, 5.2], [117.0, 7.0],
+        [119.2, 5.2], [118.2, 4.2], [117.8, 1.0], [119.0, 0.9], [117.5, -0.5], [116.5, -2.5],
+        [116.0, -3.8], [114.5, -3.5], [111.8, -3.5], [110.2, -2.9], [109.5, -0.5],
+    ],
+    // Sulawesi
+    &[
+        [119.5, -5.5], [120.5, -5.6], [120.8, -2.8], [122.0, -4.6], [123.2, -4.8], [122.5, -3.2],
+        [121.3, -1.9], [123.3, -0.9], [125.2, 1.5], [124.3, 0.4], [120.2, 0.6], [120.0, -1.0],
+        [119.3, -3.5],
+    ],
+    // New Guinea
+    &[
+        [131.0, -1.4], [134.2, -0.9], [135.5, -3.3], [138.0, -1.7], [141.0, -2.6], [145.0, -4.4],
+        [146.0, -5.6], [147.6, -6.1], [147.2, -7.4], [148.7, -9.1], [150.8, -10.3], [147.9, -10.1],
+        [146.0, -8.1], [144.2, -7.6], [143.3, -9.0], [141.0, -9.1], [139.0, -8.1], [138.0, -8.4],
+        [137.6, -5.4], [135.2, -4.5], [133.0, -4.0], [132.0, -2.8],
+    ],
+    // Australia
+    &[
+        [113.4, -22.0], [114.0, -26.0], [115.0, -29.5], [115.0, -33.6], [117.9, -35.1],
+        [121.5, -33.8], [124.0, -33.0], [126.1, -32.2], [129.0, -31.6], [131.3, -31.5],
+        [134.2, -32.6], [135.9, -34.9], [137.7, -33.0], [137.8, -35.7], [138.6, -34.7],
+        [140.0, -37.5], [143.6, -38.8], [146.3, -39.1], [150.0, -37.6], [150.8, -34.6],
+        [153.0, -31.0], [153.6, -28.0], [153.0, -25.0], [150.8, -22.5], [149.5, -22.3],
+        [146.4, -19.0], [145.3, -15.0], [143.5, -14.2], [142.5, -10.7], [141.6, -12.9],
+        [141.7, -15.1], [140.8, -17.5], [139.2, -17.3], [135.9, -15.0], [135.5, -14.6],
+        [136.9, -12.3], [135.4, -12.0], [132.6, -11.6], [131.0, -12.2], [129.6, -14.9],
+        [127.5, -14.0], [125.7, -14.2], [124.5, -16.3], [122.2, -18.2], [121.0, -19.6],
+        [117.0, -20.6], [114.6, -21.8],
+    ],
+    // Tasmania
+    &[
+        [144.7, -40.7], [148.3, -40.9], [148.0, -43.2], [146.8, -43.6], [145.3, -42.3],
+    ],
+    // New Zealand North Island
+    &[
+        [172.7, -34.5], [174.3, -35.3], [175.9, -37.5], [178.5, -37.7], [177.0, -39.2],
+        [176.0, -41.3], [174.7, -41.3], [175.2, -40.2], [173.8, -39.2], [174.6, -37.2],
+    ],
+    // New Zealand South Island
+    &[
+        [172.8, -40.5], [174.2, -41.3], [173.3, -42.8], [172.7, -43.7], [171.2, -44.5],
+        [170.6, -45.9], [169.0, -46.6], [166.5, -46.1], [166.8, -45.3], [168.3, -44.0],
+        [170.5, -43.0], [172.0, -41.5],
+    ],
+    // Antarctica
+    &[
+        [-180.0, -84.0], [-180.0, -78.0], [-160.0, -78.0], [-150.0, -76.0], [-130.0, -74.0],
+        [-100.0, -73.0], [-75.0, -73.0], [-67.0, -66.0], [-57.0, -63.3], [-60.0, -65.0],
+        [-62.0, -70.0], [-60.0, -75.0], [-45.0, -78.0], [-30.0, -76.0], [-20.0, -74.0],
+        [0.0, -70.0], [20.0, -70.0], [40.0, -69.0], [60.0, -67.0], [75.0, -69.0], [90.0, -66.5],
+        [110.0, -66.0], [130.0, -66.0], [150.0, -68.0], [165.0, -71.0], [170.0, -72.0],
+        [165.0, -78.0], [180.0, -78.0], [180.0, -84.0],
+    ],
+];
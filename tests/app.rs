@@ -0,0 +1,145 @@
+//! [`App`] built with the mock firewall, the mock pinger and a canned
+//! server list, so that it runs without the network or elevated
+//! privileges.
+
+#![cfg(feature = "gui")]
+
+use std::{
+    path::PathBuf,
+    sync::Once,
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+use steam_server_disable::{
+    app::{App, AppBuilder},
+    cli::CommandLineArguments,
+    config::Config,
+    firewall::MockFirewall,
+    ping::MockPinger,
+    steam_server::ServerState,
+    Error, Firewall, Servers,
+};
+
+/// Server list of the tests, shared with the unit tests.
+const SERVERS: &str = include_str!("data/servers.json");
+
+const RTT: Duration = Duration::from_millis(20);
+
+/// Directory of the files of the app during the tests.
+fn test_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("steam_server_disable_test_{}", std::process::id()))
+}
+
+/// Keep the app from reading or writing the files of the user, the
+/// project directories follow the XDG variables.
+fn isolate_files() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        let dir = test_dir();
+        std::env::set_var("XDG_CONFIG_HOME", dir.join("config"));
+        std::env::set_var("XDG_DATA_HOME", dir.join("data"));
+        std::env::set_var("XDG_CACHE_HOME", dir.join("cache"));
+    });
+}
+
+fn build_app() -> App {
+    isolate_files();
+    let steam_dir = test_dir().join("steam");
+    AppBuilder::new()
+        .command_line_arguments(CommandLineArguments::parse_from([
+            "steam_server_disable".as_ref(),
+            "--no-gui".as_ref(),
+            "--steam-dir".as_ref(),
+            steam_dir.as_os_str(),
+        ]))
+        .config(Config {
+            config_refresh_hours: 0,
+            ..Config::default()
+        })
+        .servers(Servers::from_json(SERVERS.as_bytes()).unwrap())
+        .firewall(Firewall::with_backend(MockFirewall::new()))
+        .pinger(|| Ok(MockPinger::new(RTT)))
+        .build()
+        .unwrap()
+}
+
+/// Update the app until the state of the region is `state`.
+fn wait_for_state(app: &mut App, region: &str, state: ServerState) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        app.update();
+        let current = app
+            .region_rows()
+            .into_iter()
+            .find(|row| row.region == region)
+            .unwrap()
+            .state;
+        if current == state {
+            return;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "{} is {:?}, expected {:?}",
+            region,
+            current,
+            state
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn blocks_and_unblocks_a_region() {
+    let mut app = build_app();
+    wait_for_state(&mut app, "fra", ServerState::NoneDisabled);
+    wait_for_state(&mut app, "sea", ServerState::NoneDisabled);
+
+    app.set_region_blocked("fra", true).unwrap();
+    let statuses = app.region_statuses().unwrap();
+    let fra = statuses
+        .iter()
+        .find(|status| status.region == "fra")
+        .unwrap();
+    assert_eq!(fra.blocked_ips, fra.ips);
+    let sea = statuses
+        .iter()
+        .find(|status| status.region == "sea")
+        .unwrap();
+    assert!(sea.blocked_ips.is_empty());
+    wait_for_state(&mut app, "fra", ServerState::AllDisabled);
+    wait_for_state(&mut app, "sea", ServerState::NoneDisabled);
+
+    app.set_region_blocked("fra", false).unwrap();
+    let statuses = app.region_statuses().unwrap();
+    assert!(statuses.iter().all(|status| status.blocked_ips.is_empty()));
+    wait_for_state(&mut app, "fra", ServerState::NoneDisabled);
+}
+
+#[test]
+fn unknown_region_is_an_error() {
+    let app = build_app();
+    assert!(matches!(
+        app.set_region_blocked("xyz", true),
+        Err(Error::UnknownRegion(region)) if region == "xyz"
+    ));
+}
+
+#[test]
+fn pings_come_from_the_pinger() {
+    let mut app = build_app();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        app.update();
+        let statuses = app.region_statuses().unwrap();
+        if statuses.iter().all(|status| status.ping_ms.is_some()) {
+            statuses.iter().for_each(|status| {
+                assert_eq!(status.ping_ms, Some(RTT.as_secs_f64() * 1000.0));
+            });
+            return;
+        }
+        assert!(Instant::now() < deadline, "no pings received");
+        thread::sleep(Duration::from_millis(10));
+    }
+}